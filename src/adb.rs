@@ -0,0 +1,270 @@
+use crate::{config::Config, filter};
+use regex::Regex;
+use std::{
+    fs, io,
+    path::Path,
+    process::{Child, Command, Output, Stdio},
+    sync::LazyLock,
+};
+
+// "main: ring buffer is 256 KiB (254 KiB consumed), ..." or, on older releases, "256Kb"
+static RE_BUFFER_SIZE: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^(\S+): ring buffer is ([0-9]+)\s*([KMG]?)i?[Bb]").unwrap()
+});
+
+/// The adb binary, overridable with $ADB like the platform tools scripts do.
+pub fn adb() -> Command {
+    Command::new(std::env::var("ADB").unwrap_or_else(|_| "adb".to_string()))
+}
+
+fn run(args: &[&str]) -> io::Result<Output> {
+    let out: Output = adb().args(args).stderr(Stdio::piped()).output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "adb {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(out)
+}
+
+/// Exec mode setup from the `[exec]` table of the config file:
+///
+/// ```toml
+/// [exec]
+/// clear_buffer = true            # adb logcat -c first
+/// buffers = ["main", "crash"]    # -b main -b crash
+/// buffer_size = "16M"            # like --setup-buffers
+/// format = "threadtime"          # -v threadtime; an array for modifiers
+/// filterspec = "MyApp:V *:W"
+/// ```
+///
+/// Arguments given after `--` take precedence over the matching keys.
+#[derive(Default)]
+pub struct Setup {
+    pub clear_buffer: bool,
+    pub buffers: Vec<String>,
+    pub buffer_size: Option<String>,
+    format: Vec<String>,
+    filterspec: Vec<String>,
+}
+
+impl Setup {
+    pub fn load(config: &Config) -> io::Result<Self> {
+        let Some(section) = config.tables("exec").next() else {
+            return Ok(Self::default());
+        };
+        section.check_keys(
+            config,
+            &[
+                "clear_buffer",
+                "buffers",
+                "buffer_size",
+                "format",
+                "filterspec",
+            ],
+        )?;
+        let words = |key: &str| -> io::Result<Vec<String>> {
+            Ok(section
+                .strings(config, key)?
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|s: &String| {
+                    s.split_whitespace()
+                        .map(String::from)
+                        .collect::<Vec<String>>()
+                })
+                .collect())
+        };
+        Ok(Self {
+            clear_buffer: section.bool(config, "clear_buffer")?.unwrap_or(false),
+            buffers: words("buffers")?,
+            buffer_size: section.str(config, "buffer_size")?.map(String::from),
+            format: words("format")?,
+            filterspec: words("filterspec")?,
+        })
+    }
+
+    /// Adds the configured buffers, format and filterspec to `args` where
+    /// the command line didn't give its own.
+    pub fn extend_args(&self, args: &mut Vec<String>) {
+        let mut extra: Vec<String> = Vec::new();
+        if !args
+            .iter()
+            .any(|a: &String| a.starts_with("-b") || a.starts_with("--buffer"))
+        {
+            for buffer in &self.buffers {
+                extra.extend(["-b".to_string(), buffer.clone()]);
+            }
+        }
+        if !has_format(args) {
+            for format in &self.format {
+                extra.extend(["-v".to_string(), format.clone()]);
+            }
+        }
+        args.splice(0..0, extra);
+        if !filter::has_filterspec(args) {
+            args.extend(self.filterspec.iter().cloned());
+        }
+    }
+}
+
+/// Runs `adb logcat -c`, on `buffers` or the default ones.
+pub fn clear_buffers(buffers: &[String]) -> io::Result<()> {
+    let mut args: Vec<&str> = vec!["logcat", "-c"];
+    for buffer in buffers {
+        args.extend(["-b", buffer.as_str()]);
+    }
+    run(&args).map(|_| ())
+}
+
+/// Starts `adb logcat` with extra arguments, its stdout piped back to us.
+pub fn spawn_logcat(args: &[String]) -> io::Result<Child> {
+    adb()
+        .arg("logcat")
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+}
+
+/// Starts `adb shell tail -F` on a device file, from its first line,
+/// following it across the rotations of `adb logcat -f -r`.
+pub fn spawn_tail(path: &str) -> io::Result<Child> {
+    // adb shell hands its arguments to the device shell as one command line
+    let quoted: String = format!("'{}'", path.replace('\'', "'\\''"));
+    adb()
+        .args(["shell", "tail", "-n", "+1", "-F", &quoted])
+        .stdout(Stdio::piped())
+        .spawn()
+}
+
+/// Whether logcat arguments ask for the binary format (`-B`, `--binary`).
+pub fn is_binary(args: &[String]) -> bool {
+    args.iter().any(|a: &String| a == "-B" || a == "--binary")
+}
+
+/// Whether logcat arguments already pick an output format (`-v brief`,
+/// `-vtime`, `--format=long`).
+pub fn has_format(args: &[String]) -> bool {
+    args.iter()
+        .any(|a: &String| a.starts_with("-v") || a == "--format" || a.starts_with("--format="))
+}
+
+/// Saves a PNG of the device screen to `path` with `adb exec-out screencap`.
+pub fn screencap(path: &Path) -> io::Result<()> {
+    let out: Output = run(&["exec-out", "screencap", "-p"])?;
+    if out.stdout.is_empty() {
+        return Err(io::Error::other("adb exec-out screencap returned no image"));
+    }
+    fs::write(path, &out.stdout)
+}
+
+/// A system property of the device, e.g. `ro.product.model`.
+pub fn getprop(name: &str) -> io::Result<String> {
+    let out: Output = run(&["shell", "getprop", name])?;
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// The device's clock, in the `-v year` logcat layout ("2024-06-01 12:00:00.000").
+pub fn date() -> io::Result<String> {
+    let out: Output = run(&["shell", "date '+%Y-%m-%d %H:%M:%S.000'"])?;
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// The pids of the device processes named `name`; empty when none is running.
+pub fn pidof(name: &str) -> io::Result<Vec<u64>> {
+    let out: Output = adb()
+        .args(["shell", "pidof", name])
+        .stderr(Stdio::piped())
+        .output()?;
+    // pidof exits 1 with no output when nothing matched
+    if !out.status.success() && !out.stderr.is_empty() {
+        return Err(io::Error::other(format!(
+            "adb shell pidof {} failed: {}",
+            name,
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .split_whitespace()
+        .filter_map(filter::parse_id)
+        .collect())
+}
+
+/// The device's processes as (pid, user, name), from `adb shell ps -A`.
+pub fn processes() -> io::Result<Vec<(String, String, String)>> {
+    let out: Output = run(&["shell", "ps", "-A", "-o", "PID,USER,NAME"])?;
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .skip(1)
+        .filter_map(|line: &str| {
+            let mut fields = line.split_whitespace();
+            Some((
+                fields.next()?.to_string(),
+                fields.next()?.to_string(),
+                fields.next()?.to_string(),
+            ))
+        })
+        .collect())
+}
+
+/// Parses a logcat buffer size ("16M", "512K", "1048576") into bytes.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size: &str = size.trim();
+    let (num, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => size.split_at(i),
+        None => (size, ""),
+    };
+    let mult: u64 = match unit.trim_end_matches(['i', 'B', 'b']) {
+        "" => 1,
+        "K" | "k" => 1 << 10,
+        "M" | "m" => 1 << 20,
+        "G" | "g" => 1 << 30,
+        _ => return None,
+    };
+    num.parse::<u64>().ok().map(|n: u64| n * mult)
+}
+
+/// Current ring buffer sizes as (buffer, bytes), from `adb logcat -g`.
+pub fn buffer_sizes() -> io::Result<Vec<(String, u64)>> {
+    let out: Output = run(&["logcat", "-g"])?;
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|l: &str| RE_BUFFER_SIZE.captures(l))
+        .filter_map(|c: regex::Captures<'_>| {
+            let bytes: u64 = parse_size(&format!("{}{}", &c[2], &c[3]))?;
+            Some((c[1].to_string(), bytes))
+        })
+        .collect())
+}
+
+/// Runs `adb logcat -G SIZE` and checks every buffer reports the new size.
+pub fn setup_buffers(size: &str) -> io::Result<()> {
+    let wanted: u64 = parse_size(size)
+        .ok_or_else(|| io::Error::other(format!("invalid buffer size: {}", size)))?;
+    run(&["logcat", "-G", size])?;
+
+    let short: Vec<String> = buffer_sizes()?
+        .into_iter()
+        .filter(|(_, bytes): &(String, u64)| *bytes != wanted)
+        .map(|(name, bytes): (String, u64)| format!("{} is {}", name, human_size(bytes)))
+        .collect();
+    if !short.is_empty() {
+        return Err(io::Error::other(format!(
+            "adb logcat -G {} did not apply: {}",
+            size,
+            short.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+pub fn human_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 && b % (1 << 30) == 0 => format!("{} GiB", b >> 30),
+        b if b >= 1 << 20 && b % (1 << 20) == 0 => format!("{} MiB", b >> 20),
+        b if b >= 1 << 10 && b % (1 << 10) == 0 => format!("{} KiB", b >> 10),
+        b => format!("{} B", b),
+    }
+}
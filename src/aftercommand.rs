@@ -0,0 +1,93 @@
+use crate::{adb, hostlog::Input};
+use std::{
+    io,
+    process::{Command, Output, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+// Lines of a failed command's output shown under its marker
+const SHOWN_OUTPUT: usize = 10;
+
+/// How the `--after-command` run ended.
+pub struct Finished {
+    pub command: String,
+    pub status: Result<(), String>, // how it failed: "exit 1", or why it didn't run
+    pub elapsed: Duration,
+    pub output: Vec<String>, // the last lines it wrote, when it failed
+    pub cleared: Option<io::Result<()>>, // the buffers, after it succeeded
+}
+
+/// Runs `command` through `sh -c` while `lines` stream in, for the
+/// edit-install-watch loop (`--after-command 'gradle installDebug'`), and
+/// yields its end where the stream was when it came. Its output is kept
+/// from the log and only shown, the tail of it, when it fails. With
+/// `clear`, the device log buffers are cleared once it succeeds, so what
+/// follows the marker is the fresh install's.
+pub fn merge(
+    lines: Box<dyn Iterator<Item = io::Result<Input>> + Send>,
+    command: String,
+    clear: bool,
+) -> impl Iterator<Item = io::Result<Input>> {
+    let (tx, rx): (Sender<io::Result<Input>>, Receiver<io::Result<Input>>) = mpsc::channel();
+
+    let lines_tx: Sender<io::Result<Input>> = tx.clone();
+    thread::spawn(move || {
+        for line in lines {
+            if lines_tx.send(line).is_err() {
+                return;
+            }
+        }
+        let _ = lines_tx.send(Ok(Input::End));
+    });
+    thread::spawn(move || {
+        let _ = tx.send(Ok(Input::Finished(run(command, clear))));
+    });
+
+    rx.into_iter()
+        .take_while(|i: &io::Result<Input>| !matches!(i, Ok(Input::End)))
+}
+
+fn run(command: String, clear: bool) -> Finished {
+    let start: Instant = Instant::now();
+    let res: io::Result<Output> = Command::new("sh")
+        .args(["-c", &command])
+        .stdin(Stdio::null())
+        .output();
+    let elapsed: Duration = start.elapsed();
+    let (status, output) = match res {
+        Ok(out) if out.status.success() => (Ok(()), Vec::new()),
+        Ok(out) => {
+            let status: String = match out.status.code() {
+                Some(code) => format!("exit {}", code),
+                None => "killed".to_string(),
+            };
+            // Build tools report failures on stderr, some on stdout only
+            let text: &[u8] = match out.stderr.iter().all(u8::is_ascii_whitespace) {
+                true => &out.stdout,
+                false => &out.stderr,
+            };
+            let text: String = String::from_utf8_lossy(text).into_owned();
+            let lines: Vec<&str> = text
+                .lines()
+                .filter(|l: &&str| !l.trim().is_empty())
+                .collect();
+            let tail: Vec<String> = lines[lines.len().saturating_sub(SHOWN_OUTPUT)..]
+                .iter()
+                .map(|l: &&str| l.to_string())
+                .collect();
+            (Err(status), tail)
+        }
+        Err(e) => (Err(format!("sh: {}", e)), Vec::new()),
+    };
+    let cleared: Option<io::Result<()>> =
+        (clear && status.is_ok()).then(|| adb::clear_buffers(&[]));
+    Finished {
+        command,
+        status,
+        elapsed,
+        output,
+        cleared,
+    }
+}
@@ -0,0 +1,524 @@
+use crate::{
+    classify::{self, EventKind},
+    config::{Config, Section},
+    crash, filter,
+    logcat::Logcat,
+    native::json_str,
+};
+use std::{
+    collections::VecDeque,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+// --alert-level doesn't ring more than once per this interval during a burst
+const QUIET: Duration = Duration::from_secs(2);
+
+const SINKS: [&str; 5] = ["notification", "bell", "webhook", "file", "command"];
+
+#[derive(Clone, Debug)]
+enum Sink {
+    Notification,
+    Bell,
+    Webhook(String),
+    File(PathBuf),
+    Command(String),
+}
+
+/// At most `count` alerts per `per`.
+#[derive(Copy, Clone, Debug)]
+struct Limit {
+    count: usize,
+    per: Duration,
+}
+
+/// Where entries of some events or levels go, from a `[[route]]` table of
+/// the config:
+///
+/// ```toml
+/// [[route]]
+/// event = ["CRASH", "ANR"]    # event labels, custom classifiers included
+/// level = "E"                 # and/or entries at or above a level
+/// sink = ["notification", "webhook"]  # also bell, file and command
+/// url = "https://hooks.example.com/logcat"  # for webhook
+/// path = "/tmp/alerts.log"    # for file
+/// command = 'notify-send "$LOGCAT_TAG" "$LOGCAT_MESSAGE"'  # for command
+/// limit = "3/min"             # at most 3 alerts a minute (also s, h, 10s)
+/// ```
+#[derive(Clone, Debug)]
+pub struct Route {
+    events: Vec<String>, // labels, or `*` for any event
+    min_rank: usize,
+    sinks: Vec<Sink>,
+    limit: Option<Limit>,
+    sent: VecDeque<Instant>,
+}
+
+impl Route {
+    fn matches(&self, lc: &Logcat, event: Option<EventKind>) -> bool {
+        let named = |k: EventKind| -> bool {
+            self.events
+                .iter()
+                .any(|e: &String| e == "*" || e.eq_ignore_ascii_case(k.label()))
+        };
+        event.is_some_and(named)
+            || filter::level_rank(&lc.level).is_some_and(|rank: usize| rank >= self.min_rank)
+    }
+
+    // Takes a slot of the limit, if one is free
+    fn admit(&mut self, now: Instant) -> bool {
+        let Some(limit) = self.limit else {
+            return true;
+        };
+        while self
+            .sent
+            .front()
+            .is_some_and(|t: &Instant| now.duration_since(*t) >= limit.per)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= limit.count {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+pub fn load(configs: &[Config]) -> io::Result<Vec<Route>> {
+    let mut routes: Vec<Route> = Vec::new();
+    for config in configs {
+        for section in config.tables("route") {
+            routes.push(load_route(config, section)?);
+        }
+    }
+    Ok(routes)
+}
+
+fn load_route(config: &Config, section: &Section) -> io::Result<Route> {
+    section.check_keys(
+        config,
+        &["event", "level", "sink", "url", "path", "command", "limit"],
+    )?;
+    let at = |key: &str| -> usize { section.get(key).map_or(section.line, |e| e.line) };
+
+    let events: Vec<String> = section.strings(config, "event")?.unwrap_or_default();
+    for event in &events {
+        let known: bool = event == "*"
+            || EventKind::all()
+                .iter()
+                .any(|k: &EventKind| k.label().eq_ignore_ascii_case(event));
+        if !known {
+            let labels: Vec<&str> = EventKind::all().iter().map(EventKind::label).collect();
+            return Err(config.error(
+                at("event"),
+                &format!(
+                    "unknown event `{}` (expected *, {})",
+                    event,
+                    labels.join(", ")
+                ),
+            ));
+        }
+    }
+    let min_rank: usize = match section.str(config, "level")? {
+        Some(level) => filter::parse_level(level)
+            .ok()
+            .and_then(|l: String| filter::level_rank(&l))
+            .ok_or_else(|| config.error(at("level"), &format!("unknown level `{}`", level)))?,
+        None => usize::MAX,
+    };
+    if events.is_empty() && min_rank == usize::MAX {
+        return Err(config.error(section.line, "a route needs an event or a level"));
+    }
+
+    let param = |key: &str, sink: &str| -> io::Result<String> {
+        match section.str(config, key)? {
+            Some(value) => Ok(value.to_string()),
+            None => Err(config.error(
+                at("sink"),
+                &format!("the {} sink needs a `{}` key", sink, key),
+            )),
+        }
+    };
+    let mut sinks: Vec<Sink> = Vec::new();
+    for name in section.strings(config, "sink")?.unwrap_or_default() {
+        sinks.push(match name.as_str() {
+            "notification" => Sink::Notification,
+            "bell" => Sink::Bell,
+            "webhook" => Sink::Webhook(
+                crash::parse_url(&param("url", &name)?)
+                    .map_err(|e: String| config.error(at("url"), &e))?,
+            ),
+            "file" => Sink::File(PathBuf::from(param("path", &name)?)),
+            "command" => Sink::Command(param("command", &name)?),
+            _ => {
+                return Err(config.error(
+                    at("sink"),
+                    &format!("unknown sink `{}` (expected {})", name, SINKS.join(", ")),
+                ));
+            }
+        });
+    }
+    if sinks.is_empty() {
+        return Err(config.error(section.line, "a route needs a sink"));
+    }
+    let limit: Option<Limit> = match section.str(config, "limit")? {
+        Some(s) => Some(parse_limit(s).map_err(|e: String| config.error(at("limit"), &e))?),
+        None => None,
+    };
+    Ok(Route {
+        events,
+        min_rank,
+        sinks,
+        limit,
+        sent: VecDeque::new(),
+    })
+}
+
+// "3/min", "1/10s", "20/h"
+fn parse_limit(s: &str) -> Result<Limit, String> {
+    let invalid = || format!("invalid limit `{}` (expected e.g. 3/min or 1/10s)", s);
+    let (count, per) = s.split_once('/').ok_or_else(invalid)?;
+    let count: usize = count
+        .trim()
+        .parse()
+        .ok()
+        .filter(|n: &usize| *n > 0)
+        .ok_or_else(invalid)?;
+    let per: &str = per.trim();
+    let digits: usize = per.bytes().take_while(u8::is_ascii_digit).count();
+    let n: u64 = match digits {
+        0 => 1,
+        _ => per[..digits]
+            .parse()
+            .ok()
+            .filter(|n: &u64| *n > 0)
+            .ok_or_else(invalid)?,
+    };
+    let unit: u64 = match &per[digits..] {
+        "s" => 1,
+        "min" => 60,
+        "h" => 3600,
+        _ => return Err(invalid()),
+    };
+    Ok(Limit {
+        count,
+        per: Duration::from_secs(n * unit),
+    })
+}
+
+/// What routing an entry asks of the terminal, and the sinks that failed.
+#[derive(Default)]
+pub struct Dispatch {
+    pub control: String, // escape sequences to write
+    pub failures: Vec<String>,
+}
+
+/// Sends entries to the sinks of the routes they match: the `[[route]]`
+/// tables, and the terminal bell and notification that `--alert-level`
+/// and `notify = true` classifiers ask for.
+pub struct Router {
+    routes: Vec<Route>,
+    running: Vec<Child>, // webhook posts and commands
+}
+
+impl Router {
+    /// None when nothing routes anywhere.
+    pub fn new(mut routes: Vec<Route>, alert_level: Option<&str>) -> Option<Self> {
+        let notify: Vec<String> = classify::customs()
+            .iter()
+            .filter(|c| c.notify)
+            .map(|c| c.name.clone())
+            .collect();
+        if alert_level.is_some() || !notify.is_empty() {
+            routes.push(Route {
+                events: notify,
+                min_rank: alert_level
+                    .and_then(filter::level_rank)
+                    .unwrap_or(usize::MAX),
+                sinks: vec![Sink::Bell, Sink::Notification],
+                limit: Some(Limit {
+                    count: 1,
+                    per: QUIET,
+                }),
+                sent: VecDeque::new(),
+            });
+        }
+        (!routes.is_empty()).then(|| Self {
+            routes,
+            running: Vec::new(),
+        })
+    }
+
+    pub fn dispatch(&mut self, lc: &Logcat, event: Option<EventKind>) -> Dispatch {
+        self.running
+            .retain_mut(|c: &mut Child| c.try_wait().is_ok_and(|s| s.is_none()));
+        let mut dispatch: Dispatch = Dispatch::default();
+        let now: Instant = Instant::now();
+        let mut sinks: Vec<Sink> = Vec::new();
+        for route in &mut self.routes {
+            if route.matches(lc, event) && route.admit(now) {
+                sinks.extend(route.sinks.iter().cloned());
+            }
+        }
+        for sink in sinks {
+            if let Err(e) = self.send(&sink, lc, event, &mut dispatch.control) {
+                dispatch.failures.push(e.to_string());
+            }
+        }
+        dispatch
+    }
+
+    fn send(
+        &mut self,
+        sink: &Sink,
+        lc: &Logcat,
+        event: Option<EventKind>,
+        control: &mut String,
+    ) -> io::Result<()> {
+        // Control characters would end the OSC strings early
+        let body: String = format!("{}: {}", lc.tag.trim(), lc.message)
+            .chars()
+            .filter(|c: &char| !c.is_control())
+            .take(200)
+            .collect();
+        let label: &str = event.map_or("", |k: EventKind| k.label());
+        match sink {
+            // Bell (urgency hint on X11 terminals, dock bounce on macOS) and
+            // iTerm2 RequestAttention
+            Sink::Bell => control.push_str("\x07\x1b]1337;RequestAttention=yes\x07"),
+            // Notifications via OSC 9 (iTerm2, WezTerm) and OSC 777 (WezTerm,
+            // foot, urxvt)
+            Sink::Notification => control.push_str(&format!(
+                "\x1b]9;{body}\x07\x1b]777;notify;logcat {level};{body}\x07",
+                level = lc.level,
+                body = body
+            )),
+            Sink::Webhook(url) => {
+                let mut child: Child = Command::new("curl")
+                    .args(["-sS", "--fail", "--max-time", "10", "-X", "POST"])
+                    .args([
+                        "-H",
+                        "Content-Type: application/json",
+                        "--data-binary",
+                        "@-",
+                    ])
+                    .arg(url)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .map_err(|e: io::Error| io::Error::other(format!("webhook: curl: {}", e)))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(payload(lc, label).as_bytes())?;
+                }
+                self.running.push(child);
+            }
+            Sink::File(path) => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut f| {
+                    writeln!(
+                        f,
+                        "{} {}/{} [{}] {}",
+                        lc.timestamp,
+                        lc.level,
+                        lc.tag.trim(),
+                        label,
+                        lc.message
+                    )
+                })
+                .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?,
+            Sink::Command(command) => {
+                let child: Child = Command::new("sh")
+                    .args(["-c", command])
+                    .env("LOGCAT_EVENT", label)
+                    .env("LOGCAT_LEVEL", &lc.level)
+                    .env("LOGCAT_TAG", lc.tag.trim())
+                    .env("LOGCAT_PID", &lc.process)
+                    .env("LOGCAT_TIMESTAMP", &lc.timestamp)
+                    .env("LOGCAT_MESSAGE", &lc.message)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|e: io::Error| io::Error::other(format!("command: {}", e)))?;
+                self.running.push(child);
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for the webhook posts and commands still running.
+    pub fn wait(&mut self) {
+        for mut child in self.running.drain(..) {
+            let _ = child.wait();
+        }
+    }
+}
+
+fn payload(lc: &Logcat, label: &str) -> String {
+    format!(
+        "{{\"event\":{},\"timestamp\":{},\"level\":{},\"tag\":{},\"pid\":{},\"tid\":{},\"message\":{}}}",
+        match label {
+            "" => "null".to_string(),
+            _ => json_str(label),
+        },
+        json_str(&lc.timestamp),
+        json_str(&lc.level),
+        json_str(lc.tag.trim()),
+        json_str(&lc.process),
+        json_str(&lc.thread),
+        json_str(&lc.message)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::entry;
+    use std::fs;
+
+    fn routes(text: &str) -> io::Result<Vec<Route>> {
+        load(&[Config::parse("config.toml", text)?])
+    }
+
+    const CRASH: &str = "06-01 12:00:00.100  4242  4242 E AndroidRuntime: FATAL EXCEPTION: main";
+    const WARNING: &str = "06-01 12:00:00.200  4242  4260 W OkHttp  : Timeout reading /v1/feed";
+
+    #[test]
+    fn matches_events_and_levels() {
+        let routes: Vec<Route> = routes(
+            "[[route]]\nevent = [\"crash\", \"ANR\"]\nsink = \"bell\"\n\
+             [[route]]\nlevel = \"W\"\nsink = \"bell\"\n\
+             [[route]]\nevent = \"*\"\nsink = \"bell\"\n",
+        )
+        .unwrap();
+        let (crash, warning) = (entry(CRASH), entry(WARNING));
+        let events: Route = routes[0].clone();
+        assert!(events.matches(&crash, Some(EventKind::Crash)));
+        assert!(events.matches(&warning, Some(EventKind::Anr)));
+        assert!(!events.matches(&crash, Some(EventKind::AppStart)));
+        assert!(!events.matches(&crash, None));
+        let level: Route = routes[1].clone();
+        assert!(level.matches(&warning, None));
+        assert!(level.matches(&crash, None));
+        assert!(!level.matches(
+            &entry("06-01 12:00:00.300   612   640 I ActivityManager: Start proc"),
+            None
+        ));
+        let any: Route = routes[2].clone();
+        assert!(any.matches(&warning, Some(EventKind::AppDeath)));
+        assert!(!any.matches(&warning, None));
+    }
+
+    #[test]
+    fn throttles_to_the_limit() {
+        let mut route: Route =
+            routes("[[route]]\nlevel = \"E\"\nsink = \"bell\"\nlimit = \"2/10s\"\n")
+                .unwrap()
+                .remove(0);
+        let start: Instant = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        assert!(route.admit(at(0)));
+        assert!(route.admit(at(1_000)));
+        assert!(!route.admit(at(2_000)));
+        assert!(!route.admit(at(9_999)));
+        // The first slot frees up ten seconds after it was taken
+        assert!(route.admit(at(10_000)));
+        assert!(!route.admit(at(10_500)));
+        assert!(route.admit(at(11_000)));
+
+        let mut unlimited: Route = routes("[[route]]\nlevel = \"E\"\nsink = \"bell\"\n")
+            .unwrap()
+            .remove(0);
+        assert!((0..100).all(|_| unlimited.admit(start)));
+    }
+
+    #[test]
+    fn limits() {
+        let per = |s: &str| parse_limit(s).map(|l: Limit| (l.count, l.per.as_secs()));
+        assert_eq!(per("3/min"), Ok((3, 60)));
+        assert_eq!(per("1/10s"), Ok((1, 10)));
+        assert_eq!(per(" 20 / h "), Ok((20, 3600)));
+        for bad in ["0/min", "3", "3/day", "3/0s", "x/s", "3/10"] {
+            assert!(per(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_routes() {
+        let error = |text: &str| routes(text).unwrap_err().to_string();
+        assert_eq!(
+            error("[[route]]\nsink = \"bell\"\n"),
+            "config.toml:1: a route needs an event or a level"
+        );
+        assert_eq!(
+            error("[[route]]\nlevel = \"E\"\n"),
+            "config.toml:1: a route needs a sink"
+        );
+        assert!(
+            error("[[route]]\nevent = \"BOOM\"\nsink = \"bell\"\n")
+                .starts_with("config.toml:2: unknown event `BOOM`")
+        );
+        assert!(
+            error("[[route]]\nlevel = \"E\"\nsink = \"pager\"\n")
+                .starts_with("config.toml:3: unknown sink `pager`")
+        );
+        assert_eq!(
+            error("[[route]]\nlevel = \"E\"\nsink = \"file\"\n"),
+            "config.toml:3: the file sink needs a `path` key"
+        );
+        assert!(
+            error("[[route]]\nlevel = \"E\"\nsink = \"bell\"\nlimit = \"often\"\n")
+                .starts_with("config.toml:4: invalid limit `often`")
+        );
+    }
+
+    #[test]
+    fn file_sink_appends_a_line_per_entry() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("alert-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join("alerts.log");
+        let text: String = format!(
+            "[[route]]\nlevel = \"W\"\nsink = [\"file\", \"bell\"]\npath = \"{}\"\n",
+            path.display()
+        );
+        let mut router: Router = Router::new(routes(&text).unwrap(), None).unwrap();
+        let dispatch: Dispatch = router.dispatch(&entry(CRASH), Some(EventKind::Crash));
+        assert!(dispatch.failures.is_empty());
+        assert!(dispatch.control.starts_with('\x07'));
+        router.dispatch(&entry(WARNING), None);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "06-01 12:00:00.100 E/AndroidRuntime [CRASH] FATAL EXCEPTION: main\n\
+             06-01 12:00:00.200 W/OkHttp [] Timeout reading /v1/feed\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn alert_level_rings_and_notifies() {
+        let mut router: Router = Router::new(Vec::new(), Some("F")).unwrap();
+        let fatal: Logcat = entry(
+            "06-01 12:00:00.300  4242  4242 F libc    : Fatal signal 6 (SIGABRT)\x07 in tid 4242",
+        );
+        assert_eq!(
+            router.dispatch(&fatal, None).control,
+            // Bell (urgency), iTerm2 attention, then OSC 9 and OSC 777
+            // notifications without the control character
+            "\x07\x1b]1337;RequestAttention=yes\x07\
+             \x1b]9;libc: Fatal signal 6 (SIGABRT) in tid 4242\x07\
+             \x1b]777;notify;logcat F;libc: Fatal signal 6 (SIGABRT) in tid 4242\x07"
+        );
+        // One alert per burst
+        assert_eq!(router.dispatch(&fatal, None).control, "");
+        // Below the level, nothing rings
+        let mut router: Router = Router::new(Vec::new(), Some("F")).unwrap();
+        assert_eq!(router.dispatch(&entry(CRASH), None).control, "");
+        assert!(Router::new(Vec::new(), None).is_none());
+    }
+}
@@ -1,3 +1,6 @@
+use crate::render::Renderer;
+use std::{env, io, sync::OnceLock};
+
 #[allow(unused)]
 pub mod color {
     pub const F_BLACK: &str = "30";
@@ -37,6 +40,237 @@ pub mod color {
     pub const B_DEFAULT: &str = "49";
 }
 
+// The 16 basic colors as xterm shows them, for downgrading
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+// The levels of each component in the 6x6x6 color cube of the palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How many colors the terminal shows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Depth {
+    Plain,     // no styling at all (--color=never, NO_COLOR, a pipe)
+    Basic,     // the 16 colors of codes 30-37, 90-97 and their backgrounds
+    Palette,   // the xterm 256-color palette
+    TrueColor, // 24-bit RGB
+}
+
+static DEPTH: OnceLock<Depth> = OnceLock::new();
+
+// Tests run in parallel, each on its own thread, so each picks its depth
+// rather than racing for DEPTH
+#[cfg(test)]
+thread_local! {
+    static THREAD_DEPTH: std::cell::Cell<Option<Depth>> = const { std::cell::Cell::new(None) };
+}
+
+/// The depth of sequences made on this thread from now on, whatever other
+/// tests set.
+#[cfg(test)]
+pub fn set_thread_depth(depth: Depth) {
+    THREAD_DEPTH.set(Some(depth));
+}
+
+/// The color depth of the terminal we run in, going by `COLORTERM` and the
+/// terminfo name in `TERM` (`xterm-256color`, `xterm-direct`), unless
+/// `set_depth` said otherwise first.
+pub fn depth() -> Depth {
+    #[cfg(test)]
+    if let Some(depth) = THREAD_DEPTH.get() {
+        return depth;
+    }
+    *DEPTH.get_or_init(|| -> Depth {
+        let var = |name: &str| -> String { env::var(name).unwrap_or_default() };
+        let term: String = var("TERM");
+        if matches!(var("COLORTERM").as_str(), "truecolor" | "24bit")
+            || term.ends_with("-direct")
+            || term.ends_with("-truecolor")
+        {
+            Depth::TrueColor
+        } else if term.contains("256color") {
+            Depth::Palette
+        } else {
+            Depth::Basic
+        }
+    })
+}
+
+/// Fixes the depth whatever the terminal, e.g. for `--render-fixture`;
+/// like `set_plain`, this only takes before the first sequence is made.
+pub fn set_depth(depth: Depth) {
+    let _ = DEPTH.set(depth);
+}
+
+/// Turns styling off for `--color=never` and its like; sequences made
+/// before this keep their codes.
+pub fn set_plain() {
+    let _ = DEPTH.set(Depth::Plain);
+}
+
+/// A color, in any of the three ways SGR codes give one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    Basic(u8),   // 0-7, then 8-15 for the bright ones
+    Palette(u8), // of the 256-color palette
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// A color as theme files write it beyond the basic names: `color208`
+    /// from the palette or `#ff8700`.
+    pub fn parse(name: &str) -> Option<Color> {
+        if let Some(n) = name.strip_prefix("color") {
+            return n.parse().ok().map(Color::Palette);
+        }
+        let hex: &str = name.strip_prefix('#')?;
+        if hex.len() != 6 || !hex.bytes().all(|b: u8| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Color::Rgb(component(0)?, component(2)?, component(4)?))
+    }
+
+    /// The codes for this color, as the foreground or the background.
+    pub fn codes(self, background: bool) -> String {
+        let base: u8 = if background { 40 } else { 30 };
+        match self {
+            Color::Basic(n) if n < 8 => (base + n).to_string(),
+            Color::Basic(n) => (base + 60 + n - 8).to_string(),
+            Color::Palette(n) => format!("{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+
+    /// The closest color a terminal of `depth` can show.
+    pub fn fit(self, depth: Depth) -> Color {
+        match (self, depth) {
+            (Color::Palette(n), Depth::Basic) if n < 16 => Color::Basic(n),
+            (Color::Palette(_) | Color::Rgb(..), Depth::Basic) => basic(self.rgb()),
+            (Color::Rgb(r, g, b), Depth::Palette) => {
+                let level = |c: u8| -> usize { nearest((c, c, c), CUBE_LEVELS.map(|l| (l, l, l))) };
+                let cube: u8 = 16 + 36 * level(r) as u8 + 6 * level(g) as u8 + level(b) as u8;
+                // The grey ramp runs from 8 to 238 in steps of 10
+                let average: u16 = (r as u16 + g as u16 + b as u16) / 3;
+                let grey: u8 = 232 + (average.saturating_sub(3).min(237) / 10) as u8;
+                let i: usize = nearest(
+                    (r, g, b),
+                    [Color::Palette(cube).rgb(), Color::Palette(grey).rgb()],
+                );
+                Color::Palette([cube, grey][i])
+            }
+            _ => self,
+        }
+    }
+
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Basic(n) => BASIC_RGB[n as usize % 16],
+            Color::Palette(n @ 0..=15) => BASIC_RGB[n as usize],
+            Color::Palette(n @ 16..=231) => {
+                let i: u8 = n - 16;
+                let level = |c: u8| CUBE_LEVELS[c as usize];
+                (level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            Color::Palette(n) => {
+                let grey: u8 = 8 + 10 * (n - 232);
+                (grey, grey, grey)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+}
+
+// The basic color of about the same hue, or black or the grey of about
+// the same lightness for dark and unsaturated colors; the closest color by
+// distance would turn pastel palettes grey
+fn basic((r, g, b): (u8, u8, u8)) -> Color {
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    if max < 80 {
+        return Color::Basic(0);
+    }
+    if max - min < 32 {
+        let lightness: u16 = (r as u16 + g as u16 + b as u16) / 3;
+        return Color::Basic(match lightness {
+            0..50 => 0,
+            50..150 => 8,
+            150..220 => 7,
+            _ => 15,
+        });
+    }
+    // The HSV hue, in degrees
+    let (r, g, b, chroma) = (r as f32, g as f32, b as f32, (max - min) as f32);
+    let hue: f32 = match max {
+        _ if max as f32 == r => 60.0 * ((g - b) / chroma).rem_euclid(6.0),
+        _ if max as f32 == g => 60.0 * ((b - r) / chroma + 2.0),
+        _ => 60.0 * ((r - g) / chroma + 4.0),
+    };
+    let color: u8 = match hue {
+        h if h < 35.0 => 1,  // red, orange
+        h if h < 66.0 => 3,  // yellow
+        h if h < 165.0 => 2, // green
+        h if h < 200.0 => 6, // cyan
+        h if h < 270.0 => 4, // blue
+        h if h < 340.0 => 5, // magenta
+        _ => 1,
+    };
+    Color::Basic(if max >= 200 { color + 8 } else { color })
+}
+
+// The index of the color of `candidates` closest to `to`
+fn nearest(to: (u8, u8, u8), candidates: impl IntoIterator<Item = (u8, u8, u8)>) -> usize {
+    let distance = |(r, g, b): (u8, u8, u8)| -> i32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, to.0) + d(g, to.1) + d(b, to.2)
+    };
+    candidates
+        .into_iter()
+        .enumerate()
+        .min_by_key(|(_, c): &(usize, (u8, u8, u8))| distance(*c))
+        .map_or(0, |(i, _)| i)
+}
+
+// `codes` with their palette and RGB colors fitted to `depth`
+fn fit_codes(codes: &str, depth: Depth) -> String {
+    let codes: Vec<&str> = codes.split(';').collect();
+    let mut fitted: Vec<String> = Vec::new();
+    let mut i: usize = 0;
+    while i < codes.len() {
+        let arg = |n: usize| codes.get(i + n).and_then(|c: &&str| c.parse::<u8>().ok());
+        let color: Option<(Color, usize)> = match (codes[i], codes.get(i + 1)) {
+            ("38" | "48", Some(&"5")) => arg(2).map(|n: u8| (Color::Palette(n), 3)),
+            ("38" | "48", Some(&"2")) => (|| Some((Color::Rgb(arg(2)?, arg(3)?, arg(4)?), 5)))(),
+            _ => None,
+        };
+        match color {
+            Some((color, len)) => {
+                fitted.push(color.fit(depth).codes(codes[i] == "48"));
+                i += len;
+            }
+            None => {
+                fitted.push(codes[i].to_string());
+                i += 1;
+            }
+        }
+    }
+    fitted.join(";")
+}
+
 #[allow(unused)]
 pub mod attr {
     pub const RESET: &str = "0";
@@ -48,20 +282,336 @@ pub mod attr {
     pub const REVERSE: &str = "7";
 }
 
-#[derive(Clone, Debug)]
+// The codes --list-ansi shows and theme-edit cycles through
+pub const FGS: [&str; 17] = [
+    color::F_BLACK,
+    color::F_RED,
+    color::F_GREEN,
+    color::F_YELLOW,
+    color::F_BLUE,
+    color::F_PURPLE,
+    color::F_CYAN,
+    color::F_GREY,
+    color::FB_BLACK,
+    color::FB_RED,
+    color::FB_GREEN,
+    color::FB_YELLOW,
+    color::FB_BLUE,
+    color::FB_PURPLE,
+    color::FB_CYAN,
+    color::F_WHITE,
+    color::F_DEFAULT,
+];
+pub const BGS: [&str; 17] = [
+    color::B_BLACK,
+    color::B_RED,
+    color::B_GREEN,
+    color::B_YELLOW,
+    color::B_BLUE,
+    color::B_PURPLE,
+    color::B_CYAN,
+    color::B_GREY,
+    color::BB_BLACK,
+    color::BB_RED,
+    color::BB_GREEN,
+    color::BB_YELLOW,
+    color::BB_BLUE,
+    color::BB_PURPLE,
+    color::BB_CYAN,
+    color::B_WHITE,
+    color::B_DEFAULT,
+];
+pub const ATTRS: [&str; 7] = [
+    attr::RESET,
+    attr::BOLD,
+    attr::FAINT,
+    attr::UNDERLINE,
+    attr::SLOWBLINK,
+    attr::FASTBLINK,
+    attr::REVERSE,
+];
+
+// Names for the entries of FGS and BGS, and of ATTRS, in theme files
+pub const COLOR_NAMES: [&str; 17] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "purple",
+    "cyan",
+    "grey",
+    "bright_black",
+    "bright_red",
+    "bright_green",
+    "bright_yellow",
+    "bright_blue",
+    "bright_purple",
+    "bright_cyan",
+    "white",
+    "default",
+];
+pub const ATTR_NAMES: [&str; 7] = [
+    "reset",
+    "bold",
+    "faint",
+    "underline",
+    "slowblink",
+    "fastblink",
+    "reverse",
+];
+
+/// The codes of a color as a theme file names it (`red`, `bright_red`,
+/// `default`, `color208` from the 256-color palette, `#ff8700`) or gives it
+/// (`91`), for the foreground or the background.
+pub fn color_code(name: &str, background: bool) -> Option<String> {
+    let codes: &[&'static str; 17] = if background { &BGS } else { &FGS };
+    if let Some(color) = Color::parse(name) {
+        return Some(color.codes(background));
+    }
+    COLOR_NAMES
+        .iter()
+        .position(|n: &&str| n.eq_ignore_ascii_case(name))
+        .map(|i: usize| codes[i])
+        .or_else(|| codes.iter().find(|c: &&&str| **c == name).copied())
+        .map(str::to_string)
+}
+
+/// Whether `codes` are SGR codes as `Seq::parse` takes them, checked
+/// without making a sequence.
+pub fn valid_codes(codes: &str) -> bool {
+    let parts: Vec<&str> = codes.split(';').collect();
+    let mut valid: bool = parts
+        .iter()
+        .all(|c: &&str| !c.is_empty() && c.len() <= 3 && c.bytes().all(|b: u8| b.is_ascii_digit()));
+    // Palette indexes and RGB components go up to 255
+    for (i, pair) in parts.windows(2).enumerate() {
+        let args: usize = match pair {
+            ["38" | "48", "5"] => 1,
+            ["38" | "48", "2"] => 3,
+            _ => continue,
+        };
+        valid &= parts.len() >= i + 2 + args
+            && parts[i + 2..i + 2 + args]
+                .iter()
+                .all(|c: &&str| c.parse::<u8>().is_ok());
+    }
+    valid
+}
+
+/// The name a theme file gives the color of `code`, as `color_code` takes
+/// it back: `red`, `color208` or `#ff8700`.
+pub fn color_name(code: &str, background: bool) -> Option<String> {
+    let codes: &[&'static str; 17] = if background { &BGS } else { &FGS };
+    if let Some(i) = codes.iter().position(|c: &&str| *c == code) {
+        return Some(COLOR_NAMES[i].to_string());
+    }
+    let args: Vec<u8> = code
+        .split(';')
+        .skip(2)
+        .map(|c: &str| c.parse().ok())
+        .collect::<Option<Vec<u8>>>()?;
+    match (code.split(';').nth(1), &args[..]) {
+        (Some("5"), [n]) => Some(format!("color{}", n)),
+        (Some("2"), [r, g, b]) => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        _ => None,
+    }
+}
+
+/// The code of an attribute named (`bold`) or given (`1`) in a theme file.
+pub fn attr_code(name: &str) -> Option<&'static str> {
+    ATTR_NAMES
+        .iter()
+        .position(|n: &&str| n.eq_ignore_ascii_case(name))
+        .map(|i: usize| ATTRS[i])
+        .or_else(|| ATTRS.iter().find(|c: &&&str| **c == name).copied())
+}
+
+/// A style's escape sequence. Palette and RGB colors are fitted to the
+/// terminal's depth, and the sequence is empty without styling, while
+/// `codes` keeps them as they were given.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Seq {
     cached: String,
+    codes: String,
+    sgr: Sgr,
 }
 
 impl Seq {
     pub fn new(attr: &str, bg: &str, fg: &str) -> Self {
+        Self::from_codes(format!("{};{};{}", attr, bg, fg))
+    }
+    fn from_codes(codes: String) -> Self {
+        if depth() == Depth::Plain {
+            return Self {
+                cached: String::new(),
+                codes,
+                sgr: Sgr::default(),
+            };
+        }
+        let fitted: String = fit_codes(&codes, depth());
         Self {
-            cached: format!("\x1b[{};{};{}m", attr, bg, fg),
+            sgr: Sgr::parse(&fitted),
+            cached: format!("\x1b[{}m", fitted),
+            codes,
         }
     }
+    /// A sequence from raw SGR codes such as `1;45;97`, `38;5;208` for a
+    /// color of the 256-color palette or `38;2;255;135;0` for an RGB one.
+    pub fn parse(codes: &str) -> Option<Self> {
+        valid_codes(codes).then(|| Self::from_codes(codes.to_string()))
+    }
     pub fn as_str(&self) -> &str {
         &self.cached
     }
+    pub fn sgr(&self) -> &Sgr {
+        &self.sgr
+    }
+    /// The SGR codes, as `parse` takes them.
+    pub fn codes(&self) -> &str {
+        &self.codes
+    }
+    /// The style as given, its colors not fitted to the terminal.
+    pub fn given(&self) -> Sgr {
+        Sgr::parse(&self.codes)
+    }
+}
+
+/// What a sequence leaves the terminal in: its attributes and colors, so
+/// that going from one style to the next needs only the codes that differ.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sgr {
+    attrs: Vec<String>, // sorted
+    fg: Option<String>, // None for the default color
+    bg: Option<String>,
+}
+
+impl Sgr {
+    fn parse(codes: &str) -> Self {
+        let codes: Vec<&str> = codes.split(';').collect();
+        let mut sgr: Sgr = Sgr::default();
+        let mut i: usize = 0;
+        while i < codes.len() {
+            // 256-color and RGB codes carry their arguments along
+            let len: usize = match (codes[i], codes.get(i + 1)) {
+                ("38" | "48", Some(&"5")) => 3,
+                ("38" | "48", Some(&"2")) => 5,
+                _ => 1,
+            };
+            let code: String = codes[i..(i + len).min(codes.len())].join(";");
+            match codes[i].parse::<u8>() {
+                Ok(0) => sgr = Sgr::default(),
+                Ok(39) => sgr.fg = None,
+                Ok(49) => sgr.bg = None,
+                Ok(30..=38 | 90..=97) => sgr.fg = Some(code),
+                Ok(40..=48 | 100..=107) => sgr.bg = Some(code),
+                _ if !sgr.attrs.contains(&code) => sgr.attrs.push(code),
+                _ => {}
+            }
+            i += len;
+        }
+        sgr.attrs.sort();
+        sgr
+    }
+
+    /// The attribute codes, `0` when there are none.
+    pub fn attr_codes(&self) -> String {
+        match self.attrs.is_empty() {
+            true => attr::RESET.to_string(),
+            false => self.attrs.join(";"),
+        }
+    }
+
+    pub fn fg_code(&self) -> &str {
+        self.fg.as_deref().unwrap_or(color::F_DEFAULT)
+    }
+
+    pub fn bg_code(&self) -> &str {
+        self.bg.as_deref().unwrap_or(color::B_DEFAULT)
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Sgr::default()
+    }
+
+    /// The codes taking the terminal from `self` to `to`, empty when they
+    /// look the same. Attributes can only be turned off all at once, with a
+    /// reset.
+    pub fn transition(&self, to: &Sgr) -> String {
+        let default: Sgr = Sgr::default();
+        let mut codes: Vec<&str> = Vec::new();
+        let from: &Sgr = match self.attrs.iter().all(|a: &String| to.attrs.contains(a)) {
+            true => self,
+            false => {
+                codes.push("0");
+                &default
+            }
+        };
+        codes.extend(
+            to.attrs
+                .iter()
+                .filter(|a: &&String| !from.attrs.contains(a))
+                .map(String::as_str),
+        );
+        if to.fg != from.fg {
+            codes.push(to.fg.as_deref().unwrap_or("39"));
+        }
+        if to.bg != from.bg {
+            codes.push(to.bg.as_deref().unwrap_or("49"));
+        }
+        codes.join(";")
+    }
+
+    /// This style over `backdrop`'s colors, for a whole highlighted line;
+    /// a style with a background of its own, such as a badge, keeps it.
+    pub fn under(&self, backdrop: &Sgr) -> Sgr {
+        if self.bg.is_some() {
+            return self.clone();
+        }
+        let mut attrs: Vec<String> = self.attrs.clone();
+        attrs.extend(
+            backdrop
+                .attrs
+                .iter()
+                .filter(|a: &&String| !self.attrs.contains(a))
+                .cloned(),
+        );
+        attrs.sort();
+        Sgr {
+            attrs,
+            fg: backdrop.fg.clone(),
+            bg: backdrop.bg.clone(),
+        }
+    }
+
+    /// This style inside text of `base`, such as a URL in a message: its
+    /// own colors where it sets them, `base`'s elsewhere, and the
+    /// attributes of both.
+    pub fn within(&self, base: &Sgr) -> Sgr {
+        let mut attrs: Vec<String> = self.attrs.clone();
+        attrs.extend(
+            base.attrs
+                .iter()
+                .filter(|a: &&String| !self.attrs.contains(a))
+                .cloned(),
+        );
+        attrs.sort();
+        Sgr {
+            attrs,
+            fg: self.fg.clone().or_else(|| base.fg.clone()),
+            bg: self.bg.clone().or_else(|| base.bg.clone()),
+        }
+    }
+
+    /// Whether blanks look the same in this style as in the default one: no
+    /// background, reverse video or line decorations.
+    pub fn blank_safe(&self) -> bool {
+        self.bg.is_none()
+            && !self
+                .attrs
+                .iter()
+                .any(|a: &String| matches!(a.parse::<u8>(), Ok(4..=9 | 21 | 51..=53)))
+    }
 }
 
 pub fn reset() -> Seq {
@@ -72,70 +622,60 @@ pub fn reset() -> Seq {
     )
 }
 
-pub fn list_ansi() {
-    let fgs: [&str; 17] = [
-        color::F_BLACK,
-        color::F_RED,
-        color::F_GREEN,
-        color::F_YELLOW,
-        color::F_BLUE,
-        color::F_PURPLE,
-        color::F_CYAN,
-        color::F_GREY,
-        color::FB_BLACK,
-        color::FB_RED,
-        color::FB_GREEN,
-        color::FB_YELLOW,
-        color::FB_BLUE,
-        color::FB_PURPLE,
-        color::FB_CYAN,
-        color::F_WHITE,
-        color::F_DEFAULT,
-    ];
-    let bgs: [&str; 17] = [
-        color::B_BLACK,
-        color::B_RED,
-        color::B_GREEN,
-        color::B_YELLOW,
-        color::B_BLUE,
-        color::B_PURPLE,
-        color::B_CYAN,
-        color::B_GREY,
-        color::BB_BLACK,
-        color::BB_RED,
-        color::BB_GREEN,
-        color::BB_YELLOW,
-        color::BB_BLUE,
-        color::BB_PURPLE,
-        color::BB_CYAN,
-        color::B_WHITE,
-        color::B_DEFAULT,
-    ];
-    let attrs: [&str; 7] = [
-        attr::RESET,
-        attr::BOLD,
-        attr::FAINT,
-        attr::UNDERLINE,
-        attr::SLOWBLINK,
-        attr::FASTBLINK,
-        attr::REVERSE,
-    ];
-
-    for (i, bg) in bgs.iter().enumerate() {
-        println!("\nBackground {i}:");
-        for fg in fgs {
-            for at in attrs {
+pub fn list_ansi(out: &mut Renderer) -> io::Result<()> {
+    for (i, bg) in BGS.iter().enumerate() {
+        out.blank()?;
+        out.text(&format!("Background {i}:"))?;
+        for fg in FGS {
+            let mut line: String = String::new();
+            for at in ATTRS {
                 let seq: Seq = Seq::new(at, bg, fg);
-                print!(
+                line.push_str(&format!(
                     "{}^[{};{};{}m{}\x20",
                     seq.as_str(),
                     at,
                     bg,
                     fg,
                     reset().as_str()
-                );
+                ));
             }
-            println!();
+            out.text(&line)?;
+        }
+    }
+    list_palette(out)
+}
+
+// The 256-color palette, as theme files name its colors (`color208`): the
+// 16 basic colors, the 6x6x6 color cube and the grey ramp
+fn list_palette(out: &mut Renderer) -> io::Result<()> {
+    out.blank()?;
+    out.text("256-color palette (colorN in theme files, codes 38;5;N and 48;5;N):")?;
+    let rows: Vec<Vec<u8>> = [(0..=15).collect()]
+        .into_iter()
+        .chain((0..6).map(|r: u8| (16 + r * 36..16 + (r + 1) * 36).collect()))
+        .chain([(232..=255).collect()])
+        .collect();
+    for row in rows {
+        let mut line: String = String::new();
+        for n in row {
+            // Numbers readable on light and dark cells alike
+            let light: bool = match n {
+                0..=15 => matches!(n, 2 | 3 | 6 | 7 | 9..=15),
+                16..=231 => {
+                    let i: u8 = n - 16;
+                    2 * (i / 36) + 3 * (i / 6 % 6) + i % 6 >= 10
+                }
+                _ => n >= 244,
+            };
+            let fg: &str = if light {
+                color::F_BLACK
+            } else {
+                color::F_WHITE
+            };
+            let seq: Seq = Seq::new(attr::RESET, &Color::Palette(n).codes(true), fg);
+            line.push_str(&format!("{}{:>4}{}", seq.as_str(), n, reset().as_str()));
         }
+        out.text(&line)?;
     }
+    Ok(())
 }
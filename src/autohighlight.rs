@@ -0,0 +1,81 @@
+use regex::{Captures, Regex};
+use std::{ops::Range, sync::LazyLock};
+
+/// What `--auto-highlight` picks out of a message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Url,
+    Address,
+    Hex,
+    Path,
+    Duration,
+}
+
+// One alternative per kind, in the order they are tried at the same offset:
+// a URL's host and path aren't taken for an address or a file path
+const BUILT_IN: [(&str, Kind); 5] = [
+    (
+        r#"\b[a-zA-Z][a-zA-Z0-9+.-]*://[^\s"'<>(){}\[\]]+"#,
+        Kind::Url,
+    ),
+    (
+        r"\b(?:[0-9]{1,3}\.){3}[0-9]{1,3}(?::[0-9]{1,5})?\b|\[[0-9a-fA-F:.]*:[0-9a-fA-F:.]*\](?::[0-9]{1,5})?",
+        Kind::Address,
+    ),
+    (r"\b0[xX][0-9a-fA-F]+\b|\b[0-9a-f]{16}\b", Kind::Hex),
+    (r"(?:/[\w.@+-]+){2,}/?", Kind::Path),
+    (
+        r"\b[0-9]+(?:\.[0-9]+)?(?:ns|us|µs|ms|s|m|h)(?:[0-9]+(?:\.[0-9]+)?(?:ms|s|m))*\b",
+        Kind::Duration,
+    ),
+];
+
+static RE: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    let alternatives: Vec<String> = BUILT_IN
+        .iter()
+        .map(|(re, _): &(&str, Kind)| format!("({})", re))
+        .collect();
+    Regex::new(&alternatives.join("|")).unwrap()
+});
+
+/// The URLs, IP:port pairs, hex addresses, file paths and durations in
+/// `text`, in order.
+pub fn find(text: &str) -> Vec<(Range<usize>, Kind)> {
+    RE.captures_iter(text)
+        .filter_map(|caps: Captures| {
+            let (i, m) = (1..=BUILT_IN.len()).find_map(|i: usize| Some((i, caps.get(i)?)))?;
+            let kind: Kind = BUILT_IN[i - 1].1;
+            // Sentence punctuation after a URL or path isn't part of it
+            let end: usize = match kind {
+                Kind::Url | Kind::Path => {
+                    m.start()
+                        + m.as_str()
+                            .trim_end_matches(['.', ',', ';', ':', '!', '?'])
+                            .len()
+                }
+                _ => m.end(),
+            };
+            let ok: bool = match kind {
+                Kind::Address => octets_fit(m.as_str()),
+                // A path starts a word: not "and/or/not", nor a ratio
+                Kind::Path => text[..m.start()]
+                    .chars()
+                    .next_back()
+                    .is_none_or(|c: char| c.is_whitespace() || "'\"=([{,<".contains(c)),
+                _ => true,
+            };
+            ok.then_some((m.start()..end, kind))
+        })
+        .collect()
+}
+
+// 10.0.2.2:5555 but not the version 1.300.2.1
+fn octets_fit(address: &str) -> bool {
+    address.starts_with('[')
+        || address
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .all(|octet: &str| octet.parse::<u8>().is_ok())
+}
@@ -0,0 +1,115 @@
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+};
+
+// Bytes of the terminal's answers read at most
+const MAX_REPLY: usize = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+pub fn parse(s: &str) -> Result<Background, String> {
+    match s {
+        "light" => Ok(Background::Light),
+        "dark" => Ok(Background::Dark),
+        _ => Err(format!(
+            "unknown background `{}` (expected light or dark)",
+            s
+        )),
+    }
+}
+
+/// The terminal's background, from `COLORFGBG` as rxvt and Konsole set it,
+/// or else by asking the terminal its background color (OSC 11); None when
+/// neither tells.
+pub fn detect() -> Option<Background> {
+    env::var("COLORFGBG")
+        .ok()
+        .and_then(|v: String| from_colorfgbg(&v))
+        .or_else(query)
+}
+
+// "15;0" or "15;default;0": the last field is the background's color index
+fn from_colorfgbg(value: &str) -> Option<Background> {
+    match value.rsplit(';').next()?.parse::<u8>().ok()? {
+        7 | 15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+// Asks for the background color, then for the device attributes, which
+// every terminal answers, so that one ignoring the first question doesn't
+// keep us waiting for more than the read timeout
+fn query() -> Option<Background> {
+    let mut tty: File = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let saved: String = stty(&tty, &["-g"]).ok()?;
+    // Reads give up after 0.2 s without input
+    stty(&tty, &["-icanon", "-echo", "min", "0", "time", "2"]).ok()?;
+    let reply: io::Result<Vec<u8>> = (|| {
+        tty.write_all(b"\x1b]11;?\x1b\\\x1b[c")?;
+        let mut reply: Vec<u8> = Vec::new();
+        let mut buf: [u8; 64] = [0; 64];
+        while reply.len() < MAX_REPLY && !answered(&reply) {
+            match tty.read(&mut buf)? {
+                0 => break,
+                n => reply.extend_from_slice(&buf[..n]),
+            }
+        }
+        Ok(reply)
+    })();
+    let _ = stty(&tty, &[saved.trim()]);
+    from_osc11(&String::from_utf8_lossy(&reply.ok()?))
+}
+
+// Whether the device attributes answer, `ESC [ ? ... c`, came in
+fn answered(reply: &[u8]) -> bool {
+    reply
+        .windows(3)
+        .position(|w: &[u8]| w == b"\x1b[?")
+        .is_some_and(|i: usize| reply[i..].contains(&b'c'))
+}
+
+// `ESC ] 11 ; rgb:RRRR/GGGG/BBBB`, with 1 to 4 hex digits per component
+fn from_osc11(reply: &str) -> Option<Background> {
+    let rgb: &str = &reply[reply.find("11;rgb:")? + 7..];
+    let components: Vec<&str> = rgb.splitn(3, '/').collect();
+    if components.len() < 3 {
+        return None;
+    }
+    let mut luminance: f64 = 0.0;
+    for (component, weight) in components.iter().zip([0.2126, 0.7152, 0.0722]) {
+        let hex: &str = &component[..component.bytes().take_while(u8::is_ascii_hexdigit).count()];
+        if hex.is_empty() || hex.len() > 4 {
+            return None;
+        }
+        let max: f64 = (16_u32.pow(hex.len() as u32) - 1) as f64;
+        luminance += weight * u32::from_str_radix(hex, 16).ok()? as f64 / max;
+    }
+    Some(match luminance > 0.5 {
+        true => Background::Light,
+        false => Background::Dark,
+    })
+}
+
+fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+    let out: std::process::Output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty.try_clone()?))
+        .stderr(Stdio::null())
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
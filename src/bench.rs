@@ -0,0 +1,176 @@
+use crate::{
+    logcat::{self, FormatKind, Logcat},
+    render::Renderer,
+};
+use std::{
+    fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+// Lines the generator makes when no file is given
+const GENERATED_LINES: usize = 200_000;
+// Lines parsed differently that get shown
+const SHOWN_MISMATCHES: usize = 5;
+
+const TAGS: [&str; 8] = [
+    "ActivityManager",
+    "OkHttp",
+    "chromium",
+    "MyApp:Net",
+    "Fragment(Home)",
+    "SurfaceFlinger",
+    "System.err",
+    "  padded",
+];
+const WORDS: [&str; 12] = [
+    "connection",
+    "timeout:",
+    "key=value",
+    "(retry 3)",
+    "failed",
+    "Start proc 1234:com.example/u0a45",
+    "at com.example.Main.run(Main.java:42)",
+    "ok",
+    "state: idle",
+    "…",
+    "日本語",
+    "{\"id\": 7}",
+];
+
+type Parser = fn(FormatKind, &str) -> Option<Logcat>;
+
+/// Times the regex parser against the byte scanner over a capture, or over
+/// generated lines of every format (`--bench [FILE]`), and checks that
+/// they agree on every line.
+pub fn run(out: &mut Renderer, file: Option<&Path>) -> io::Result<()> {
+    let text: String = match file {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?,
+        None => generate(GENERATED_LINES),
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    out.text(&format!(
+        "{} lines, {:.1} MB, from {}",
+        lines.len(),
+        text.len() as f64 / 1e6,
+        file.map_or("the generator".to_string(), |p: &Path| p
+            .display()
+            .to_string())
+    ))?;
+
+    let (regex, by_regex) = time(&lines, logcat::parse_regex);
+    let (scanner, by_scanner) = time(&lines, logcat::parse_as);
+    for (name, elapsed, parsed) in [
+        ("regex", regex, &by_regex),
+        ("scanner", scanner, &by_scanner),
+    ] {
+        let secs: f64 = elapsed.as_secs_f64().max(1e-9);
+        out.text(&format!(
+            "{:<8}{:>8.3} s {:>12.0} lines/s {:>8.1} MB/s {:>10} parsed",
+            name,
+            secs,
+            lines.len() as f64 / secs,
+            text.len() as f64 / 1e6 / secs,
+            parsed.iter().filter(|p| p.is_some()).count()
+        ))?;
+    }
+    out.text(&format!(
+        "scanner: {:.1}x the regex throughput",
+        regex.as_secs_f64() / scanner.as_secs_f64().max(1e-9)
+    ))?;
+
+    let mismatches: Vec<usize> = (0..lines.len())
+        .filter(|i: &usize| by_regex[*i] != by_scanner[*i])
+        .collect();
+    out.text(&format!("{} lines parsed differently", mismatches.len()))?;
+    for i in mismatches.iter().take(SHOWN_MISMATCHES) {
+        let kind = |p: &Option<(FormatKind, Logcat)>| -> &str {
+            p.as_ref().map_or("none", |(k, _)| k.name())
+        };
+        out.text(&format!(
+            "  line {}: regex {}, scanner {}: {}",
+            i + 1,
+            kind(&by_regex[*i]),
+            kind(&by_scanner[*i]),
+            lines[*i]
+        ))?;
+    }
+    Ok(())
+}
+
+// Every line parsed as `parse_line` does, trying the formats in turn
+fn time(lines: &[&str], parse: Parser) -> (Duration, Vec<Option<(FormatKind, Logcat)>>) {
+    let start: Instant = Instant::now();
+    let parsed: Vec<Option<(FormatKind, Logcat)>> = lines
+        .iter()
+        .map(|line: &&str| {
+            FormatKind::ALL
+                .iter()
+                .find_map(|k: &FormatKind| parse(*k, line).map(|lc: Logcat| (*k, lc)))
+        })
+        .collect();
+    (start.elapsed(), parsed)
+}
+
+// Lines in every format, mostly threadtime as adb gives by default, with
+// odd tags and messages and some lines of no format
+fn generate(count: usize) -> String {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = |n: usize| -> usize {
+        // xorshift64
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n as u64) as usize
+    };
+    let mut text: String = String::new();
+    for i in 0..count {
+        let level: char = ['V', 'D', 'I', 'W', 'E', 'F', 'A'][next(7)];
+        let tag: &str = TAGS[next(TAGS.len())];
+        let message: String = (0..1 + next(8))
+            .map(|_| WORDS[next(WORDS.len())])
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let (pid, tid) = (1 + next(32000), 1 + next(32000));
+        let time: String = format!(
+            "{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+            1 + i / 2_000_000 % 12,
+            1 + i / 100_000 % 28,
+            i / 3600 % 24,
+            i / 60 % 60,
+            i % 60,
+            next(1000)
+        );
+        let line: String = match next(100) {
+            0..=59 => format!("{time} {pid:>5} {tid:>5} {level} {tag}: {message}"),
+            60..=64 => format!(
+                "{time}  u0_a{} {pid:>5} {tid:>5} {level} {tag}: {message}",
+                next(300)
+            ),
+            65..=67 => format!(
+                "2025-{time}{:03} {pid:>5} {tid:>5} {level} {tag}: {message}",
+                next(1000)
+            ),
+            68..=75 => format!("{time} {level}/{tag}({pid:>5}): {message}"),
+            76..=81 => format!("{level}/{tag}({pid:>5}): {message}"),
+            82..=85 => format!("{level}/{tag}: {message}"),
+            86..=88 => format!(
+                "{}.{:03} {pid:>5} {tid:>5} {level} {tag}: {message}",
+                1_700_000_000 + i,
+                next(1000)
+            ),
+            89..=91 => format!(
+                "<{}>[{:>5}.{:06}] {message}",
+                next(8),
+                i / 1000,
+                next(1_000_000)
+            ),
+            92..=94 => format!("{level}({pid:>5}) {message}  ({tag})"),
+            _ => message,
+        };
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
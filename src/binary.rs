@@ -0,0 +1,215 @@
+use crate::logcat::Logcat;
+use std::io::{self, Read};
+
+// Buffers whose payload is a binary event rather than tag and message
+const LOG_ID_EVENTS: u32 = 2;
+const LOG_ID_STATS: u32 = 5;
+const LOG_ID_SECURITY: u32 = 6;
+
+/// Entries of `adb logcat -B` output (or a saved binary log), decoded from
+/// the kernel/logd `logger_entry` records:
+///
+/// ```text
+/// u16 len, u16 hdr_size, i32 pid, u32 tid, u32 sec, u32 nsec,
+/// [u32 lid (v3+), u32 uid (v4)], payload[len]
+/// ```
+///
+/// A text payload is a priority byte, then the NUL-terminated tag and
+/// message. Timestamps come out as `sec.nsec`, as with `-v epoch,nsec`.
+pub fn entries<R: Read>(reader: R) -> Entries<R> {
+    Entries {
+        reader,
+        pending: Vec::new(),
+    }
+}
+
+pub struct Entries<R> {
+    reader: R,
+    pending: Vec<Logcat>, // further lines of a multi-line message, reversed
+}
+
+impl<R: Read> Iterator for Entries<R> {
+    type Item = io::Result<Logcat>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(lc) = self.pending.pop() {
+            return Some(Ok(lc));
+        }
+        match self.read_entry() {
+            Ok(Some(mut lines)) => {
+                lines.reverse();
+                let first: Logcat = lines.pop()?;
+                self.pending = lines;
+                Some(Ok(first))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl<R: Read> Entries<R> {
+    // One record, as one entry per message line; None at a clean EOF
+    fn read_entry(&mut self) -> io::Result<Option<Vec<Logcat>>> {
+        let mut head: [u8; 4] = [0; 4];
+        match self.reader.read_exact(&mut head) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            res => res?,
+        }
+        let len: usize = u16::from_le_bytes([head[0], head[1]]) as usize;
+        // v1 records have padding where later ones keep the header size
+        let hdr_size: usize = match u16::from_le_bytes([head[2], head[3]]) {
+            0 => 20,
+            n => n as usize,
+        };
+        if !(20..=64).contains(&hdr_size) {
+            return Err(io::Error::other(format!(
+                "not a binary log: header size {} (is the input `adb logcat -B`?)",
+                hdr_size
+            )));
+        }
+
+        let mut rest: Vec<u8> = vec![0; hdr_size - 4];
+        self.reader.read_exact(&mut rest)?;
+        let word = |i: usize| -> u32 {
+            rest.get(i * 4..i * 4 + 4)
+                .map_or(0, |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let (pid, tid, sec, nsec) = (word(0) as i32, word(1), word(2), word(3));
+        let lid: Option<u32> = (hdr_size >= 24).then(|| word(4));
+
+        let mut payload: Vec<u8> = vec![0; len];
+        self.reader.read_exact(&mut payload)?;
+
+        let base: Logcat = Logcat {
+            timestamp: format!("{}.{:09}", sec, nsec),
+            process: pid.to_string(),
+            thread: tid.to_string(),
+            ..Default::default()
+        };
+        if matches!(lid, Some(LOG_ID_EVENTS | LOG_ID_STATS | LOG_ID_SECURITY)) {
+            return Ok(Some(vec![event(base, &payload)]));
+        }
+
+        let level: &str = match payload.first() {
+            Some(2) => "V",
+            Some(3) => "D",
+            Some(4) => "I",
+            Some(5) => "W",
+            Some(6) => "E",
+            Some(7) => "F",
+            _ => "V",
+        };
+        let mut fields = payload.get(1..).unwrap_or(&[]).split(|b: &u8| *b == 0);
+        let tag: String = String::from_utf8_lossy(fields.next().unwrap_or(&[])).into_owned();
+        let message: String = String::from_utf8_lossy(fields.next().unwrap_or(&[])).into_owned();
+
+        // Like the text formats, one entry per line of the message, with
+        // `\r\n` and a bare `\r` ending a line as well
+        Ok(Some(
+            message
+                .replace("\r\n", "\n")
+                .trim_end_matches(['\r', '\n'])
+                .split(['\r', '\n'])
+                .map(|line: &str| Logcat {
+                    level: level.to_string(),
+                    tag: tag.clone(),
+                    message: line.to_string(),
+                    ..base.clone()
+                })
+                .collect(),
+        ))
+    }
+}
+
+// Binary events aren't decoded (that needs the device's event-log-tags);
+// show the tag number and the raw bytes
+fn event(base: Logcat, payload: &[u8]) -> Logcat {
+    let tag: u32 = payload
+        .get(..4)
+        .map_or(0, |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let bytes: Vec<String> = payload
+        .iter()
+        .skip(4)
+        .map(|b: &u8| format!("{:02x}", b))
+        .collect();
+    Logcat {
+        level: "I".to_string(),
+        tag: format!("event:{}", tag),
+        message: bytes.join(" "),
+        ..base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A v4 record of the main buffer: header, then priority, tag and message
+    fn record(priority: u8, tag: &str, message: &[u8]) -> Vec<u8> {
+        let mut payload: Vec<u8> = vec![priority];
+        payload.extend_from_slice(tag.as_bytes());
+        payload.push(0);
+        payload.extend_from_slice(message);
+        payload.push(0);
+        let mut rec: Vec<u8> = Vec::new();
+        rec.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        rec.extend_from_slice(&28u16.to_le_bytes());
+        for word in [1234u32, 1240, 1_717_243_200, 5_000_000, 0, 10_012] {
+            rec.extend_from_slice(&word.to_le_bytes());
+        }
+        rec.extend_from_slice(&payload);
+        rec
+    }
+
+    fn decode(input: &[u8]) -> Vec<Logcat> {
+        entries(input).collect::<io::Result<_>>().unwrap()
+    }
+
+    fn messages(entries: &[Logcat]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|lc: &Logcat| lc.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn fields() {
+        let lcs: Vec<Logcat> = decode(&record(6, "Tag", b"failed"));
+        assert_eq!(lcs.len(), 1);
+        let lc: &Logcat = &lcs[0];
+        assert_eq!(lc.level, "E");
+        assert_eq!(lc.tag, "Tag");
+        assert_eq!((lc.process.as_str(), lc.thread.as_str()), ("1234", "1240"));
+        assert_eq!(lc.timestamp, "1717243200.005000000");
+    }
+
+    #[test]
+    fn splits_on_crlf_and_bare_cr() {
+        let lcs: Vec<Logcat> = decode(&record(4, "Tag", b"one\r\ntwo\rthree\nfour\r\n"));
+        assert_eq!(messages(&lcs), ["one", "two", "three", "four"]);
+        assert!(
+            lcs.iter()
+                .all(|lc: &Logcat| lc.tag == "Tag" && lc.level == "I")
+        );
+    }
+
+    #[test]
+    fn lines_stay_with_their_record() {
+        let mut input: Vec<u8> = record(5, "First", b"a\r\nb");
+        input.extend(record(3, "Second", b"c\rd\r"));
+        let lcs: Vec<Logcat> = decode(&input);
+        assert_eq!(messages(&lcs), ["a", "b", "c", "d"]);
+        let tags: Vec<&str> = lcs.iter().map(|lc: &Logcat| lc.tag.as_str()).collect();
+        assert_eq!(tags, ["First", "First", "Second", "Second"]);
+    }
+
+    #[test]
+    fn not_a_binary_log() {
+        let err: io::Error = entries(&b"06-01 12:00:00.123"[..])
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().contains("not a binary log"));
+    }
+}
@@ -0,0 +1,113 @@
+use regex::Regex;
+use std::{
+    fs::File,
+    io::{self, BufRead, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, Output, Stdio},
+    sync::LazyLock,
+};
+
+// "------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------",
+// and the "------ 0.245s was the duration of 'SYSTEM LOG' ------" closing it
+static RE_SECTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^------ (.*?)(?: \(.*\))? ------$").unwrap());
+
+// The logcat dumps; everything else in a bugreport is dumpsys and friends
+const LOG_SECTIONS: [&str; 2] = ["SYSTEM LOG", "EVENT LOG"];
+
+/// The text of the bugreport at `path`: the file itself, or the
+/// `bugreport-*.txt` inside a bugreport zip, streamed by `unzip -p` (the
+/// returned child, to be reaped by the caller).
+pub fn open(path: &Path) -> io::Result<(Box<dyn Read + Send>, Option<Child>)> {
+    let mut file: File = File::open(path)?;
+    let mut magic: [u8; 4] = [0; 4];
+    let n: usize = file.read(&mut magic)?;
+    if magic[..n] != *b"PK\x03\x04" {
+        return Ok((Box::new(File::open(path)?), None));
+    }
+
+    let unzip_err = |e: io::Error| -> io::Error {
+        io::Error::other(format!(
+            "{}: unzip is needed for zipped bugreports: {}",
+            path.display(),
+            e
+        ))
+    };
+    let listing: Output = Command::new("unzip")
+        .arg("-Z1")
+        .arg(path)
+        .output()
+        .map_err(unzip_err)?;
+    let names: String = String::from_utf8_lossy(&listing.stdout).into_owned();
+    let entry: &str = names
+        .lines()
+        .find(|n: &&str| n.starts_with("bugreport-") && n.ends_with(".txt"))
+        .ok_or_else(|| {
+            io::Error::other(format!(
+                "{}: no bugreport-*.txt inside; is it a bugreport zip?",
+                path.display()
+            ))
+        })?;
+    let mut child: Child = Command::new("unzip")
+        .arg("-p")
+        .arg(path)
+        .arg(entry)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(unzip_err)?;
+    let stdout: ChildStdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| io::Error::other("unzip has no stdout"))?;
+    Ok((Box::new(stdout), Some(child)))
+}
+
+/// Only the lines of the SYSTEM LOG and EVENT LOG sections of a bugreport,
+/// without their section headers; their `--------- beginning of main`
+/// separators stay to tell the buffers apart.
+pub fn sections<R: BufRead>(reader: R) -> Sections<R> {
+    Sections {
+        reader,
+        inside: false,
+        found: false,
+        buf: Vec::new(),
+        pos: 0,
+    }
+}
+
+pub struct Sections<R> {
+    reader: R,
+    inside: bool,
+    found: bool, // whether any log section came up
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> Read for Sections<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+                if !self.found {
+                    return Err(io::Error::other(
+                        "no SYSTEM LOG or EVENT LOG section; is the input a bugreport?",
+                    ));
+                }
+                return Ok(0);
+            }
+            let line: String = String::from_utf8_lossy(&self.buf).into_owned();
+            if let Some(c) = RE_SECTION.captures(line.trim_end()) {
+                self.inside = LOG_SECTIONS.contains(&&c[1]);
+                self.found |= self.inside;
+                self.buf.clear();
+            } else if !self.inside {
+                self.buf.clear();
+            }
+        }
+        let n: usize = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{classify::EventKind, filter::Hidden, stats::Stats};
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+// How often progress is saved; at most this much output repeats on resume
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// Progress through a file read on stdin, so an interrupted run can pick up
+/// where it stopped: the byte offset of the next unread line, the size and
+/// modification time telling it is still the same file, and the counts
+/// behind --stats and --fail-on. Kept as `key\tvalue` lines.
+pub struct Checkpoint {
+    path: PathBuf,
+    input: (u64, u64), // size, mtime
+    pub offset: u64,
+    pub stats: Stats,
+    saved: Instant,
+}
+
+/// Stdin as the file it was redirected from, positioned at the checkpoint
+/// in `path` if an earlier run left one.
+pub fn resume(path: &Path) -> io::Result<(File, Checkpoint)> {
+    let mut file: File = stdin_file()?;
+    let meta: Metadata = file.metadata()?;
+    if !meta.is_file() {
+        return Err(io::Error::other(
+            "--resume: stdin must be redirected from a file (`< capture.log`)",
+        ));
+    }
+    let checkpoint: Checkpoint = Checkpoint::load(path, input_id(&meta))?;
+    file.seek(SeekFrom::Start(checkpoint.offset))?;
+    Ok((file, checkpoint))
+}
+
+#[cfg(unix)]
+fn stdin_file() -> io::Result<File> {
+    use std::os::fd::AsFd;
+    Ok(File::from(io::stdin().as_fd().try_clone_to_owned()?))
+}
+
+#[cfg(windows)]
+fn stdin_file() -> io::Result<File> {
+    use std::os::windows::io::AsHandle;
+    Ok(File::from(io::stdin().as_handle().try_clone_to_owned()?))
+}
+
+fn input_id(meta: &Metadata) -> (u64, u64) {
+    let mtime: u64 = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d: Duration| d.as_secs());
+    (meta.len(), mtime)
+}
+
+impl Checkpoint {
+    fn load(path: &Path, input: (u64, u64)) -> io::Result<Self> {
+        let mut checkpoint: Checkpoint = Checkpoint {
+            path: path.to_path_buf(),
+            input,
+            offset: 0,
+            stats: Stats::default(),
+            saved: Instant::now(),
+        };
+        let text: String = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(checkpoint),
+            Err(e) => return Err(e),
+        };
+
+        let bad = || -> io::Error {
+            io::Error::other(format!(
+                "--resume {}: not a checkpoint file",
+                path.display()
+            ))
+        };
+        let (mut size, mut mtime): (Option<u64>, Option<u64>) = (None, None);
+        let stats: &mut Stats = &mut checkpoint.stats;
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let num = |i: usize| -> io::Result<usize> {
+                fields
+                    .get(i)
+                    .and_then(|f: &&str| f.parse().ok())
+                    .ok_or_else(bad)
+            };
+            match fields[0] {
+                "size" => size = Some(num(1)? as u64),
+                "mtime" => mtime = Some(num(1)? as u64),
+                "offset" => checkpoint.offset = num(1)? as u64,
+                "total" => stats.total = num(1)?,
+                "unparsed" => stats.unparsed = num(1)?,
+                "out_of_order" => stats.out_of_order = num(1)?,
+                "levels" => {
+                    for (i, count) in stats.levels.iter_mut().enumerate() {
+                        *count = num(i + 1)?;
+                    }
+                }
+                // Events of classifiers no longer configured are dropped
+                "event" => {
+                    if let Some(kind) = EventKind::all()
+                        .into_iter()
+                        .find(|k: &EventKind| Some(&k.label()) == fields.get(1))
+                    {
+                        stats.events.insert(kind, num(2)?);
+                    }
+                }
+                "blocking" => {
+                    let tag: &str = fields.get(1).ok_or_else(bad)?;
+                    stats.blocking.insert(tag.to_string(), num(2)?);
+                }
+                "dropped" => {
+                    if let Some(i) = Hidden::ALL
+                        .iter()
+                        .position(|h: &Hidden| Some(&h.label()) == fields.get(1))
+                    {
+                        stats.dropped[i] = num(2)?;
+                    }
+                }
+                _ => return Err(bad()),
+            }
+        }
+        if (size, mtime) != (Some(input.0), Some(input.1)) {
+            return Err(io::Error::other(format!(
+                "--resume {}: saved for a different or changed input; remove it to start over",
+                path.display()
+            )));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Whether progress should be saved again.
+    pub fn due(&self) -> bool {
+        self.saved.elapsed() >= INTERVAL
+    }
+
+    /// Records that the input was handled up to `offset`, with `stats`.
+    pub fn save(&mut self, offset: u64, stats: &Stats) -> io::Result<()> {
+        self.offset = offset;
+        self.saved = Instant::now();
+
+        // Write aside then rename, so an interruption mid-write keeps the last one
+        let tmp: PathBuf = self.path.with_extension("tmp");
+        let mut f: io::BufWriter<File> = io::BufWriter::new(File::create(&tmp)?);
+        writeln!(f, "size\t{}", self.input.0)?;
+        writeln!(f, "mtime\t{}", self.input.1)?;
+        writeln!(f, "offset\t{}", offset)?;
+        writeln!(f, "total\t{}", stats.total)?;
+        writeln!(f, "unparsed\t{}", stats.unparsed)?;
+        writeln!(f, "out_of_order\t{}", stats.out_of_order)?;
+        let levels: Vec<String> = stats.levels.iter().map(usize::to_string).collect();
+        writeln!(f, "levels\t{}", levels.join("\t"))?;
+        for (kind, count) in &stats.events {
+            writeln!(f, "event\t{}\t{}", kind.label(), count)?;
+        }
+        for (tag, count) in &stats.blocking {
+            writeln!(f, "blocking\t{}\t{}", tag, count)?;
+        }
+        for (why, count) in Hidden::ALL.iter().zip(stats.dropped) {
+            if count > 0 {
+                writeln!(f, "dropped\t{}\t{}", why.label(), count)?;
+            }
+        }
+        f.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// The input was read to the end; there is nothing left to resume.
+    pub fn remove(self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
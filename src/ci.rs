@@ -0,0 +1,129 @@
+use crate::{classify::EventKind, logcat::Logcat};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CiSystem {
+    Github,
+    Gitlab,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Output {
+    Terminal,
+    Ci(CiSystem),
+}
+
+/// clap value parser for `--output`.
+pub fn parse_output(s: &str) -> Result<Output, String> {
+    match s {
+        "terminal" => Ok(Output::Terminal),
+        "ci-github" => Ok(Output::Ci(CiSystem::Github)),
+        "ci-gitlab" => Ok(Output::Ci(CiSystem::Gitlab)),
+        _ => Err("expected terminal, ci-github or ci-gitlab".to_string()),
+    }
+}
+
+/// Folds the log into sections, one per app start, crash, ANR or death,
+/// and turns warnings and errors into GitHub annotations.
+pub struct Sections {
+    system: CiSystem,
+    open: Option<String>, // GitLab section name
+    count: usize,
+}
+
+impl Sections {
+    pub fn new(system: CiSystem) -> Self {
+        Self {
+            system,
+            open: None,
+            count: 0,
+        }
+    }
+
+    /// Marker lines to write before `lc`, closing the current section and
+    /// opening another when `lc` starts one.
+    pub fn before(&mut self, lc: &Logcat, event: Option<EventKind>) -> Vec<String> {
+        let starts: bool = matches!(
+            event,
+            Some(EventKind::AppStart | EventKind::Crash | EventKind::Anr | EventKind::AppDeath)
+        );
+        if !starts && self.count > 0 {
+            return Vec::new();
+        }
+        let title: String = match event {
+            Some(kind) if starts => format!("{} {}: {}", kind.label(), lc.tag.trim(), lc.message),
+            _ => "logcat".to_string(),
+        };
+        let mut lines: Vec<String> = self.close().into_iter().collect();
+        self.count += 1;
+        lines.push(match self.system {
+            CiSystem::Github => format!("::group::{}", escape_data(&title)),
+            CiSystem::Gitlab => {
+                let name: String = format!("logcat_{}", self.count);
+                let line: String = format!(
+                    "\x1b[0Ksection_start:{}:{}\r\x1b[0K{}",
+                    now(),
+                    name,
+                    title.replace(['\r', '\n'], " ")
+                );
+                self.open = Some(name);
+                line
+            }
+        });
+        lines
+    }
+
+    /// The marker ending the current section, if one is open.
+    pub fn close(&mut self) -> Option<String> {
+        match self.system {
+            CiSystem::Github => (self.count > 0).then(|| "::endgroup::".to_string()),
+            CiSystem::Gitlab => self
+                .open
+                .take()
+                .map(|name: String| format!("\x1b[0Ksection_end:{}:{}\r\x1b[0K", now(), name)),
+        }
+    }
+
+    /// The workflow command replacing a warning or error entry, for GitHub.
+    pub fn annotation(&self, lc: &Logcat) -> Option<String> {
+        let command: &str = match (self.system, lc.level.as_str()) {
+            (CiSystem::Github, "E" | "F" | "A") => "error",
+            (CiSystem::Github, "W") => "warning",
+            _ => return None,
+        };
+        let mut text: String = String::new();
+        if !lc.timestamp.is_empty() {
+            text.push_str(&lc.timestamp);
+            text.push(' ');
+        }
+        match (lc.process.is_empty(), lc.thread.is_empty()) {
+            (true, _) => {}
+            (false, true) => text.push_str(&format!("[{}] ", lc.process)),
+            (false, false) => text.push_str(&format!("[{}/{}] ", lc.process, lc.thread)),
+        }
+        text.push_str(&lc.message);
+        Some(format!(
+            "::{} title={}::{}",
+            command,
+            escape_property(lc.tag.trim()),
+            escape_data(&text)
+        ))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// Workflow command escaping, as in @actions/core
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
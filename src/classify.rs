@@ -0,0 +1,266 @@
+use crate::{
+    ansi::Seq,
+    config::{Config, Section},
+};
+use regex::Regex;
+use std::{
+    io,
+    sync::{LazyLock, OnceLock},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Security,
+    Crash,
+    Anr,
+    AppStart,
+    AppDeath,
+    Jank,
+    GcPressure,
+    Blocking, // the main thread held up: slow messages, disk or network I/O, binder
+    Connectivity,
+    Custom(usize), // index into the registry loaded from --config
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 9] = [
+        EventKind::Security,
+        EventKind::Crash,
+        EventKind::Anr,
+        EventKind::AppStart,
+        EventKind::AppDeath,
+        EventKind::Jank,
+        EventKind::GcPressure,
+        EventKind::Blocking,
+        EventKind::Connectivity,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EventKind::Security => "SEC",
+            EventKind::Crash => "CRASH",
+            EventKind::Anr => "ANR",
+            EventKind::AppStart => "START",
+            EventKind::AppDeath => "DEATH",
+            EventKind::Jank => "JANK",
+            EventKind::GcPressure => "GC",
+            EventKind::Blocking => "BLOCK",
+            EventKind::Connectivity => "NET",
+            EventKind::Custom(i) => &customs()[*i].name,
+        }
+    }
+
+    /// The built-in kinds followed by the user-defined ones.
+    pub fn all() -> Vec<EventKind> {
+        let mut all: Vec<EventKind> = Self::ALL.to_vec();
+        all.extend((0..customs().len()).map(EventKind::Custom));
+        all
+    }
+
+    pub fn custom(&self) -> Option<&'static Custom> {
+        match self {
+            EventKind::Custom(i) => customs().get(*i),
+            _ => None,
+        }
+    }
+}
+
+pub struct Classifier {
+    pub kind: EventKind,
+    re: Regex,
+}
+
+impl Classifier {
+    fn new(kind: EventKind, pattern: &str) -> Self {
+        Self {
+            kind,
+            re: Regex::new(pattern).unwrap(),
+        }
+    }
+
+    pub fn matches(&self, message: &str) -> bool {
+        self.re.is_match(message)
+    }
+}
+
+// Built-in classifiers, checked in order
+pub static CLASSIFIERS: LazyLock<Vec<Classifier>> = LazyLock::new(|| -> Vec<Classifier> {
+    vec![
+        // SELinux: "avc: denied { read } for ..."
+        Classifier::new(EventKind::Security, r"\bavc:\s+denied\b"),
+        // ActivityManager / PackageManager: "Permission Denial: ..."
+        Classifier::new(EventKind::Security, r"\bPermission Denial\b"),
+        Classifier::new(EventKind::Security, r"\bSecurityException\b"),
+        // libc / java.io: "open failed: EACCES (Permission denied)"
+        Classifier::new(EventKind::Security, r"(?i)\bpermission denied\b"),
+        // AndroidRuntime: "FATAL EXCEPTION: main"; libc: "Fatal signal 11 (SIGSEGV), code 1 ..."
+        Classifier::new(
+            EventKind::Crash,
+            r"\bFATAL EXCEPTION\b|\bFatal signal [0-9]+",
+        ),
+        // ActivityManager: "ANR in com.example (com.example/.MainActivity)"
+        Classifier::new(
+            EventKind::Anr,
+            r"\bANR in \S+|\bApplication Not Responding\b",
+        ),
+        // ActivityManager: "Start proc 1234:com.example/u0a123 for ..."; "Displayed com.example/.Main: +512ms"
+        Classifier::new(
+            EventKind::AppStart,
+            r"\bStart proc [0-9]+:|\bDisplayed \S+: \+",
+        ),
+        // ActivityManager: "Process com.example (pid 1234) has died"; "Killing 1234:com.example/u0a123 ..."
+        Classifier::new(
+            EventKind::AppDeath,
+            r"\bProcess \S+ \(pid [0-9]+\) has died|\bKilling [0-9]+:",
+        ),
+        // Choreographer: "Skipped 42 frames!  The application may be doing too much work ..."; HWUI: "Davey! duration=812ms"
+        Classifier::new(
+            EventKind::Jank,
+            r"\bSkipped [0-9]+ frames!|\bDavey! duration=",
+        ),
+        // art: "Waiting for a blocking GC Alloc", "WaitForGcToComplete blocked ...", "Clamp target GC heap ..."
+        Classifier::new(
+            EventKind::GcPressure,
+            r"\bWaiting for a blocking GC\b|\bWaitForGcToComplete\b|\bClamp target GC heap\b|\bThrowing OutOfMemoryError\b",
+        ),
+        // Looper: "Slow dispatch took 212ms main h=android.app.ActivityThread$H ...",
+        // "Slow delivery took 1034ms main ...", "Slow Looper main: doFrame is 546ms late ..."
+        Classifier::new(
+            EventKind::Blocking,
+            r"\bSlow (?:dispatch|delivery) took [0-9]+ms main\b|\bSlow Looper main:",
+        ),
+        // StrictMode: "StrictMode policy violation; ~duration=25 ms: android.os.strictmode.DiskReadViolation"
+        Classifier::new(
+            EventKind::Blocking,
+            r"\bStrictMode policy violation\b.*\b(?:Disk(?:Read|Write)|Network)Violation\b",
+        ),
+        // IPCThreadState: "binder thread pool (15 threads) starved for 1203 ms"; "Slow Binder call ..."
+        Classifier::new(
+            EventKind::Blocking,
+            r"\bbinder thread pool \([0-9]+ threads\) starved\b|(?i)\bslow binder\b",
+        ),
+        // ConnectivityService / WifiService state changes
+        Classifier::new(
+            EventKind::Connectivity,
+            r"(?i)\b(?:network|wifi|wi-fi|mobile data)\b.*\b(?:connected|disconnected|lost|validated)\b",
+        ),
+    ]
+});
+
+/// A classifier defined in a `[[classifier]]` table of the config file:
+///
+/// ```toml
+/// [[classifier]]
+/// name = "LOGIN"
+/// regex = 'Login (?P<result>ok|failed) for (?P<user>\S+)'
+/// style = "1;45;97"      # SGR codes for the badge, as in --list-ansi
+/// fields = ["user"]      # named groups to show (default: all of them)
+/// notify = true          # alert like --alert-level does
+/// ```
+pub struct Custom {
+    pub name: String,
+    re: Regex,
+    pub style: Option<Seq>,
+    pub fields: Vec<String>,
+    pub notify: bool,
+}
+
+impl Custom {
+    /// The extraction fields captured from `message`, as name/value pairs.
+    pub fn extract(&self, message: &str) -> Vec<(&str, String)> {
+        let Some(caps) = self.re.captures(message) else {
+            return Vec::new();
+        };
+        self.fields
+            .iter()
+            .filter_map(|f: &String| {
+                caps.name(f)
+                    .map(|m: regex::Match| (f.as_str(), m.as_str().to_string()))
+            })
+            .collect()
+    }
+}
+
+static CUSTOM: OnceLock<Vec<Custom>> = OnceLock::new();
+
+pub fn customs() -> &'static [Custom] {
+    CUSTOM.get().map_or(&[], Vec::as_slice)
+}
+
+/// Installs the classifiers of the configs and packs; they are checked
+/// before the built-in ones.
+pub fn register(configs: &[Config]) -> io::Result<()> {
+    let mut customs: Vec<Custom> = Vec::new();
+    for (config, section) in configs
+        .iter()
+        .flat_map(|c: &Config| c.tables("classifier").map(move |s: &Section| (c, s)))
+    {
+        let custom: Custom = load_custom(config, section)?;
+        let same = |label: &str| -> bool { label.eq_ignore_ascii_case(&custom.name) };
+        let taken: bool = EventKind::ALL.iter().any(|k: &EventKind| same(k.label()))
+            || customs.iter().any(|c: &Custom| same(&c.name));
+        if taken {
+            return Err(config.error(
+                section.line,
+                &format!("classifier `{}` is already defined", custom.name),
+            ));
+        }
+        customs.push(custom);
+    }
+    let _ = CUSTOM.set(customs);
+    Ok(())
+}
+
+fn load_custom(config: &Config, section: &Section) -> io::Result<Custom> {
+    section.check_keys(config, &["name", "regex", "style", "fields", "notify"])?;
+    let at = |key: &str| -> usize { section.get(key).map_or(section.line, |e| e.line) };
+
+    let name: &str = section.required_str(config, "name")?;
+    if name.is_empty() || !name.chars().all(|c: char| c.is_alphanumeric() || c == '_') {
+        return Err(config.error(
+            at("name"),
+            &format!("classifier name `{}` should be letters, digits or _", name),
+        ));
+    }
+    let pattern: &str = section.required_str(config, "regex")?;
+    let re: Regex = Regex::new(pattern)
+        .map_err(|e: regex::Error| config.error(at("regex"), &format!("invalid regex: {}", e)))?;
+    let style: Option<Seq> = match section.str(config, "style")? {
+        Some(codes) => Some(Seq::parse(codes).ok_or_else(|| {
+            config.error(
+                at("style"),
+                &format!("style `{}` should be SGR codes like \"1;45;97\"", codes),
+            )
+        })?),
+        None => None,
+    };
+    let groups: Vec<String> = re.capture_names().flatten().map(String::from).collect();
+    let fields: Vec<String> = section.strings(config, "fields")?.unwrap_or(groups.clone());
+    if let Some(f) = fields.iter().find(|f: &&String| !groups.contains(f)) {
+        return Err(config.error(
+            at("fields"),
+            &format!("`{}` is not a named group (?P<{}>...) of the regex", f, f),
+        ));
+    }
+
+    Ok(Custom {
+        name: name.to_string(),
+        re,
+        style,
+        fields,
+        notify: section.bool(config, "notify")?.unwrap_or(false),
+    })
+}
+
+pub fn classify(message: &str) -> Option<EventKind> {
+    if let Some(i) = customs()
+        .iter()
+        .position(|c: &Custom| c.re.is_match(message))
+    {
+        return Some(EventKind::Custom(i));
+    }
+    CLASSIFIERS
+        .iter()
+        .find(|c: &&Classifier| c.matches(message))
+        .map(|c: &Classifier| c.kind)
+}
@@ -0,0 +1,74 @@
+// ASCII output for Windows consoles on a legacy code page, where the
+// separators and gutters would come out as mojibake.
+
+use crate::width;
+use std::borrow::Cow;
+
+// The glyphs this program draws, and what stands in for them; each takes
+// as many columns as the glyph did, so columns stay aligned
+const GLYPHS: &[(char, char)] = &[
+    ('─', '-'),
+    ('━', '-'),
+    ('│', '|'),
+    ('┃', '|'),
+    ('…', '~'),
+    ('·', '.'),
+    ('•', '*'),
+    ('→', '>'),
+    ('←', '<'),
+    ('↑', '^'),
+    ('↓', 'v'),
+    ('›', '>'),
+    ('‹', '<'),
+    ('µ', 'u'),
+    ('‘', '\''),
+    ('’', '\''),
+    ('“', '"'),
+    ('”', '"'),
+    ('–', '-'),
+    ('—', '-'),
+    ('\u{a0}', ' '),
+];
+
+// Latin-1 letters from U+00C0, as their base letter
+const LATIN1: &str = "AAAAAAACEEEEIIIIDNOOOOOxOUUUUYTsaaaaaaaceeeeiiiidnooooo/ouuuuyty";
+
+/// Whether output goes to a Windows console whose code page isn't UTF-8,
+/// such as 437 or 1252 on older cmd.exe setups.
+#[cfg(windows)]
+pub fn legacy_console() -> bool {
+    use std::io::{self, IsTerminal};
+    use windows_sys::Win32::{Globalization::CP_UTF8, System::Console::GetConsoleOutputCP};
+
+    // Zero when there is no console
+    io::stdout().is_terminal() && !matches!(unsafe { GetConsoleOutputCP() }, 0 | CP_UTF8)
+}
+
+#[cfg(not(windows))]
+pub fn legacy_console() -> bool {
+    false
+}
+
+/// `text` in ASCII: the glyphs as their stand-ins, accented Latin letters
+/// as their base letters, and anything else as a `?` per column.
+pub fn to_ascii(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    let mut out: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            out.push(c);
+        } else if let Some((_, ascii)) = GLYPHS.iter().find(|(g, _)| *g == c) {
+            out.push(*ascii);
+        } else if let Some(base) = LATIN1.chars().nth((c as u32).wrapping_sub(0xC0) as usize) {
+            out.push(base);
+        } else if matches!(c as u32, 0x2500..=0x257F) {
+            // Other box drawing: corners and junctions
+            out.push('+');
+        } else {
+            out.push_str(&"?".repeat(width::char_width(c)));
+        }
+    }
+    Cow::Owned(out)
+}
@@ -0,0 +1,607 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A configuration file in the subset of TOML this tool needs: `[table]`
+/// and `[[array.of.tables]]` headers, `key = value` pairs with bare or
+/// quoted keys, strings (basic and literal), integers, floats, booleans and
+/// arrays of those. Inline tables, dotted keys, multi-line strings and dates
+/// are rejected with the line they are on.
+///
+/// ```toml
+/// [[classifier]]
+/// name = "LOGIN"
+/// regex = 'Login (?P<result>ok|failed) for (?P<user>\S+)'
+/// fields = ["user", "result"]
+/// ```
+pub struct Config {
+    pub path: String,
+    pub sections: Vec<Section>,
+}
+
+/// A table; the keys before any header live in one named "".
+pub struct Section {
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Str(_) => "a string",
+            Value::Int(_) => "an integer",
+            Value::Float(_) => "a number",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/logcat-colorize/FILE`, falling back to `~/.config`,
+/// for the `config.toml` and `theme.toml` read when not given otherwise.
+pub fn default_path(file: &str) -> Option<PathBuf> {
+    let base: PathBuf = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("logcat-colorize").join(file))
+}
+
+impl Config {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text: String = fs::read_to_string(path)
+            .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?;
+        Self::parse(&path.display().to_string(), &text)
+    }
+
+    pub fn parse(path: &str, text: &str) -> io::Result<Self> {
+        let mut config: Config = Config {
+            path: path.to_string(),
+            sections: vec![Section {
+                name: String::new(),
+                line: 0,
+                entries: Vec::new(),
+            }],
+        };
+
+        let mut lines = text.lines().enumerate();
+        while let Some((n, line)) = lines.next() {
+            let line: &str = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let name: &str = header
+                    .strip_prefix('[')
+                    .and_then(|h: &str| h.strip_suffix("]]"))
+                    .or_else(|| header.strip_suffix(']'))
+                    .filter(|name: &&str| {
+                        name.split('.').all(|part: &str| {
+                            let part: &str = part.trim();
+                            !part.is_empty()
+                                && part.chars().all(|c: char| {
+                                    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+                                })
+                        })
+                    })
+                    .ok_or_else(|| config.error(n + 1, "malformed table header"))?;
+                config.sections.push(Section {
+                    name: name.trim().to_string(),
+                    line: n + 1,
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+
+            let (key, value) =
+                split_key(line).ok_or_else(|| config.error(n + 1, "expected `key = value`"))?;
+            let key: String =
+                parse_key(key.trim()).map_err(|msg: String| config.error(n + 1, &msg))?;
+            // Arrays may continue over the following lines
+            let mut value: String = value.trim().to_string();
+            while value.starts_with('[') && !brackets_closed(&value) {
+                match lines.next() {
+                    Some((_, more)) => {
+                        value.push(' ');
+                        value.push_str(strip_comment(more).trim());
+                    }
+                    None => return Err(config.error(n + 1, "unterminated array")),
+                }
+            }
+            let (parsed, rest) =
+                parse_value(&value).map_err(|msg: String| config.error(n + 1, &msg))?;
+            if !rest.trim().is_empty() {
+                return Err(config.error(
+                    n + 1,
+                    &format!("unexpected `{}` after the value", rest.trim()),
+                ));
+            }
+            let section: &mut Section = config.sections.last_mut().unwrap();
+            if section.entries.iter().any(|e: &Entry| e.key == key) {
+                return Err(config.error(n + 1, &format!("duplicate key `{}`", key)));
+            }
+            section.entries.push(Entry {
+                key,
+                value: parsed,
+                line: n + 1,
+            });
+        }
+        Ok(config)
+    }
+
+    /// The keys before the first table header.
+    pub fn root(&self) -> &Section {
+        &self.sections[0]
+    }
+
+    /// Every `[name]` or `[[name]]` table, in file order.
+    pub fn tables<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Section> {
+        self.sections
+            .iter()
+            .filter(move |s: &&Section| s.name == name)
+    }
+
+    pub fn error(&self, line: usize, msg: &str) -> io::Error {
+        io::Error::other(format!("{}:{}: {}", self.path, line, msg))
+    }
+}
+
+impl Section {
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e: &&Entry| e.key == key)
+    }
+
+    /// Rejects keys outside `known`, naming the offending one.
+    pub fn check_keys(&self, config: &Config, known: &[&str]) -> io::Result<()> {
+        match self
+            .entries
+            .iter()
+            .find(|e: &&Entry| !known.contains(&e.key.as_str()))
+        {
+            Some(e) => Err(config.error(
+                e.line,
+                &format!(
+                    "unknown key `{}` {} (expected one of {})",
+                    e.key,
+                    match self.name.is_empty() {
+                        true => "before any table".to_string(),
+                        false => format!("in [{}]", self.name),
+                    },
+                    known.join(", ")
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub fn str(&self, config: &Config, key: &str) -> io::Result<Option<&str>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::Str(s),
+                ..
+            }) => Ok(Some(s)),
+            Some(e) => Err(mistyped(config, e, "a string")),
+        }
+    }
+
+    pub fn bool(&self, config: &Config, key: &str) -> io::Result<Option<bool>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::Bool(b),
+                ..
+            }) => Ok(Some(*b)),
+            Some(e) => Err(mistyped(config, e, "true or false")),
+        }
+    }
+
+    /// An array of strings; a single string counts as a one-element array.
+    pub fn strings(&self, config: &Config, key: &str) -> io::Result<Option<Vec<String>>> {
+        let Some(e) = self.get(key) else {
+            return Ok(None);
+        };
+        match &e.value {
+            Value::Str(s) => Ok(Some(vec![s.clone()])),
+            Value::Array(items) => items
+                .iter()
+                .map(|v: &Value| match v {
+                    Value::Str(s) => Ok(s.clone()),
+                    _ => Err(mistyped(config, e, "an array of strings")),
+                })
+                .collect::<io::Result<Vec<String>>>()
+                .map(Some),
+            _ => Err(mistyped(config, e, "an array of strings")),
+        }
+    }
+
+    /// A required string, for keys a table is useless without.
+    pub fn required_str(&self, config: &Config, key: &str) -> io::Result<&str> {
+        self.str(config, key)?.ok_or_else(|| {
+            config.error(self.line, &format!("[{}] is missing `{}`", self.name, key))
+        })
+    }
+}
+
+fn mistyped(config: &Config, e: &Entry, wanted: &str) -> io::Error {
+    config.error(
+        e.line,
+        &format!("`{}` should be {}, not {}", e.key, wanted, e.value.kind()),
+    )
+}
+
+// Drops a `#` comment, leaving `#` inside strings alone
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped: bool = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn brackets_closed(value: &str) -> bool {
+    let mut depth: i32 = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped: bool = false;
+    for c in value.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth <= 0
+}
+
+// Splits `key = value` at the first `=` outside a quoted key
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let mut quote: Option<char> = None;
+    let mut escaped: bool = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '=') => return Some((&line[..i], &line[i + 1..])),
+            _ => {}
+        }
+        escaped = false;
+    }
+    None
+}
+
+// A bare key (letters, digits, `_` and `-`) or a quoted one
+fn parse_key(key: &str) -> Result<String, String> {
+    if key.starts_with(['"', '\'']) {
+        return match parse_value(key)? {
+            (Value::Str(s), "") => Ok(s),
+            (_, rest) if !rest.trim_start().starts_with('.') => {
+                Err(format!("invalid key `{}` (quote it)", key))
+            }
+            _ => Err(format!(
+                "dotted key `{}` is not supported; use a [table]",
+                key
+            )),
+        };
+    }
+    if key.is_empty() {
+        return Err("missing key before `=`".to_string());
+    }
+    if key.contains('.') {
+        return Err(format!(
+            "dotted key `{}` is not supported; use a [table]",
+            key
+        ));
+    }
+    match key
+        .chars()
+        .all(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        true => Ok(key.to_string()),
+        false => Err(format!("invalid key `{}` (quote it)", key)),
+    }
+}
+
+// Parses one value from the start of `s`, returning it and what follows
+fn parse_value(s: &str) -> Result<(Value, &str), String> {
+    let s: &str = s.trim_start();
+    if s.starts_with("\"\"\"") || s.starts_with("'''") {
+        return Err("multi-line strings are not supported".to_string());
+    }
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut out: String = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::Str(out), &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, e): (usize, char)| e) {
+                    Some('b') => out.push('\u{8}'),
+                    Some('t') => out.push('\t'),
+                    Some('n') => out.push('\n'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('r') => out.push('\r'),
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some(u @ ('u' | 'U')) => {
+                        let len: usize = if u == 'u' { 4 } else { 8 };
+                        let hex: String = chars.by_ref().take(len).map(|(_, h)| h).collect();
+                        let c: char = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == len)
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape `\\{}{}`", u, hex))?;
+                        out.push(c);
+                    }
+                    Some(e) => {
+                        return Err(format!("unknown escape `\\{}` (use '...' for regexes)", e));
+                    }
+                    None => break,
+                },
+                _ => out.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    if let Some(rest) = s.strip_prefix('\'') {
+        let end: usize = rest
+            .find('\'')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        return Ok((Value::Str(rest[..end].to_string()), &rest[end + 1..]));
+    }
+    if let Some(mut rest) = s.strip_prefix('[') {
+        let mut items: Vec<Value> = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_value(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected `,` or `]` in array".to_string());
+            }
+        }
+    }
+    if s.starts_with('{') {
+        return Err("inline tables are not supported; use a [table]".to_string());
+    }
+
+    let end: usize = s.find([',', ']', ' ']).unwrap_or(s.len());
+    let (word, rest) = s.split_at(end);
+    let value: Value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        w => {
+            let digits: String = w.replace('_', "");
+            if let Ok(n) = digits.parse::<i64>() {
+                Value::Int(n)
+            } else if let Ok(f) = digits.parse::<f64>() {
+                Value::Float(f)
+            } else if w.is_empty() {
+                return Err("missing value".to_string());
+            } else {
+                return Err(format!("invalid value `{}` (strings need quotes)", w));
+            }
+        }
+    };
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        Config::parse("config.toml", text).unwrap()
+    }
+
+    fn error(text: &str) -> String {
+        match Config::parse("config.toml", text) {
+            Ok(_) => panic!("accepted {:?}", text),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn value(text: &str) -> Value {
+        parse(&format!("key = {}", text)).root().entries[0]
+            .value
+            .clone()
+    }
+
+    #[test]
+    fn reads_tables_and_keys() {
+        let config: Config = parse(concat!(
+            "top = 1 # a comment\n",
+            "\n",
+            "[theme]\n",
+            "\"quoted = key\" = 'a # b'\n",
+            "'literal' = true\n",
+            "bare-key_2 = -3\n",
+            "[[route]]\n",
+            "when = \"level >= E\"\n",
+            "[[route]]\n",
+            "when = \"tag == x\"\n",
+        ));
+        assert_eq!(config.root().get("top").unwrap().value, Value::Int(1));
+        let theme: &Section = config.tables("theme").next().unwrap();
+        assert_eq!(theme.line, 3);
+        let keys: Vec<&str> = theme
+            .entries
+            .iter()
+            .map(|e: &Entry| e.key.as_str())
+            .collect();
+        assert_eq!(keys, ["quoted = key", "literal", "bare-key_2"]);
+        assert_eq!(theme.entries[0].value, Value::Str("a # b".into()));
+        assert_eq!(theme.entries[0].line, 4);
+        assert_eq!(config.tables("route").count(), 2);
+    }
+
+    #[test]
+    fn reads_values() {
+        assert_eq!(value("1_000"), Value::Int(1000));
+        assert_eq!(value("+7"), Value::Int(7));
+        assert_eq!(value("2.5"), Value::Float(2.5));
+        assert_eq!(value("1e3"), Value::Float(1000.0));
+        assert_eq!(value("false"), Value::Bool(false));
+        assert_eq!(
+            value(r"'C:\no\escapes'"),
+            Value::Str(r"C:\no\escapes".into())
+        );
+        assert_eq!(
+            value(r#""\b\t\n\f\r\"\\""#),
+            Value::Str("\u{8}\t\n\u{c}\r\"\\".into())
+        );
+        assert_eq!(value(r#""\u00e9\U0001F600""#), Value::Str("é😀".into()));
+        assert_eq!(
+            value("[1, 'two', [true]]"),
+            Value::Array(vec![
+                Value::Int(1),
+                Value::Str("two".into()),
+                Value::Array(vec![Value::Bool(true)]),
+            ])
+        );
+        assert_eq!(value("[]"), Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn arrays_continue_over_lines() {
+        let config: Config = parse("tags = [\n  'a', # first\n  \"]\",\n]\nnext = 1\n");
+        let tags: &Entry = config.root().get("tags").unwrap();
+        assert_eq!(
+            tags.value,
+            Value::Array(vec![Value::Str("a".into()), Value::Str("]".into())])
+        );
+        assert_eq!(tags.line, 1);
+        assert_eq!(config.root().get("next").unwrap().line, 5);
+    }
+
+    #[test]
+    fn rejects_with_file_and_line() {
+        let cases: [(&str, &str); 24] = [
+            ("[theme\n", "config.toml:1: malformed table header"),
+            ("[a b]\n", "config.toml:1: malformed table header"),
+            ("[]\n", "config.toml:1: malformed table header"),
+            ("\njust words\n", "config.toml:2: expected `key = value`"),
+            ("= 1\n", "config.toml:1: missing key before `=`"),
+            (
+                "a.b = 1\n",
+                "config.toml:1: dotted key `a.b` is not supported; use a [table]",
+            ),
+            (
+                "'a'.b = 1\n",
+                "config.toml:1: dotted key `'a'.b` is not supported; use a [table]",
+            ),
+            ("a b = 1\n", "config.toml:1: invalid key `a b` (quote it)"),
+            (
+                "\"a\"b = 1\n",
+                "config.toml:1: invalid key `\"a\"b` (quote it)",
+            ),
+            ("a =\n", "config.toml:1: missing value"),
+            (
+                "a = word\n",
+                "config.toml:1: invalid value `word` (strings need quotes)",
+            ),
+            (
+                "a = 1979-05-27\n",
+                "config.toml:1: invalid value `1979-05-27` (strings need quotes)",
+            ),
+            ("a = 'open\n", "config.toml:1: unterminated string"),
+            ("a = \"open\n", "config.toml:1: unterminated string"),
+            (
+                r#"a = "\d+""#,
+                r"config.toml:1: unknown escape `\d` (use '...' for regexes)",
+            ),
+            (r#"a = "\u12""#, r#"config.toml:1: invalid escape `\u12"`"#),
+            (r#"a = "\uD800""#, r"config.toml:1: invalid escape `\uD800`"),
+            (
+                r#"a = """text""""#,
+                "config.toml:1: multi-line strings are not supported",
+            ),
+            (
+                "a = '''text'''",
+                "config.toml:1: multi-line strings are not supported",
+            ),
+            (
+                "a = { b = 1 }\n",
+                "config.toml:1: inline tables are not supported; use a [table]",
+            ),
+            ("a = 1 2\n", "config.toml:1: unexpected `2` after the value"),
+            ("a = [1 2]\n", "config.toml:1: expected `,` or `]` in array"),
+            ("x = 1\na = [1,\n2\n", "config.toml:2: unterminated array"),
+            ("[t]\na = 1\na = 2\n", "config.toml:3: duplicate key `a`"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(error(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn checks_keys_and_types() {
+        let config: Config = parse("[theme]\ncolor = 3\nname = ['a', 1]\n");
+        let theme: &Section = config.tables("theme").next().unwrap();
+        assert_eq!(
+            theme
+                .check_keys(&config, &["color"])
+                .unwrap_err()
+                .to_string(),
+            "config.toml:3: unknown key `name` in [theme] (expected one of color)"
+        );
+        assert_eq!(
+            theme.str(&config, "color").unwrap_err().to_string(),
+            "config.toml:2: `color` should be a string, not an integer"
+        );
+        assert_eq!(
+            theme.strings(&config, "name").unwrap_err().to_string(),
+            "config.toml:3: `name` should be an array of strings, not an array"
+        );
+        assert_eq!(
+            theme
+                .required_str(&config, "regex")
+                .unwrap_err()
+                .to_string(),
+            "config.toml:1: [theme] is missing `regex`"
+        );
+    }
+}
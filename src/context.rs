@@ -0,0 +1,53 @@
+use crate::logcat::Logcat;
+use std::collections::VecDeque;
+
+/// Entries around --grep and --exclude matches, as grep's `-B`, `-A` and
+/// `-C` show them: the ones the message filters hid, but no other filter
+/// would have.
+pub struct Context {
+    before: usize,
+    after: usize,
+    recent: VecDeque<Logcat>, // hidden since the last match, up to `before`
+    left: usize,              // entries still to show after the last match
+    gap: bool,                // entries went unshown since then
+    shown: bool,
+}
+
+impl Context {
+    pub fn new(before: usize, after: usize) -> Self {
+        Self {
+            before,
+            after,
+            recent: VecDeque::with_capacity(before),
+            left: 0,
+            gap: false,
+            shown: false,
+        }
+    }
+
+    /// Takes an entry the message filters hid; returns it when it follows a
+    /// match closely enough to show now.
+    pub fn hidden(&mut self, lc: Logcat) -> Option<Logcat> {
+        if self.left > 0 {
+            self.left -= 1;
+            return Some(lc);
+        }
+        if self.recent.len() == self.before {
+            self.gap |= self.recent.pop_front().is_some() || self.before == 0;
+        }
+        if self.before > 0 {
+            self.recent.push_back(lc);
+        }
+        None
+    }
+
+    /// Before an entry that matched: whether a `--` separates it from what
+    /// was shown last, and the entries to show before it.
+    pub fn matched(&mut self) -> (bool, Vec<Logcat>) {
+        let separate: bool = self.shown && self.gap;
+        self.gap = false;
+        self.shown = true;
+        self.left = self.after;
+        (separate, self.recent.drain(..).collect())
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{
+    expr::{self, Expr},
+    filter,
+    spotlight::{self, Mode, Rule},
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+};
+
+pub const USAGE: &str = "commands: spotlight add PATTERN[=STYLE] | spotlight clear | level LEVEL|all \
+                         | mute tag NAME | unmute tag NAME | filter EXPR|clear | mark [NOTE]";
+
+/// A change to the running colorizer, sent over the control socket.
+pub enum Command {
+    SpotlightAdd(Rule),
+    SpotlightClear,
+    Level(Option<String>),
+    Mute(String),
+    Unmute(String),
+    Filter(Option<Expr>),
+    Mark(Option<String>), // the last entry shown, as a --findings finding
+}
+
+/// Parses one control line, e.g. `spotlight add \bWifi\w+` or `mute tag chatty`.
+pub fn parse(line: &str, mode: Mode) -> Result<Command, String> {
+    let line: &str = line.trim();
+    let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let rest: &str = rest.trim();
+    let (sub, arg) = rest.split_once(' ').unwrap_or((rest, ""));
+    let arg: &str = arg.trim();
+
+    match (verb, sub) {
+        ("spotlight", "add") if !arg.is_empty() => {
+            spotlight::parse_rule(arg, mode).map(Command::SpotlightAdd)
+        }
+        ("spotlight", "clear") => Ok(Command::SpotlightClear),
+        ("level", "all") => Ok(Command::Level(None)),
+        ("level", _) if !sub.is_empty() => {
+            filter::parse_level(sub).map(|l: String| Command::Level(Some(l)))
+        }
+        ("mute", "tag") if !arg.is_empty() => Ok(Command::Mute(arg.to_string())),
+        ("unmute", "tag") if !arg.is_empty() => Ok(Command::Unmute(arg.to_string())),
+        ("filter", "clear") => Ok(Command::Filter(None)),
+        ("filter", _) if !rest.is_empty() => {
+            expr::parse(rest).map(|e: Expr| Command::Filter(Some(e)))
+        }
+        ("mark", _) => Ok(Command::Mark((!rest.is_empty()).then(|| rest.to_string()))),
+        _ => Err(format!("unknown command `{}`; {}", line, USAGE)),
+    }
+}
+
+/// The receiving end of a control socket, removed from disk when dropped.
+pub struct Control {
+    path: PathBuf,
+    pub rx: Receiver<Command>,
+}
+
+impl Control {
+    /// Listens on a unix socket at `path`. Each connection sends one command
+    /// per line and gets `ok` or `error: ...` back for each; spotlight
+    /// patterns read as `mode` says.
+    #[cfg(unix)]
+    pub fn listen(path: &Path, mode: Mode) -> io::Result<Self> {
+        use std::{
+            fs,
+            io::{BufRead, BufReader, Write},
+            os::unix::{
+                fs::FileTypeExt,
+                net::{UnixListener, UnixStream},
+            },
+            sync::mpsc::{self, Sender},
+            thread,
+        };
+
+        // Replace a socket left behind by a previous run, but nothing else
+        if let Ok(meta) = fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                return Err(io::Error::other(format!(
+                    "{}: exists and is not a socket",
+                    path.display()
+                )));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener: UnixListener = UnixListener::bind(path)
+            .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?;
+
+        let (tx, rx): (Sender<Command>, Receiver<Command>) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx: Sender<Command> = tx.clone();
+                thread::spawn(move || -> io::Result<()> {
+                    let mut reply: UnixStream = stream.try_clone()?;
+                    for line in BufReader::new(stream).lines() {
+                        let line: String = line?;
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        match parse(&line, mode) {
+                            Ok(cmd) => {
+                                if tx.send(cmd).is_err() {
+                                    return Ok(());
+                                }
+                                writeln!(reply, "ok")?;
+                            }
+                            Err(e) => writeln!(reply, "error: {}", e)?,
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            rx,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn listen(_path: &Path, _mode: Mode) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--control needs unix domain sockets",
+        ))
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(line: &str) -> Command {
+        match parse(line, Mode::default()) {
+            Ok(cmd) => cmd,
+            Err(e) => panic!("`{}`: {}", line, e),
+        }
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(command("spotlight add \\bWifi\\w+=yellow"),
+            Command::SpotlightAdd(r) if r.pattern == "\\bWifi\\w+"));
+        assert!(matches!(
+            command("  spotlight clear  "),
+            Command::SpotlightClear
+        ));
+        assert!(matches!(command("level all"), Command::Level(None)));
+        assert!(matches!(command("level w"), Command::Level(Some(l)) if l == "W"));
+        assert!(matches!(command("mute tag chatty"), Command::Mute(t) if t == "chatty"));
+        assert!(
+            matches!(command("unmute tag Wifi Service"), Command::Unmute(t) if t == "Wifi Service")
+        );
+        assert!(matches!(command("filter clear"), Command::Filter(None)));
+        assert!(matches!(
+            command("filter level>=W && tag~OkHttp"),
+            Command::Filter(Some(_))
+        ));
+        assert!(matches!(command("mark"), Command::Mark(None)));
+        assert!(
+            matches!(command("mark  login fails here"), Command::Mark(Some(n)) if n == "login fails here")
+        );
+    }
+
+    #[test]
+    fn spotlight_patterns_read_as_the_mode_says() {
+        let mode: Mode = Mode {
+            ignore_case: true,
+            fixed: true,
+        };
+        assert!(matches!(parse("spotlight add a.b", mode),
+            Ok(Command::SpotlightAdd(r)) if r.pattern == "(?i)a\\.b"));
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        for line in [
+            "",
+            "spotlight",
+            "spotlight add",
+            "spotlight remove x",
+            "level",
+            "mute chatty",
+            "mute tag",
+            "unmute",
+            "filter",
+            "reboot now",
+            "\u{1b}[A",
+        ] {
+            let e: String = parse(line, Mode::default()).err().unwrap();
+            assert!(
+                e.starts_with("unknown command") && e.ends_with(USAGE),
+                "{}",
+                e
+            );
+        }
+        // Bad arguments say what's wrong with them
+        for (line, error) in [
+            ("level Q", "unknown level `Q`"),
+            ("spotlight add (", "invalid regex"),
+            ("filter level>=", "expected a value"),
+            ("filter tag==\"x", "unterminated string"),
+        ] {
+            let e: String = parse(line, Mode::default()).err().unwrap();
+            assert!(e.starts_with(error), "{}: {}", line, e);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn answers_each_line_over_the_socket() {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            os::unix::net::UnixStream,
+        };
+
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("control-{}.sock", std::process::id()));
+        let control: Control = Control::listen(&path, Mode::default()).unwrap();
+        let mut stream: UnixStream = UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"mute tag chatty\n\nlevel Q\nbogus\nlevel E\n")
+            .unwrap();
+        let replies: Vec<String> = BufReader::new(stream)
+            .lines()
+            .take(4)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(replies[0], "ok");
+        assert!(replies[1].starts_with("error: unknown level `Q`"));
+        assert!(replies[2].starts_with("error: unknown command `bogus`"));
+        assert_eq!(replies[3], "ok");
+        // Only the good commands reach the stream
+        let cmds: Vec<Command> = control.rx.try_iter().collect();
+        assert!(matches!(
+            &cmds[..],
+            [Command::Mute(_), Command::Level(Some(_))]
+        ));
+        drop(control);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,377 @@
+use crate::{
+    adb,
+    classify::{self, EventKind},
+    logcat::Logcat,
+    native::json_str,
+};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    process::{Child, Command, Stdio},
+    sync::LazyLock,
+};
+
+// AndroidRuntime: "Process: com.example, PID: 1234"
+static RE_PROCESS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^Process: ([^,]+), PID: [0-9]+").unwrap());
+// "java.lang.IllegalStateException: ...", "Caused by: java.io.IOException"
+static RE_EXCEPTION: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^(?:Caused by: )?([A-Za-z_$][\w$]*(?:\.[\w$]+)+(?:Exception|Error|Throwable))\b")
+        .unwrap()
+});
+// "at com.example.Foo.bar(Foo.java:42)"; the line number is left out
+static RE_JAVA_FRAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^at ([^(]+)").unwrap());
+// libc: "Fatal signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), ..., pid 1234 (com.example)"
+static RE_SIGNAL: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^Fatal signal [0-9]+ \((\w+)\).*?pid [0-9]+ \(([^)]*)\)").unwrap()
+});
+// debuggerd: "#00 pc 000000000001e0c4  /system/lib64/libc.so (abort+164) (BuildId: ...)"
+static RE_NATIVE_FRAME: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^#[0-9]+ pc [0-9a-f]+\s+(.*?)(?:\s+\(BuildId: [0-9a-f]+\))?$").unwrap()
+});
+
+// Top frames that make up the fingerprint, so deep differences don't split groups
+const FRAMES: usize = 8;
+// A crash's lines are collected until this many, or until this many
+// unrelated entries went by
+const MAX_LINES: usize = 128;
+const IDLE: usize = 200;
+
+/// A crash with its stack, identified by the exception or signal and the
+/// top frames.
+pub struct Group {
+    pub fingerprint: String,
+    pub reason: String,
+    process: String,
+    first: Logcat,
+    stack: Vec<String>,
+}
+
+struct Open {
+    first: Logcat,
+    native: bool,
+    lines: Vec<String>,
+    idle: usize,
+}
+
+impl Open {
+    // The crashing process's lines under the same tag, and the tombstone
+    // debuggerd writes (from its own pid) for a native crash
+    fn takes(&self, lc: &Logcat) -> bool {
+        (lc.process == self.first.process && lc.tag == self.first.tag)
+            || (self.native && lc.tag.trim() == "DEBUG")
+    }
+}
+
+/// Posts each new crash group once, as JSON, to a `--crash-webhook`.
+pub struct Crashes {
+    url: String,
+    exec: bool,
+    open: Option<Open>,
+    seen: HashMap<String, usize>,
+    device: Option<String>, // JSON object, looked up on first use
+    posts: Vec<Child>,
+}
+
+/// clap value parser for `--crash-webhook`.
+pub fn parse_url(s: &str) -> Result<String, String> {
+    match s.starts_with("https://") || s.starts_with("http://") {
+        true => Ok(s.to_string()),
+        false => Err("expected an http:// or https:// URL".to_string()),
+    }
+}
+
+impl Crashes {
+    /// With `exec`, device properties are read over adb for the payload.
+    pub fn new(url: String, exec: bool) -> Self {
+        Self {
+            url,
+            exec,
+            open: None,
+            seen: HashMap::new(),
+            device: None,
+            posts: Vec::new(),
+        }
+    }
+
+    /// Follows the stream; returns a group the first time its crash
+    /// completes.
+    pub fn observe(&mut self, lc: &Logcat) -> Option<Group> {
+        let starts: bool = classify::classify(&lc.message) == Some(EventKind::Crash);
+        if !starts && let Some(open) = &mut self.open {
+            if open.takes(lc) {
+                open.lines.push(lc.message.clone());
+                open.idle = 0;
+                if open.lines.len() < MAX_LINES {
+                    return None;
+                }
+            } else {
+                open.idle += 1;
+                if open.idle < IDLE {
+                    return None;
+                }
+            }
+        }
+        let done: Option<Group> = self.close();
+        if starts {
+            self.open = Some(Open {
+                first: lc.clone(),
+                native: RE_SIGNAL.is_match(&lc.message),
+                lines: vec![lc.message.clone()],
+                idle: 0,
+            });
+        }
+        done
+    }
+
+    /// The crash still being collected at the end of the stream, if new.
+    pub fn close(&mut self) -> Option<Group> {
+        let group: Group = group(self.open.take()?);
+        let count: &mut usize = self.seen.entry(group.fingerprint.clone()).or_default();
+        *count += 1;
+        (*count == 1).then_some(group)
+    }
+
+    /// Sends `group` with curl, in the background.
+    pub fn post(&mut self, group: &Group) -> io::Result<()> {
+        let body: String = self.payload(group);
+        let mut child: Child = Command::new("curl")
+            .args(["-sS", "--fail", "--max-time", "10", "-X", "POST"])
+            .args([
+                "-H",
+                "Content-Type: application/json",
+                "--data-binary",
+                "@-",
+            ])
+            .arg(&self.url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e: io::Error| io::Error::other(format!("curl: {}", e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(body.as_bytes())?;
+        }
+        self.posts.push(child);
+        Ok(())
+    }
+
+    /// Waits for the posts still in flight.
+    pub fn wait(&mut self) {
+        for mut child in self.posts.drain(..) {
+            let _ = child.wait();
+        }
+    }
+
+    fn payload(&mut self, group: &Group) -> String {
+        let stack: Vec<String> = group.stack.iter().map(|l: &String| json_str(l)).collect();
+        let device: &str = self.device.get_or_insert_with(|| device_json(self.exec));
+        format!(
+            "{{\"fingerprint\":{},\"reason\":{},\"process\":{},\"pid\":{},\"timestamp\":{},\"tag\":{},\"stack\":[{}],\"device\":{},\"reporter\":{}}}",
+            json_str(&group.fingerprint),
+            json_str(&group.reason),
+            json_str(&group.process),
+            json_str(&group.first.process),
+            json_str(&group.first.timestamp),
+            json_str(group.first.tag.trim()),
+            stack.join(","),
+            device,
+            json_str(&format!(
+                "{}/{}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ))
+        )
+    }
+}
+
+fn group(open: Open) -> Group {
+    let mut reasons: Vec<String> = Vec::new();
+    let mut frames: Vec<String> = Vec::new();
+    let mut process: String = String::new();
+    for line in &open.lines {
+        let line: &str = line.trim();
+        if let Some(c) = RE_SIGNAL.captures(line) {
+            reasons.push(c[1].to_string());
+            process = c[2].to_string();
+        } else if let Some(c) = RE_PROCESS.captures(line) {
+            process = c[1].to_string();
+        } else if let Some(c) = RE_EXCEPTION.captures(line) {
+            reasons.push(c[1].to_string());
+        } else if let Some(c) = RE_JAVA_FRAME
+            .captures(line)
+            .or_else(|| RE_NATIVE_FRAME.captures(line))
+        {
+            frames.push(c[1].trim().to_string());
+        }
+    }
+
+    let mut key: String = reasons.join("\n");
+    for frame in frames.iter().take(FRAMES) {
+        key.push('\n');
+        key.push_str(frame);
+    }
+    Group {
+        fingerprint: format!("{:016x}", fnv1a(key.as_bytes())),
+        reason: reasons
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "crash".to_string()),
+        process,
+        first: open.first,
+        stack: open.lines,
+    }
+}
+
+// Stable across builds and platforms, unlike std's hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h: u64, b: &u8| {
+        (h ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// Model and build of the device adb is talking to, or null
+fn device_json(exec: bool) -> String {
+    const PROPS: [(&str, &str); 4] = [
+        ("model", "ro.product.model"),
+        ("manufacturer", "ro.product.manufacturer"),
+        ("release", "ro.build.version.release"),
+        ("build", "ro.build.fingerprint"),
+    ];
+    if !exec {
+        return "null".to_string();
+    }
+    let mut fields: Vec<String> = Vec::new();
+    for (key, prop) in PROPS {
+        match adb::getprop(prop) {
+            Ok(value) => fields.push(format!("{}:{}", json_str(key), json_str(&value))),
+            Err(_) => return "null".to_string(),
+        }
+    }
+    format!("{{{}}}", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::entry;
+
+    // Each line through `observe`, then the end of the stream
+    fn groups(lines: &[&str]) -> Vec<Group> {
+        let mut crashes: Crashes = Crashes::new("https://example.com".to_string(), false);
+        let mut groups: Vec<Group> = lines
+            .iter()
+            .filter_map(|line: &&str| crashes.observe(&entry(line)))
+            .collect();
+        groups.extend(crashes.close());
+        groups
+    }
+
+    const FATAL: [&str; 7] = [
+        "06-01 12:00:00.100  4242  4242 E AndroidRuntime: FATAL EXCEPTION: main",
+        "06-01 12:00:00.100  4242  4242 E AndroidRuntime: Process: com.example.app, PID: 4242",
+        "06-01 12:00:00.100  4242  4242 E AndroidRuntime: java.lang.IllegalStateException: no view",
+        "06-01 12:00:00.100  4242  4242 E AndroidRuntime: \tat com.example.app.MainActivity.onCreate(MainActivity.java:42)",
+        "06-01 12:00:00.100  4242  4242 E AndroidRuntime: \tat android.app.Activity.performCreate(Activity.java:8051)",
+        "06-01 12:00:00.100  4242  4242 E AndroidRuntime: Caused by: java.io.IOException: disk full",
+        "06-01 12:00:00.100  4242  4242 E AndroidRuntime: \tat com.example.app.Store.save(Store.java:7)",
+    ];
+
+    const ANR: [&str; 4] = [
+        "06-01 12:00:05.000   612   640 E ActivityManager: ANR in com.example.app (com.example.app/.MainActivity)",
+        "06-01 12:00:05.000   612   640 E ActivityManager: PID: 4242",
+        "06-01 12:00:05.000   612   640 E ActivityManager: Reason: Input dispatching timed out",
+        "06-01 12:00:05.000   612   640 E ActivityManager: Load: 1.2 / 0.9 / 0.7",
+    ];
+
+    #[test]
+    fn groups_a_fatal_exception() {
+        let groups: Vec<Group> = groups(&FATAL);
+        assert_eq!(groups.len(), 1);
+        let group: &Group = &groups[0];
+        assert_eq!(group.reason, "java.lang.IllegalStateException");
+        assert_eq!(group.process, "com.example.app");
+        assert_eq!(group.first.message, "FATAL EXCEPTION: main");
+        assert_eq!(group.stack.len(), FATAL.len());
+    }
+
+    #[test]
+    fn reports_a_crash_group_once() {
+        let mut twice: Vec<&str> = FATAL.to_vec();
+        twice.extend(FATAL);
+        assert_eq!(groups(&twice).len(), 1);
+        // A different top frame is a different crash
+        let resumed: Vec<String> = FATAL
+            .iter()
+            .map(|l: &&str| l.replace("onCreate", "onResume"))
+            .collect();
+        let mut other: Vec<&str> = FATAL.to_vec();
+        other.extend(resumed.iter().map(String::as_str));
+        let found: Vec<Group> = groups(&other);
+        assert_eq!(found.len(), 2);
+        assert_ne!(found[0].fingerprint, found[1].fingerprint);
+    }
+
+    #[test]
+    fn keeps_other_threads_out_of_the_stack() {
+        let lines: Vec<&str> = vec![
+            FATAL[0],
+            "06-01 12:00:00.100  4242  4260 D OkHttp  : <-- HTTP FAILED: java.net.SocketException: closed",
+            FATAL[1],
+            "06-01 12:00:00.100   612   640 I ActivityManager: Force stopping com.example.app",
+            FATAL[2],
+            FATAL[3],
+            "06-01 12:00:00.100  4242  4261 W System.err: \tat com.example.app.Sync.run(Sync.java:3)",
+            FATAL[4],
+            FATAL[5],
+            FATAL[6],
+        ];
+        let groups: Vec<Group> = groups(&lines);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].stack.len(), FATAL.len());
+        assert!(
+            !groups[0]
+                .stack
+                .iter()
+                .any(|l: &String| l.contains("Sync.run"))
+        );
+        assert_eq!(
+            groups[0].fingerprint,
+            super::tests::groups(&FATAL)[0].fingerprint
+        );
+    }
+
+    #[test]
+    fn an_anr_is_not_a_crash() {
+        assert_eq!(classify::classify(ANR[0]), Some(EventKind::Anr));
+        assert!(groups(&ANR).is_empty());
+        // Nor does it cut short a crash it lands in the middle of
+        let mut lines: Vec<&str> = FATAL[..3].to_vec();
+        lines.extend(ANR);
+        lines.extend(&FATAL[3..]);
+        let groups: Vec<Group> = groups(&lines);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].stack.len(), FATAL.len());
+    }
+
+    #[test]
+    fn groups_a_native_crash_with_its_tombstone() {
+        let groups: Vec<Group> = groups(&[
+            "06-01 12:00:00.100  4242  4260 F libc    : Fatal signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0 in tid 4260 (RenderThread), pid 4242 (com.example.app)",
+            "06-01 12:00:00.300  4300  4300 F DEBUG   : *** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***",
+            "06-01 12:00:00.300  4300  4300 F DEBUG   : pid: 4242, tid: 4260, name: RenderThread  >>> com.example.app <<<",
+            "06-01 12:00:00.300  4300  4300 F DEBUG   :       #00 pc 000000000001e0c4  /system/lib64/libc.so (abort+164) (BuildId: 4f1b2c)",
+            "06-01 12:00:00.300  4300  4300 F DEBUG   :       #01 pc 0000000000052a10  /data/app/com.example.app/lib/arm64/libgame.so (render+20)",
+        ]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reason, "SIGSEGV");
+        assert_eq!(groups[0].process, "com.example.app");
+        assert_eq!(groups[0].stack.len(), 5);
+    }
+
+    #[test]
+    fn urls() {
+        assert!(parse_url("https://hooks.example.com/crash").is_ok());
+        assert!(parse_url("ftp://example.com").is_err());
+    }
+}
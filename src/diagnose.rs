@@ -0,0 +1,106 @@
+use crate::logcat::FormatKind;
+use regex_automata::{
+    Anchored, Input,
+    hybrid::{
+        LazyStateID,
+        dfa::{Cache, DFA},
+    },
+};
+use std::sync::LazyLock;
+
+// Each format's regex as a lazy DFA, walked byte by byte to see where a
+// line stops being able to match
+static DFAS: LazyLock<Vec<(FormatKind, DFA)>> = LazyLock::new(|| -> Vec<(FormatKind, DFA)> {
+    FormatKind::ALL
+        .iter()
+        .filter_map(|k: &FormatKind| Some((*k, DFA::new(k.regex().as_str()).ok()?)))
+        .collect()
+});
+
+/// Where a line that no format parsed went wrong.
+pub struct Divergence {
+    pub kind: FormatKind,
+    pub offset: usize, // byte offset of the first character no match can take
+}
+
+/// The format that matched the longest prefix of `line`, and where it
+/// stopped matching; ties go to the format `parse_line` tries first.
+pub fn closest(line: &str) -> Option<Divergence> {
+    let mut best: Option<Divergence> = None;
+    for (kind, dfa) in DFAS.iter() {
+        let Some(offset) = diverges_at(dfa, line) else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|b: &Divergence| offset > b.offset) {
+            best = Some(Divergence {
+                kind: *kind,
+                offset,
+            });
+        }
+    }
+    best
+}
+
+// The offset of the character after which no match is possible (the
+// length of the line when it ended too early); None if the line matches, or
+// the DFA gave up
+fn diverges_at(dfa: &DFA, line: &str) -> Option<usize> {
+    let mut cache: Cache = dfa.create_cache();
+    let input: Input<'_> = Input::new(line).anchored(Anchored::Yes);
+    let mut sid: LazyStateID = dfa.start_state_forward(&mut cache, &input).ok()?;
+    for (i, b) in line.bytes().enumerate() {
+        sid = dfa.next_state(&mut cache, sid, b).ok()?;
+        if sid.is_dead() || sid.is_quit() {
+            // A character the DFA gave up on partway, e.g. `…` against `\s`,
+            // counts from its first byte
+            let start: usize = (0..=i).rev().find(|j: &usize| line.is_char_boundary(*j))?;
+            return sid.is_dead().then_some(start);
+        }
+    }
+    sid = dfa.next_eoi_state(&mut cache, sid).ok()?;
+    (!sid.is_match()).then_some(line.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line: &str) -> (FormatKind, usize) {
+        let d: Divergence = closest(line).unwrap();
+        (d.kind, d.offset)
+    }
+
+    #[test]
+    fn a_line_that_ends_early() {
+        let line: &str = "06-01 12:00:00.100  1234  1240 I";
+        assert_eq!(at(line), (FormatKind::ThreadTime, line.len()));
+    }
+
+    #[test]
+    fn a_line_that_goes_wrong_midway() {
+        let line: &str = "06-01 12:00:00.100  1234  12x0 I Sync: message";
+        assert_eq!(at(line), (FormatKind::ThreadTime, line.find('x').unwrap()));
+    }
+
+    #[test]
+    fn a_multibyte_character_where_it_goes_wrong() {
+        // `\s` takes some characters starting with the bytes of `…` (U+2026)
+        let line: &str = "06-01 12:00:00.100…1234  1240 I Sync: message";
+        let (kind, offset) = at(line);
+        assert_eq!(
+            (kind, offset),
+            (FormatKind::ThreadTime, line.find('…').unwrap())
+        );
+        assert!(line.is_char_boundary(offset));
+    }
+
+    #[test]
+    fn a_matching_line_has_no_divergence() {
+        let (_, dfa) = DFAS
+            .iter()
+            .find(|(k, _)| *k == FormatKind::ThreadTime)
+            .unwrap();
+        let line: &str = "06-01 12:00:00.100  1234  1240 I Sync: message";
+        assert_eq!(diverges_at(dfa, line), None);
+    }
+}
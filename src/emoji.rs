@@ -0,0 +1,152 @@
+// Emoji removal for consoles that can't render them (serial, CI logs).
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmojiMode {
+    Strip,
+    Shortcode,
+}
+
+// Common emoji and their shortcodes; anything else becomes `:u<hex>:`
+const SHORTCODES: &[(char, &str)] = &[
+    ('☀', "sunny"),
+    ('☁', "cloud"),
+    ('☎', "phone"),
+    ('☑', "ballot_box_with_check"),
+    ('☕', "coffee"),
+    ('☠', "skull_and_crossbones"),
+    ('☹', "frowning_face"),
+    ('☺', "relaxed"),
+    ('♻', "recycle"),
+    ('⚙', "gear"),
+    ('⚠', "warning"),
+    ('⚡', "zap"),
+    ('⛔', "no_entry"),
+    ('✅', "white_check_mark"),
+    ('✈', "airplane"),
+    ('✉', "envelope"),
+    ('✋', "raised_hand"),
+    ('✏', "pencil2"),
+    ('✔', "heavy_check_mark"),
+    ('✖', "heavy_multiplication_x"),
+    ('✨', "sparkles"),
+    ('❌', "x"),
+    ('❓', "question"),
+    ('❗', "exclamation"),
+    ('❤', "heart"),
+    ('➡', "arrow_right"),
+    ('⬅', "arrow_left"),
+    ('⭐', "star"),
+    ('🌍', "earth_africa"),
+    ('🌐', "globe_with_meridians"),
+    ('🎉', "tada"),
+    ('🏁', "checkered_flag"),
+    ('🐛', "bug"),
+    ('🐞', "lady_beetle"),
+    ('👀', "eyes"),
+    ('👋', "wave"),
+    ('👍', "+1"),
+    ('👎', "-1"),
+    ('💀', "skull"),
+    ('💡', "bulb"),
+    ('💥', "boom"),
+    ('💬', "speech_balloon"),
+    ('💾', "floppy_disk"),
+    ('📁', "file_folder"),
+    ('📝', "memo"),
+    ('📡', "satellite"),
+    ('📦', "package"),
+    ('📱', "iphone"),
+    ('📶', "signal_strength"),
+    ('🔄', "arrows_counterclockwise"),
+    ('🔋', "battery"),
+    ('🔌', "electric_plug"),
+    ('🔍', "mag"),
+    ('🔑', "key"),
+    ('🔒', "lock"),
+    ('🔓', "unlock"),
+    ('🔔', "bell"),
+    ('🔥', "fire"),
+    ('🔧', "wrench"),
+    ('🔴', "red_circle"),
+    ('🕐', "clock1"),
+    ('😀', "grinning"),
+    ('😂', "joy"),
+    ('😊', "blush"),
+    ('😎', "sunglasses"),
+    ('😡', "rage"),
+    ('😢', "cry"),
+    ('😱', "scream"),
+    ('🙏', "pray"),
+    ('🚀', "rocket"),
+    ('🚨', "rotating_light"),
+    ('🚫', "no_entry_sign"),
+    ('🛑', "stop_sign"),
+    ('🟡', "yellow_circle"),
+    ('🟢', "green_circle"),
+    ('🤔', "thinking"),
+    ('🤖', "robot"),
+    ('🥳', "partying_face"),
+    ('🧪', "test_tube"),
+    ('🧹', "broom"),
+];
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x2300..=0x23FF     // misc technical (watch, hourglass, media controls)
+        | 0x2600..=0x27BF   // misc symbols, dingbats
+        | 0x2B00..=0x2BFF   // arrows, stars, circles
+        | 0x1F000..=0x1FAFF // mahjong to symbols and pictographs extended A
+    )
+}
+
+// Glue inside emoji sequences: ZWJ, variation selectors, keycap, tag characters
+fn is_joiner(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x200D | 0xFE0E | 0xFE0F | 0x20E3 | 0xE0020..=0xE007F
+    )
+}
+
+fn shortcode(c: char) -> String {
+    match SHORTCODES.binary_search_by_key(&c, |&(e, _): &(char, &str)| e) {
+        Ok(i) => format!(":{}:", SHORTCODES[i].1),
+        Err(_) => format!(":u{:x}:", c as u32),
+    }
+}
+
+/// Removes emoji from `s`, or replaces each emoji sequence with the
+/// shortcode of its first character. Returns None when nothing changed.
+pub fn replace(s: &str, mode: EmojiMode) -> Option<String> {
+    if !s.chars().any(|c: char| is_emoji(c) || is_joiner(c)) {
+        return None;
+    }
+
+    let mut out: String = String::with_capacity(s.len());
+    let mut prev: Option<char> = None;
+    let mut flag_half: bool = false;
+    for c in s.chars() {
+        if is_joiner(c) {
+            if prev.is_some() {
+                prev = Some(c);
+            }
+            continue;
+        }
+        if is_emoji(c) {
+            let regional: bool = (0x1F1E6..=0x1F1FF).contains(&(c as u32));
+            // ZWJ sequences ("👨‍👩‍👧"), skin tones and flag pairs stay one emoji
+            let continues: bool = prev == Some('\u{200D}')
+                || (prev.is_some() && (0x1F3FB..=0x1F3FF).contains(&(c as u32)))
+                || (regional && flag_half);
+            if !continues && mode == EmojiMode::Shortcode {
+                out.push_str(&shortcode(c));
+            }
+            flag_half = regional && !flag_half;
+            prev = Some(c);
+            continue;
+        }
+        prev = None;
+        flag_half = false;
+        out.push(c);
+    }
+    Some(out)
+}
@@ -0,0 +1,227 @@
+use crate::{logcat::Logcat, timestamp};
+use regex::Regex;
+use std::{fs, io, path::Path};
+
+/// Assertions over the stream, checked live or against a replayed capture.
+///
+/// One rule per line; patterns are quoted regexes matched against the tag
+/// and the message, and time limits use entry timestamps (`500ms`, `5s`,
+/// `2m`). Blank lines and `#` comments are skipped:
+///
+/// ```text
+/// expect 'Activity started' within 5s after 'Button clicked'
+/// expect 'Displayed com\.example'
+/// never 'FATAL EXCEPTION'
+/// ```
+pub struct Script {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    line: usize,
+    text: String,
+    kind: Kind,
+}
+
+enum Kind {
+    Expect(Regex),
+    Within {
+        target: Regex,
+        millis: u64,
+        trigger: Regex,
+    },
+    Never(Regex),
+}
+
+impl Script {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text: String = fs::read_to_string(path)?;
+        let mut rules: Vec<Rule> = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let kind: Kind = parse_rule(line).map_err(|msg: String| {
+                io::Error::other(format!("{}:{}: {}", path.display(), n + 1, msg))
+            })?;
+            rules.push(Rule {
+                line: n + 1,
+                text: line.to_string(),
+                kind,
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+fn parse_rule(line: &str) -> Result<Kind, String> {
+    let words: Vec<String> = split_words(line)?;
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let re = |s: &str| -> Result<Regex, String> {
+        Regex::new(s).map_err(|e: regex::Error| format!("invalid regex `{}`: {}", s, e))
+    };
+
+    match words.as_slice() {
+        ["expect", target] => Ok(Kind::Expect(re(target)?)),
+        ["expect", target, "within", limit, "after", trigger] => Ok(Kind::Within {
+            target: re(target)?,
+            millis: timestamp::parse_duration(limit)
+                .ok_or_else(|| format!("invalid duration `{}` (e.g. 500ms, 5s, 2m)", limit))?,
+            trigger: re(trigger)?,
+        }),
+        ["never", target] => Ok(Kind::Never(re(target)?)),
+        _ => Err(
+            "expected `expect 'RE'`, `expect 'RE' within 5s after 'RE'` or `never 'RE'`"
+                .to_string(),
+        ),
+    }
+}
+
+// Whitespace-separated words, where '...' or "..." quote one word
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut word: String = String::new();
+        if c == '\'' || c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some(ch) if ch == c => break,
+                    Some(ch) => word.push(ch),
+                    None => return Err(format!("unterminated {} quote", c)),
+                }
+            }
+        } else {
+            while let Some(&ch) = chars.peek()
+                && !ch.is_whitespace()
+            {
+                word.push(ch);
+                chars.next();
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Per-rule state while a script runs over one stream.
+pub struct Checker<'a> {
+    script: &'a Script,
+    seen: Vec<bool>,
+    // Unanswered triggers of `within` rules: (deadline ms, trigger timestamp)
+    pending: Vec<Vec<(Option<u64>, String)>>,
+    failed: Vec<bool>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(script: &'a Script) -> Self {
+        let n: usize = script.rules.len();
+        Self {
+            script,
+            seen: vec![false; n],
+            pending: vec![Vec::new(); n],
+            failed: vec![false; n],
+        }
+    }
+
+    /// Feeds one entry, returning descriptions of the rules it broke.
+    pub fn check(&mut self, lc: &Logcat) -> Vec<String> {
+        let ts: Option<u64> = timestamp::parse_millis(&lc.timestamp);
+        let hit = |re: &Regex| -> bool { re.is_match(&lc.message) || re.is_match(lc.tag.trim()) };
+        let mut failures: Vec<String> = Vec::new();
+
+        for (i, rule) in self.script.rules.iter().enumerate() {
+            match &rule.kind {
+                Kind::Expect(target) => {
+                    self.seen[i] |= hit(target);
+                }
+                Kind::Never(target) => {
+                    if hit(target) {
+                        self.failed[i] = true;
+                        failures.push(format!("{} (matched at {})", describe(rule), at(lc)));
+                    }
+                }
+                Kind::Within {
+                    target,
+                    millis,
+                    trigger,
+                } => {
+                    // Triggers whose deadline passed before this entry went unanswered
+                    if let Some(now) = ts {
+                        for (_, started) in self.pending[i].extract_if(
+                            ..,
+                            |(deadline, _): &mut (Option<u64>, String)| {
+                                deadline.is_some_and(|d: u64| d < now)
+                            },
+                        ) {
+                            self.failed[i] = true;
+                            failures.push(format!("{} (trigger at {})", describe(rule), started));
+                        }
+                    }
+                    if hit(target) {
+                        self.pending[i].clear();
+                    }
+                    if hit(trigger) {
+                        self.pending[i].push((ts.map(|t: u64| t + millis), at(lc).to_string()));
+                    }
+                }
+            }
+        }
+        failures
+    }
+
+    /// Closes the stream, returning the rules still unmet and the count of
+    /// rules that held.
+    pub fn finish(&mut self) -> (Vec<String>, usize) {
+        let mut failures: Vec<String> = Vec::new();
+        for (i, rule) in self.script.rules.iter().enumerate() {
+            match &rule.kind {
+                Kind::Expect(_) if !self.seen[i] => {
+                    failures.push(format!("{} (never seen)", describe(rule)));
+                    self.failed[i] = true;
+                }
+                Kind::Within { .. } => {
+                    for (_, started) in self.pending[i].drain(..) {
+                        failures.push(format!(
+                            "{} (trigger at {}, stream ended)",
+                            describe(rule),
+                            started
+                        ));
+                        self.failed[i] = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let passed: usize = self.failed.iter().filter(|f: &&bool| !**f).count();
+        (failures, passed)
+    }
+
+    pub fn any_failed(&self) -> bool {
+        self.failed.contains(&true)
+    }
+}
+
+fn describe(rule: &Rule) -> String {
+    format!("line {}: {}", rule.line, rule.text)
+}
+
+fn at(lc: &Logcat) -> &str {
+    if lc.timestamp.is_empty() {
+        "?"
+    } else {
+        &lc.timestamp
+    }
+}
@@ -0,0 +1,547 @@
+use crate::{filter, logcat::Logcat};
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+
+/// A parsed `--filter` expression over entry fields:
+///
+/// ```text
+/// level>=W && tag~"OkHttp|Retrofit" && !msg~"cache"
+/// (pid==1234 || tag=="ActivityManager") && level!=V
+/// ```
+///
+/// Fields are `level`, `tag`, `msg` (or `message`), `pid` and `tid`.
+/// `~` and `!~` match a regex, `==` and `!=` compare exactly, and `<`, `<=`,
+/// `>`, `>=` compare levels by severity and pids/tids as numbers. Values are
+/// bare words or double-quoted strings (`\"` and `\\` escape); after `~` and
+/// `!~` a regex may also be written between slashes with `i`, `m`, `s` or
+/// `x` flags, as in `msg~/timeout/i`.
+pub type Expr = Logic<Test>;
+
+/// `&&`, `||`, `!` and parentheses over tests of type `A`.
+#[derive(Clone, Debug)]
+pub enum Logic<A> {
+    And(Box<Logic<A>>, Box<Logic<A>>),
+    Or(Box<Logic<A>>, Box<Logic<A>>),
+    Not(Box<Logic<A>>),
+    Test(A),
+}
+
+#[derive(Clone, Debug)]
+pub enum Test {
+    Level(Cmp, usize),
+    Number(Field, Cmp, u64),
+    Text(Field, TextOp),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Field {
+    Level,
+    Tag,
+    Message,
+    Pid,
+    Tid,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+pub enum TextOp {
+    Eq(String),
+    Ne(String),
+    Match(Regex),
+    NoMatch(Regex),
+}
+
+impl Cmp {
+    pub fn from_op(op: &str) -> Option<Cmp> {
+        match op {
+            "==" => Some(Cmp::Eq),
+            "!=" => Some(Cmp::Ne),
+            "<" => Some(Cmp::Lt),
+            "<=" => Some(Cmp::Le),
+            ">" => Some(Cmp::Gt),
+            ">=" => Some(Cmp::Ge),
+            _ => None,
+        }
+    }
+
+    pub fn holds(&self, ord: Ordering) -> bool {
+        match self {
+            Cmp::Eq => ord == Ordering::Equal,
+            Cmp::Ne => ord != Ordering::Equal,
+            Cmp::Lt => ord == Ordering::Less,
+            Cmp::Le => ord != Ordering::Greater,
+            Cmp::Gt => ord == Ordering::Greater,
+            Cmp::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+impl<A> Logic<A> {
+    pub fn eval(&self, test: &impl Fn(&A) -> bool) -> bool {
+        match self {
+            Logic::And(a, b) => a.eval(test) && b.eval(test),
+            Logic::Or(a, b) => a.eval(test) || b.eval(test),
+            Logic::Not(e) => !e.eval(test),
+            Logic::Test(t) => test(t),
+        }
+    }
+}
+
+impl Expr {
+    pub fn matches(&self, lc: &Logcat) -> bool {
+        self.eval(&|t: &Test| t.matches(lc))
+    }
+}
+
+impl Test {
+    fn matches(&self, lc: &Logcat) -> bool {
+        match self {
+            Test::Level(cmp, rank) => {
+                filter::level_rank(&lc.level).is_some_and(|r: usize| cmp.holds(r.cmp(rank)))
+            }
+            Test::Number(field, cmp, n) => {
+                let value: &str = match field {
+                    Field::Tid => &lc.thread,
+                    _ => &lc.process,
+                };
+                filter::parse_id(value).is_some_and(|v: u64| cmp.holds(v.cmp(n)))
+            }
+            Test::Text(field, op) => {
+                let value: &str = match field {
+                    Field::Tag => lc.tag.trim(),
+                    _ => &lc.message,
+                };
+                match op {
+                    TextOp::Eq(s) => value == s,
+                    TextOp::Ne(s) => value != s,
+                    TextOp::Match(re) => re.is_match(value),
+                    TextOp::NoMatch(re) => !re.is_match(value),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Word(String),
+    Str(String),
+    Regex(String, String), // pattern, flags
+    Op(&'static str),
+    End,
+}
+
+// Longest first so `<=` isn't read as `<`
+const OPS: [&str; 13] = [
+    "&&", "||", "==", "!=", "<=", ">=", "!~", "<", ">", "~", "!", "(", ")",
+];
+
+/// clap value parser for `--filter`. Errors point at the offending column.
+pub fn parse(src: &str) -> Result<Expr, String> {
+    parse_with(src, entry_test)
+}
+
+/// Parses `src` as a boolean combination of tests read by `test`.
+pub fn parse_with<A>(
+    src: &str,
+    test: fn(&mut Parser<A>) -> ParseResult<A>,
+) -> Result<Logic<A>, String> {
+    let tokens: Vec<(usize, Token)> = lex(src).map_err(|(pos, msg)| describe(src, pos, &msg))?;
+    let mut parser: Parser<A> = Parser {
+        tokens,
+        at: 0,
+        test,
+    };
+    let expr: Logic<A> = parser
+        .or()
+        .and_then(|e: Logic<A>| match parser.peek() {
+            Token::End => Ok(e),
+            t => Err((
+                parser.pos(),
+                format!("expected `&&`, `||` or end, found {}", show(t)),
+            )),
+        })
+        .map_err(|(pos, msg)| describe(src, pos, &msg))?;
+    Ok(expr)
+}
+
+fn describe(src: &str, pos: usize, msg: &str) -> String {
+    let col: usize = src[..pos].chars().count();
+    format!("{}\n    {}\n    {}^", msg, src, " ".repeat(col))
+}
+
+pub fn show(t: &Token) -> String {
+    match t {
+        Token::Word(w) => format!("`{}`", w),
+        Token::Str(s) => format!("\"{}\"", s),
+        Token::Regex(r, flags) => format!("`/{}/{}`", r, flags),
+        Token::Op(op) => format!("`{}`", op),
+        Token::End => "end of expression".to_string(),
+    }
+}
+
+fn lex(src: &str) -> Result<Vec<(usize, Token)>, (usize, String)> {
+    let mut tokens: Vec<(usize, Token)> = Vec::new();
+    let mut chars = src.char_indices().peekable();
+
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut s: String = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, e @ ('"' | '\\'))) => s.push(e),
+                        // Keep other escapes for the regex (`\b`, `\d`, ...)
+                        Some((_, e)) => {
+                            s.push('\\');
+                            s.push(e);
+                        }
+                        None => return Err((pos, "unterminated string".to_string())),
+                    },
+                    Some((_, ch)) => s.push(ch),
+                    None => return Err((pos, "unterminated string".to_string())),
+                }
+            }
+            tokens.push((pos, Token::Str(s)));
+        } else if c == '/' && matches!(tokens.last(), Some((_, Token::Op("~" | "!~")))) {
+            chars.next();
+            let mut re: String = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '/')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, '/')) => re.push('/'),
+                        Some((_, e)) => {
+                            re.push('\\');
+                            re.push(e);
+                        }
+                        None => return Err((pos, "unterminated regex".to_string())),
+                    },
+                    Some((_, ch)) => re.push(ch),
+                    None => return Err((pos, "unterminated regex".to_string())),
+                }
+            }
+            let mut flags: String = String::new();
+            while let Some(&(at, ch)) = chars.peek()
+                && is_word_char(ch)
+            {
+                if !matches!(ch, 'i' | 'm' | 's' | 'x') {
+                    return Err((
+                        at,
+                        format!("unknown regex flag `{}` (expected i, m, s or x)", ch),
+                    ));
+                }
+                flags.push(ch);
+                chars.next();
+            }
+            tokens.push((pos, Token::Regex(re, flags)));
+        } else if let Some(op) = OPS.iter().find(|op: &&&str| src[pos..].starts_with(**op)) {
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            tokens.push((pos, Token::Op(op)));
+        } else if is_word_char(c) {
+            let mut w: String = String::new();
+            while let Some(&(_, ch)) = chars.peek()
+                && is_word_char(ch)
+            {
+                w.push(ch);
+                chars.next();
+            }
+            tokens.push((pos, Token::Word(w)));
+        } else {
+            return Err((pos, format!("unexpected character `{}`", c)));
+        }
+    }
+    tokens.push((src.len(), Token::End));
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '$' | '*' | ':' | '/')
+}
+
+// An error message and the byte offset it points at
+pub type ParseResult<T> = Result<T, (usize, String)>;
+
+pub struct Parser<A> {
+    tokens: Vec<(usize, Token)>,
+    at: usize,
+    test: fn(&mut Parser<A>) -> ParseResult<A>,
+}
+
+impl<A> Parser<A> {
+    pub fn peek(&self) -> &Token {
+        &self.tokens[self.at].1
+    }
+
+    pub fn pos(&self) -> usize {
+        self.tokens[self.at].0
+    }
+
+    pub fn next(&mut self) -> (usize, Token) {
+        let t: (usize, Token) = self.tokens[self.at].clone();
+        if self.at + 1 < self.tokens.len() {
+            self.at += 1;
+        }
+        t
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Token::Op(o) if *o == op) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    /// The comparison operator at the cursor, e.g. `>=`.
+    pub fn comparison(&mut self, what: &str) -> ParseResult<(usize, &'static str)> {
+        match self.next() {
+            (pos, Token::Op(op @ ("==" | "!=" | "<" | "<=" | ">" | ">=" | "~" | "!~"))) => {
+                Ok((pos, op))
+            }
+            (pos, t) => Err((
+                pos,
+                format!(
+                    "expected a comparison after the {}, found {}",
+                    what,
+                    show(&t)
+                ),
+            )),
+        }
+    }
+
+    /// A bare word or quoted string at the cursor.
+    pub fn value(&mut self) -> ParseResult<(usize, String)> {
+        match self.next() {
+            (pos, Token::Word(w) | Token::Str(w)) => Ok((pos, w)),
+            (pos, t) => Err((pos, format!("expected a value, found {}", show(&t)))),
+        }
+    }
+
+    fn or(&mut self) -> ParseResult<Logic<A>> {
+        let mut lhs: Logic<A> = self.and()?;
+        while self.eat("||") {
+            lhs = Logic::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> ParseResult<Logic<A>> {
+        let mut lhs: Logic<A> = self.unary()?;
+        while self.eat("&&") {
+            lhs = Logic::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> ParseResult<Logic<A>> {
+        if self.eat("!") {
+            return Ok(Logic::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let inner: Logic<A> = self.or()?;
+            if !self.eat(")") {
+                return Err((
+                    self.pos(),
+                    format!("expected `)`, found {}", show(self.peek())),
+                ));
+            }
+            return Ok(inner);
+        }
+        (self.test)(self).map(Logic::Test)
+    }
+}
+
+fn entry_test(p: &mut Parser<Test>) -> ParseResult<Test> {
+    let (field_pos, field) = p.next();
+    let field: Field = match &field {
+        Token::Word(w) => match w.as_str() {
+            "level" => Field::Level,
+            "tag" => Field::Tag,
+            "msg" | "message" => Field::Message,
+            "pid" => Field::Pid,
+            "tid" => Field::Tid,
+            _ => {
+                return Err((
+                    field_pos,
+                    format!(
+                        "unknown field `{}` (expected level, tag, msg, pid or tid)",
+                        w
+                    ),
+                ));
+            }
+        },
+        t => return Err((field_pos, format!("expected a field, found {}", show(t)))),
+    };
+
+    let (op_pos, op) = p.comparison("field")?;
+    let (value_pos, value, flags) = match p.next() {
+        (pos, Token::Regex(re, flags)) => (pos, re, flags),
+        (pos, Token::Word(w) | Token::Str(w)) => (pos, w, String::new()),
+        (pos, t) => return Err((pos, format!("expected a value, found {}", show(&t)))),
+    };
+    let cmp: Option<Cmp> = Cmp::from_op(op);
+
+    match (field, cmp) {
+        (Field::Level, Some(cmp)) => {
+            let level: String = filter::parse_level(&value).map_err(|e: String| (value_pos, e))?;
+            Ok(Test::Level(cmp, filter::level_rank(&level).unwrap_or(0)))
+        }
+        (Field::Pid | Field::Tid, Some(cmp)) => value
+            .parse::<u64>()
+            .map(|n: u64| Test::Number(field, cmp, n))
+            .map_err(|_| (value_pos, format!("`{}` is not a number", value))),
+        (Field::Tag | Field::Message, Some(Cmp::Eq)) => Ok(Test::Text(field, TextOp::Eq(value))),
+        (Field::Tag | Field::Message, Some(Cmp::Ne)) => Ok(Test::Text(field, TextOp::Ne(value))),
+        (Field::Tag | Field::Message, Some(_)) => {
+            Err((op_pos, format!("`{}` only compares level, pid and tid", op)))
+        }
+        (Field::Level | Field::Pid | Field::Tid, None) => {
+            Err((op_pos, format!("`{}` only matches tag and msg", op)))
+        }
+        (Field::Tag | Field::Message, None) => {
+            // The regex error spans several lines; its last one names the problem
+            let re: Regex = RegexBuilder::new(&value)
+                .case_insensitive(flags.contains('i'))
+                .multi_line(flags.contains('m'))
+                .dot_matches_new_line(flags.contains('s'))
+                .ignore_whitespace(flags.contains('x'))
+                .build()
+                .map_err(|e: regex::Error| {
+                    let reason: String = e
+                        .to_string()
+                        .lines()
+                        .last()
+                        .map(|l: &str| l.trim_start_matches("error: ").to_string())
+                        .unwrap_or_default();
+                    (value_pos, format!("invalid regex: {}", reason))
+                })?;
+            Ok(Test::Text(
+                field,
+                if op == "~" {
+                    TextOp::Match(re)
+                } else {
+                    TextOp::NoMatch(re)
+                },
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::entry;
+
+    fn error(src: &str) -> String {
+        parse(src).unwrap_err()
+    }
+
+    const OKHTTP: &str = "06-01 12:00:00.100  1234  1240 W OkHttp  : Timeout reading /v1/feed";
+    const AM: &str = "06-01 12:00:00.200   612   640 I ActivityManager: Start proc 1234";
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let expr: Expr = parse("tag==a || tag==b && tag==c").unwrap();
+        assert!(matches!(&expr, Logic::Or(_, rhs) if matches!(**rhs, Logic::And(..))));
+        let expr: Expr = parse("(tag==a || tag==b) && tag==c").unwrap();
+        assert!(matches!(&expr, Logic::And(lhs, _) if matches!(**lhs, Logic::Or(..))));
+        let expr: Expr = parse("!tag==a && tag==b").unwrap();
+        assert!(matches!(&expr, Logic::And(lhs, _) if matches!(**lhs, Logic::Not(_))));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(error("level>=W &&").contains("expected a field, found end of expression"));
+        assert!(error("colour==red").starts_with("unknown field `colour`"));
+        assert!(error("tag W").starts_with("expected a comparison after the field"));
+        assert!(error("tag>=W").starts_with("`>=` only compares level, pid and tid"));
+        assert!(error("pid~12").starts_with("`~` only matches tag and msg"));
+        assert!(error("pid==abc").starts_with("`abc` is not a number"));
+        assert!(error("level==Q").contains("V D I W E F A"));
+        assert!(error("(tag==a").starts_with("expected `)`"));
+        assert!(error("tag==a tag==b").starts_with("expected `&&`, `||` or end"));
+        assert!(error("tag==a # b").starts_with("unexpected character `#`"));
+        assert!(error("msg~\"(\"").starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn rejects_unterminated_strings() {
+        assert_eq!(
+            error("tag==\"OkHttp"),
+            "unterminated string\n    tag==\"OkHttp\n         ^"
+        );
+        assert!(error("msg~\"ends in \\").starts_with("unterminated string"));
+        assert!(error("msg~/timeout").starts_with("unterminated regex"));
+        assert!(error("msg~/timeout/q").starts_with("unknown regex flag `q`"));
+    }
+
+    #[test]
+    fn points_at_the_column() {
+        assert_eq!(
+            error("level>=W && pid==x1"),
+            "`x1` is not a number\n    level>=W && pid==x1\n                     ^"
+        );
+    }
+
+    #[test]
+    fn evaluates_against_entries() {
+        let okhttp: Logcat = entry(OKHTTP);
+        let am: Logcat = entry(AM);
+        let cases: [(&str, bool, bool); 10] = [
+            ("level>=W", true, false),
+            ("level<W", false, true),
+            ("tag==OkHttp", true, false),
+            ("tag~\"OkHttp|Retrofit\" && !msg~\"cache\"", true, false),
+            ("pid==1234 || tag==\"ActivityManager\"", true, true),
+            ("tid>1000", true, false),
+            ("msg==\"Start proc 1234\"", false, true),
+            ("message!~proc", true, false),
+            ("!(level>=W || pid==612)", false, false),
+            ("level!=V && (pid<1000 || tid==1240)", true, true),
+        ];
+        for (src, on_okhttp, on_am) in cases {
+            let expr: Expr = parse(src).unwrap();
+            assert_eq!(expr.matches(&okhttp), on_okhttp, "{} on OkHttp", src);
+            assert_eq!(expr.matches(&am), on_am, "{} on ActivityManager", src);
+        }
+    }
+
+    #[test]
+    fn quoted_strings_keep_regex_escapes() {
+        let lc: Logcat = entry(OKHTTP);
+        assert!(parse(r#"msg~"\bTimeout\b""#).unwrap().matches(&lc));
+        assert!(parse(r#"msg=="say \"hi\"""#).is_ok());
+        assert!(!parse(r#"msg~"\d{5}""#).unwrap().matches(&lc));
+    }
+
+    #[test]
+    fn slash_regexes_take_flags() {
+        let lc: Logcat = entry(OKHTTP);
+        assert!(!parse("msg~/timeout/").unwrap().matches(&lc));
+        assert!(parse("msg~/timeout/i").unwrap().matches(&lc));
+        assert!(parse("msg!~/^reading/").unwrap().matches(&lc));
+        // `\/` is a slash inside the regex
+        assert!(parse(r"msg~/\/v1\/feed$/").unwrap().matches(&lc));
+        assert!(parse("msg~/ t i m e o u t /ix").unwrap().matches(&lc));
+        // Without `~` before it, a slash is part of a word
+        let expr: Expr = parse("tag==/a/ && msg~/b/").unwrap();
+        assert!(matches!(&expr, Logic::And(lhs, _)
+            if matches!(&**lhs, Logic::Test(Test::Text(Field::Tag, TextOp::Eq(s))) if s == "/a/")));
+    }
+}
@@ -0,0 +1,450 @@
+use crate::{
+    expr::Expr,
+    logcat::{Logcat, Source},
+    pids::PackagePids,
+    timestamp::{self, Dates},
+    users,
+};
+use regex::{Regex, RegexBuilder};
+
+// Severity order used by logcat filterspecs
+const LEVELS: [&str; 6] = ["V", "D", "I", "W", "E", "F"];
+
+/// Position of `level` in severity order; assert (`A`) ranks with fatal.
+pub fn level_rank(level: &str) -> Option<usize> {
+    match level {
+        "A" => Some(LEVELS.len() - 1),
+        _ => LEVELS.iter().position(|l: &&str| *l == level),
+    }
+}
+
+/// clap value parser for level options: a letter (`W`) or a name (`warning`).
+pub fn parse_level(s: &str) -> Result<String, String> {
+    let letter: String = match s.to_ascii_lowercase().as_str() {
+        "v" | "verbose" => "V",
+        "d" | "debug" => "D",
+        "i" | "info" => "I",
+        "w" | "warn" | "warning" => "W",
+        "e" | "error" => "E",
+        "f" | "fatal" | "a" | "assert" => "F",
+        _ => {
+            return Err(format!(
+                "unknown level `{}` (expected one of V D I W E F A)",
+                s
+            ));
+        }
+    }
+    .to_string();
+    Ok(letter)
+}
+
+/// A pid or tid field as a number; `-v long` gives tids in hex.
+pub fn parse_id(s: &str) -> Option<u64> {
+    let s: &str = s.trim();
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// `tag` trimmed, with inner whitespace runs collapsed and, with
+/// `fold_case`, lowercased, so OEM variants of a tag match the same
+/// filters. None when it is already in that form.
+pub fn normalize_tag(tag: &str, fold_case: bool) -> Option<String> {
+    let mut norm: String = tag.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if fold_case {
+        norm = norm.to_lowercase();
+    }
+    (norm != tag).then_some(norm)
+}
+
+/// What kept a line from being shown, as counted for `--stats` and
+/// `--show-dropped`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Hidden {
+    Tag,
+    Pid,
+    Tid,
+    Package,
+    User,
+    Message,
+    Muted,
+    Level,
+    Time,
+    Expr,
+    Unparsed, // --ignore
+    NotEvent, // --events-only
+}
+
+impl Hidden {
+    pub const ALL: [Hidden; 12] = [
+        Hidden::Tag,
+        Hidden::Pid,
+        Hidden::Tid,
+        Hidden::Package,
+        Hidden::User,
+        Hidden::Message,
+        Hidden::Muted,
+        Hidden::Level,
+        Hidden::Time,
+        Hidden::Expr,
+        Hidden::Unparsed,
+        Hidden::NotEvent,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Hidden::Tag => "by tag",
+            Hidden::Pid => "by pid",
+            Hidden::Tid => "by tid",
+            Hidden::Package => "by package",
+            Hidden::User => "by user",
+            Hidden::Message => "by message",
+            Hidden::Muted => "muted",
+            Hidden::Level => "by level",
+            Hidden::Time => "by time",
+            Hidden::Expr => "by --filter",
+            Hidden::Unparsed => "unparsed",
+            Hidden::NotEvent => "not events",
+        }
+    }
+}
+
+/// A `--tag` or `--exclude-tag` value: a tag name, a glob with `*` and `?`
+/// (`MyApp*`), or a regex between slashes (`/^(Wifi|Bt)/`).
+#[derive(Clone)]
+pub enum TagPattern {
+    Name(String),
+    Match(Regex),
+}
+
+impl TagPattern {
+    /// With `fold_case`, names are lowercased and patterns ignore case.
+    pub fn parse(s: &str, fold_case: bool) -> Result<Self, String> {
+        let source: String = if let Some(re) = s.strip_prefix('/').and_then(|r| r.strip_suffix('/'))
+        {
+            re.to_string()
+        } else if s.contains(['*', '?']) {
+            let mut re: String = "^".to_string();
+            for c in s.chars() {
+                match c {
+                    '*' => re.push_str(".*"),
+                    '?' => re.push('.'),
+                    _ => re.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            re.push('$');
+            re
+        } else {
+            return Ok(TagPattern::Name(match fold_case {
+                true => s.to_lowercase(),
+                false => s.to_string(),
+            }));
+        };
+        RegexBuilder::new(&source)
+            .case_insensitive(fold_case)
+            .build()
+            .map(TagPattern::Match)
+            .map_err(|e: regex::Error| format!("`{}`: {}", s, e))
+    }
+
+    pub fn matches(&self, tag: &str) -> bool {
+        match self {
+            TagPattern::Name(name) => name == tag,
+            TagPattern::Match(re) => re.is_match(tag),
+        }
+    }
+
+    // The name, when a filterspec can carry it (no spaces or colons)
+    fn spec_name(&self) -> Option<&str> {
+        match self {
+            TagPattern::Name(name) if !name.is_empty() && !name.contains([':', ' ']) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Host-side filters over parsed fields.
+#[derive(Clone, Default)]
+pub struct Filters {
+    pub tags: Vec<TagPattern>,
+    pub excluded_tags: Vec<TagPattern>,
+    pub pids: Vec<u64>,
+    pub tids: Vec<u64>,
+    pub packages: Option<PackagePids>,
+    pub users: Vec<u32>,  // entries whose user can't be told count as user 0's
+    pub grep: Vec<Regex>, // any must match the message
+    pub excluded: Vec<Regex>, // none may match the message
+    pub min_level: Option<String>,
+    pub since: Option<u64>, // milliseconds since 1970, as `Dates` has them
+    pub until: Option<u64>,
+    pub dates: Dates, // the entries' times, for since and until
+    pub expr: Option<Expr>,
+    pub muted: Vec<String>, // tags hidden through the control socket
+}
+
+impl Filters {
+    pub fn accepts(&self, lc: &Logcat) -> bool {
+        self.rejects(lc).is_none()
+    }
+
+    /// The filter that hides `lc`, if any.
+    pub fn rejects(&self, lc: &Logcat) -> Option<Hidden> {
+        self.check(lc, true)
+    }
+
+    /// Whether a filter but --grep and --exclude rejects `lc`, which makes
+    /// an entry those hid fit to show as context.
+    pub fn rejects_besides_message(&self, lc: &Logcat) -> bool {
+        self.check(lc, false).is_some()
+    }
+
+    fn check(&self, lc: &Logcat, message: bool) -> Option<Hidden> {
+        // Host log lines carry the file name as their tag, not a logcat tag
+        if lc.source == Source::Device {
+            let tag: &str = lc.tag.trim();
+            if !self.tags.is_empty() && !self.tags.iter().any(|t: &TagPattern| t.matches(tag)) {
+                return Some(Hidden::Tag);
+            }
+            if self
+                .excluded_tags
+                .iter()
+                .any(|t: &TagPattern| t.matches(tag))
+            {
+                return Some(Hidden::Tag);
+            }
+        }
+        if !self.pids.is_empty()
+            && !parse_id(&lc.process).is_some_and(|pid: u64| self.pids.contains(&pid))
+        {
+            return Some(Hidden::Pid);
+        }
+        if let Some(packages) = &self.packages
+            && !parse_id(&lc.process).is_some_and(|pid: u64| packages.contains(pid))
+        {
+            return Some(Hidden::Package);
+        }
+        if !self.users.is_empty() {
+            let user: u32 = lc.user.or_else(|| users::from_uid(&lc.uid)).unwrap_or(0);
+            if !self.users.contains(&user) {
+                return Some(Hidden::User);
+            }
+        }
+        if !self.tids.is_empty()
+            && !parse_id(&lc.thread).is_some_and(|tid: u64| self.tids.contains(&tid))
+        {
+            return Some(Hidden::Tid);
+        }
+        if message
+            && !self.grep.is_empty()
+            && !self.grep.iter().any(|r: &Regex| r.is_match(&lc.message))
+        {
+            return Some(Hidden::Message);
+        }
+        if message
+            && self
+                .excluded
+                .iter()
+                .any(|r: &Regex| r.is_match(&lc.message))
+        {
+            return Some(Hidden::Message);
+        }
+        if !self.muted.is_empty() && self.muted.iter().any(|t: &String| t == lc.tag.trim()) {
+            return Some(Hidden::Muted);
+        }
+        if let Some(min) = self.min_level.as_deref().and_then(level_rank)
+            && level_rank(&lc.level).is_some_and(|rank: usize| rank < min)
+        {
+            return Some(Hidden::Level);
+        }
+        // Epoch times compare as UTC wall clock times; monotonic ones can't
+        if (self.since.is_some() || self.until.is_some())
+            && !timestamp::is_monotonic(&lc.timestamp)
+            && let Some(ms) = self.dates.millis(&lc.timestamp)
+            && (self.since.is_some_and(|s: u64| ms < s) || self.until.is_some_and(|u: u64| ms > u))
+        {
+            return Some(Hidden::Time);
+        }
+        match self.expr.as_ref().is_none_or(|e: &Expr| e.matches(lc)) {
+            true => None,
+            false => Some(Hidden::Expr),
+        }
+    }
+
+    /// The part of these filters adb can apply on the device, as logcat
+    /// filterspecs (`Tag:W *:S`). Whatever can't be expressed is left to the
+    /// host-side pass, which always runs.
+    ///
+    /// With `--package`, the tags that log process starts and deaths are let
+    /// through at `I`: the package's pids are read from them before the host
+    /// pass hides them.
+    pub fn device_filterspecs(&self) -> Vec<String> {
+        let specs: Vec<String> = self.device_filters();
+        if self.packages.is_none() || specs.is_empty() {
+            return specs;
+        }
+        let level: &str = self.min_level.as_deref().unwrap_or("V");
+        let level: &str = match level_rank(level) < level_rank("I") {
+            true => level,
+            false => "I",
+        };
+        // These replace any spec of the same tags, `ActivityManager:S` included
+        let others = specs.into_iter().filter(|spec: &String| {
+            !PROCESS_TAGS.contains(&spec.rsplit_once(':').map_or("", |(tag, _)| tag))
+        });
+        PROCESS_TAGS
+            .iter()
+            .map(|t: &&str| format!("{}:{}", t, level))
+            .chain(others)
+            .collect()
+    }
+
+    fn device_filters(&self) -> Vec<String> {
+        let level: &str = self.min_level.as_deref().unwrap_or("V");
+        let tags: Option<Vec<&str>> = self.tags.iter().map(TagPattern::spec_name).collect();
+        if !self.tags.is_empty()
+            && let Some(tags) = tags
+        {
+            let mut specs: Vec<String> = tags
+                .iter()
+                .filter(|t: &&&str| !self.excluded_tags.iter().any(|x| x.matches(t)))
+                .map(|t: &&str| format!("{}:{}", t, level))
+                .collect();
+            specs.push("*:S".to_string());
+            return specs;
+        }
+        // Exclusions that are plain names are silenced on the device too
+        let mut specs: Vec<String> = match self.tags.is_empty() {
+            true => self
+                .excluded_tags
+                .iter()
+                .filter_map(TagPattern::spec_name)
+                .map(|t: &str| format!("{}:S", t))
+                .collect(),
+            false => Vec::new(),
+        };
+        if self.min_level.is_some() || !specs.is_empty() {
+            specs.push(format!("*:{}", level));
+        }
+        specs
+    }
+}
+
+// Tags whose entries start and end a package's processes (see `PackagePids`)
+const PROCESS_TAGS: [&str; 2] = ["ActivityManager", "Zygote"];
+
+/// Whether logcat arguments already include a filterspec such as `Tag:V` or `*:S`.
+pub fn has_filterspec(args: &[String]) -> bool {
+    args.iter().any(|a: &String| {
+        !a.starts_with('-')
+            && a.rsplit_once(':')
+                .is_some_and(|(tag, level): (&str, &str)| {
+                    !tag.is_empty() && matches!(level, "V" | "D" | "I" | "W" | "E" | "F" | "S")
+                })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(tags: &[&str], excluded: &[&str], level: Option<&str>) -> Filters {
+        let parse = |tags: &[&str]| -> Vec<TagPattern> {
+            tags.iter()
+                .map(|t: &&str| TagPattern::parse(t, false).unwrap())
+                .collect()
+        };
+        Filters {
+            tags: parse(tags),
+            excluded_tags: parse(excluded),
+            min_level: level.map(str::to_string),
+            ..Filters::default()
+        }
+    }
+
+    #[test]
+    fn specs_without_packages() {
+        let specs = |f: Filters| -> String { f.device_filterspecs().join(" ") };
+        assert_eq!(specs(filters(&[], &[], None)), "");
+        assert_eq!(specs(filters(&[], &[], Some("W"))), "*:W");
+        assert_eq!(specs(filters(&["A", "B"], &["B"], Some("D"))), "A:D *:S");
+        assert_eq!(specs(filters(&[], &["Chatty"], None)), "Chatty:S *:V");
+        assert_eq!(specs(filters(&["My*"], &[], Some("E"))), "*:E");
+    }
+
+    #[test]
+    fn packages_keep_process_tags() {
+        let specs = |mut f: Filters| -> String {
+            f.packages = Some(PackagePids::default());
+            f.device_filterspecs().join(" ")
+        };
+        // Nothing is silenced, so nothing is added
+        assert_eq!(specs(filters(&[], &[], None)), "");
+        assert_eq!(
+            specs(filters(&["MyApp"], &[], None)),
+            "ActivityManager:V Zygote:V MyApp:V *:S"
+        );
+        assert_eq!(
+            specs(filters(&[], &[], Some("E"))),
+            "ActivityManager:I Zygote:I *:E"
+        );
+        assert_eq!(
+            specs(filters(&["ActivityManager"], &["Zygote"], Some("W"))),
+            "ActivityManager:I Zygote:I *:S"
+        );
+    }
+
+    #[test]
+    fn absolute_since_and_until() {
+        let since = timestamp::parse_bound("06-01 12:00:00.300").unwrap();
+        let until = timestamp::parse_bound("06-01 12:00:00.700").unwrap();
+        let (since, until) =
+            timestamp::resolve_bounds(Some(&since), Some(&until), || unreachable!()).unwrap();
+        let f: Filters = Filters {
+            since,
+            until,
+            ..Filters::default()
+        };
+        let at = |ts: &str| -> Logcat {
+            Logcat {
+                timestamp: ts.to_string(),
+                level: "I".to_string(),
+                ..Logcat::default()
+            }
+        };
+        assert_eq!(f.rejects(&at("06-01 12:00:00.200")), Some(Hidden::Time));
+        assert_eq!(f.rejects(&at("06-01 12:00:00.500")), None);
+        assert_eq!(f.rejects(&at("06-01 12:00:00.800")), Some(Hidden::Time));
+    }
+
+    #[test]
+    fn time_window_over_new_year() {
+        // 2027-01-01 00:02:00, a minute after the window closes
+        let now: u64 = 1_798_761_720_000;
+        let bound = |s: &str| timestamp::parse_bound(s).unwrap().resolve(now).ok();
+        let f: Filters = Filters {
+            since: bound("12-31 23:59:00"),
+            until: bound("01-01 00:01:00"),
+            dates: Dates::new(now),
+            ..Filters::default()
+        };
+        let hidden: Vec<Option<Hidden>> = [
+            "12-31 23:58:59.999",
+            "12-31 23:59:30.000",
+            "01-01 00:00:30.000",
+            "12-31 23:59:59.999",
+            "01-01 00:01:00.001",
+        ]
+        .iter()
+        .map(|ts: &&str| {
+            f.rejects(&Logcat {
+                timestamp: ts.to_string(),
+                level: "I".to_string(),
+                ..Logcat::default()
+            })
+        })
+        .collect();
+        let time: Option<Hidden> = Some(Hidden::Time);
+        assert_eq!(hidden, [time, None, None, None, time]);
+    }
+}
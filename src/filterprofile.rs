@@ -0,0 +1,151 @@
+use crate::{
+    config::{Config, Section},
+    expr::{self, Expr},
+    filter,
+    spotlight::{self, Mode, Rule},
+};
+use regex::Regex;
+use std::io;
+
+/// A named bundle of filters, spotlights, a theme and keys from a `[[profile]]`
+/// table, picked with `--profile NAME`:
+///
+/// ```toml
+/// [[profile]]
+/// name = "network"
+/// tag = ["OkHttp", "/^Cronet/"]
+/// exclude_tag = "chatty"
+/// level = "I"
+/// spotlight = ['<-- [45][0-9]{2}=yellow']
+/// theme = "dim"    # a [theme.dim] table
+/// keys = "vi"      # a [keys.vi] table
+/// ```
+pub struct FilterProfile<'a> {
+    pub tags: Vec<String>,
+    pub excluded_tags: Vec<String>,
+    pub level: Option<String>,
+    pub grep: Vec<Regex>,
+    pub exclude: Vec<Regex>,
+    pub spotlight: Vec<Rule>,
+    pub filter: Option<Expr>,
+    pub theme: Option<(&'a Config, &'a Section)>,
+    pub keys: Option<(&'a Config, &'a Section)>,
+}
+
+/// The keys of a `[[profile]]` table, besides its name.
+pub const KEYS: [&str; 9] = [
+    "tag",
+    "exclude_tag",
+    "level",
+    "grep",
+    "exclude",
+    "spotlight",
+    "filter",
+    "theme",
+    "keys",
+];
+
+pub fn load<'a>(configs: &'a [Config], name: &str) -> io::Result<FilterProfile<'a>> {
+    let mut found: Option<(&Config, &Section)> = None;
+    let mut names: Vec<String> = Vec::new();
+    let known: Vec<&str> = ["name"].into_iter().chain(KEYS).collect();
+    for config in configs {
+        for section in config.tables("profile") {
+            section.check_keys(config, &known)?;
+            let this: &str = section.required_str(config, "name")?;
+            if this != name {
+                names.push(this.to_string());
+            } else if found.is_some() {
+                return Err(config.error(
+                    section.line,
+                    &format!("profile `{}` is already defined", name),
+                ));
+            } else {
+                found = Some((config, section));
+            }
+        }
+    }
+    match found {
+        Some((config, section)) => load_profile(configs, config, section),
+        None if names.is_empty() => Err(io::Error::other(format!(
+            "--profile {}: no [[profile]] tables in the config",
+            name
+        ))),
+        None => Err(io::Error::other(format!(
+            "--profile {}: unknown profile (expected one of {})",
+            name,
+            names.join(", ")
+        ))),
+    }
+}
+
+/// The defaults set by keys before the first table of the config, which
+/// take the same keys as a profile (with `theme` and `keys` naming
+/// `[theme.NAME]` and `[keys.NAME]` tables) and are merged with the command line the same way.
+pub fn defaults<'a>(configs: &'a [Config], config: &'a Config) -> io::Result<FilterProfile<'a>> {
+    load_profile(configs, config, config.root())
+}
+
+fn load_profile<'a>(
+    configs: &'a [Config],
+    config: &'a Config,
+    section: &'a Section,
+) -> io::Result<FilterProfile<'a>> {
+    let at = |key: &str| -> usize { section.get(key).map_or(section.line, |e| e.line) };
+    let regexes = |key: &str| -> io::Result<Vec<Regex>> {
+        section
+            .strings(config, key)?
+            .unwrap_or_default()
+            .iter()
+            .map(|s: &String| {
+                Regex::new(s).map_err(|e: regex::Error| {
+                    config.error(at(key), &format!("invalid regex: {}", e))
+                })
+            })
+            .collect()
+    };
+
+    let spotlight: Vec<Rule> = section
+        .strings(config, "spotlight")?
+        .unwrap_or_default()
+        .iter()
+        .map(|s: &String| spotlight::parse_rule(s, Mode::default()))
+        .collect::<Result<_, String>>()
+        .map_err(|e: String| config.error(at("spotlight"), &e))?;
+    // `theme = "dim"` names a [theme.dim] table, in any of the configs
+    let named = |key: &str| -> io::Result<Option<(&'a Config, &'a Section)>> {
+        let Some(name) = section.str(config, key)? else {
+            return Ok(None);
+        };
+        let table: String = format!("{}.{}", key, name);
+        let found: Option<(&Config, &Section)> = configs.iter().find_map(|c: &'a Config| {
+            c.sections
+                .iter()
+                .find(|s: &&Section| s.name == table)
+                .map(|s: &Section| (c, s))
+        });
+        Ok(Some(found.ok_or_else(|| {
+            config.error(at(key), &format!("no [{}] table", table))
+        })?))
+    };
+
+    Ok(FilterProfile {
+        tags: section.strings(config, "tag")?.unwrap_or_default(),
+        excluded_tags: section.strings(config, "exclude_tag")?.unwrap_or_default(),
+        level: section
+            .str(config, "level")?
+            .map(filter::parse_level)
+            .transpose()
+            .map_err(|e: String| config.error(at("level"), &e))?,
+        grep: regexes("grep")?,
+        exclude: regexes("exclude")?,
+        spotlight,
+        filter: section
+            .str(config, "filter")?
+            .map(expr::parse)
+            .transpose()
+            .map_err(|e: String| config.error(at("filter"), &e))?,
+        theme: named("theme")?,
+        keys: named("keys")?,
+    })
+}
@@ -0,0 +1,73 @@
+use crate::{logcat::Logcat, timestamp};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Entries marked during a triage session with the `mark [NOTE]` control
+/// command. Each is appended to the `--findings` file as it is marked, in
+/// threadtime form under a comment with the time it was marked and the
+/// note, so the file can be read back through the colorizer:
+///
+/// ```text
+/// # marked 10-15 12:00:04.211 UTC: login button does nothing
+/// 10-15 12:00:03.870  4242  4260 E AuthRepo: token refresh failed
+/// ```
+pub struct Findings {
+    path: PathBuf,
+    file: File,
+    pub marked: Vec<(Logcat, Option<String>)>,
+}
+
+impl Findings {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file: File = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            marked: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn mark(&mut self, lc: &Logcat, note: Option<String>) -> io::Result<()> {
+        let now: Duration = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let at: String =
+            timestamp::epoch_to_wallclock(&format!("{}.{:03}", now.as_secs(), now.subsec_millis()))
+                .unwrap_or_default();
+        let header: String = match &note {
+            Some(note) => format!("# marked {} UTC: {}", at, note),
+            None => format!("# marked {} UTC", at),
+        };
+        writeln!(self.file, "{}\n{}", header, threadtime(lc))?;
+        self.file.flush()?;
+        self.marked.push((lc.clone(), note));
+        Ok(())
+    }
+}
+
+// The entry as `adb logcat -v threadtime` prints it, leaving out what the
+// input format didn't have
+fn threadtime(lc: &Logcat) -> String {
+    let mut line: String = String::new();
+    if !lc.timestamp.is_empty() {
+        line.push_str(&lc.timestamp);
+        line.push(' ');
+    }
+    if !lc.process.is_empty() {
+        line.push_str(&format!("{:>5} {:>5} ", lc.process, lc.thread));
+    }
+    line.push_str(&format!("{} {}: {}", lc.level, lc.tag, lc.message));
+    line
+}
@@ -0,0 +1,31 @@
+use std::io::{self, Write};
+
+/// The columns fixtures render at, whatever the terminal's width.
+pub const COLUMNS: usize = 80;
+
+/// `--render-fixture`: output with its escape sequences written out, so a
+/// snapshot shows the styles as text (`\e[1;31m`) that diffs line by line.
+/// `ESC` comes out as `\e`, other control characters but newline and tab
+/// as `\xNN`, and a backslash doubled.
+pub struct Escaped<W: Write>(pub W);
+
+impl<W: Write> Write for Escaped<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut out: Vec<u8> = Vec::with_capacity(buf.len() + buf.len() / 4);
+        for b in buf {
+            match b {
+                0x1b => out.extend_from_slice(b"\\e"),
+                b'\\' => out.extend_from_slice(b"\\\\"),
+                b'\n' | b'\t' => out.push(*b),
+                0..0x20 | 0x7f => out.extend_from_slice(format!("\\x{:02x}", b).as_bytes()),
+                _ => out.push(*b),
+            }
+        }
+        self.0.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+// A tag is flagged when its error rate is this many times its baseline...
+const SPIKE_FACTOR: f64 = 2.0;
+// ...and it logged at least this many errors in the current run
+const SPIKE_MIN_ERRORS: u64 = 5;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TagCounts {
+    pub entries: u64,
+    pub errors: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Baseline {
+    sessions: u64,
+    entries: u64,
+    errors: u64,
+}
+
+/// Per-tag counts accumulated across runs, kept as a small TSV file
+/// (`tag, sessions, entries, errors` per line).
+pub struct History {
+    path: PathBuf,
+    tags: HashMap<String, Baseline>,
+}
+
+pub struct Spike {
+    pub tag: String,
+    pub errors: u64,
+    pub rate: f64,
+    pub baseline: Option<f64>, // None for tags never seen before
+}
+
+/// `$XDG_DATA_HOME/logcat-colorize/tag-stats.tsv`, falling back to `~/.local/share`.
+pub fn default_path() -> Option<PathBuf> {
+    let base: PathBuf = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("logcat-colorize").join("tag-stats.tsv"))
+}
+
+impl History {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text: String = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut tags: HashMap<String, Baseline> = HashMap::new();
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if let [tag, sessions, entries, errors] = fields[..]
+                && let (Ok(sessions), Ok(entries), Ok(errors)) =
+                    (sessions.parse(), entries.parse(), errors.parse())
+            {
+                tags.insert(
+                    tag.to_string(),
+                    Baseline {
+                        sessions,
+                        entries,
+                        errors,
+                    },
+                );
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            tags,
+        })
+    }
+
+    /// Tags whose error rate in `session` stands out against their history,
+    /// worst first.
+    pub fn spikes(&self, session: &HashMap<String, TagCounts>) -> Vec<Spike> {
+        let mut spikes: Vec<Spike> = session
+            .iter()
+            .filter(|(_, c): &(&String, &TagCounts)| c.errors >= SPIKE_MIN_ERRORS)
+            .filter_map(|(tag, c): (&String, &TagCounts)| {
+                let rate: f64 = c.errors as f64 / c.entries as f64;
+                let baseline: Option<f64> = self
+                    .tags
+                    .get(tag)
+                    .filter(|b: &&Baseline| b.entries > 0)
+                    .map(|b: &Baseline| b.errors as f64 / b.entries as f64);
+                match baseline {
+                    Some(base) if rate < base * SPIKE_FACTOR => None,
+                    _ => Some(Spike {
+                        tag: tag.clone(),
+                        errors: c.errors,
+                        rate,
+                        baseline,
+                    }),
+                }
+            })
+            .collect();
+        spikes.sort_by_key(|s: &Spike| Reverse(s.errors));
+        spikes
+    }
+
+    pub fn merge(&mut self, session: &HashMap<String, TagCounts>) {
+        for (tag, c) in session {
+            let b: &mut Baseline = self.tags.entry(tag.clone()).or_default();
+            b.sessions += 1;
+            b.entries += c.entries;
+            b.errors += c.errors;
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tags: Vec<(&String, &Baseline)> = self.tags.iter().collect();
+        tags.sort_by(|a: &(&String, &Baseline), b: &(&String, &Baseline)| a.0.cmp(b.0));
+
+        // Write aside then rename, so an interrupted run can't truncate the history
+        let tmp: PathBuf = self.path.with_extension("tsv.tmp");
+        let mut f: io::BufWriter<fs::File> = io::BufWriter::new(fs::File::create(&tmp)?);
+        for (tag, b) in tags {
+            writeln!(f, "{}\t{}\t{}\t{}", tag, b.sessions, b.entries, b.errors)?;
+        }
+        f.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+/// Tag key as stored in the history file.
+pub fn tag_key(tag: &str) -> String {
+    tag.trim().replace(['\t', '\n', '\r'], " ")
+}
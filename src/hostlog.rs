@@ -0,0 +1,134 @@
+use crate::{
+    aftercommand::Finished,
+    input,
+    logcat::{Logcat, Source},
+};
+use regex::Regex;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        LazyLock,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+// "2025-06-01 12:34:56.789", "2025-06-01T12:34:56,789Z", "06-01 12:34:56.789"
+static RE_DATETIME: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^\[?(?:[0-9]{4}-)?([0-9]{2}-[0-9]{2})[ T]([0-9]{2}:[0-9]{2}:[0-9]{2})[.,]([0-9]{3})[0-9]*Z?\]?\s*").unwrap()
+});
+// "12:34:56.789" with the date taken from the device stream
+static RE_TIME_OF_DAY: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^\[?([0-9]{2}:[0-9]{2}:[0-9]{2})[.,]([0-9]{3})[0-9]*\]?\s*").unwrap()
+});
+static RE_LEVEL: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"\b(FATAL|ERROR|WARN|WARNING|INFO|DEBUG|TRACE|VERBOSE)\b").unwrap()
+});
+
+const POLL: Duration = Duration::from_millis(200);
+
+pub enum Input {
+    Device(String),
+    Host(String),
+    Finished(Finished), // the --after-command run
+    Idle,               // nothing came for a while, see `reorder::ticking`
+    End,
+}
+
+/// Reads the device stream and follows the host log on two threads,
+/// yielding their lines in arrival order until the device stream ends.
+pub fn merge<R: BufRead + Send + 'static>(
+    device: R,
+    host: PathBuf,
+) -> impl Iterator<Item = io::Result<Input>> {
+    let (tx, rx): (Sender<io::Result<Input>>, Receiver<io::Result<Input>>) = mpsc::channel();
+
+    let device_tx: Sender<io::Result<Input>> = tx.clone();
+    thread::spawn(move || {
+        for line in input::lines(device) {
+            let failed: bool = line.is_err();
+            if device_tx.send(line.map(Input::Device)).is_err() || failed {
+                return;
+            }
+        }
+        let _ = device_tx.send(Ok(Input::End));
+    });
+    thread::spawn(move || {
+        if let Err(e) = follow(&host, &tx) {
+            let _ = tx.send(Err(io::Error::other(format!("{}: {}", host.display(), e))));
+        }
+    });
+
+    rx.into_iter()
+        .take_while(|i: &io::Result<Input>| !matches!(i, Ok(Input::End)))
+}
+
+// Like `tail -n +1 -f`, starting over if the file gets truncated.
+fn follow(path: &Path, tx: &Sender<io::Result<Input>>) -> io::Result<()> {
+    let mut reader: BufReader<File> = BufReader::new(File::open(path)?);
+    let mut pos: u64 = 0;
+    let mut buf: Vec<u8> = Vec::new();
+
+    loop {
+        buf.clear();
+        let n: usize = reader.read_until(b'\n', &mut buf)?;
+        if n > 0 && buf.ends_with(b"\n") {
+            pos += n as u64;
+            for line in input::lines(buf.as_slice()) {
+                if tx.send(line.map(Input::Host)).is_err() {
+                    return Ok(());
+                }
+            }
+            continue;
+        }
+
+        // Partial line or EOF: rewind to the line start and wait for more
+        thread::sleep(POLL);
+        if std::fs::metadata(path)?.len() < pos {
+            pos = 0;
+        }
+        reader.seek(SeekFrom::Start(pos))?;
+    }
+}
+
+/// Turns a host log line into an entry, normalizing its timestamp to the
+/// logcat layout so it sorts against device entries. `device_ts` supplies
+/// the date when the host line only has a time of day.
+pub fn parse(line: &str, name: &str, device_ts: &str) -> Logcat {
+    let (timestamp, rest): (String, &str) = if let Some(c) = RE_DATETIME.captures(line) {
+        (
+            format!("{} {}.{}", &c[1], &c[2], &c[3]),
+            &line[c.get(0).map_or(0, |m: regex::Match<'_>| m.end())..],
+        )
+    } else if let Some(c) = RE_TIME_OF_DAY.captures(line)
+        && let Some((date, _)) = device_ts.split_once(' ')
+    {
+        (
+            format!("{} {}.{}", date, &c[1], &c[2]),
+            &line[c.get(0).map_or(0, |m: regex::Match<'_>| m.end())..],
+        )
+    } else {
+        (String::new(), line)
+    };
+
+    let level: &str = match RE_LEVEL.captures(rest).as_ref().map(|c| &c[1]) {
+        Some("FATAL") => "F",
+        Some("ERROR") => "E",
+        Some("WARN" | "WARNING") => "W",
+        Some("DEBUG") => "D",
+        Some("TRACE" | "VERBOSE") => "V",
+        _ => "I",
+    };
+
+    Logcat {
+        timestamp,
+        level: level.to_string(),
+        tag: name.to_string(),
+        message: rest.to_string(),
+        source: Source::Host,
+        ..Default::default()
+    }
+}
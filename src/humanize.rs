@@ -0,0 +1,204 @@
+use crate::{
+    config::{Config, Section},
+    filter::TagPattern,
+    timestamp,
+};
+use regex::Regex;
+use std::io;
+
+/// What a number in a message counts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Unit {
+    Bytes,
+    Nanos,
+    Micros,
+    Millis,
+    EpochMillis,
+    EpochSeconds,
+}
+
+// The `unit` values of [[humanize]] tables
+const UNITS: [(&str, Unit); 6] = [
+    ("bytes", Unit::Bytes),
+    ("ns", Unit::Nanos),
+    ("us", Unit::Micros),
+    ("ms", Unit::Millis),
+    ("epoch_ms", Unit::EpochMillis),
+    ("epoch_s", Unit::EpochSeconds),
+];
+
+// Numbers the platform and common libraries log raw; the first group is the
+// number. Epoch millis are taken in 2017-2033 only, so ids and counters of
+// 13 digits are mostly left alone
+const BUILT_IN: [(&str, Unit); 5] = [
+    (r"\b([0-9]{4,}) ?(?:bytes|B)\b", Unit::Bytes),
+    (
+        r"(?i)\b\w*(?:size|bytes|mem|memory|heap|rss|pss)\s*[=:]\s*([0-9]{4,})\b",
+        Unit::Bytes,
+    ),
+    (r"\b([0-9]{4,}) ?ns\b", Unit::Nanos),
+    (
+        r"(?i)\b\w*(?:nanos|_ns|timens)\s*[=:]\s*([0-9]{4,})\b",
+        Unit::Nanos,
+    ),
+    (r"\b(1[5-9][0-9]{11})\b", Unit::EpochMillis),
+];
+
+struct Rule {
+    tag: Option<TagPattern>,
+    regex: Regex,
+    unit: Unit,
+}
+
+/// Appends humanized forms to raw numbers in messages (`--humanize`): byte
+/// counts, durations and epoch times, as in "134217728 (128 MiB)". The
+/// built-in rules follow those of `[[humanize]]` tables, which take a regex
+/// whose first group (or whole match) is the number, a unit and optionally
+/// a tag:
+///
+/// ```toml
+/// [[humanize]]
+/// tag = "MyCache"
+/// regex = 'evicted ([0-9]+)'
+/// unit = "bytes"    # bytes, ns, us, ms, epoch_ms or epoch_s
+/// ```
+pub struct Humanizer {
+    rules: Vec<Rule>,
+}
+
+pub fn load(configs: &[Config], fold_case: bool) -> io::Result<Humanizer> {
+    let mut rules: Vec<Rule> = Vec::new();
+    for config in configs {
+        for section in config.tables("humanize") {
+            rules.push(load_rule(config, section, fold_case)?);
+        }
+    }
+    rules.extend(BUILT_IN.iter().map(|(pattern, unit): &(&str, Unit)| Rule {
+        tag: None,
+        regex: Regex::new(pattern).unwrap(),
+        unit: *unit,
+    }));
+    Ok(Humanizer { rules })
+}
+
+fn load_rule(config: &Config, section: &Section, fold_case: bool) -> io::Result<Rule> {
+    section.check_keys(config, &["tag", "regex", "unit"])?;
+    let at = |key: &str| -> usize { section.get(key).map_or(section.line, |e| e.line) };
+
+    let tag: Option<TagPattern> = section
+        .str(config, "tag")?
+        .map(|tag: &str| TagPattern::parse(tag, fold_case))
+        .transpose()
+        .map_err(|e: String| config.error(at("tag"), &e))?;
+    let regex: Regex = Regex::new(section.required_str(config, "regex")?)
+        .map_err(|e: regex::Error| config.error(at("regex"), &format!("invalid regex: {}", e)))?;
+    let name: &str = section.required_str(config, "unit")?;
+    let unit: Unit = UNITS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, unit)| *unit)
+        .ok_or_else(|| {
+            config.error(
+                at("unit"),
+                &format!(
+                    "unknown unit `{}` (expected one of {})",
+                    name,
+                    UNITS.map(|(n, _)| n).join(", ")
+                ),
+            )
+        })?;
+    Ok(Rule { tag, regex, unit })
+}
+
+impl Humanizer {
+    /// Where in `message` to add what: after each match of a rule, the
+    /// first rule matching a part deciding its unit. Numbers too small to gain
+    /// from it are left alone.
+    pub fn annotate(&self, tag: &str, message: &str) -> Vec<(usize, String)> {
+        let tag: &str = tag.trim();
+        let mut taken: Vec<(usize, usize, String)> = Vec::new();
+        for rule in &self.rules {
+            if rule
+                .tag
+                .as_ref()
+                .is_some_and(|t: &TagPattern| !t.matches(tag))
+            {
+                continue;
+            }
+            for c in rule.regex.captures_iter(message) {
+                // The annotation goes after the whole match, past a unit
+                let whole: regex::Match = c.get(0).unwrap();
+                let number: regex::Match = c.get(1).unwrap_or(whole);
+                if taken
+                    .iter()
+                    .any(|(start, end, _)| whole.start() < *end && *start < whole.end())
+                {
+                    continue;
+                }
+                let Some(text) = number
+                    .as_str()
+                    .parse()
+                    .ok()
+                    .and_then(|n: u64| human(rule.unit, n))
+                else {
+                    continue;
+                };
+                taken.push((whole.start(), whole.end(), text));
+            }
+        }
+        taken.sort_by_key(|(start, _, _)| *start);
+        taken
+            .into_iter()
+            .map(|(_, end, text)| (end, text))
+            .collect()
+    }
+}
+
+fn human(unit: Unit, n: u64) -> Option<String> {
+    match unit {
+        Unit::Bytes => size(n),
+        Unit::Nanos => duration(n as u128),
+        Unit::Micros => duration(n as u128 * 1_000),
+        Unit::Millis => duration(n as u128 * 1_000_000),
+        Unit::EpochMillis => Some(timestamp::epoch_date(n / 1000)),
+        Unit::EpochSeconds => Some(timestamp::epoch_date(n)),
+    }
+}
+
+// In binary units, from KiB up
+fn size(bytes: u64) -> Option<String> {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    let mut value: f64 = bytes as f64 / 1024.0;
+    if value < 1.0 {
+        return None;
+    }
+    let mut unit: usize = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    Some(format!("{} {}", decimal(value), UNITS[unit]))
+}
+
+// From microseconds up, in the largest unit that keeps it above 1
+fn duration(nanos: u128) -> Option<String> {
+    let secs: u128 = nanos / 1_000_000_000;
+    Some(match nanos {
+        0..1_000 => return None,
+        1_000..1_000_000 => format!("{} µs", decimal(nanos as f64 / 1e3)),
+        1_000_000..1_000_000_000 => format!("{} ms", decimal(nanos as f64 / 1e6)),
+        _ if secs < 60 => format!("{} s", decimal(nanos as f64 / 1e9)),
+        _ if secs < 3600 => format!("{} min {} s", secs / 60, secs % 60),
+        _ if secs < 86_400 => format!("{} h {} min", secs / 3600, secs / 60 % 60),
+        _ => format!("{} d {} h", secs / 86_400, secs / 3600 % 24),
+    })
+}
+
+// One decimal, dropped when it is zero
+fn decimal(value: f64) -> String {
+    let text: String = format!("{:.1}", value);
+    match text.strip_suffix(".0") {
+        Some(whole) => whole.to_string(),
+        None => text,
+    }
+}
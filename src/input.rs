@@ -0,0 +1,163 @@
+use regex::Regex;
+use std::{
+    collections::VecDeque,
+    io,
+    io::BufRead,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+// SGR sequences already in the input (`adb logcat -v color`, colorizing scripts)
+static RE_SGR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1b\[[0-9;:]*m").unwrap());
+
+/// Lines of `reader` with line endings normalized: `\n`, `\r\n` and
+/// `\r\r\n` all end a line, and a bare `\r` (Windows adb, PuTTY captures,
+/// progress output) splits one too, so no carriage return ever reaches the
+/// parser or the terminal. Color codes already in the input are stripped so
+/// the line can be parsed and colorized afresh, and bytes that aren't UTF-8
+/// (apps logging raw binary) become U+FFFD instead of ending the stream.
+pub fn lines<R: BufRead>(reader: R) -> Lines<R> {
+    Lines {
+        reader,
+        pending: VecDeque::new(),
+        read: 0,
+        offset: None,
+    }
+}
+
+pub struct Lines<R> {
+    reader: R,
+    pending: VecDeque<String>,
+    read: u64,
+    offset: Option<Arc<AtomicU64>>,
+}
+
+impl<R> Lines<R> {
+    /// Keeps `offset` at the end of the last line handed out, counting on
+    /// from its current value (where the reader starts).
+    pub fn tracking(mut self, offset: Arc<AtomicU64>) -> Self {
+        self.read = offset.load(Ordering::Relaxed);
+        self.offset = Some(offset);
+        self
+    }
+
+    // Once every piece of the line read last is out
+    fn settle(&self) {
+        if let Some(offset) = &self.offset
+            && self.pending.is_empty()
+        {
+            offset.store(self.read, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                self.settle();
+                return Some(Ok(line));
+            }
+
+            let mut bytes: Vec<u8> = Vec::new();
+            match self.reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => return None,
+                Ok(n) => self.read += n as u64,
+                Err(e) => return Some(Err(e)),
+            }
+            let buf: String = match String::from_utf8(bytes) {
+                Ok(buf) => buf,
+                Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            };
+            let line: &str = buf
+                .strip_suffix('\n')
+                .unwrap_or(&buf)
+                .trim_end_matches('\r');
+            if !line.contains('\r') {
+                self.settle();
+                return Some(Ok(strip_sgr(line)));
+            }
+            self.pending.extend(
+                line.split('\r')
+                    .filter(|s: &&str| !s.is_empty())
+                    .map(strip_sgr),
+            );
+        }
+    }
+}
+
+fn strip_sgr(line: &str) -> String {
+    if line.contains('\x1b') {
+        RE_SGR.replace_all(line, "").into_owned()
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &[u8]) -> Vec<String> {
+        lines(io::Cursor::new(input))
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn line_feed() {
+        assert_eq!(read(b"one\ntwo\n\nthree"), ["one", "two", "", "three"]);
+    }
+
+    #[test]
+    fn crlf() {
+        assert_eq!(
+            read(b"one\r\ntwo\r\n\r\nthree\r\n"),
+            ["one", "two", "", "three"]
+        );
+    }
+
+    #[test]
+    fn cr_cr_lf() {
+        assert_eq!(read(b"one\r\r\ntwo\r\r\n"), ["one", "two"]);
+    }
+
+    #[test]
+    fn bare_cr() {
+        assert_eq!(read(b"one\rtwo\rthree"), ["one", "two", "three"]);
+        assert_eq!(read(b"one\r\rtwo\r"), ["one", "two"]);
+    }
+
+    #[test]
+    fn mixed_endings() {
+        assert_eq!(
+            read(b"lf\ncrlf\r\ncrcrlf\r\r\nbare\rcr\r\nlast"),
+            ["lf", "crlf", "crcrlf", "bare", "cr", "last"]
+        );
+    }
+
+    #[test]
+    fn colors_and_invalid_utf8() {
+        assert_eq!(
+            read(b"\x1b[31mred\x1b[0m\r\nraw \xff\r"),
+            ["red", "raw \u{fffd}"]
+        );
+    }
+
+    #[test]
+    fn offset_settles_after_split_pieces() {
+        let offset: Arc<AtomicU64> = Arc::new(AtomicU64::new(10));
+        let mut it = lines(io::Cursor::new(&b"a\rb\r\nc\n"[..])).tracking(offset.clone());
+        assert_eq!(it.next().unwrap().unwrap(), "a");
+        assert_eq!(offset.load(Ordering::Relaxed), 10);
+        assert_eq!(it.next().unwrap().unwrap(), "b");
+        assert_eq!(offset.load(Ordering::Relaxed), 15);
+        assert_eq!(it.next().unwrap().unwrap(), "c");
+        assert_eq!(offset.load(Ordering::Relaxed), 17);
+        assert!(it.next().is_none());
+    }
+}
@@ -0,0 +1,23 @@
+/// Lets the first Ctrl-C end the stream instead of the process.
+///
+/// The producer in front of us (`adb logcat`, `tail -f`) shares the
+/// terminal's process group and dies from the same SIGINT, so input reaches
+/// EOF and end-of-stream work (restoring the terminal, summaries) still runs.
+/// A second Ctrl-C kills the process as usual.
+#[cfg(unix)]
+pub fn defer_sigint() {
+    extern "C" fn on_sigint(_: libc::c_int) {}
+    // SAFETY: the action is zeroed, then filled in before it is installed
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // The default disposition is back once the handler has run; reads
+        // resume rather than fail with EINTR
+        action.sa_flags = libc::SA_RESETHAND | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+    }
+}
+
+#[cfg(not(unix))]
+pub fn defer_sigint() {}
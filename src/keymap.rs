@@ -0,0 +1,278 @@
+use crate::config::{Config, Section};
+use std::io;
+
+/// A key as the terminal sends it in cbreak mode.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(u8),
+    Meta(u8), // ESC, then the key: Alt or Meta held
+}
+
+impl Key {
+    /// `up`, `down`, `left`, `right`, a single character, `C-x` (or
+    /// `ctrl-x`) and `M-x` (or `alt-x`).
+    fn parse(s: &str) -> Option<Self> {
+        let char = |s: &str| -> Option<u8> {
+            match s.as_bytes() {
+                [c] if c.is_ascii_graphic() => Some(*c),
+                _ => None,
+            }
+        };
+        let lower: String = s.to_ascii_lowercase();
+        Some(match lower.as_str() {
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "space" => Key::Char(b' '),
+            _ if lower.starts_with("c-") || lower.starts_with("ctrl-") => {
+                let c: u8 = char(&lower[lower.find('-')? + 1..])?;
+                Key::Char(c.to_ascii_uppercase() & 0x1f)
+            }
+            _ if lower.starts_with("m-") || lower.starts_with("alt-") => {
+                Key::Meta(char(&s[s.find('-')? + 1..])?)
+            }
+            _ => Key::Char(char(s)?),
+        })
+    }
+
+    fn name(&self) -> String {
+        match *self {
+            Key::Up => "↑".to_string(),
+            Key::Down => "↓".to_string(),
+            Key::Left => "←".to_string(),
+            Key::Right => "→".to_string(),
+            Key::Char(b' ') => "space".to_string(),
+            Key::Char(c) if c < 0x20 => format!("C-{}", (c | 0x60) as char),
+            Key::Char(c) => (c as char).to_string(),
+            Key::Meta(c) => format!("M-{}", c as char),
+        }
+    }
+}
+
+/// What a key does in `theme-edit`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    SlotUp,
+    SlotDown,
+    FgNext,
+    FgPrev,
+    BgNext,
+    BgPrev,
+    AttrNext,
+    AttrPrev,
+    Reset,
+    Save,
+    Quit,
+}
+
+// The actions by the names a [keys] table binds them under
+const ACTIONS: [(&str, Action); 11] = [
+    ("slot_up", Action::SlotUp),
+    ("slot_down", Action::SlotDown),
+    ("fg_next", Action::FgNext),
+    ("fg_prev", Action::FgPrev),
+    ("bg_next", Action::BgNext),
+    ("bg_prev", Action::BgPrev),
+    ("attr_next", Action::AttrNext),
+    ("attr_prev", Action::AttrPrev),
+    ("reset", Action::Reset),
+    ("save", Action::Save),
+    ("quit", Action::Quit),
+];
+
+pub const PRESETS: [&str; 3] = ["default", "vi", "emacs"];
+
+// Keys per action, in the order of ACTIONS; arrows work in every preset
+const DEFAULT: [&[&str]; 11] = [
+    &["up", "k"],
+    &["down", "j"],
+    &["right", "l"],
+    &["left", "h"],
+    &["b"],
+    &["B"],
+    &["a"],
+    &["A"],
+    &["r"],
+    &["s"],
+    &["q", "C-c"],
+];
+const VI: [&[&str]; 11] = [
+    &["k", "up"],
+    &["j", "down"],
+    &["l", "right"],
+    &["h", "left"],
+    &["w"],
+    &["b"],
+    &["}"],
+    &["{"],
+    &["u"],
+    &["s"],
+    &["q", "C-c"],
+];
+const EMACS: [&[&str]; 11] = [
+    &["C-p", "up"],
+    &["C-n", "down"],
+    &["C-f", "right"],
+    &["C-b", "left"],
+    &["M-f"],
+    &["M-b"],
+    &["M-n"],
+    &["M-p"],
+    &["M-r"],
+    &["C-s"],
+    &["C-g", "C-c"],
+];
+
+/// The keys of the interactive editor, from a preset and the `[keys]`
+/// table of the config:
+///
+/// ```toml
+/// [keys]
+/// preset = "emacs"        # or "default", "vi"
+/// save = ["C-s", "C-w"]   # C-x, M-x, up, down, left, right, space or a character
+/// quit = "C-g"
+/// ```
+///
+/// A filter profile's `keys = "NAME"` picks a `[keys.NAME]` table of the
+/// same form.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<Vec<Key>>, // in the order of ACTIONS
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from(DEFAULT)
+    }
+}
+
+impl Keymap {
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::from(DEFAULT)),
+            "vi" => Some(Self::from(VI)),
+            "emacs" => Some(Self::from(EMACS)),
+            _ => None,
+        }
+    }
+
+    fn from(keys: [&[&str]; 11]) -> Self {
+        Self {
+            bindings: keys
+                .iter()
+                .map(|keys: &&[&str]| keys.iter().filter_map(|k: &&str| Key::parse(k)).collect())
+                .collect(),
+        }
+    }
+
+    /// Applies the `[keys]` table of `config`, if it has one.
+    pub fn apply(&mut self, config: &Config) -> io::Result<()> {
+        match config.tables("keys").next() {
+            Some(section) => self.apply_table(config, section),
+            None => Ok(()),
+        }
+    }
+
+    /// Switches to the table's preset, if it names one, then rebinds the
+    /// actions it lists.
+    pub fn apply_table(&mut self, config: &Config, section: &Section) -> io::Result<()> {
+        let known: Vec<&str> = ["preset"]
+            .into_iter()
+            .chain(ACTIONS.iter().map(|(name, _)| *name))
+            .collect();
+        section.check_keys(config, &known)?;
+        let at = |key: &str| -> usize { section.get(key).map_or(section.line, |e| e.line) };
+
+        if let Some(name) = section.str(config, "preset")? {
+            *self = Self::preset(name).ok_or_else(|| {
+                config.error(
+                    at("preset"),
+                    &format!(
+                        "unknown preset `{}` (expected {})",
+                        name,
+                        PRESETS.join(", ")
+                    ),
+                )
+            })?;
+        }
+        for (i, (name, _)) in ACTIONS.iter().enumerate() {
+            let Some(keys) = section.strings(config, name)? else {
+                continue;
+            };
+            if keys.is_empty() {
+                return Err(config.error(at(name), &format!("`{}` needs a key", name)));
+            }
+            self.bindings[i] = keys
+                .iter()
+                .map(|k: &String| {
+                    Key::parse(k).ok_or_else(|| {
+                        config.error(
+                            at(name),
+                            &format!(
+                                "{}: unknown key `{}` (expected e.g. q, C-x, M-x or up)",
+                                name, k
+                            ),
+                        )
+                    })
+                })
+                .collect::<io::Result<_>>()?;
+        }
+
+        // A key can only do one thing
+        for (i, keys) in self.bindings.iter().enumerate() {
+            for key in keys {
+                if let Some(j) =
+                    (i + 1..ACTIONS.len()).find(|j: &usize| self.bindings[*j].contains(key))
+                {
+                    return Err(config.error(
+                        section.line,
+                        &format!(
+                            "`{}` is bound to both {} and {}",
+                            key.name(),
+                            ACTIONS[i].0,
+                            ACTIONS[j].0
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .position(|keys: &Vec<Key>| keys.contains(&key))
+            .map(|i: usize| ACTIONS[i].1)
+    }
+
+    /// The first key of `action`, for hints.
+    pub fn name(&self, action: Action) -> String {
+        ACTIONS
+            .iter()
+            .position(|(_, a)| *a == action)
+            .and_then(|i: usize| self.bindings[i].first())
+            .map_or_else(String::new, Key::name)
+    }
+
+    /// The help line of the editor.
+    pub fn help(&self) -> String {
+        let pair =
+            |a: Action, b: Action| -> String { format!("{}/{}", self.name(a), self.name(b)) };
+        format!(
+            "{} slot  {} foreground  {} background  {} attribute  {} default  {} save  {} quit",
+            pair(Action::SlotUp, Action::SlotDown),
+            pair(Action::FgPrev, Action::FgNext),
+            pair(Action::BgNext, Action::BgPrev),
+            pair(Action::AttrNext, Action::AttrPrev),
+            self.name(Action::Reset),
+            self.name(Action::Save),
+            self.name(Action::Quit)
+        )
+    }
+}
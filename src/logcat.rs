@@ -1,35 +1,128 @@
 use crate::{
-    ansi::{Seq, attr, color},
+    aftercommand::{self, Finished},
+    alert::{Dispatch, Route, Router},
+    ansi::Seq,
+    binary,
+    checkpoint::Checkpoint,
+    ci::{CiSystem, Sections},
+    classify::{self, EventKind},
+    context::Context,
+    control::{Command, Control},
+    crash::{Crashes, Group},
+    diagnose,
+    emoji::{self, EmojiMode},
+    expect::{Checker, Script},
+    filter::{self, Filters, Hidden, TagPattern},
+    findings::Findings,
+    history::{self, History, Spike, TagCounts},
+    hostlog::{self, Input},
+    humanize::Humanizer,
+    input,
+    merge::{self, Capture, Item},
+    multiline::Joiner,
+    parquet,
+    pids::{DeviceProcs, PackageEvent, PackagePids, PidTracker, Reuse},
+    poll::{self, Poll},
+    procgroup::{self, ProcGroups},
+    profile::Profile,
+    prologue,
+    render::{self, Decor, Line, Renderer},
+    reorder::{self, OrderCheck, Reorder},
+    rotate::Policy,
+    scan,
+    screenshot::{self, ImageProtocol, Screenshots},
+    spotlight::{self, Rule, Scoped, Spot, Spotlights},
+    statediff::{Change, StateDiff},
+    stats::{self, Stats},
+    tagcolor::TagColors,
+    tee::Tee,
     theme::Theme,
+    timestamp::{self, Dates},
+    tmuxstatus::TmuxStatus,
+    translate::Translations,
+    users::UserTracker,
+    verdict::{FailOn, Verdict},
+    width,
 };
 use regex::Regex;
 use std::{
-    io::{self, BufRead},
-    sync::LazyLock,
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, Read},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-// Regexes for formats
-pub static RE_TAG: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^([VDIWEF])/(.*?): (.*)$").unwrap());
-pub static RE_PROCESS: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^([VDIWEF])\(([ 0-9]{1,})\) (.*) \(((.*?)?)\)$").unwrap());
-pub static RE_BRIEF: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^([VDIWEF])/(.*?)\(([ 0-9]{1,})\): (.*)$").unwrap());
+// Regexes for formats; dates may carry a year (`-v year`) and ids a uid
+// (`-v uid`) before the pid. Tags end at the first `: ` (or a `:` ending the
+// line, when an empty message lost its trailing space), so tags with their
+// own colons, parentheses or padding come through whole. The tags of
+// `scan::QUALIFIED_TAGS` take along a `(qualifier)` followed by another, as
+// in `ReactNativeJS: (dev): msg`; in process format the tag is the last
+// parenthesized group, which may nest one level
+pub static RE_TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^([VDIWEFA])/(ReactNativeJS: \([^()]*\)|.*?):(?: (.*))?$").unwrap()
+});
+pub static RE_PROCESS: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^([VDIWEFA])\(([ 0-9]{1,})\) (.*?) \(([^()]*(?:\([^()]*\)[^()]*)*)\)$").unwrap()
+});
+pub static RE_BRIEF: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^([VDIWEFA])/(.*?)\((?:\s*([A-Za-z0-9_]+):)?([ 0-9]{1,})\):(?: (.*))?$").unwrap()
+});
 pub static RE_TIME: LazyLock<Regex> = LazyLock::new(|| -> Regex {
-    Regex::new(r"^([0-9]{2}-[0-9]{2} [0-9]{2}:[0-9]{2}:[0-9]{2}\.[0-9]{3}):? ([VDIWEF])/(.*?)\(([ 0-9]{1,})\)\s*: (.*)$").unwrap()
+    Regex::new(r"^((?:[0-9]{4}-)?[0-9]{2}-[0-9]{2} [0-9]{2}:[0-9]{2}:[0-9]{2}\.[0-9]{3}(?:[0-9]{3}){0,2}):? ([VDIWEFA])/(.*?)\((?:\s*([A-Za-z0-9_]+):)?([ 0-9]{1,})\)\s*:(?: (.*))?$").unwrap()
 });
 pub static RE_THREADTIME: LazyLock<Regex> = LazyLock::new(|| -> Regex {
-    Regex::new(r"^([0-9]{2}-[0-9]{2} [0-9]{2}:[0-9]{2}:[0-9]{2}\.[0-9]{3})\s*([0-9]{1,})\s*([0-9]{1,}) ([VDIWEF]) (.*?): (.*)$").unwrap()
+    Regex::new(r"^((?:[0-9]{4}-)?[0-9]{2}-[0-9]{2} [0-9]{2}:[0-9]{2}:[0-9]{2}\.[0-9]{3}(?:[0-9]{3}){0,2})\s+(?:([A-Za-z0-9_]+)\s+)?([0-9]{1,})\s+([0-9]{1,}) ([VDIWEFA]) (ReactNativeJS: \([^()]*\)|.*?):(?: (.*))?$").unwrap()
+});
+// `-v epoch` (threadtime layout): seconds since 1970 instead of the date
+pub static RE_EPOCH: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^\s*([0-9]{9,}\.[0-9]{3,})\s+(?:([A-Za-z0-9_]+)\s+)?([0-9]{1,})\s+([0-9]{1,}) ([VDIWEFA]) (ReactNativeJS: \([^()]*\)|.*?):(?: (.*))?$")
+        .unwrap()
+});
+// `-v monotonic` (threadtime layout): seconds since boot, right-aligned
+pub static RE_MONOTONIC: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^\s*([0-9]{1,8}\.[0-9]{3,})\s+(?:([A-Za-z0-9_]+)\s+)?([0-9]{1,})\s+([0-9]{1,}) ([VDIWEFA]) (ReactNativeJS: \([^()]*\)|.*?):(?: (.*))?$")
+        .unwrap()
+});
+// Kernel log (`-b kernel`, forwarded dmesg): "<6>[ 1234.567890] msg", the
+// printk level being optional
+pub static RE_KERNEL: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^(?:<([0-7])>)?\[\s*([0-9]+\.[0-9]+)\] (.*)$").unwrap()
 });
+// `-v long` header; the message follows on its own lines, up to a blank one
+pub static RE_LONG: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"^\[ ((?:[0-9]{4}-)?[0-9]{2}-[0-9]{2} [0-9]{2}:[0-9]{2}:[0-9]{2}\.[0-9]{3}(?:[0-9]{3}){0,2})\s+(?:([A-Za-z0-9_]+):\s*)?([0-9]{1,}):\s*(0x[0-9a-f]+|[0-9]{1,}) ([VDIWEFA])/(.*?)\s*\]$").unwrap()
+});
+// Separators logcat writes when a buffer starts or the stream switches buffers
+static RE_BUFFER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^-{9} (beginning of|switch to) (\S+)$").unwrap());
+
+// Held entries go out once a live stream has been quiet this long
+const IDLE: Duration = Duration::from_millis(500);
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Logcat {
-    timestamp: String,
-    level: String, // V D I W E F
-    tag: String,
-    process: String, // pid
-    message: String,
-    thread: String, // tid
+    pub timestamp: String,
+    pub level: String, // V D I W E F A
+    pub tag: String,
+    pub process: String, // pid
+    pub message: String,
+    pub thread: String, // tid
+    pub uid: String,    // user or package uid, with `-v uid`
+    pub source: Source,
+    pub origin: Option<usize>, // index of the --merge capture it came from
+    pub user: Option<u32>,     // Android user, when it can be told
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Source {
+    #[default]
+    Device,
+    Host, // a line from --host-log
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -39,238 +132,1711 @@ pub enum FormatKind {
     Brief,
     Process,
     Tag,
+    Long,
+    Epoch,
+    Monotonic,
+    Kernel,
 }
 
-fn parse_line(line: &str) -> Option<(FormatKind, Logcat)> {
-    if let Some(c) = RE_THREADTIME.captures(line) {
-        return Some((
-            FormatKind::ThreadTime,
-            Logcat {
-                timestamp: c[1].to_string(),
-                process: c[2].trim().to_string(),
-                thread: c[3].trim().to_string(),
-                level: c[4].to_string(),
-                tag: c[5].to_string(),
-                message: c[6].to_string(),
-            },
-        ));
+impl FormatKind {
+    // In the order `parse_line` tries them, most specific first
+    pub const ALL: [FormatKind; 9] = [
+        FormatKind::Long,
+        FormatKind::ThreadTime,
+        FormatKind::Epoch,
+        FormatKind::Monotonic,
+        FormatKind::Kernel,
+        FormatKind::Time,
+        FormatKind::Brief,
+        FormatKind::Process,
+        FormatKind::Tag,
+    ];
+
+    /// The `-v` name of the format.
+    pub fn name(self) -> &'static str {
+        match self {
+            FormatKind::ThreadTime => "threadtime",
+            FormatKind::Time => "time",
+            FormatKind::Brief => "brief",
+            FormatKind::Process => "process",
+            FormatKind::Tag => "tag",
+            FormatKind::Long => "long",
+            FormatKind::Epoch => "epoch",
+            FormatKind::Monotonic => "monotonic",
+            FormatKind::Kernel => "kernel",
+        }
     }
-    if let Some(c) = RE_TIME.captures(line) {
-        return Some((
-            FormatKind::Time,
-            Logcat {
-                timestamp: c[1].to_string(),
-                level: c[2].to_string(),
-                tag: c[3].to_string(),
-                process: c[4].trim().to_string(),
-                message: c[5].to_string(),
-                ..Default::default()
-            },
-        ));
+
+    pub fn regex(self) -> &'static Regex {
+        match self {
+            FormatKind::ThreadTime => &RE_THREADTIME,
+            FormatKind::Time => &RE_TIME,
+            FormatKind::Brief => &RE_BRIEF,
+            FormatKind::Process => &RE_PROCESS,
+            FormatKind::Tag => &RE_TAG,
+            FormatKind::Long => &RE_LONG,
+            FormatKind::Epoch => &RE_EPOCH,
+            FormatKind::Monotonic => &RE_MONOTONIC,
+            FormatKind::Kernel => &RE_KERNEL,
+        }
     }
-    if let Some(c) = RE_BRIEF.captures(line) {
-        return Some((
-            FormatKind::Brief,
-            Logcat {
-                level: c[1].to_string(),
-                tag: c[2].to_string(),
-                process: c[3].trim().to_string(),
-                message: c[4].to_string(),
-                ..Default::default()
-            },
-        ));
+}
+
+/// Parses `line` as one format. For `Long` the entry is the header,
+/// without its message yet.
+pub fn parse_as(kind: FormatKind, line: &str) -> Option<Logcat> {
+    match scan::KINDS.contains(&kind) {
+        true => scan::parse(kind, line),
+        false => parse_regex(kind, line),
     }
-    if let Some(c) = RE_PROCESS.captures(line) {
-        return Some((
-            FormatKind::Process,
-            Logcat {
-                level: c[1].to_string(),
-                process: c[2].trim().to_string(),
-                message: c[3].to_string(),
-                tag: c
-                    .get(4)
-                    .map(|m: regex::Match<'_>| m.as_str().to_string())
-                    .unwrap_or_default(),
-                ..Default::default()
-            },
-        ));
+}
+
+/// `parse_as` by the format's regex: the parser of the rarer formats, and
+/// the reference `--bench` checks the byte scanner of the common ones
+/// against.
+pub fn parse_regex(kind: FormatKind, line: &str) -> Option<Logcat> {
+    let c: regex::Captures<'_> = kind.regex().captures(line)?;
+    let text = |i: usize| -> String { c.get(i).map_or("", |m| m.as_str()).trim().to_string() };
+    // Messages may be missing altogether
+    let rest = |i: usize| -> String { c.get(i).map_or("", |m| m.as_str()).to_string() };
+    Some(match kind {
+        // The threadtime layout, whatever the clock
+        FormatKind::ThreadTime | FormatKind::Epoch | FormatKind::Monotonic => Logcat {
+            timestamp: c[1].to_string(),
+            uid: text(2),
+            process: text(3),
+            thread: text(4),
+            level: c[5].to_string(),
+            tag: c[6].to_string(),
+            message: rest(7),
+            ..Default::default()
+        },
+        FormatKind::Long => Logcat {
+            timestamp: c[1].to_string(),
+            uid: text(2),
+            process: text(3),
+            thread: text(4),
+            level: c[5].to_string(),
+            tag: c[6].to_string(),
+            ..Default::default()
+        },
+        FormatKind::Time => Logcat {
+            timestamp: c[1].to_string(),
+            level: c[2].to_string(),
+            tag: c[3].to_string(),
+            uid: text(4),
+            process: text(5),
+            message: rest(6),
+            ..Default::default()
+        },
+        FormatKind::Brief => Logcat {
+            level: c[1].to_string(),
+            tag: c[2].to_string(),
+            uid: text(3),
+            process: text(4),
+            message: rest(5),
+            ..Default::default()
+        },
+        FormatKind::Process => Logcat {
+            level: c[1].to_string(),
+            process: text(2),
+            message: c[3].to_string(),
+            tag: c[4].to_string(),
+            ..Default::default()
+        },
+        FormatKind::Kernel => Logcat {
+            timestamp: c[2].to_string(),
+            // printk: emerg, alert, crit, err, warning, notice, info, debug
+            level: match c.get(1).map(|m| m.as_str()) {
+                Some("0" | "1" | "2") => "F",
+                Some("3") => "E",
+                Some("4") => "W",
+                Some("7") => "D",
+                _ => "I",
+            }
+            .to_string(),
+            tag: "kernel".to_string(),
+            message: c[3].to_string(),
+            ..Default::default()
+        },
+        FormatKind::Tag => Logcat {
+            level: c[1].to_string(),
+            tag: c[2].to_string(),
+            message: rest(3),
+            ..Default::default()
+        },
+    })
+}
+
+pub fn parse_line(line: &str) -> Option<(FormatKind, Logcat)> {
+    FormatKind::ALL
+        .iter()
+        .find_map(|k: &FormatKind| parse_as(*k, line).map(|lc: Logcat| (*k, lc)))
+}
+
+// How often --show-dropped notes what was hidden, while lines are coming in
+const DROPPED_INTERVAL: Duration = Duration::from_secs(5);
+
+// Parsed lines that vote on the stream's format before it is settled
+const DETECT_SAMPLE: usize = 16;
+
+/// Sticky format detection. Until `DETECT_SAMPLE` lines have parsed, each
+/// line is parsed as the most specific format it matches and votes for it;
+/// the format with the most votes is then kept, so a stray first line (a
+/// Tag-format line ahead of Brief ones, which Tag would also take) doesn't
+/// decide for the whole stream. A kept format that stops matching is
+/// guessed again from the line at hand.
+#[derive(Default)]
+struct Detector {
+    votes: Vec<(FormatKind, usize)>,
+    kept: Option<FormatKind>,
+    // The vote as text, when it settled with other formats holding at least
+    // a quarter of the sample: the input likely mixes formats
+    split: Option<String>,
+}
+
+impl Detector {
+    fn parse(&mut self, line: &str) -> Option<(FormatKind, Logcat)> {
+        if let Some(kind) = self.kept {
+            if let Some(lc) = parse_as(kind, line) {
+                return Some((kind, lc));
+            }
+            let (kind, lc) = parse_line(line)?;
+            self.kept = Some(kind);
+            return Some((kind, lc));
+        }
+
+        let (kind, lc) = parse_line(line)?;
+        match self.votes.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, n)) => *n += 1,
+            None => self.votes.push((kind, 1)),
+        }
+        if self.votes.iter().map(|(_, n)| n).sum::<usize>() == DETECT_SAMPLE {
+            // Most votes, then the more specific format
+            self.votes.sort_by_key(|(k, n): &(FormatKind, usize)| {
+                (
+                    std::cmp::Reverse(*n),
+                    FormatKind::ALL.iter().position(|a: &FormatKind| a == k),
+                )
+            });
+            self.kept = Some(self.votes[0].0);
+            let others: usize = self.votes.iter().skip(1).map(|(_, n)| n).sum();
+            if others * 4 >= DETECT_SAMPLE {
+                let votes: Vec<String> = self
+                    .votes
+                    .iter()
+                    .map(|(k, n): &(FormatKind, usize)| format!("{} {}", k.name(), n))
+                    .collect();
+                self.split = Some(votes.join(", "));
+            }
+        }
+        Some((kind, lc))
     }
-    if let Some(c) = RE_TAG.captures(line) {
-        return Some((
-            FormatKind::Tag,
-            Logcat {
-                level: c[1].to_string(),
-                tag: c[2].to_string(),
-                message: c[3].to_string(),
-                ..Default::default()
+}
+
+#[derive(Default)]
+pub struct Options {
+    pub spotlight: Vec<Rule>,
+    pub spotlight_mode: spotlight::Mode, // for the control socket's patterns
+    pub spotlight_line: bool,
+    pub auto_highlight: bool,
+    pub join_multiline: bool,
+    pub context: (usize, usize), // entries before and after --grep matches
+    pub scoped_spotlights: Vec<Scoped>,
+    pub ignore: bool,
+    pub stats: bool,
+    pub expand: bool,
+    pub check_order: bool,
+    pub reorder_window: Option<u64>,
+    pub track_pids: bool,
+    pub vary_pid_color: bool,
+    pub color_tags: bool,
+    pub translations: Option<Translations>,
+    pub filters: Filters,
+    pub host_log: Option<PathBuf>,
+    pub tag_width: Option<usize>,
+    pub alert_level: Option<String>,
+    pub routes: Vec<Route>,
+    pub emoji: Option<EmojiMode>,
+    pub stats_db: Option<PathBuf>,
+    pub events_only: bool,
+    pub show_dropped: bool,
+    pub control: Option<PathBuf>,
+    pub screenshot_on: Option<Regex>,
+    pub screenshot_preview: Option<ImageProtocol>,
+    pub debug_parse: bool, // explain lines no format matched
+    pub fail_on: Option<FailOn>,
+    pub sparse_time: bool,
+    pub expect: Option<Script>,
+    pub captures: Vec<Capture>,
+    pub format_hint: Option<String>, // command to suggest for timestamp-less input
+    pub epoch_utc: bool,
+    pub profile: bool,
+    pub output: Option<CiSystem>,
+    pub pins: Vec<String>, // tags whose latest entry stays at the top
+    pub recap: Option<usize>,
+    pub no_parse: bool,
+    pub thread_indent: bool,
+    pub crash_webhook: Option<String>,
+    pub adb_poll_interval: Option<Duration>, // poll::INTERVAL when None
+    pub raw_tags: bool,
+    pub fold_tag_case: bool,
+    pub detect_per_line: bool, // no sticky format guess, for mixed streams
+    pub export_parquet: Option<PathBuf>,
+    pub tee: Option<PathBuf>, // the raw capture, rotated by `rotate`
+    pub rotate: Option<Policy>,
+    pub exec: bool, // reading from a device we started adb on
+    pub device_file: Option<String>,
+    pub findings: Option<PathBuf>,
+    pub tmux_status: Option<Option<PathBuf>>,
+    pub diff_tags: Vec<TagPattern>,
+    pub pid_groups: bool,
+    pub after_command: Option<String>, // run alongside, marking where it ended
+    pub clear_after_command: bool,
+    pub humanizer: Option<Humanizer>,
+}
+
+// Receives parsed entries and raw lines, in stream order.
+struct Emitter<'a> {
+    out: &'a mut Renderer,
+    theme: &'a Theme,
+    opts: &'a Options,
+    stats: Stats,
+    dropped_noted: ([usize; Hidden::ALL.len()], Instant), // as of the last --show-dropped note
+    prologue: Vec<Logcat>,
+    reorder: Option<Reorder>,
+    order: OrderCheck,
+    dates: Dates, // for --reorder-window and --check-order
+    pids: PidTracker,
+    device_procs: Option<DeviceProcs>, // names for reused pids, in exec mode
+    users: UserTracker,
+    last_device_ts: String,
+    last_printed_ts: String,
+    router: Option<Router>,
+    joiner: Option<Joiner>, // --join-multiline
+    context: Option<Context>,
+    screenshots: Option<Screenshots>,
+    crashes: Option<Crashes>,
+    parquet: Option<parquet::Writer>,
+    checker: Option<Checker<'a>>,
+    profile: Option<Profile>,
+    ci: Option<Sections>,
+    pane: bool, // rows reserved for --pin
+    recap: VecDeque<Logcat>,
+    session: Option<HashMap<String, TagCounts>>,
+    package_poll: Poll, // --package's pidof, run once before the stream
+    checkpoint: Option<Checkpoint>,
+    // Adjusted at runtime through the control socket
+    control: Option<Control>,
+    filters: Filters,
+    spot_rules: Vec<Rule>,
+    spotlight: Option<Spot>,
+    spotlights: Spotlights, // the spotlight joined with [[spotlight]] tables, per tag
+    tag_colors: Option<TagColors>,
+    findings: Option<Findings>,
+    tmux: Option<TmuxStatus>,
+    last_shown: Option<Logcat>, // what a `mark` command marks
+    diffs: Option<StateDiff>,
+    groups: Option<ProcGroups>,
+}
+
+impl<'a> Emitter<'a> {
+    fn new(
+        out: &'a mut Renderer,
+        theme: &'a Theme,
+        opts: &'a Options,
+        control: Option<Control>,
+    ) -> io::Result<Self> {
+        let poll_interval: Duration = opts.adb_poll_interval.unwrap_or(poll::INTERVAL);
+        Ok(Self {
+            out,
+            theme,
+            opts,
+            stats: Stats::default(),
+            dropped_noted: ([0; Hidden::ALL.len()], Instant::now()),
+            prologue: Vec::new(),
+            reorder: opts.reorder_window.map(Reorder::new),
+            order: OrderCheck::default(),
+            dates: Dates::default(),
+            pids: PidTracker::default(),
+            device_procs: opts.exec.then(|| DeviceProcs::new(poll_interval)),
+            users: UserTracker::default(),
+            last_device_ts: String::new(),
+            last_printed_ts: String::new(),
+            joiner: opts.join_multiline.then(Joiner::default),
+            context: (opts.context != (0, 0)).then(|| Context::new(opts.context.0, opts.context.1)),
+            router: Router::new(opts.routes.clone(), opts.alert_level.as_deref()),
+            screenshots: opts.screenshot_on.clone().map(Screenshots::new),
+            crashes: opts
+                .crash_webhook
+                .clone()
+                .map(|url: String| Crashes::new(url, opts.exec)),
+            checker: opts.expect.as_ref().map(Checker::new),
+            profile: opts.profile.then(Profile::default),
+            ci: opts.output.map(Sections::new),
+            pane: false,
+            recap: VecDeque::new(),
+            session: opts.stats_db.as_ref().map(|_| HashMap::new()),
+            package_poll: Poll::polled(poll_interval),
+            checkpoint: None,
+            control,
+            filters: opts.filters.clone(),
+            spot_rules: opts.spotlight.clone(),
+            spotlight: Spot::new(opts.spotlight.clone()),
+            spotlights: Spotlights::new(opts.scoped_spotlights.clone()),
+            tag_colors: opts.color_tags.then(|| TagColors::new(theme)),
+            findings: opts.findings.as_deref().map(Findings::open).transpose()?,
+            tmux: opts
+                .tmux_status
+                .as_ref()
+                .map(|file: &Option<PathBuf>| TmuxStatus::start(file.as_deref()))
+                .transpose()?,
+            last_shown: None,
+            groups: opts
+                .pid_groups
+                .then(|| ProcGroups::new(opts.exec, poll_interval)),
+            diffs: (!opts.diff_tags.is_empty()).then(|| StateDiff::new(opts.diff_tags.clone())),
+            parquet: opts
+                .export_parquet
+                .as_deref()
+                .map(parquet::Writer::create)
+                .transpose()?,
+        })
+    }
+
+    // Applies commands received since the previous line
+    fn poll_control(&mut self) -> io::Result<()> {
+        let Some(control) = &self.control else {
+            return Ok(());
+        };
+        let cmds: Vec<Command> = control.rx.try_iter().collect();
+        for cmd in cmds {
+            match cmd {
+                Command::SpotlightAdd(rule) => self.spot_rules.push(rule),
+                Command::SpotlightClear => self.spot_rules.clear(),
+                Command::Level(level) => self.filters.min_level = level,
+                Command::Mute(tag) => self.filters.muted.push(tag),
+                Command::Unmute(tag) => self.filters.muted.retain(|t: &String| *t != tag),
+                Command::Filter(expr) => self.filters.expr = expr,
+                Command::Mark(note) => {
+                    self.mark(note)?;
+                    continue;
+                }
+            }
+            self.spotlight = Spot::new(self.spot_rules.clone());
+            self.spotlights.clear();
+        }
+        Ok(())
+    }
+
+    // An entry shown around a match, after what came before it
+    fn context_line(&mut self, lc: &Logcat) -> io::Result<()> {
+        self.drain_reorder()?;
+        self.flush_prologue()?;
+        let decor: Decor = Decor {
+            context: true,
+            ..Default::default()
+        };
+        self.out
+            .line(&render::entry(lc, self.theme, self.opts, &decor))
+    }
+
+    // Keeps the entry last shown in the --findings file
+    fn mark(&mut self, note: Option<String>) -> io::Result<()> {
+        let text: String = match (&mut self.findings, &self.last_shown) {
+            (None, _) => "--- mark: no --findings file to keep it in".to_string(),
+            (Some(_), None) => "--- mark: no entry shown yet".to_string(),
+            (Some(findings), Some(lc)) => {
+                findings.mark(lc, note)?;
+                format!("--- marked as finding #{}", findings.marked.len())
+            }
+        };
+        self.out
+            .status_line(&render::note(&self.theme.finding, &text))
+    }
+
+    // Picks the counts up from an interrupted run
+    fn resume(&mut self, checkpoint: Checkpoint) {
+        self.stats = checkpoint.stats.clone();
+        self.dropped_noted.0 = self.stats.dropped;
+        self.checkpoint = Some(checkpoint);
+    }
+
+    // Saves progress up to `offset` when it's time to, unless entries before
+    // it are still held back (a `-v long` header, a prologue)
+    fn save_checkpoint(&mut self, offset: u64, held: bool) -> io::Result<()> {
+        let Some(checkpoint) = &mut self.checkpoint else {
+            return Ok(());
+        };
+        if held || !self.prologue.is_empty() || !checkpoint.due() {
+            return Ok(());
+        }
+        // What the checkpoint covers must have been written
+        self.out.flush()?;
+        checkpoint.save(offset, &self.stats)
+    }
+
+    fn entry(&mut self, lc: Logcat) -> io::Result<()> {
+        let ready: Vec<Logcat> = match &mut self.joiner {
+            None => vec![lc],
+            Some(joiner) => joiner.push(lc),
+        };
+        for lc in ready {
+            self.accept(lc)?;
+        }
+        Ok(())
+    }
+
+    fn accept(&mut self, mut lc: Logcat) -> io::Result<()> {
+        self.poll_control()?;
+        if !self.opts.raw_tags
+            && lc.source == Source::Device
+            && let Some(tag) = filter::normalize_tag(&lc.tag, self.opts.fold_tag_case)
+        {
+            lc.tag = tag;
+        }
+        if self.opts.epoch_utc
+            && let Some(ts) = timestamp::epoch_to_wallclock(&lc.timestamp)
+        {
+            lc.timestamp = ts;
+        }
+        if let Some(message) = self
+            .opts
+            .translations
+            .as_ref()
+            .and_then(|t: &Translations| t.apply(&lc.message))
+        {
+            lc.message = message;
+        }
+        if lc.source == Source::Device {
+            lc.user = self.users.observe(&lc);
+        }
+        if let Some(mode) = self.opts.emoji {
+            if let Some(message) = emoji::replace(&lc.message, mode) {
+                lc.message = message;
+            }
+            if let Some(tag) = emoji::replace(&lc.tag, mode) {
+                lc.tag = tag;
+            }
+        }
+        // Assertions see the whole stream, not just what the filters let through
+        if let Some(checker) = &mut self.checker {
+            for failure in checker.check(&lc) {
+                self.print_assertion(&failure)?;
+            }
+        }
+        if let Some(group) = self
+            .crashes
+            .as_mut()
+            .and_then(|c: &mut Crashes| c.observe(&lc))
+        {
+            self.post_crash(&group)?;
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.record(&lc);
+        }
+        if let Some(packages) = &mut self.filters.packages
+            && self.package_poll.due()
+        {
+            // Without the device's answer, the log still tells starts and deaths
+            let events: Vec<PackageEvent> = packages.refresh().unwrap_or_default();
+            if !self.opts.events_only {
+                for event in &events {
+                    self.print_package_event(event)?;
+                }
+            }
+        }
+        if let Some(event) = self
+            .filters
+            .packages
+            .as_mut()
+            .and_then(|p: &mut PackagePids| p.observe(&lc))
+            && !self.opts.events_only
+        {
+            self.print_package_event(&event)?;
+        }
+        if let Some(why) = self.filters.rejects(&lc) {
+            if why == Hidden::Message
+                && let Some(context) = &mut self.context
+                && !self.filters.rejects_besides_message(&lc)
+                && let Some(lc) = context.hidden(lc)
+            {
+                self.context_line(&lc)?;
+            }
+            return self.hide(why);
+        }
+        if let Some(context) = &mut self.context {
+            let (separate, before) = context.matched();
+            if separate {
+                self.drain_reorder()?;
+                self.flush_prologue()?;
+                self.out.line(&render::note(&self.theme.context, "--"))?;
+            }
+            for lc in &before {
+                self.context_line(lc)?;
+            }
+        }
+        if lc.source == Source::Device && !lc.timestamp.is_empty() {
+            self.last_device_ts.clone_from(&lc.timestamp);
+        }
+
+        let ready: Vec<Logcat> = match &mut self.reorder {
+            None => vec![lc],
+            Some(reorder) => match self.dates.millis(&lc.timestamp) {
+                Some(ts) => reorder.push(ts, lc),
+                None => {
+                    let mut ready: Vec<Logcat> = reorder.drain();
+                    ready.push(lc);
+                    ready
+                }
             },
-        ));
+        };
+        for lc in ready {
+            self.ordered(lc)?;
+        }
+        Ok(())
     }
-    None
-}
 
-fn spot_if_needed(s: &str, spot: &Option<Regex>, spot_paint: &str, resume_seq: &str) -> String {
-    if let Some(re) = spot {
-        // Insert colored $1 then resume sequence
-        let replacement: String = format!("{}$1{}", spot_paint, resume_seq);
-        re.replace_all(s, replacement.as_str()).into_owned()
-    } else {
-        s.to_string()
+    // Nothing came for a while: what's held for a later entry won't be
+    // released by one soon, so it goes out now
+    fn idle(&mut self) -> io::Result<()> {
+        self.poll_control()?;
+        self.drain_reorder()?;
+        self.out.flush()
     }
-}
 
-fn print_log(l: &Logcat, theme: &Theme, spot: &Option<Regex>) {
-    // Spotlight color: bold, red background, white fg
-    let spot_seq: String = Seq::new(attr::RESET, color::B_RED, color::F_WHITE)
-        .as_str()
-        .to_string();
-
-    // Level colors
-    let (id_seq, msg_seq) = match l.level.as_str() {
-        "V" => (&theme.id_verbose, &theme.msg_verbose),
-        "D" => (&theme.id_debug, &theme.msg_debug),
-        "I" => (&theme.id_info, &theme.msg_info),
-        "W" => (&theme.id_warning, &theme.msg_warning),
-        "E" => (&theme.id_error, &theme.msg_error),
-        "F" => (&theme.id_fatal, &theme.msg_fatal),
-        _ => (&theme.reset, &theme.reset),
-    };
+    fn drain_reorder(&mut self) -> io::Result<()> {
+        // A burst being joined is older still
+        if let Some(joiner) = &mut self.joiner {
+            for lc in joiner.drain() {
+                self.accept(lc)?;
+            }
+        }
+        if let Some(reorder) = &mut self.reorder {
+            for lc in reorder.drain() {
+                self.ordered(lc)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ordered(&mut self, lc: Logcat) -> io::Result<()> {
+        if !self.opts.expand {
+            if !self.prologue.is_empty() {
+                if prologue::continues(&self.prologue, &lc) {
+                    let closing: bool = prologue::is_banner(&lc.message);
+                    self.prologue.push(lc);
+                    if closing {
+                        self.flush_prologue()?;
+                    }
+                    return Ok(());
+                }
+                self.flush_prologue()?;
+            }
+            if prologue::is_banner(&lc.message) {
+                self.prologue.push(lc);
+                return Ok(());
+            }
+        }
+        self.print(&lc)
+    }
 
-    // Timestamp
-    if !l.timestamp.is_empty() {
-        let seg: String = spot_if_needed(&l.timestamp, spot, &spot_seq, theme.timestamp.as_str());
-        print!(
-            "{}{}{} ",
-            theme.timestamp.as_str(),
-            seg,
-            theme.reset.as_str()
+    fn host(&mut self, line: &str, path: &Path) -> io::Result<()> {
+        let name: String = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
         );
+        let lc: Logcat = hostlog::parse(line, &name, &self.last_device_ts);
+        self.entry(lc)
+    }
+
+    fn raw(&mut self, line: &str) -> io::Result<()> {
+        self.poll_control()?;
+        self.drain_reorder()?;
+        self.flush_prologue()?;
+        self.stats.record_unparsed();
+        if let Some(tmux) = &self.tmux {
+            tmux.update(&self.stats);
+        }
+        if self.opts.ignore {
+            return self.hide(Hidden::Unparsed);
+        }
+        if self.opts.events_only {
+            return self.hide(Hidden::NotEvent);
+        }
+        self.out.text(line)?;
+        if self.opts.debug_parse && !line.is_empty() {
+            self.explain(line)?;
+        }
+        Ok(())
     }
 
-    // Level
-    if !l.level.is_empty() {
-        print!("{} {} {} ", id_seq.as_str(), l.level, theme.reset.as_str());
+    fn hide(&mut self, why: Hidden) -> io::Result<()> {
+        self.stats.record_dropped(why);
+        self.note_dropped(false)
     }
 
-    // [pid/tid]
-    if !l.process.is_empty() {
-        let bracket: String = if l.thread.is_empty() {
-            format!("[{}]", l.process)
-        } else {
-            format!("[{}/{}]", l.process, l.thread)
+    // --show-dropped: every few seconds, what was hidden since the last note,
+    // so a quiet stream can be told from a filtered one
+    fn note_dropped(&mut self, force: bool) -> io::Result<()> {
+        let (noted, at) = self.dropped_noted;
+        if !self.opts.show_dropped || (!force && at.elapsed() < DROPPED_INTERVAL) {
+            return Ok(());
+        }
+        let since: [usize; Hidden::ALL.len()] =
+            std::array::from_fn(|i: usize| self.stats.dropped[i] - noted[i]);
+        self.dropped_noted = (self.stats.dropped, Instant::now());
+        let Some(text) = stats::describe_dropped(&since) else {
+            return Ok(());
         };
-        let seg: String = spot_if_needed(&bracket, spot, &spot_seq, theme.tid_pid.as_str());
-        print!("{}{}{} ", theme.tid_pid.as_str(), seg, theme.reset.as_str());
+        let text: String = format!(
+            "--- hidden in the last {}s: {}",
+            at.elapsed().as_secs().max(1),
+            text
+        );
+        self.out
+            .status_line(&render::note(&self.theme.dropped, &text))
     }
 
-    // Tag
-    if !l.tag.is_empty() {
-        let seg: String = spot_if_needed(&l.tag, spot, &spot_seq, theme.tag.as_str());
-        print!("{}{}{} ", theme.tag.as_str(), seg, theme.reset.as_str());
+    // Under the raw line, a caret where the closest format stopped matching
+    fn explain(&mut self, line: &str) -> io::Result<()> {
+        let Some(d) = diagnose::closest(line) else {
+            return Ok(());
+        };
+        if d.offset == 0 {
+            return self.out.status_line(&render::note(
+                &self.theme.diagnostic,
+                "^ no format matches from the first character on",
+            ));
+        }
+        let column: usize = width::str_width(&line[..d.offset]);
+        let at: String = match line[d.offset..].chars().next() {
+            Some(c) => format!("column {} ({:?})", column + 1, c),
+            None => "the end of the line".to_string(),
+        };
+        let text: String = format!(
+            "{}^ closest format: {}, which stops matching at {}",
+            " ".repeat(column),
+            d.kind.name(),
+            at
+        );
+        self.out
+            .status_line(&render::note(&self.theme.diagnostic, &text))
     }
 
-    // Message
-    if !l.message.is_empty() {
-        let seg: String = spot_if_needed(&l.message, spot, &spot_seq, msg_seq.as_str());
-        print!("{}{}{} ", msg_seq.as_str(), seg, theme.reset.as_str());
+    // A buffer separator, drawn as a divider instead of a raw line
+    fn divider(&mut self, action: &str, buffer: &str) -> io::Result<()> {
+        self.poll_control()?;
+        self.drain_reorder()?;
+        self.flush_prologue()?;
+        if self.opts.ignore || self.opts.events_only {
+            return Ok(());
+        }
+        let cols: usize = width::terminal_columns();
+        self.out
+            .line(&render::divider(self.theme, action, buffer, cols))
     }
 
-    println!();
-}
+    fn command_started(&mut self, command: &str) -> io::Result<()> {
+        let text: String = format!("--- running `{}`", command);
+        self.out
+            .status_line(&render::note(&self.theme.banner, &text))
+    }
 
-pub fn format_with(theme: &Theme, spotlight_re: Option<Regex>, ignore: bool) -> io::Result<()> {
-    let stdin: io::Stdin = io::stdin();
-    let mut guessed_kind: Option<FormatKind> = None;
+    // The --after-command marker, with the tail of a failed command's output
+    fn command_finished(&mut self, finished: &Finished) -> io::Result<()> {
+        self.poll_control()?;
+        self.drain_reorder()?;
+        self.flush_prologue()?;
+        let cols: usize = width::terminal_columns();
+        self.out
+            .status_line(&render::command_marker(self.theme, finished, cols))?;
+        for line in &finished.output {
+            self.out
+                .status_line(&render::note(&self.theme.diagnostic, line))?;
+        }
+        if let Some(Err(e)) = &finished.cleared {
+            let text: String = format!("--- could not clear the log buffers: {}", e);
+            self.out
+                .status_line(&render::note(&self.theme.hint, &text))?;
+        }
+        Ok(())
+    }
 
-    for line in stdin.lock().lines() {
-        let line: String = line?;
-        if guessed_kind.is_none() {
-            if let Some((kind, lc)) = parse_line(&line) {
-                guessed_kind = Some(kind);
-                print_log(&lc, theme, &spotlight_re);
-                continue;
-            } else if !ignore {
-                println!("{}", line);
+    // A line under --no-parse: the whole line is the message
+    fn passthrough(&mut self, line: &str) -> io::Result<()> {
+        self.poll_control()?;
+        let lc: Logcat = Logcat {
+            message: line.to_string(),
+            ..Default::default()
+        };
+        if let Some(why) = self.filters.rejects(&lc) {
+            return self.hide(why);
+        }
+        self.out.line(&render::passthrough(
+            line,
+            self.theme,
+            self.spotlight.as_ref(),
+            self.opts.spotlight_line,
+        ))
+    }
+
+    fn finish(&mut self) -> io::Result<Verdict> {
+        self.drain_reorder()?;
+        self.flush_prologue()?;
+        self.note_dropped(true)?;
+        if let Some(marker) = self.ci.as_mut().and_then(Sections::close) {
+            self.marker(&marker)?;
+        }
+        self.out.release_rows()?;
+        if let Some(group) = self.crashes.as_mut().and_then(Crashes::close) {
+            self.post_crash(&group)?;
+        }
+        if let Some(crashes) = &mut self.crashes {
+            crashes.wait();
+        }
+        if let Some(router) = &mut self.router {
+            router.wait();
+        }
+        self.print_recap()?;
+        self.print_findings()?;
+        if self.opts.stats {
+            self.stats.print(self.out, self.theme)?;
+        }
+        if let Some(profile) = &self.profile {
+            profile.print(self.out, self.theme)?;
+        }
+        if let (Some(path), Some(session)) = (&self.opts.stats_db, self.session.take()) {
+            let mut history: History = History::load(path)?;
+            self.print_spikes(&history.spikes(&session))?;
+            history.merge(&session);
+            history.save()?;
+        }
+        let mut verdict: Verdict = Verdict::Pass;
+        if let Some(mut checker) = self.checker.take() {
+            let (failures, passed) = checker.finish();
+            for failure in &failures {
+                self.print_assertion(failure)?;
             }
-            continue;
+            if checker.any_failed() {
+                verdict = Verdict::Fail;
+            }
+            let text: String = format!(
+                "--- assertions: {} passed, {} failed",
+                passed,
+                self.opts.expect.as_ref().map_or(0, Script::len) - passed
+            );
+            self.out
+                .status_line(&render::note(&self.theme.banner, &text))?;
+        }
+        if let Some(fail_on) = &self.opts.fail_on
+            && fail_on.verdict(&self.stats) == Verdict::Fail
+        {
+            verdict = Verdict::Fail;
+        }
+        if let Some(fail_on) = &self.opts.fail_on {
+            self.out
+                .status_text(&fail_on.summary(verdict, &self.stats))?;
+        }
+        if let Some(parquet) = self.parquet.take() {
+            parquet.finish()?;
+        }
+        if let Some(checkpoint) = self.checkpoint.take() {
+            checkpoint.remove()?;
+        }
+        if let Some(tmux) = &self.tmux {
+            tmux.close()?;
+        }
+        self.out.flush()?;
+        Ok(verdict)
+    }
+
+    fn count(&mut self, lc: &Logcat, event: Option<EventKind>) -> io::Result<()> {
+        self.stats.record(&lc.level, &lc.tag, event);
+        if let Some(tmux) = &self.tmux {
+            tmux.update(&self.stats);
+        }
+        if let Some(parquet) = &mut self.parquet {
+            parquet.push(lc)?;
+        }
+        if let Some(session) = &mut self.session {
+            let c: &mut TagCounts = session.entry(history::tag_key(&lc.tag)).or_default();
+            c.entries += 1;
+            if matches!(lc.level.as_str(), "E" | "F" | "A") {
+                c.errors += 1;
+            }
+        }
+        if let Some(n) = self.opts.recap
+            && matches!(lc.level.as_str(), "E" | "F" | "A")
+        {
+            if self.recap.len() == n {
+                self.recap.pop_front();
+            }
+            self.recap.push_back(lc.clone());
+        }
+        Ok(())
+    }
+
+    // The last --recap errors again, so they needn't be scrolled back to
+    fn print_recap(&mut self) -> io::Result<()> {
+        if self.recap.is_empty() {
+            return Ok(());
+        }
+        let total: usize = self.stats.levels[4] + self.stats.levels[5];
+        let text: String = match total - self.recap.len() {
+            0 => format!("--- recap: {} errors", total),
+            _ => format!("--- recap: last {} of {} errors", self.recap.len(), total),
+        };
+        self.out.status_blank()?;
+        self.out
+            .status_line(&render::note(&self.theme.banner, &text))?;
+        for lc in std::mem::take(&mut self.recap) {
+            let decor: Decor = Decor {
+                event: classify::classify(&lc.message),
+                tag_seq: self
+                    .tag_colors
+                    .as_mut()
+                    .and_then(|t: &mut TagColors| t.get(&lc.tag)),
+                ..Default::default()
+            };
+            self.out
+                .status_line(&render::entry(&lc, self.theme, self.opts, &decor))?;
         }
+        Ok(())
+    }
 
-        // Fast path using the already-guessed kind, with fallback once.
-        let mut parsed: Option<Logcat> = None;
-        match guessed_kind.unwrap() {
-            FormatKind::ThreadTime => {
-                if let Some(c) = RE_THREADTIME.captures(&line) {
-                    parsed = Some(Logcat {
-                        timestamp: c[1].to_string(),
-                        process: c[2].trim().to_string(),
-                        thread: c[3].trim().to_string(),
-                        level: c[4].to_string(),
-                        tag: c[5].to_string(),
-                        message: c[6].to_string(),
-                    });
+    // The entries marked this session, under their notes
+    fn print_findings(&mut self) -> io::Result<()> {
+        let Some(findings) = self.findings.take() else {
+            return Ok(());
+        };
+        if findings.marked.is_empty() {
+            return Ok(());
+        }
+        let text: String = format!(
+            "--- findings: {} marked, appended to {}",
+            findings.marked.len(),
+            findings.path().display()
+        );
+        self.out.status_blank()?;
+        self.out
+            .status_line(&render::note(&self.theme.banner, &text))?;
+        for (lc, note) in &findings.marked {
+            if let Some(note) = note {
+                self.out
+                    .status_line(&render::note(&self.theme.finding, &format!("# {}", note)))?;
+            }
+            let decor: Decor = Decor {
+                event: classify::classify(&lc.message),
+                tag_seq: self
+                    .tag_colors
+                    .as_mut()
+                    .and_then(|t: &mut TagColors| t.get(&lc.tag)),
+                ..Default::default()
+            };
+            self.out
+                .status_line(&render::entry(lc, self.theme, self.opts, &decor))?;
+        }
+        Ok(())
+    }
+
+    fn print_spikes(&mut self, spikes: &[Spike]) -> io::Result<()> {
+        let theme: &Theme = self.theme;
+        self.out.status_blank()?;
+        if spikes.is_empty() {
+            return self
+                .out
+                .status_text("No tag error spikes against previous sessions");
+        }
+        self.out
+            .status_text("Tag error spikes against previous sessions:")?;
+        for spike in spikes {
+            let baseline: String = match spike.baseline {
+                Some(rate) => format!("{:.1}% before", rate * 100.0),
+                None => "new".to_string(),
+            };
+            let mut line: Line = Line::new();
+            line.badge(&theme.id_error, "E")
+                .styled(&theme.tag, spike.tag.as_str())
+                .plain(&format!(
+                    "  {} errors, {:.1}% of its entries ({})",
+                    spike.errors,
+                    spike.rate * 100.0,
+                    baseline
+                ));
+            self.out.status_line(&line)?;
+        }
+        Ok(())
+    }
+
+    fn print(&mut self, lc: &Logcat) -> io::Result<()> {
+        let event: Option<EventKind> = classify::classify(&lc.message);
+        let out_of_order: bool = self.opts.check_order
+            && self
+                .dates
+                .millis(&lc.timestamp)
+                .is_some_and(|ts: u64| self.order.is_out_of_order(ts, lc));
+        self.count(lc, event)?;
+        if out_of_order {
+            self.stats.out_of_order += 1;
+        }
+
+        let mut pid_seq: Option<&Seq> = None;
+        if self.opts.track_pids || self.opts.vary_pid_color {
+            if let Some(mut reuse) = self.pids.observe(lc) {
+                // The log didn't say what runs under the pid now; the device may
+                if reuse.new.is_none()
+                    && let Some(procs) = &mut self.device_procs
+                {
+                    reuse.new = procs.name(&reuse.pid);
+                    if let Some(name) = &reuse.new {
+                        self.pids.name(&reuse.pid, name.clone());
+                    }
                 }
+                if !self.opts.events_only {
+                    self.print_reuse(&reuse)?;
+                }
+            }
+            let alt: &[Seq] = &self.theme.tid_pid_alt;
+            let generation: usize = self.pids.generation(&lc.process);
+            if self.opts.vary_pid_color && generation > 0 && !alt.is_empty() {
+                pid_seq = Some(&alt[(generation - 1) % alt.len()]);
             }
-            FormatKind::Time => {
-                if let Some(c) = RE_TIME.captures(&line) {
-                    parsed = Some(Logcat {
-                        timestamp: c[1].to_string(),
-                        level: c[2].to_string(),
-                        tag: c[3].to_string(),
-                        process: c[4].trim().to_string(),
-                        message: c[5].to_string(),
-                        ..Default::default()
-                    });
+        }
+        // A recolored reuse says more than the pid's group
+        if pid_seq.is_none()
+            && let Some(group) = self
+                .groups
+                .as_mut()
+                .and_then(|g: &mut ProcGroups| g.observe(lc))
+        {
+            pid_seq = Some(match group {
+                procgroup::Group::App => &self.theme.pid_app,
+                procgroup::Group::System => &self.theme.pid_system,
+                procgroup::Group::Native => &self.theme.pid_native,
+            });
+        }
+
+        if let Some(row) = self
+            .opts
+            .pins
+            .iter()
+            .position(|t: &String| t == lc.tag.trim())
+        {
+            self.pin(row, lc, event)?;
+        }
+        if let Some(ci) = &mut self.ci {
+            for marker in ci.before(lc, event) {
+                self.marker(&marker)?;
+            }
+        }
+        let annotation: Option<String> =
+            self.ci.as_ref().and_then(|ci: &Sections| ci.annotation(lc));
+
+        if let Some(annotation) = annotation.filter(|_| !self.opts.events_only || event.is_some()) {
+            self.out.text(&annotation)?;
+            self.last_printed_ts.clone_from(&lc.timestamp);
+        } else if !self.opts.events_only {
+            let change: Option<Change> = self
+                .diffs
+                .as_mut()
+                .and_then(|d: &mut StateDiff| d.observe(lc));
+            let humanized: Vec<(usize, String)> = self
+                .opts
+                .humanizer
+                .as_ref()
+                .map(|h: &Humanizer| h.annotate(&lc.tag, &lc.message))
+                .unwrap_or_default();
+            let decor: Decor = Decor {
+                event,
+                pid_seq,
+                out_of_order,
+                spotlight: self.spotlights.get(self.spotlight.as_ref(), &lc.tag),
+                prev_timestamp: Some(&self.last_printed_ts),
+                change: change.as_ref(),
+                humanized: &humanized,
+                tag_seq: self
+                    .tag_colors
+                    .as_mut()
+                    .and_then(|t: &mut TagColors| t.get(&lc.tag)),
+                ..Default::default()
+            };
+            self.out
+                .line(&render::entry(lc, self.theme, self.opts, &decor))?;
+            self.last_printed_ts.clone_from(&lc.timestamp);
+        } else if let Some(kind) = event {
+            self.out.line(&render::event(lc, kind, self.theme))?;
+        } else {
+            self.hide(Hidden::NotEvent)?;
+        }
+        if self.findings.is_some() && (!self.opts.events_only || event.is_some()) {
+            self.last_shown = Some(lc.clone());
+        }
+
+        if let Some(router) = &mut self.router {
+            let dispatch: Dispatch = router.dispatch(lc, event);
+            if !dispatch.control.is_empty() {
+                self.out.control(&dispatch.control)?;
+            }
+            for failure in &dispatch.failures {
+                self.out.status_line(&render::note(
+                    &self.theme.webhook,
+                    &format!("--- alert failed: {}", failure),
+                ))?;
+            }
+        }
+        if let Some(shot) = self
+            .screenshots
+            .as_mut()
+            .and_then(|s: &mut Screenshots| s.check(lc))
+        {
+            let text: String = match &shot {
+                Ok(path) => format!("--- screenshot: {}", path.display()),
+                Err(e) => format!("--- screenshot failed: {}", e),
+            };
+            self.out
+                .status_line(&render::note(&self.theme.screenshot, &text))?;
+            if let (Ok(path), Some(protocol)) = (shot, self.opts.screenshot_preview) {
+                match screenshot::thumbnail(protocol, &path) {
+                    Ok(seq) => self.out.control(&seq)?,
+                    Err(e) => self.out.status_line(&render::note(
+                        &self.theme.screenshot,
+                        &format!("--- screenshot preview failed: {}", e),
+                    ))?,
                 }
             }
-            FormatKind::Brief => {
-                if let Some(c) = RE_BRIEF.captures(&line) {
-                    parsed = Some(Logcat {
-                        level: c[1].to_string(),
-                        tag: c[2].to_string(),
-                        process: c[3].trim().to_string(),
-                        message: c[4].to_string(),
-                        ..Default::default()
-                    });
+        }
+        Ok(())
+    }
+
+    // Shows `lc` in its pinned row, setting the pane up on first use
+    fn pin(&mut self, row: usize, lc: &Logcat, event: Option<EventKind>) -> io::Result<()> {
+        if !self.pane {
+            let separator: Line = render::note(
+                &self.theme.banner,
+                &format!("--- pinned: {}", self.opts.pins.join(", ")),
+            );
+            self.out.reserve_rows(self.opts.pins.len(), &separator)?;
+            self.pane = true;
+        }
+        let decor: Decor = Decor {
+            event,
+            spotlight: self.spotlights.get(self.spotlight.as_ref(), &lc.tag),
+            tag_seq: self
+                .tag_colors
+                .as_mut()
+                .and_then(|t: &mut TagColors| t.get(&lc.tag)),
+            ..Default::default()
+        };
+        self.out
+            .pin(row + 1, &render::entry(lc, self.theme, self.opts, &decor))
+    }
+
+    // CI folding markers, written verbatim into the log stream
+    fn marker(&mut self, marker: &str) -> io::Result<()> {
+        let mut line: Line = Line::new();
+        line.plain(marker);
+        self.out.line(&line)
+    }
+
+    fn format_hint(&mut self) -> io::Result<()> {
+        let Some(command) = &self.opts.format_hint else {
+            return Ok(());
+        };
+        let text: String = format!(
+            "--- hint: this format has no timestamps or thread ids; try `{}` (--quiet-hints to hide)",
+            command
+        );
+        self.out.status_line(&render::note(&self.theme.hint, &text))
+    }
+
+    // Format detection settled on a split vote
+    fn mixed_formats_hint(&mut self, votes: &str) -> io::Result<()> {
+        if self.opts.format_hint.is_none() {
+            return Ok(());
+        }
+        let text: String = format!(
+            "--- hint: the input seems to mix formats ({}); try --detect=line (--quiet-hints to hide)",
+            votes
+        );
+        self.out.status_line(&render::note(&self.theme.hint, &text))
+    }
+
+    fn post_crash(&mut self, group: &Group) -> io::Result<()> {
+        let Some(crashes) = &mut self.crashes else {
+            return Ok(());
+        };
+        let text: String = match crashes.post(group) {
+            Ok(()) => format!(
+                "--- crash {} ({}) sent to the webhook",
+                group.fingerprint, group.reason
+            ),
+            Err(e) => format!("--- crash webhook failed: {}", e),
+        };
+        self.out
+            .status_line(&render::note(&self.theme.webhook, &text))
+    }
+
+    fn print_assertion(&mut self, failure: &str) -> io::Result<()> {
+        let text: String = format!("--- assertion failed: {}", failure);
+        self.out
+            .status_line(&render::note(&self.theme.assertion, &text))
+    }
+
+    fn print_reuse(&mut self, reuse: &Reuse) -> io::Result<()> {
+        let name = |n: &Option<String>| -> String { n.clone().unwrap_or_else(|| "?".to_string()) };
+        let text: String = format!(
+            "--- pid {} reused: {} -> {}",
+            reuse.pid,
+            name(&reuse.old),
+            name(&reuse.new)
+        );
+        self.out
+            .status_line(&render::note(&self.theme.pid_reuse, &text))
+    }
+
+    fn print_package_event(&mut self, event: &PackageEvent) -> io::Result<()> {
+        let text: String = format!(
+            "--- {} {} (pid {})",
+            event.name,
+            if event.started { "started" } else { "died" },
+            event.pid
+        );
+        self.out
+            .status_line(&render::note(&self.theme.package, &text))
+    }
+
+    fn flush_prologue(&mut self) -> io::Result<()> {
+        let entries: Vec<Logcat> = std::mem::take(&mut self.prologue);
+        match entries.len() {
+            0 => Ok(()),
+            1 => self.print(&entries[0]),
+            _ => {
+                for e in &entries {
+                    self.count(e, None)?;
                 }
+                if self.opts.events_only {
+                    for _ in &entries {
+                        self.hide(Hidden::NotEvent)?;
+                    }
+                    return Ok(());
+                }
+                let summary: Logcat = prologue::summarize(&entries);
+                let decor: Decor = Decor {
+                    msg_override: Some(&self.theme.collapsed),
+                    spotlight: self.spotlights.get(self.spotlight.as_ref(), &summary.tag),
+                    ..Default::default()
+                };
+                self.out
+                    .line(&render::entry(&summary, self.theme, self.opts, &decor))
             }
-            FormatKind::Process => {
-                if let Some(c) = RE_PROCESS.captures(&line) {
-                    parsed = Some(Logcat {
-                        level: c[1].to_string(),
-                        process: c[2].trim().to_string(),
-                        message: c[3].to_string(),
-                        tag: c
-                            .get(4)
-                            .map(|m: regex::Match<'_>| m.as_str().to_string())
-                            .unwrap_or_default(),
-                        ..Default::default()
-                    });
+        }
+    }
+}
+
+/// Renders `adb logcat -B` records.
+pub fn format_binary<R: Read>(
+    input: R,
+    out: &mut Renderer,
+    theme: &Theme,
+    opts: &Options,
+) -> io::Result<Verdict> {
+    let control: Option<Control> = opts
+        .control
+        .as_deref()
+        .map(|path: &Path| Control::listen(path, opts.spotlight_mode))
+        .transpose()?;
+    let mut emitter: Emitter = Emitter::new(out, theme, opts, control)?;
+    for lc in binary::entries(input) {
+        emitter.entry(lc?)?;
+    }
+    emitter.finish()
+}
+
+/// Renders the `--merge` captures as one timeline.
+pub fn format_merged(out: &mut Renderer, theme: &Theme, opts: &Options) -> io::Result<Verdict> {
+    let items: Vec<Item> = merge::read_all(&opts.captures)?;
+    let mut emitter: Emitter = Emitter::new(out, theme, opts, None)?;
+    for item in items {
+        match item {
+            Item::Entry(lc) => emitter.entry(lc)?,
+            Item::Raw(line) => emitter.raw(&line)?,
+        }
+    }
+    emitter.finish()
+}
+
+pub fn format_with<R: BufRead + Send + 'static>(
+    input: R,
+    out: &mut Renderer,
+    theme: &Theme,
+    opts: &Options,
+    checkpoint: Option<Checkpoint>,
+) -> io::Result<Verdict> {
+    let mut detector: Detector = Detector::default();
+    let mut hinted: bool = false; // the format hint, on the first parsed line
+    // The `-v long` header whose message lines are being read
+    let mut long: Option<Logcat> = None;
+    let control: Option<Control> = opts
+        .control
+        .as_deref()
+        .map(|path: &Path| Control::listen(path, opts.spotlight_mode))
+        .transpose()?;
+    let mut emitter: Emitter = Emitter::new(out, theme, opts, control)?;
+    let mut tee: Option<Tee> = opts
+        .tee
+        .as_deref()
+        .map(|path: &Path| Tee::create(path, opts.rotate.clone()))
+        .transpose()?;
+    let offset: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+    // The offset must not run ahead of what was shown
+    let tick: bool = checkpoint.is_none() && (opts.reorder_window.is_some() || opts.join_multiline);
+    if let Some(checkpoint) = checkpoint {
+        offset.store(checkpoint.offset, Ordering::Relaxed);
+        emitter.resume(checkpoint);
+    }
+
+    let mut lines: Box<dyn Iterator<Item = io::Result<Input>> + Send> = match &opts.host_log {
+        None => Box::new(
+            input::lines(input)
+                .tracking(Arc::clone(&offset))
+                .map(|l: io::Result<String>| l.map(Input::Device)),
+        ),
+        Some(path) => Box::new(hostlog::merge(input, path.clone())),
+    };
+    if let Some(command) = &opts.after_command {
+        emitter.command_started(command)?;
+        lines = Box::new(aftercommand::merge(
+            lines,
+            command.clone(),
+            opts.clear_after_command,
+        ));
+    }
+    if tick {
+        let quiet: Duration = Duration::from_millis(opts.reorder_window.unwrap_or(0)).max(IDLE);
+        lines = Box::new(
+            reorder::ticking(lines, quiet)
+                .map(|item: Option<io::Result<Input>>| item.unwrap_or(Ok(Input::Idle))),
+        );
+    }
+
+    loop {
+        emitter.save_checkpoint(offset.load(Ordering::Relaxed), long.is_some())?;
+        let Some(item) = lines.next() else {
+            break;
+        };
+        let line: String = match item? {
+            Input::Device(line) => {
+                if let Some(tee) = &mut tee {
+                    tee.line(&line)?;
                 }
+                line
             }
-            FormatKind::Tag => {
-                if let Some(c) = RE_TAG.captures(&line) {
-                    parsed = Some(Logcat {
-                        level: c[1].to_string(),
-                        tag: c[2].to_string(),
-                        message: c[3].to_string(),
-                        ..Default::default()
-                    });
+            Input::Host(line) => {
+                if let Some(path) = &opts.host_log {
+                    emitter.host(&line, path)?;
                 }
+                continue;
             }
+            Input::Finished(finished) => {
+                emitter.command_finished(&finished)?;
+                continue;
+            }
+            Input::Idle => {
+                emitter.idle()?;
+                continue;
+            }
+            Input::End => break,
+        };
+        if opts.no_parse {
+            emitter.passthrough(&line)?;
+            continue;
         }
-
-        if let Some(lc) = parsed {
-            print_log(&lc, theme, &spotlight_re);
-        } else {
-            // Fallback: try re-guess once, then print raw if still failing.
-            if let Some((kind, lc)) = parse_line(&line) {
-                guessed_kind = Some(kind);
-                print_log(&lc, theme, &spotlight_re);
-            } else if !ignore {
-                println!("{}", line);
+        if let Some(c) = RE_BUFFER.captures(&line) {
+            long = None;
+            emitter.divider(&c[1], &c[2])?;
+            continue;
+        }
+        if let Some(header) = &long
+            && !RE_LONG.is_match(&line)
+        {
+            if line.is_empty() {
+                long = None;
+            } else {
+                emitter.entry(Logcat {
+                    message: line,
+                    ..header.clone()
+                })?;
+            }
+            continue;
+        }
+        let parsed: Option<(FormatKind, Logcat)> = match opts.detect_per_line {
+            true => parse_line(&line),
+            false => detector.parse(&line),
+        };
+        let Some((kind, lc)) = parsed else {
+            // An app's own log file: read with the --host-log rules
+            match &opts.device_file {
+                Some(path) if !line.is_empty() => emitter.host(&line, Path::new(path))?,
+                _ => emitter.raw(&line)?,
             }
+            continue;
+        };
+        if !hinted {
+            hinted = true;
+            if matches!(
+                kind,
+                FormatKind::Brief | FormatKind::Process | FormatKind::Tag
+            ) {
+                emitter.format_hint()?;
+            }
+        }
+        if let Some(votes) = detector.split.take() {
+            emitter.mixed_formats_hint(&votes)?;
+        }
+        if kind == FormatKind::Long {
+            long = Some(lc);
+            continue;
+        }
+        emitter.entry(lc)?;
+    }
+
+    emitter.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ansi::{self, Depth},
+        config::Config,
+        humanize,
+        testutil::{Shared, entry},
+        theme::make_theme,
+        verdict,
+    };
+    use std::fs;
+
+    // Feeds lines to a fresh detector, returning the formats they parsed as
+    fn detect(detector: &mut Detector, lines: &[&str]) -> Vec<FormatKind> {
+        lines
+            .iter()
+            .map(|line: &&str| detector.parse(line).unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn detection_keeps_the_majority_of_a_mixed_sample() {
+        let mut detector: Detector = Detector::default();
+        let mut lines: Vec<&str> = vec!["I/Sync( 1234): brief"; 10];
+        lines.extend(["06-01 12:00:00.100  1234  1240 I Sync: threadtime"; 6]);
+        detect(&mut detector, &lines);
+        assert_eq!(detector.kept, Some(FormatKind::Brief));
+        assert_eq!(detector.split.as_deref(), Some("brief 10, threadtime 6"));
+        // A line the kept format can't take is guessed again, and sticks
+        let kinds: Vec<FormatKind> = detect(
+            &mut detector,
+            &["06-01 12:00:00.200  1234  1240 I Sync: threadtime"],
+        );
+        assert_eq!(kinds, [FormatKind::ThreadTime]);
+        assert_eq!(detector.kept, Some(FormatKind::ThreadTime));
+    }
+
+    #[test]
+    fn detection_breaks_ties_for_the_more_specific_format() {
+        let mut detector: Detector = Detector::default();
+        let mut lines: Vec<&str> = vec!["I/Sync( 1234): brief"; 8];
+        lines.extend(["06-01 12:00:00.100 I/Sync( 1234): time"; 8]);
+        detect(&mut detector, &lines);
+        assert_eq!(detector.kept, Some(FormatKind::Time));
+        assert_eq!(detector.split.as_deref(), Some("time 8, brief 8"));
+    }
+
+    #[test]
+    fn detection_outvotes_a_stray_first_line() {
+        let mut detector: Detector = Detector::default();
+        let mut lines: Vec<&str> = vec!["I/Sync: tag"];
+        lines.extend(["I/Sync( 1234): brief"; 15]);
+        let kinds: Vec<FormatKind> = detect(&mut detector, &lines);
+        assert_eq!(kinds[0], FormatKind::Tag);
+        assert_eq!(detector.kept, Some(FormatKind::Brief));
+        // Too few dissenters to call the input mixed
+        assert_eq!(detector.split, None);
+        assert_eq!(detect(&mut detector, &["I/Sync: tag"]), [FormatKind::Tag]);
+    }
+
+    #[test]
+    fn detection_per_line_reads_what_sticky_detection_keeps() {
+        let mut detector: Detector = Detector::default();
+        detect(&mut detector, &["I/Sync: tag"; 16]);
+        assert_eq!(detector.kept, Some(FormatKind::Tag));
+        // Once Tag is kept, a Brief line still matches it, pid and all in the tag
+        let line: &str = "W/Sync( 1234): brief";
+        let (kind, lc) = detector.parse(line).unwrap();
+        assert_eq!((kind, lc.process.as_str()), (FormatKind::Tag, ""));
+        // --detect=line picks the most specific format for each line
+        let (kind, lc) = parse_line(line).unwrap();
+        assert_eq!((kind, lc.process.as_str()), (FormatKind::Brief, "1234"));
+        assert_eq!(lc.tag, "Sync");
+    }
+
+    // What an emitter prints for `lines` under `opts`, as plain text, and
+    // the verdict it ends on
+    fn emit(opts: &Options, lines: &[&str]) -> (String, Verdict) {
+        ansi::set_thread_depth(Depth::Plain);
+        let theme: Theme = make_theme();
+        let shared: Shared = Shared::default();
+        let mut out: Renderer = Renderer::new(Box::new(shared.clone()));
+        let mut emitter: Emitter = Emitter::new(&mut out, &theme, opts, None).unwrap();
+        for line in lines {
+            emitter.entry(entry(line)).unwrap();
         }
+        let verdict: Verdict = emitter.finish().unwrap();
+        drop(emitter);
+        drop(out);
+        (shared.text(), verdict)
     }
 
-    Ok(())
+    #[test]
+    fn humanize_follows_numbers_with_their_readable_forms() {
+        let config: Config = Config::parse(
+            "config.toml",
+            "[[humanize]]\ntag = 'Sync'\nregex = 'took ([0-9]+)'\nunit = 'ms'\n",
+        )
+        .unwrap();
+        let opts: Options = Options {
+            humanizer: Some(humanize::load(&[config], false).unwrap()),
+            ..Default::default()
+        };
+        let (text, _) = emit(
+            &opts,
+            &[
+                "06-01 12:00:00.100  1234  1240 I Heap: alloc 134217728 bytes",
+                "06-01 12:00:00.200  1234  1240 I Sync: took 95000",
+                "06-01 12:00:00.300  1234  1240 I Other: took 95000",
+            ],
+        );
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(
+            lines[0].ends_with("alloc 134217728 bytes (128 MiB) "),
+            "{:?}",
+            lines[0]
+        );
+        // A [[humanize]] rule holds for its tag only
+        assert!(
+            lines[1].ends_with("took 95000 (1 min 35 s) "),
+            "{:?}",
+            lines[1]
+        );
+        assert!(lines[2].ends_with("took 95000 "), "{:?}", lines[2]);
+    }
+
+    #[test]
+    fn emoji_are_stripped_or_named_in_tags_and_messages() {
+        let lines: [&str; 2] = [
+            "06-01 12:00:00.100  1234  1240 I Sync✨: done ✅",
+            "06-01 12:00:00.200  1234  1240 I Sync: family 👨\u{200D}👩\u{200D}👧 home",
+        ];
+        let render = |mode: EmojiMode| -> Vec<String> {
+            let opts: Options = Options {
+                emoji: Some(mode),
+                ..Default::default()
+            };
+            let (text, _) = emit(&opts, &lines);
+            text.lines().map(str::to_string).collect()
+        };
+        let stripped: Vec<String> = render(EmojiMode::Strip);
+        assert!(stripped[0].ends_with("] Sync done  "), "{:?}", stripped[0]);
+        assert!(stripped[1].ends_with("family  home "), "{:?}", stripped[1]);
+        let named: Vec<String> = render(EmojiMode::Shortcode);
+        assert!(
+            named[0].ends_with("] Sync:sparkles: done :white_check_mark: "),
+            "{:?}",
+            named[0]
+        );
+        // A ZWJ sequence is one emoji, named after its first character, by code
+        // point when it has no shortcode
+        assert!(
+            named[1].ends_with("family :u1f468: home "),
+            "{:?}",
+            named[1]
+        );
+    }
+
+    #[test]
+    fn translations_rewrite_messages_before_they_are_classified() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("translations-{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            "# MIUI, zh-CN\n^进程 (\\S+) \\(pid (\\d+)\\) 已终止 => Process $1 (pid $2) has died\n",
+        )
+        .unwrap();
+        let opts: Options = Options {
+            translations: Some(Translations::load(&path).unwrap()),
+            ..Default::default()
+        };
+        fs::remove_file(&path).unwrap();
+        let (text, _) = emit(
+            &opts,
+            &[
+                "06-01 12:00:00.100  1000  1020 I ActivityManager: 进程 com.example (pid 4321) 已终止",
+                "06-01 12:00:00.200  1000  1020 I ActivityManager: 进程列表已更新",
+            ],
+        );
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].contains(" DEATH "), "{:?}", lines[0]);
+        assert!(
+            lines[0].ends_with("Process com.example (pid 4321) has died "),
+            "{:?}",
+            lines[0]
+        );
+        assert!(lines[1].ends_with("进程列表已更新 "), "{:?}", lines[1]);
+    }
+
+    #[test]
+    fn recap_and_verdict_count_asserts_as_fatal() {
+        let opts: Options = Options {
+            recap: Some(2),
+            fail_on: Some(verdict::parse("E>=3 || F>=1").unwrap()),
+            ..Default::default()
+        };
+        let (text, verdict) = emit(
+            &opts,
+            &[
+                "06-01 12:00:00.100  1234  1240 E Loader: first error",
+                "06-01 12:00:00.200  1234  1240 I Loader: fine",
+                "06-01 12:00:00.300  1234  1240 A libc: Assertion failed",
+                "06-01 12:00:00.400  1234  1240 E Loader: last error",
+            ],
+        );
+        assert_eq!(verdict, Verdict::Fail);
+        let tail: Vec<&str> = text.lines().skip(4).collect();
+        assert_eq!(tail[0], "");
+        assert!(
+            tail[1].ends_with("--- recap: last 2 of 3 errors"),
+            "{:?}",
+            tail[1]
+        );
+        assert!(tail[2].ends_with("libc Assertion failed "), "{:?}", tail[2]);
+        assert!(tail[3].ends_with("Loader last error "), "{:?}", tail[3]);
+        assert_eq!(
+            tail[4],
+            "verdict=FAIL fail_on=\"E>=3 || F>=1\" V=0 D=0 I=1 W=0 E=2 F=1 total=4 unparsed=0"
+        );
+        assert_eq!(tail.len(), 5);
+
+        // Under the thresholds, the verdict passes
+        let opts: Options = Options {
+            fail_on: Some(verdict::parse("E>=3").unwrap()),
+            ..Default::default()
+        };
+        let (text, verdict) = emit(&opts, &["06-01 12:00:00.100  1234  1240 E Loader: error"]);
+        assert_eq!(verdict, Verdict::Pass);
+        assert!(text.ends_with(
+            "verdict=PASS fail_on=\"E>=3\" V=0 D=0 I=0 W=0 E=1 F=0 total=1 unparsed=0\n"
+        ));
+    }
+
+    #[test]
+    fn debug_parse_points_at_a_multibyte_divergence() {
+        ansi::set_thread_depth(Depth::Plain);
+        let theme: Theme = make_theme();
+        let opts: Options = Options {
+            debug_parse: true,
+            ..Default::default()
+        };
+        let shared: Shared = Shared::default();
+        let mut out: Renderer = Renderer::new(Box::new(shared.clone()));
+        let mut emitter: Emitter = Emitter::new(&mut out, &theme, &opts, None).unwrap();
+        emitter
+            .raw("06-01 12:00:00.100…1234  1240 I Sync: message")
+            .unwrap();
+        let text: String = shared.text();
+        assert!(
+            text.ends_with(&format!(
+                "{}^ closest format: threadtime, which stops matching at column 19 ('…')\n",
+                " ".repeat(18)
+            )),
+            "{:?}",
+            text
+        );
+    }
+
+    #[test]
+    fn idle_releases_the_reorder_window() {
+        ansi::set_thread_depth(Depth::Plain);
+        let theme: Theme = make_theme();
+        let opts: Options = Options {
+            reorder_window: Some(1000),
+            ..Default::default()
+        };
+        let shared: Shared = Shared::default();
+        let mut out: Renderer = Renderer::new(Box::new(shared.clone()));
+        let mut emitter: Emitter = Emitter::new(&mut out, &theme, &opts, None).unwrap();
+        emitter
+            .entry(entry("06-01 12:00:00.200  1234  1240 I Sync: second"))
+            .unwrap();
+        emitter
+            .entry(entry("06-01 12:00:00.100  1234  1240 I Sync: first"))
+            .unwrap();
+        assert_eq!(shared.text(), "");
+        emitter.idle().unwrap();
+        let text: String = shared.text();
+        assert!(text.find("first").unwrap() < text.find("second").unwrap());
+    }
+
+    #[test]
+    fn idle_releases_a_held_burst() {
+        ansi::set_thread_depth(Depth::Plain);
+        let theme: Theme = make_theme();
+        let opts: Options = Options {
+            join_multiline: true,
+            ..Default::default()
+        };
+        let shared: Shared = Shared::default();
+        let mut out: Renderer = Renderer::new(Box::new(shared.clone()));
+        let mut emitter: Emitter = Emitter::new(&mut out, &theme, &opts, None).unwrap();
+        // The last words before the stream went quiet, still an open object
+        for line in [
+            "06-01 12:00:00.100  1234  1240 E Crash: {",
+            "06-01 12:00:00.110  1234  1240 E Crash:   \"reason\": \"NullPointerException\",",
+        ] {
+            emitter.entry(entry(line)).unwrap();
+        }
+        assert_eq!(shared.text(), "");
+        emitter.idle().unwrap();
+        assert!(shared.text().contains("NullPointerException"));
+    }
+
+    #[test]
+    fn tombstone_keeps_what_crashed() {
+        ansi::set_thread_depth(Depth::Plain);
+        let theme: Theme = make_theme();
+        let opts: Options = Options::default();
+        let shared: Shared = Shared::default();
+        let mut out: Renderer = Renderer::new(Box::new(shared.clone()));
+        let mut emitter: Emitter = Emitter::new(&mut out, &theme, &opts, None).unwrap();
+        for message in [
+            "*** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***",
+            "Build fingerprint: 'google/sdk_gphone64_arm64/emu64a:14/UE1A.230829.036/10855291:userdebug/dev-keys'",
+            "Revision: '0'",
+            "ABI: 'arm64'",
+            "Timestamp: 2024-06-01 12:00:00.412377012+0000",
+            "Process uptime: 37s",
+            "Cmdline: com.example.app",
+            "pid: 4242, tid: 4260, name: RenderThread  >>> com.example.app <<<",
+            "uid: 10123",
+            "signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0000000000000000",
+        ] {
+            let line: String = format!("06-01 12:00:00.500  4281  4281 F DEBUG   : {}", message);
+            emitter.entry(entry(&line)).unwrap();
+        }
+        emitter.finish().unwrap();
+        let text: String = shared.text();
+        assert!(text.contains("[7 lines collapsed: Build fingerprint, Revision, ABI"));
+        assert!(text.contains("pid: 4242, tid: 4260, name: RenderThread  >>> com.example.app <<<"));
+        assert!(text.contains("SIGSEGV"));
+        assert!(!text.contains("'arm64'"));
+    }
 }
@@ -12,88 +12,1275 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod adb;
+mod aftercommand;
+mod alert;
 mod ansi;
+mod autohighlight;
+mod background;
+mod bench;
+mod binary;
+mod bugreport;
+mod checkpoint;
+mod ci;
+mod classify;
+mod codepage;
+mod config;
+mod context;
+mod control;
+mod crash;
+mod diagnose;
+mod emoji;
+mod expect;
+mod expr;
+mod filter;
+mod filterprofile;
+mod findings;
+mod fixture;
+mod history;
+mod hostlog;
+mod humanize;
+mod input;
+mod interrupt;
+mod keymap;
 mod logcat;
+mod merge;
+mod multiline;
+mod native;
+mod pack;
+mod parquet;
+mod pids;
+mod poll;
+mod procgroup;
+mod profile;
+mod prologue;
+mod render;
+mod reorder;
+mod rotate;
+mod scan;
+mod screenshot;
+mod spotlight;
+mod statediff;
+mod stats;
+mod tagcolor;
+mod tee;
+#[cfg(test)]
+mod testutil;
 mod theme;
+mod themeedit;
+mod timestamp;
+mod tmuxstatus;
+mod translate;
+mod users;
+mod verdict;
+mod width;
 
-use crate::theme::{Theme, make_theme};
-use clap::Parser;
+use crate::{
+    background::Background,
+    config::Config,
+    emoji::EmojiMode,
+    expect::Script,
+    filter::{Filters, TagPattern},
+    filterprofile::FilterProfile,
+    keymap::Keymap,
+    merge::Capture,
+    pids::PackagePids,
+    render::Renderer,
+    theme::{Theme, make_theme},
+    timestamp::Bound,
+    translate::Translations,
+    verdict::{FailOn, Verdict},
+};
+use clap::{CommandFactory, Parser, Subcommand};
 use regex::Regex;
 use std::{
-    io::{self, IsTerminal},
-    sync::LazyLock,
+    fs::File,
+    io::{self, BufReader, IsTerminal},
+    path::PathBuf,
+    process::{Child, ChildStdout, ExitCode},
+    time::Duration,
 };
 
+/// A simple tool to colorize Android adb logcat output.
+///
+/// Pipe adb into this program. Supports Tag, Process, Brief, Time,
+/// ThreadTime, Long, Epoch, Monotonic, and kernel (dmesg) lines.
 #[derive(Parser, Debug)]
-#[command(version, disable_help_flag = true)]
+#[command(
+    version,
+    disable_help_flag = true,
+    disable_help_subcommand = true,
+    help_template = "\
+{name} v{version}
+
+{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}",
+    override_usage = "\
+adb logcat [OPTIONS] | logcat-colorize [OPTIONS]
+       logcat-colorize --exec [OPTIONS] [-- LOGCAT OPTIONS]
+       logcat-colorize --merge FILE... [--offset FILE=+2.5s] [OPTIONS]
+       logcat-colorize [--config FILE] [--profile NAME] theme-edit",
+    after_long_help = "\
+Examples:
+  adb logcat | logcat-colorize
+  adb -s emulator-5556 logcat -v time System.err:V *:S | logcat-colorize
+  adb logcat -v time | egrep -i '(sensor|wifi)' | logcat-colorize
+  logcat-colorize --exec --setup-buffers 16M -- -v threadtime
+  echo 'spotlight add Wifi' | nc -U /tmp/lc.sock   (with --control /tmp/lc.sock)
+
+Authors: Bruno Braga, Luca Carlon
+Adapted to Rust: Chmouel Boudjnah
+Bugs: https://github.com/chmouel/logcat-colorize-rs/issues"
+)]
 struct Args {
+    /// Prints this help
+    #[arg(short, long)]
+    help: bool,
+
+    /// When to color the output: auto (on a terminal, unless NO_COLOR is
+    /// set or CLICOLOR_FORCE asks for it), always or never
+    #[arg(long, value_name = "WHEN", value_parser = ["auto", "always", "never"], default_value = "auto")]
+    color: String,
+
     /// Does not output non-matching data
     #[arg(short, long)]
     ignore: bool,
 
-    /// Highlight pattern in the output, value as REGEXP (e.g. -s '\bWORD\b')
-    #[arg(short, long)]
-    spotlight: Option<String>,
+    /// Run `adb logcat` directly instead of reading stdin, with -v threadtime
+    /// unless the logcat arguments after `--` pick a format; --level (and --tag
+    /// with --raw-tags) go to adb as filterspecs unless those arguments include
+    /// some
+    #[arg(short = 'x', long, help_heading = INPUT)]
+    exec: bool,
 
-    /// Prints this help
-    #[arg(short, long)]
-    help: bool,
+    /// Arguments passed through to `adb logcat` in exec mode
+    #[arg(last = true, help_heading = INPUT)]
+    adb_args: Vec<String>,
+
+    /// Merge offline captures into one timeline sorted by timestamp
+    #[arg(long, value_name = "FILE", num_args = 1.., conflicts_with_all = ["exec", "host_log"], help_heading = INPUT)]
+    merge: Vec<PathBuf>,
+
+    /// Shift a --merge capture's timestamps, e.g. b.log=+2.5s (repeatable)
+    #[arg(long, value_name = "FILE=OFFSET", requires = "merge", value_parser = merge::parse_offset, help_heading = INPUT)]
+    offset: Vec<(String, i64)>,
+
+    /// Follow a host-side log file and interleave its lines with the device
+    /// stream (sorted by time with --reorder-window)
+    #[arg(long, value_name = "FILE", help_heading = INPUT)]
+    host_log: Option<PathBuf>,
+
+    /// Colorize the SYSTEM LOG and EVENT LOG sections of a bugreport (text, or
+    /// zip through unzip), skipping the dumpsys output around them
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["exec", "merge", "binary", "native_messaging", "resume"], help_heading = INPUT)]
+    bugreport: Option<PathBuf>,
+
+    /// Follow a log file on the device, such as one written by
+    /// `adb logcat -f`, through `adb shell tail -F`
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["exec", "merge", "binary", "native_messaging", "resume", "bugreport"], help_heading = INPUT)]
+    device_file: Option<String>,
+
+    /// Save progress through a file redirected to stdin in FILE every few
+    /// seconds, and continue from there when FILE exists; FILE is removed once
+    /// the input is done
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["exec", "merge", "host_log", "binary", "native_messaging", "reorder_window"], help_heading = INPUT)]
+    resume: Option<PathBuf>,
+
+    /// Read the binary format of `adb logcat -B`, with lossless sec.nsec
+    /// timestamps (implied by -B in exec mode)
+    #[arg(long, conflicts_with_all = ["host_log", "merge", "no_parse", "native_messaging"], help_heading = INPUT)]
+    binary: bool,
+
+    /// Don't parse logcat formats: pass lines through with only the spotlight,
+    /// --grep, --exclude and --filter (over msg) applied, for gradle, fastboot
+    /// and other output
+    #[arg(long, conflicts_with_all = ["tags", "excluded_tags", "pids", "tids", "packages", "level", "events_only", "merge", "native_messaging"], help_heading = INPUT)]
+    no_parse: bool,
+
+    /// Format detection: by majority over the first 16 lines and kept while
+    /// lines match, hinting when they disagree (sticky), or on every line
+    /// (line), for streams mixing `-v` formats
+    #[arg(long, value_name = "MODE", value_parser = ["sticky", "line"], default_value = "sticky", help_heading = INPUT)]
+    detect: String,
+
+    /// Run `adb logcat` and serve entries to a browser extension over Chrome's
+    /// native messaging protocol on stdin/stdout; the extension sends --control
+    /// commands as {"command": "level W"}
+    #[arg(long, conflicts_with_all = ["exec", "merge", "host_log"], help_heading = INPUT)]
+    native_messaging: bool,
+
+    /// Run a build/install command through `sh -c` alongside the log stream,
+    /// e.g. 'gradle installDebug', marking where it finished with its exit
+    /// status and time; a failed command's last output lines follow
+    #[arg(long, value_name = "CMD", conflicts_with_all = ["binary", "merge", "native_messaging"], help_heading = INPUT)]
+    after_command: Option<String>,
+
+    /// Clear the device log buffers once the --after-command succeeds
+    #[arg(long, requires = "after_command", help_heading = INPUT)]
+    clear_after_command: bool,
+
+    /// In exec mode, resize the device log buffers first (e.g. 16M)
+    #[arg(long, value_name = "SIZE", requires = "exec", help_heading = INPUT)]
+    setup_buffers: Option<String>,
+
+    /// In exec mode, save a device screenshot named after the entry's timestamp
+    /// when a tag or message matches REGEXP, at most one every 3 seconds
+    #[arg(long, value_name = "REGEXP", requires = "exec", value_parser = Regex::new, help_heading = INPUT)]
+    screenshot_on: Option<Regex>,
+
+    /// Show each --screenshot-on capture inline as a thumbnail, on terminals
+    /// with the kitty or iTerm2 image protocol (kitty, Ghostty, iTerm2,
+    /// WezTerm)
+    #[arg(long, requires = "screenshot_on", help_heading = INPUT)]
+    screenshot_preview: bool,
+
+    /// Ask the device about processes (reused pids in exec mode, --package's
+    /// pids, --pid-groups) at most once every INTERVAL (10s by default), and
+    /// only while entries come in
+    #[arg(long, value_name = "INTERVAL", value_parser = poll::parse_interval, help_heading = INPUT)]
+    adb_poll_interval: Option<Duration>,
+
+    /// Also write the lines read to FILE as they come, a raw capture that can
+    /// be colorized again later
+    #[arg(long, visible_alias = "record", value_name = "FILE", conflicts_with_all = ["merge", "binary"], help_heading = INPUT)]
+    tee: Option<PathBuf>,
+
+    /// Rotate the --tee capture by POLICY: size=100M or time=1h, with keep=N
+    /// (5 by default) and gzip, e.g. size=100M,keep=3,gzip
+    #[arg(long, value_name = "POLICY", value_parser = rotate::parse_policy, requires = "tee", help_heading = INPUT)]
+    rotate: Option<rotate::Policy>,
+
+    /// Only show entries at or above this level (V D I W E F), checked after
+    /// parsing so saved logs can be replayed at any level
+    #[arg(short, long, visible_alias = "min-level", value_name = "LEVEL", value_parser = filter::parse_level, help_heading = FILTERING)]
+    level: Option<String>,
+
+    /// Only show entries with this tag (repeatable): a name, a glob such as
+    /// 'MyApp*', or a regex between slashes
+    #[arg(long = "tag", value_name = "NAME", help_heading = FILTERING)]
+    tags: Vec<String>,
+
+    /// Hide entries with this tag (repeatable), given like --tag
+    #[arg(long = "exclude-tag", value_name = "NAME", help_heading = FILTERING)]
+    excluded_tags: Vec<String>,
+
+    /// Only show entries from this process id (repeatable); a single one is
+    /// passed to adb in exec mode
+    #[arg(long = "pid", value_name = "N", help_heading = FILTERING)]
+    pids: Vec<u64>,
+
+    /// Only show entries from this thread id (repeatable)
+    #[arg(long = "tid", value_name = "N", help_heading = FILTERING)]
+    tids: Vec<u64>,
+
+    /// Only show entries of this Android user or work profile (repeatable),
+    /// e.g. 10, told from -v uid, from platform messages naming an app's user
+    /// and from the user a process was started for; others count as user 0's
+    #[arg(long = "user", value_name = "N", help_heading = FILTERING)]
+    users: Vec<u32>,
+
+    /// Only show entries from this app's processes (repeatable), found with
+    /// `adb shell pidof` and followed across restarts
+    #[arg(long = "package", value_name = "PKG", conflicts_with_all = ["merge", "resume", "bugreport"], help_heading = FILTERING)]
+    packages: Vec<String>,
+
+    /// Only show entries whose message matches RE (repeatable, any may match)
+    #[arg(long, value_name = "RE", value_parser = Regex::new, help_heading = FILTERING)]
+    grep: Vec<Regex>,
+
+    /// Hide entries whose message matches RE (repeatable)
+    #[arg(long, value_name = "RE", value_parser = Regex::new, help_heading = FILTERING)]
+    exclude: Vec<Regex>,
+
+    /// Show N entries after each entry --grep and --exclude let through,
+    /// dimmed, of those they hid
+    #[arg(short = 'A', long, value_name = "N", help_heading = FILTERING)]
+    after_context: Option<usize>,
+
+    /// Show N entries before each entry --grep and --exclude let through
+    #[arg(short = 'B', long, value_name = "N", help_heading = FILTERING)]
+    before_context: Option<usize>,
+
+    /// Show N entries before and after each entry --grep and --exclude let
+    /// through, with -- between groups as grep does
+    #[arg(short = 'C', long, value_name = "N", help_heading = FILTERING)]
+    context: Option<usize>,
+
+    /// Only show entries matching EXPR over level, tag, msg, pid and tid, e.g.
+    /// 'level>=W && tag~"OkHttp" && !msg~"cache"'; regexes may also be written
+    /// /like this/i
+    #[arg(long, value_name = "EXPR", value_parser = expr::parse, help_heading = FILTERING)]
+    filter: Option<expr::Expr>,
+
+    /// Hide entries stamped before WHEN: "06-01 12:00:00" (the latest such
+    /// time), "2024-06-01 12:00:00" or a duration back from now, like 5m, on
+    /// the device's clock in exec mode and the host's otherwise
+    #[arg(long, value_name = "WHEN", value_parser = timestamp::parse_bound, help_heading = FILTERING)]
+    since: Option<Bound>,
+
+    /// Hide entries stamped after WHEN, given like --since
+    #[arg(long, value_name = "WHEN", value_parser = timestamp::parse_bound, help_heading = FILTERING)]
+    until: Option<Bound>,
+
+    /// Only print classified events (crashes, ANRs, app starts/deaths, jank, ...)
+    #[arg(long, help_heading = FILTERING)]
+    events_only: bool,
+
+    /// Every few seconds, note how many lines the filters, --ignore and
+    /// --events-only hid, and why
+    #[arg(long, help_heading = FILTERING)]
+    show_dropped: bool,
+
+    /// Keep tags as they come instead of trimming them and collapsing their
+    /// inner whitespace
+    #[arg(long, conflicts_with = "fold_tag_case", help_heading = FILTERING)]
+    raw_tags: bool,
+
+    /// Lowercase tags (and --tag, --pin values) so casing differences
+    /// between OEMs don't defeat filters
+    #[arg(long, help_heading = FILTERING)]
+    fold_tag_case: bool,
+
+    /// Highlight pattern in the output, value as REGEXP (e.g. -s '\bWORD\b'),
+    /// repeatable; `REGEXP=STYLE` gives its matches a background color
+    /// (yellow, color208, #ff8700) or SGR codes (1;43;30), else patterns
+    /// take red, yellow, green, cyan, purple and blue in turn
+    #[arg(short, long, value_name = "REGEXP[=STYLE]", help_heading = DISPLAY)]
+    spotlight: Vec<String>,
+
+    /// Read spotlight patterns from FILE (repeatable): one REGEXP[=STYLE]
+    /// per line as -s takes it, blank lines and # comments skipped
+    #[arg(long, value_name = "FILE", help_heading = DISPLAY)]
+    spotlight_file: Vec<PathBuf>,
+
+    /// Match the --spotlight patterns (and those added over --control)
+    /// regardless of case
+    #[arg(long, help_heading = DISPLAY)]
+    spotlight_ignore_case: bool,
+
+    /// Take the --spotlight patterns (and those added over --control) as
+    /// literal text rather than regexes
+    #[arg(long, help_heading = DISPLAY)]
+    spotlight_fixed: bool,
+
+    /// Highlight the whole line of an entry a spotlight pattern matches, in the
+    /// pattern's colors, rather than the match alone; badges keep theirs
+    #[arg(long, help_heading = DISPLAY)]
+    spotlight_line: bool,
+
+    /// Pick out URLs, IP:port pairs, hex addresses, file paths and durations in
+    /// messages in their own subtle styles (the auto_* theme slots; spotlights
+    /// win)
+    #[arg(long, help_heading = DISPLAY)]
+    auto_highlight: bool,
+
+    /// Join JSON that an app pretty-printed over several entries (same tag,
+    /// pid and tid in quick succession) back into one entry
+    #[arg(long, help_heading = DISPLAY)]
+    join_multiline: bool,
+
+    /// Remove emoji from tags and messages
+    #[arg(long, conflicts_with = "emoji_shortcodes", help_heading = DISPLAY)]
+    strip_emoji: bool,
+
+    /// Replace emoji in tags and messages with :shortcode: names
+    #[arg(long, help_heading = DISPLAY)]
+    emoji_shortcodes: bool,
+
+    /// Write only ASCII: separators and gutters as - and |, accented letters as
+    /// plain ones and other characters as ?; the default on Windows consoles
+    /// whose code page isn't UTF-8 (chcp 65001)
+    #[arg(long, help_heading = DISPLAY)]
+    ascii: bool,
+
+    /// Show `-v epoch` timestamps as UTC dates and times
+    #[arg(long, help_heading = DISPLAY)]
+    epoch_utc: bool,
+
+    /// Show the date only when it changes and dim the milliseconds
+    #[arg(long, help_heading = DISPLAY)]
+    sparse_time: bool,
+
+    /// Follow byte counts, durations and epoch times in messages with humanized
+    /// forms, e.g. "134217728 (128 MiB)"; [[humanize]] tables of --config add
+    /// rules
+    #[arg(long, help_heading = DISPLAY)]
+    humanize: bool,
+
+    /// Indent entries from worker threads (tid != pid) behind a gutter mark
+    /// in the thread's color, leaving main-thread entries flush left
+    #[arg(long, help_heading = DISPLAY)]
+    thread_indent: bool,
+
+    /// Pad or truncate tags to N terminal columns so messages line up
+    #[arg(long, value_name = "N", help_heading = DISPLAY)]
+    tag_width: Option<usize>,
+
+    /// Diff each message of TAG (repeatable, given like --tag) against the
+    /// tag's previous one as key=value or key: value pairs, dimming all but the
+    /// values that changed
+    #[arg(long = "diff-tag", value_name = "TAG", help_heading = DISPLAY)]
+    diff_tags: Vec<String>,
+
+    /// Keep the latest entry of TAG in a pane at the top of the terminal (repeatable)
+    #[arg(long, value_name = "TAG", help_heading = DISPLAY)]
+    pin: Vec<String>,
+
+    /// Do not collapse banner-framed prologues (e.g. crash dump headers)
+    #[arg(long, help_heading = DISPLAY)]
+    expand: bool,
+
+    /// Mark entries older than the previous entry of the same pid/tid
+    #[arg(long, help_heading = DISPLAY)]
+    check_order: bool,
+
+    /// Buffer entries for MS milliseconds and emit them sorted by timestamp,
+    /// letting them out once the stream goes quiet
+    #[arg(long, value_name = "MS", help_heading = DISPLAY)]
+    reorder_window: Option<u64>,
+
+    /// Follow process starts and deaths and annotate when a pid gets reused
+    #[arg(long, help_heading = DISPLAY)]
+    track_pids: bool,
+
+    /// Like --track-pids, and also use a different color for each pid reuse
+    #[arg(long, help_heading = DISPLAY)]
+    vary_pid_color: bool,
+
+    /// Give each tag its own color, the same on every run, picked from a
+    /// palette without the levels' colors
+    #[arg(long, help_heading = DISPLAY)]
+    color_tags: bool,
+
+    /// Tint the pid column by whether the process is an app (uid >= 10000), the
+    /// system server or a zygote, or a native daemon, told from -v uid,
+    /// ActivityManager's process starts, platform tags and, in exec mode, adb
+    /// shell ps
+    #[arg(long, help_heading = DISPLAY)]
+    pid_groups: bool,
+
+    /// Rewrite messages with `PATTERN => REPLACEMENT` rules read from FILE
+    #[arg(long, value_name = "FILE", help_heading = DISPLAY)]
+    translations: Option<PathBuf>,
+
+    /// Output for a CI log viewer: terminal (default), ci-github or ci-gitlab,
+    /// folding the log into sections at app starts, crashes, ANRs and deaths;
+    /// on GitHub, warnings and errors also become ::warning::/::error::
+    /// annotations
+    #[arg(long, value_name = "KIND", value_parser = ci::parse_output, help_heading = DISPLAY)]
+    output: Option<ci::Output>,
+
+    /// Write banners, notices, summaries and verdicts to stderr so stdout
+    /// carries only log lines
+    #[arg(long, help_heading = DISPLAY)]
+    route: bool,
+
+    /// Don't suggest a better `adb logcat -v` format for the input
+    #[arg(long, help_heading = DISPLAY)]
+    quiet_hints: bool,
+
+    /// Under each line no format matched, show which format came closest and
+    /// where it stopped matching, for reporting unsupported formats
+    #[arg(long, conflicts_with_all = ["ignore", "no_parse", "binary"], help_heading = DISPLAY)]
+    debug_parse: bool,
+
+    /// Ring the bell and request terminal attention for entries at or above LEVEL
+    #[arg(long, value_name = "LEVEL", value_parser = filter::parse_level, help_heading = REPORTS)]
+    alert_level: Option<String>,
+
+    /// At the end of the stream, or on Ctrl-C, print the last N error and fatal
+    /// entries again, before the --stats summary
+    #[arg(long, value_name = "N", help_heading = REPORTS)]
+    recap: Option<usize>,
+
+    /// Print a summary of levels and detected events at the end of the stream
+    #[arg(long, help_heading = REPORTS)]
+    stats: bool,
+
+    /// At the end of the stream, print a key=value verdict line and exit with 1
+    /// if the counts match EXPR, e.g. 'E>=5 || F>=1 || CRASH>0'
+    #[arg(long, value_name = "EXPR", help_heading = REPORTS)]
+    fail_on: Option<String>,
+
+    /// Check the stream against assertions read from FILE, such as `expect
+    /// 'Activity started' within 5s after 'Clicked'` or `never 'FATAL
+    /// EXCEPTION'`, and exit with 1 if any fail
+    #[arg(long, value_name = "FILE", help_heading = REPORTS)]
+    expect: Option<PathBuf>,
+
+    /// At the end, report which tags the logged time went to, attributing the
+    /// gap between consecutive entries to the earlier entry's tag
+    #[arg(long, help_heading = REPORTS)]
+    time_profile: bool,
+
+    /// Keep per-tag error counts across runs in a local file and report tags
+    /// whose error rate spiked (default: ~/.local/share/logcat-colorize/tag-stats.tsv)
+    #[arg(long, value_name = "FILE", help_heading = REPORTS)]
+    stats_db: Option<Option<PathBuf>>,
+
+    /// Also write the entries that get through the filters to FILE in Apache
+    /// Parquet format (timestamp, level, tag, pid, tid, message), for pandas,
+    /// polars or duckdb
+    #[arg(long, value_name = "FILE", conflicts_with = "resume", help_heading = REPORTS)]
+    export_parquet: Option<PathBuf>,
 
-    /// List available ansi escape codes to format the output
-    #[arg(long)]
+    /// Post each new crash group (fingerprint, reason, stack, and the device's
+    /// model and build in exec mode) as JSON to URL, using curl; repeats of a
+    /// group are not sent
+    #[arg(long, value_name = "URL", value_parser = crash::parse_url, help_heading = REPORTS)]
+    crash_webhook: Option<String>,
+
+    /// Accept commands adjusting spotlight and filters on a unix socket at
+    /// PATH: `spotlight add RE[=STYLE]`, `spotlight clear`, `level LEVEL`,
+    /// `level all`, `mute tag NAME`, `unmute tag NAME`, `filter EXPR`, `filter
+    /// clear` and `mark [NOTE]` (with --findings)
+    #[arg(long, value_name = "PATH", help_heading = REPORTS)]
+    control: Option<PathBuf>,
+
+    /// Append the entries marked with the `mark [NOTE]` control command to
+    /// FILE, in threadtime form under a comment with the time and note, and
+    /// list them again at the end
+    #[arg(long, value_name = "FILE", requires = "control", help_heading = REPORTS)]
+    findings: Option<PathBuf>,
+
+    /// Keep error counts and whether lines still come in the `@logcat` tmux
+    /// option, or in FILE, for a status bar ("3 err 12 warn 1 crash · live"),
+    /// refreshed every second: set status-right '#{@logcat}'
+    #[arg(long, value_name = "FILE", num_args = 0..=1, help_heading = REPORTS)]
+    tmux_status: Option<Option<PathBuf>>,
+
+    /// Read settings from a TOML FILE (default:
+    /// ~/.config/logcat-colorize/config.toml): default options before any table
+    /// (pin, ignore, humanize, tag, exclude_tag, level, grep, exclude,
+    /// spotlight, filter, and theme naming a [theme.NAME] table), added to or
+    /// overridden by the command line; [[profile]] tables; [[classifier]]
+    /// tables defining custom events (name, regex, style, fields, notify);
+    /// [[spotlight]] tables highlighting a regex in some tags' messages (tag,
+    /// regex, style); [[humanize]] tables annotating numbers (regex, unit:
+    /// bytes, ns, us, ms, epoch_ms or epoch_s, tag); [[route]] tables sending
+    /// events or levels to sinks (event, level, sink: notification, bell,
+    /// webhook, file or command, url, path, command, limit such as 3/min); an
+    /// [exec] table setting exec mode up (clear_buffer, buffers, buffer_size,
+    /// format, filterspec); and a [theme] table of styles (msg_error =
+    /// "1;49;91")
+    #[arg(long, value_name = "FILE", help_heading = CONFIGURATION)]
+    config: Option<PathBuf>,
+
+    /// Don't read the default config and theme files
+    #[arg(long, conflicts_with = "config", help_heading = CONFIGURATION)]
+    no_config: bool,
+
+    /// Apply the `[[profile]]` table of --config named NAME: its tag,
+    /// exclude_tag, grep, exclude and spotlight patterns add to the command
+    /// line's, and its level, filter, theme and keys apply unless given there
+    #[arg(
+        long = "profile",
+        visible_alias = "filter-profile",
+        value_name = "NAME",
+        help_heading = CONFIGURATION
+    )]
+    filter_profile: Option<String>,
+
+    /// Load a built-in config fragment of spotlights and classifiers for a
+    /// library (repeatable): okhttp, firebase or exoplayer; the files are in
+    /// packs/ to copy
+    #[arg(long = "pack", value_name = "NAME", value_parser = pack::parse, help_heading = CONFIGURATION)]
+    packs: Vec<String>,
+
+    /// Start from a built-in theme, under the theme file and the config's
+    /// [theme] tables: default, light, solarized, dracula, nord (24-bit colors,
+    /// downgraded on other terminals), high-contrast or mono (attributes only);
+    /// the files are in themes/ to copy
+    #[arg(long, value_name = "NAME", value_parser = theme::parse_preset, help_heading = CONFIGURATION)]
+    theme: Option<String>,
+
+    /// The terminal's background, light or dark, choosing the default theme's
+    /// variant; told from COLORFGBG or by asking the terminal (OSC 11) when not
+    /// given
+    #[arg(long, value_name = "SHADE", value_parser = background::parse, conflicts_with = "theme", help_heading = CONFIGURATION)]
+    background: Option<Background>,
+
+    /// Read styles from a TOML theme FILE (default:
+    /// ~/.config/logcat-colorize/theme.toml), under the config's [theme]
+    /// tables: slot = "1;49;91" at the top, or [slot] tables of attr (bold,
+    /// faint, ...), bg and fg (red, bright_red, default, ..., color0 to
+    /// color255, or #rrggbb); colors are downgraded to what COLORTERM and TERM
+    /// say the terminal shows
+    #[arg(long, value_name = "FILE", help_heading = CONFIGURATION)]
+    theme_file: Option<PathBuf>,
+
+    /// Print the theme in effect, after --theme, the theme file and the
+    /// config's [theme] tables, in the theme file format
+    #[arg(long, help_heading = CONFIGURATION)]
+    dump_theme: bool,
+
+    /// List the built-in themes with sample lines
+    #[arg(long, help_heading = CONFIGURATION)]
+    list_themes: bool,
+
+    /// List available ansi escape codes to format the output, and the
+    /// 256-color palette
+    #[arg(long, help_heading = CONFIGURATION)]
     list_ansi: bool,
-}
 
-static HELP_TEXT: LazyLock<String> = LazyLock::new(|| -> String {
-    format!(
-        "{name} v{ver}
+    /// Render FILE for a snapshot test: 24-bit color whatever the terminal,
+    /// 80 columns, no config but --config, and escape sequences written out
+    /// as text (\e[1;31m)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["color", "exec", "merge", "bugreport", "device_file", "native_messaging"], help_heading = CONFIGURATION)]
+    render_fixture: Option<PathBuf>,
 
-A simple tool to colorize Android adb logcat output.
-Pipe adb into this program. Supports Tag, Process, Brief, Time, and ThreadTime.
+    /// Time the regex parser against the byte scanner over FILE, or over
+    /// generated lines, and check they parse alike
+    #[arg(long, value_name = "FILE", num_args = 0..=1, help_heading = CONFIGURATION)]
+    bench: Option<Option<PathBuf>>,
 
-Usage:
-  adb logcat [options] | {name} [options]
+    #[command(subcommand)]
+    command: Option<Cmd>,
+}
 
-Options:
-  -i, --ignore        do not output non-matching lines
-  -h, --help          show help
-  -s, --spotlight RE  highlight regex pattern in output
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Edit the theme interactively: cycle each style's colors and attribute
+    /// over sample lines and save them to the [theme] table of --config (or
+    /// the default config file), with keys from its [keys] table (preset =
+    /// "vi" or "emacs", or one key list per action)
+    ThemeEdit,
+}
 
-Examples:
-  adb logcat | {name}
-  adb -s emulator-5556 logcat -v time System.err:V *:S | {name}
-  adb logcat -v time | egrep -i '(sensor|wifi)' | {name}
+impl Args {
+    fn theme_edit(&self) -> bool {
+        matches!(self.command, Some(Cmd::ThemeEdit))
+    }
 
-Authors: Bruno Braga, Luca Carlon
-Adapted to Rust: Chmouel Boudjnah
-Bugs: https://github.com/chmouel/logcat-colorize-rs/issues",
-        name = env!("CARGO_PKG_NAME"),
-        ver = env!("CARGO_PKG_VERSION")
-    )
-});
+    // What clap can't tell: options that make no sense with the subcommand
+    fn check_command(&self) {
+        let conflicts: [(bool, &str); 3] = [
+            (self.no_config, "--no-config"),
+            (self.dump_theme, "--dump-theme"),
+            (self.render_fixture.is_some(), "--render-fixture"),
+        ];
+        if let Some((_, flag)) = conflicts.iter().find(|(set, _)| *set && self.theme_edit()) {
+            Args::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    format!("the subcommand 'theme-edit' cannot be used with '{}'", flag),
+                )
+                .exit();
+        }
+    }
+}
+
+// Where --help groups the options
+const INPUT: &str = "Input";
+const FILTERING: &str = "Filtering";
+const DISPLAY: &str = "Display";
+const REPORTS: &str = "Alerts and reports";
+const CONFIGURATION: &str = "Configuration and themes";
 
-fn main() -> io::Result<()> {
+// clap's help, printed for --help and when stdin is a terminal
+fn help_text() -> String {
+    Args::command().render_long_help().to_string()
+}
+
+fn main() -> io::Result<ExitCode> {
     let args: Args = Args::parse();
+    args.check_command();
+    match run(args) {
+        // The reader went away (e.g. `| head`); every written line was already complete.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(ExitCode::SUCCESS),
+        Ok(Verdict::Fail) => Ok(ExitCode::FAILURE),
+        res => res.map(|_| ExitCode::SUCCESS),
+    }
+}
+
+fn run(mut args: Args) -> io::Result<Verdict> {
+    // Before any style is made
+    if args.render_fixture.is_some() {
+        ansi::set_depth(ansi::Depth::TrueColor);
+        width::set_terminal_columns(fixture::COLUMNS);
+        args.no_config |= args.config.is_none();
+    } else if !use_color(&args.color) {
+        ansi::set_plain();
+    }
+    let mut out: Renderer = match args.render_fixture {
+        Some(_) => Renderer::new(Box::new(fixture::Escaped(io::stdout().lock()))),
+        None => Renderer::stdout(),
+    };
+    if args.ascii || args.render_fixture.is_none() && codepage::legacy_console() {
+        out.set_ascii();
+    }
 
     if args.help {
-        println!("{}", *HELP_TEXT);
-        return Ok(());
+        return out.text(&help_text()).map(|_| Verdict::Pass);
     }
     if args.list_ansi {
-        ansi::list_ansi();
-        return Ok(());
+        return ansi::list_ansi(&mut out).map(|_| Verdict::Pass);
+    }
+    if args.list_themes {
+        return theme::list_themes(&mut out).map(|_| Verdict::Pass);
+    }
+    if let Some(file) = &args.bench {
+        return bench::run(&mut out, file.as_deref()).map(|_| Verdict::Pass);
+    }
+
+    if !args.exec
+        && !args.native_messaging
+        && args.merge.is_empty()
+        && args.bugreport.is_none()
+        && args.device_file.is_none()
+        && !args.theme_edit()
+        && args.render_fixture.is_none()
+        && io::stdin().is_terminal()
+    {
+        return out.text(&help_text()).map(|_| Verdict::Pass);
     }
 
-    if io::stdin().is_terminal() {
-        println!("{}", *HELP_TEXT);
-        return Ok(());
+    if args.route {
+        out.route_status(Box::new(io::stderr()));
     }
 
-    let theme: Theme = make_theme();
-    let spotlight_re: Option<Regex> = args
+    // Without --config, the default file is read when there is one
+    if args.config.is_none() && !args.no_config {
+        args.config = config::default_path("config.toml")
+            .filter(|p: &PathBuf| args.theme_edit() || p.is_file());
+    }
+    let config: Option<Config> = match args.config.as_deref() {
+        // theme-edit creates the file when saving
+        Some(path) if args.theme_edit() && !path.exists() => None,
+        path => path.map(Config::load).transpose()?,
+    };
+    // Packs first, so the config's own tables come after theirs
+    let mut configs: Vec<Config> = args
+        .packs
+        .iter()
+        .map(|p: &String| pack::load(p))
+        .collect::<io::Result<Vec<Config>>>()?;
+    let own: bool = config.is_some();
+    configs.extend(config);
+    let root_keys: Vec<&str> = ["pin", "ignore", "humanize"]
+        .into_iter()
+        .chain(filterprofile::KEYS)
+        .collect();
+    let mut pins: Vec<String> = args.pin.clone();
+    for (i, config) in configs.iter().enumerate() {
+        // Only the user's own config sets default options
+        match own && i + 1 == configs.len() {
+            true => config.root().check_keys(config, &root_keys)?,
+            false => config.root().check_keys(config, &["pin"])?,
+        }
+        pins.extend(config.root().strings(config, "pin")?.unwrap_or_default());
+    }
+    classify::register(&configs)?;
+    let routes: Vec<alert::Route> = alert::load(&configs)?;
+    let config: Option<&Config> = configs.last().filter(|_| own);
+    // The profile fills in options before the config's defaults do
+    let profile: Option<FilterProfile> = args
+        .filter_profile
+        .as_deref()
+        .map(|name: &str| filterprofile::load(&configs, name))
+        .transpose()?;
+    if let Some(profile) = &profile {
+        use_profile(&mut args, profile);
+    }
+    let defaults: Option<FilterProfile> = config
+        .map(|c: &Config| filterprofile::defaults(&configs, c))
+        .transpose()?;
+    if let Some(defaults) = &defaults {
+        use_profile(&mut args, defaults);
+    }
+    if let Some(config) = config {
+        args.ignore |= config.root().bool(config, "ignore")?.unwrap_or(false);
+        args.humanize |= config.root().bool(config, "humanize")?.unwrap_or(false);
+    }
+    let spotlight_mode: spotlight::Mode = spotlight::Mode {
+        ignore_case: args.spotlight_ignore_case,
+        fixed: args.spotlight_fixed,
+    };
+    // The modes are for typed patterns; profiles and configs write regexes
+    let spot_rules: Vec<spotlight::Rule> = args
         .spotlight
-        .as_ref()
-        .and_then(|s: &String| Regex::new(&format!("({})", s)).ok());
+        .iter()
+        .map(|s: &String| spotlight::parse_rule(s, spotlight_mode))
+        .collect::<Result<Vec<spotlight::Rule>, String>>()
+        .map_err(|e: String| io::Error::other(format!("--spotlight: {}", e)))?
+        .into_iter()
+        .chain(
+            args.spotlight_file
+                .iter()
+                .map(|path: &PathBuf| spotlight::load_file(path, spotlight_mode))
+                .collect::<io::Result<Vec<Vec<spotlight::Rule>>>>()?
+                .into_iter()
+                .flatten(),
+        )
+        .chain(
+            profile
+                .iter()
+                .chain(&defaults)
+                .flat_map(|p: &FilterProfile| p.spotlight.iter().cloned()),
+        )
+        .collect();
+    let scoped_spotlights: Vec<spotlight::Scoped> = spotlight::load(&configs, args.fold_tag_case)?;
+    let humanizer: Option<humanize::Humanizer> = args
+        .humanize
+        .then(|| humanize::load(&configs, args.fold_tag_case))
+        .transpose()?;
+    // The pane needs a terminal to stay on top of
+    if !io::stdout().is_terminal() {
+        pins.clear();
+    }
+    if args.fold_tag_case {
+        for pin in pins.iter_mut() {
+            *pin = pin.to_lowercase();
+        }
+    }
+    let patterns = |values: &[String], flag: &str| -> io::Result<Vec<TagPattern>> {
+        values
+            .iter()
+            .map(|v: &String| TagPattern::parse(v, args.fold_tag_case))
+            .collect::<Result<Vec<TagPattern>, String>>()
+            .map_err(|e: String| io::Error::other(format!("{}: {}", flag, e)))
+    };
+    let tags: Vec<TagPattern> = patterns(&args.tags, "--tag")?;
+    let excluded_tags: Vec<TagPattern> = patterns(&args.excluded_tags, "--exclude-tag")?;
+    let diff_tags: Vec<TagPattern> = patterns(&args.diff_tags, "--diff-tag")?;
+    if !pins.is_empty() || args.recap.is_some() {
+        interrupt::defer_sigint();
+    }
+    let fail_on: Option<FailOn> = args
+        .fail_on
+        .as_deref()
+        .map(verdict::parse)
+        .transpose()
+        .map_err(|e: String| io::Error::other(format!("--fail-on: {}", e)))?;
 
-    logcat::format_with(&theme, spotlight_re, args.ignore)
+    // Only a terminal we color for is worth asking its background
+    let background: Option<Background> = args.background.or_else(|| {
+        (args.theme.is_none()
+            && args.render_fixture.is_none()
+            && ansi::depth() != ansi::Depth::Plain
+            && io::stdout().is_terminal())
+        .then(background::detect)
+        .flatten()
+    });
+    let mut theme: Theme = match (&args.theme, background) {
+        (Some(name), _) => theme::preset(name)?,
+        (None, Some(Background::Light)) => theme::preset("light")?,
+        (None, _) => make_theme(),
+    };
+    let theme_file: Option<PathBuf> = match &args.theme_file {
+        Some(path) => Some(path.clone()),
+        None if args.no_config => None,
+        None => config::default_path("theme.toml").filter(|p: &PathBuf| p.is_file()),
+    };
+    if let Some(path) = &theme_file {
+        theme.apply_file(&Config::load(path)?)?;
+    }
+    if let Some(config) = &config {
+        theme.apply(config)?;
+    }
+    // A profile's theme over the default one
+    let profiles: [Option<FilterProfile>; 2] = [defaults, profile];
+    for (config, section) in profiles
+        .iter()
+        .flatten()
+        .filter_map(|p: &FilterProfile| p.theme)
+    {
+        theme.apply_table(config, section)?;
+    }
+    if args.dump_theme {
+        for line in theme.to_file().lines() {
+            out.text(line)?;
+        }
+        return Ok(Verdict::Pass);
+    }
+    if args.theme_edit() {
+        let path: &PathBuf = args.config.as_ref().ok_or_else(|| {
+            io::Error::other("theme-edit: no HOME or XDG_CONFIG_HOME to keep the config in")
+        })?;
+        // The [keys] table, then a profile's keys over the default ones
+        let mut keys: Keymap = Keymap::default();
+        if let Some(config) = &config {
+            keys.apply(config)?;
+        }
+        for (config, section) in profiles
+            .iter()
+            .flatten()
+            .filter_map(|p: &FilterProfile| p.keys)
+        {
+            keys.apply_table(config, section)?;
+        }
+        return themeedit::run(&mut out, theme, path, &keys).map(|_| Verdict::Pass);
+    }
+    let (since, until) =
+        timestamp::resolve_bounds(args.since.as_ref(), args.until.as_ref(), || {
+            clock_now(args.exec || args.device_file.is_some())
+        })?;
+    let filters: Filters = Filters {
+        tags,
+        excluded_tags,
+        pids: args.pids.clone(),
+        tids: args.tids.clone(),
+        users: args.users.clone(),
+        min_level: args.level.clone(),
+        expr: args.filter.clone(),
+        packages: match args.packages.is_empty() {
+            true => None,
+            false => Some(PackagePids::resolve(args.packages.clone())?),
+        },
+        grep: args.grep.clone(),
+        excluded: args.exclude.clone(),
+        since,
+        until,
+        ..Default::default()
+    };
+    let setup: adb::Setup = match &config {
+        Some(config) => adb::Setup::load(config)?,
+        None => adb::Setup::default(),
+    };
+    let mut logcat_args: Vec<String> = args.adb_args.clone();
+    if args.exec {
+        setup.extend_args(&mut logcat_args);
+    }
+    let binary: bool = args.binary || (args.exec && adb::is_binary(&logcat_args));
+    if binary && args.tee.is_some() {
+        return Err(io::Error::other(
+            "--tee: the capture is written as text lines, not in the -B format",
+        ));
+    }
+    if !adb::has_format(&logcat_args) && !binary {
+        logcat_args.splice(0..0, ["-v".to_string(), "threadtime".to_string()]);
+    }
+    if !filter::has_filterspec(&logcat_args) {
+        // The device matches tags as logged, the host once padding is trimmed
+        // (and case folded): a padded tag would be silenced before it is seen.
+        // Exclusions only silence what the host hides too, unless folded.
+        let device: Filters = Filters {
+            tags: match args.raw_tags {
+                true => filters.tags.clone(),
+                false => Vec::new(),
+            },
+            excluded_tags: match args.fold_tag_case {
+                true => Vec::new(),
+                false => filters.excluded_tags.clone(),
+            },
+            ..filters.clone()
+        };
+        logcat_args.extend(device.device_filterspecs());
+    }
+    // adb skips the older entries itself
+    if args.exec
+        && let Some(since) = filters.since
+        && !logcat_args
+            .iter()
+            .any(|a: &String| a.starts_with("-t") || a.starts_with("-T"))
+    {
+        logcat_args.splice(0..0, ["-T".to_string(), timestamp::format_millis(since)]);
+    }
+    // adb logcat takes a single --pid
+    if let [pid] = args.pids[..]
+        && !logcat_args.iter().any(|a: &String| a.starts_with("--pid"))
+    {
+        logcat_args.insert(0, format!("--pid={}", pid));
+    }
+
+    if args.native_messaging {
+        return native::serve(&logcat_args, filters).map(|_| Verdict::Pass);
+    }
+
+    let opts: logcat::Options = logcat::Options {
+        spotlight: spot_rules,
+        spotlight_mode,
+        spotlight_line: args.spotlight_line,
+        auto_highlight: args.auto_highlight,
+        join_multiline: args.join_multiline,
+        context: (
+            args.before_context.or(args.context).unwrap_or(0),
+            args.after_context.or(args.context).unwrap_or(0),
+        ),
+        scoped_spotlights,
+        ignore: args.ignore,
+        stats: args.stats,
+        expand: args.expand,
+        check_order: args.check_order,
+        reorder_window: args.reorder_window,
+        track_pids: args.track_pids,
+        vary_pid_color: args.vary_pid_color,
+        color_tags: args.color_tags,
+        translations: args
+            .translations
+            .as_deref()
+            .map(Translations::load)
+            .transpose()?,
+        filters,
+        host_log: args.host_log.clone(),
+        tag_width: args.tag_width,
+        alert_level: args.alert_level.clone(),
+        routes,
+        emoji: match (args.strip_emoji, args.emoji_shortcodes) {
+            (true, _) => Some(EmojiMode::Strip),
+            (_, true) => Some(EmojiMode::Shortcode),
+            _ => None,
+        },
+        events_only: args.events_only,
+        show_dropped: args.show_dropped,
+        control: args.control.clone(),
+        screenshot_on: args.screenshot_on.clone(),
+        debug_parse: args.debug_parse,
+        // Images only go to a terminal, on the stream status output takes
+        screenshot_preview: match args.screenshot_preview
+            && (args.route && io::stderr().is_terminal()
+                || !args.route && io::stdout().is_terminal())
+        {
+            true => screenshot::image_protocol(),
+            false => None,
+        },
+        fail_on,
+        sparse_time: args.sparse_time,
+        epoch_utc: args.epoch_utc,
+        profile: args.time_profile,
+        pins,
+        no_parse: args.no_parse,
+        thread_indent: args.thread_indent,
+        crash_webhook: args.crash_webhook.clone(),
+        raw_tags: args.raw_tags,
+        fold_tag_case: args.fold_tag_case,
+        detect_per_line: args.detect == "line",
+        export_parquet: args.export_parquet.clone(),
+        tee: args.tee.clone(),
+        rotate: args.rotate.clone(),
+        exec: args.exec || args.device_file.is_some(),
+        device_file: args.device_file.clone(),
+        findings: args.findings.clone(),
+        tmux_status: args.tmux_status.clone(),
+        diff_tags,
+        pid_groups: args.pid_groups,
+        adb_poll_interval: args.adb_poll_interval,
+        after_command: args.after_command.clone(),
+        clear_after_command: args.clear_after_command,
+        humanizer,
+        recap: args.recap.filter(|n: &usize| *n > 0),
+        output: match args.output {
+            Some(ci::Output::Ci(system)) => Some(system),
+            _ => None,
+        },
+        expect: args.expect.as_deref().map(Script::load).transpose()?,
+        captures: captures(&args.merge, &args.offset)?,
+        format_hint: match (args.quiet_hints, args.exec) {
+            (true, _) => None,
+            (false, _) if args.device_file.is_some() => Some(format!(
+                "adb logcat -v threadtime -f {}",
+                args.device_file.as_deref().unwrap_or_default()
+            )),
+            (false, true) => Some(format!(
+                "{} --exec -- -v threadtime",
+                env!("CARGO_PKG_NAME")
+            )),
+            (false, false) => Some(format!(
+                "adb logcat -v threadtime | {}",
+                env!("CARGO_PKG_NAME")
+            )),
+        },
+        stats_db: match &args.stats_db {
+            Some(Some(path)) => Some(path.clone()),
+            Some(None) => Some(history::default_path().ok_or_else(|| {
+                io::Error::other("--stats-db: no HOME or XDG_DATA_HOME to store it in")
+            })?),
+            None => None,
+        },
+    };
+
+    if !opts.captures.is_empty() {
+        return logcat::format_merged(&mut out, &theme, &opts);
+    }
+
+    if let Some(path) = &args.render_fixture {
+        let file: File = File::open(path)
+            .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?;
+        return logcat::format_with(BufReader::new(file), &mut out, &theme, &opts, None);
+    }
+
+    if args.exec {
+        if let Some(size) = args.setup_buffers.as_ref().or(setup.buffer_size.as_ref()) {
+            adb::setup_buffers(size)?;
+        }
+        if setup.clear_buffer {
+            adb::clear_buffers(&setup.buffers)?;
+        }
+        print_exec_banner(&mut out, &theme)?;
+
+        let mut child: Child = adb::spawn_logcat(&logcat_args)?;
+        let stdout: ChildStdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("adb logcat has no stdout"))?;
+        let res: io::Result<Verdict> = match binary {
+            true => logcat::format_binary(BufReader::new(stdout), &mut out, &theme, &opts),
+            false => logcat::format_with(BufReader::new(stdout), &mut out, &theme, &opts, None),
+        };
+        let _ = child.kill();
+        let _ = child.wait();
+        return res;
+    }
+
+    if let Some(path) = &args.device_file {
+        let mut child: Child = adb::spawn_tail(path)?;
+        let stdout: ChildStdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| io::Error::other("adb shell tail has no stdout"))?;
+        let res: io::Result<Verdict> =
+            logcat::format_with(BufReader::new(stdout), &mut out, &theme, &opts, None);
+        let _ = child.kill();
+        let _ = child.wait();
+        return res;
+    }
+    if binary {
+        return logcat::format_binary(BufReader::new(io::stdin()), &mut out, &theme, &opts);
+    }
+    if let Some(path) = &args.bugreport {
+        let (text, child) = bugreport::open(path)?;
+        let res: io::Result<Verdict> = logcat::format_with(
+            BufReader::new(bugreport::sections(BufReader::new(text))),
+            &mut out,
+            &theme,
+            &opts,
+            None,
+        );
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        return res;
+    }
+    if let Some(path) = &args.resume {
+        let (file, checkpoint) = checkpoint::resume(path)?;
+        if checkpoint.offset > 0 {
+            let text: String = format!("--- resuming at byte {}", checkpoint.offset);
+            out.status_line(&render::note(&theme.banner, &text))?;
+        }
+        return logcat::format_with(
+            BufReader::new(file),
+            &mut out,
+            &theme,
+            &opts,
+            Some(checkpoint),
+        );
+    }
+    logcat::format_with(BufReader::new(io::stdin()), &mut out, &theme, &opts, None)
+}
+
+// Whether to style the output for --color WHEN. In auto mode, NO_COLOR
+// (set and not empty) turns colors off and CLICOLOR_FORCE (set and not 0)
+// on, as does CLICOLOR=0 off; otherwise stdout decides
+fn use_color(when: &str) -> bool {
+    let var = |name: &str| -> Option<String> {
+        std::env::var(name).ok().filter(|v: &String| !v.is_empty())
+    };
+    match when {
+        "always" => true,
+        "never" => false,
+        _ if var("NO_COLOR").is_some() => false,
+        _ if var("CLICOLOR_FORCE").is_some_and(|v: String| v != "0") => true,
+        _ if var("CLICOLOR").as_deref() == Some("0") => false,
+        _ => io::stdout().is_terminal(),
+    }
+}
+
+// Adds a --profile's patterns to the command line's; its single
+// values only fill in for options not given
+fn use_profile(args: &mut Args, profile: &FilterProfile) {
+    args.tags.extend(profile.tags.iter().cloned());
+    args.excluded_tags
+        .extend(profile.excluded_tags.iter().cloned());
+    args.grep.extend(profile.grep.iter().cloned());
+    args.exclude.extend(profile.exclude.iter().cloned());
+    if args.level.is_none() {
+        args.level.clone_from(&profile.level);
+    }
+    if args.filter.is_none() {
+        args.filter.clone_from(&profile.filter);
+    }
+}
+
+// Labels each --merge file with its name and matches up its --offset
+fn captures(paths: &[PathBuf], offsets: &[(String, i64)]) -> io::Result<Vec<Capture>> {
+    let name = |p: &PathBuf| -> String {
+        p.file_name().map_or_else(
+            || p.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        )
+    };
+    let is = |p: &PathBuf, file: &str| -> bool { p.as_os_str() == file || name(p) == file };
+
+    if let Some((file, _)) = offsets
+        .iter()
+        .find(|(file, _): &&(String, i64)| !paths.iter().any(|p: &PathBuf| is(p, file)))
+    {
+        return Err(io::Error::other(format!(
+            "--offset {}: not one of the --merge files",
+            file
+        )));
+    }
+
+    Ok(paths
+        .iter()
+        .map(|p: &PathBuf| Capture {
+            path: p.clone(),
+            label: name(p),
+            offset: offsets
+                .iter()
+                .filter(|(file, _): &&(String, i64)| is(p, file))
+                .map(|(_, ms): &(String, i64)| *ms)
+                .sum(),
+        })
+        .collect())
+}
+
+// The time as the entries are stamped, in milliseconds since 1970: the
+// device's clock in exec mode, the host's otherwise
+fn clock_now(exec: bool) -> io::Result<u64> {
+    if !exec {
+        return timestamp::local_millis().ok_or_else(|| {
+            io::Error::other("relative --since/--until: the local time zone is unknown here")
+        });
+    }
+    let now: String = adb::date()?;
+    timestamp::parse_millis(&now)
+        .ok_or_else(|| io::Error::other(format!("unexpected clock time `{}`", now)))
+}
+
+fn print_exec_banner(out: &mut Renderer, theme: &Theme) -> io::Result<()> {
+    let sizes: String = match adb::buffer_sizes() {
+        Ok(sizes) if !sizes.is_empty() => sizes
+            .iter()
+            .map(|(name, bytes): &(String, u64)| format!("{} {}", name, adb::human_size(*bytes)))
+            .collect::<Vec<String>>()
+            .join(", "),
+        Ok(_) => "unknown".to_string(),
+        Err(e) => format!("unknown ({})", e),
+    };
+    let text: String = format!("--- adb logcat, buffers: {}", sizes);
+    out.status_line(&render::note(&theme.banner, &text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_lists_every_option() {
+        let cmd: clap::Command = Args::command();
+        cmd.clone().debug_assert();
+        let help: String = help_text();
+        for arg in cmd.get_arguments() {
+            assert!(arg.get_help().is_some(), "{} has no help", arg.get_id());
+            if let Some(long) = arg.get_long() {
+                assert!(help.contains(&format!("--{}", long)), "--{} missing", long);
+            }
+        }
+        assert!(help.contains("\nCommands:\n  theme-edit"));
+        assert!(help.ends_with("logcat-colorize-rs/issues\n"));
+    }
 }
@@ -0,0 +1,164 @@
+use crate::{
+    input,
+    logcat::{self, Logcat},
+    timestamp::{self, Dates},
+};
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+};
+
+/// One offline capture given to `--merge`.
+pub struct Capture {
+    pub path: PathBuf,
+    pub label: String,
+    pub offset: i64, // milliseconds added to its timestamps
+}
+
+pub enum Item {
+    Entry(Logcat),
+    Raw(String),
+}
+
+/// Parses an `--offset FILE=+2.5s` (or `FILE=-300ms`) argument.
+pub fn parse_offset(s: &str) -> Result<(String, i64), String> {
+    let (file, shift) = s
+        .rsplit_once('=')
+        .ok_or_else(|| "expected FILE=OFFSET, e.g. b.log=+2.5s".to_string())?;
+    let (sign, amount) = match shift.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, shift.strip_prefix('+').unwrap_or(shift)),
+    };
+    let ms: u64 = timestamp::parse_duration(amount)
+        .ok_or_else(|| format!("invalid offset `{}` (e.g. +2.5s, -300ms)", shift))?;
+    Ok((file.to_string(), sign * ms as i64))
+}
+
+/// Reads every capture and interleaves their lines by (offset-corrected)
+/// timestamp. Lines without one keep their place after the line before them.
+pub fn read_all(captures: &[Capture]) -> io::Result<Vec<Item>> {
+    let mut keyed: Vec<(u64, usize, usize, Item)> = Vec::new();
+
+    for (i, capture) in captures.iter().enumerate() {
+        let err = |e: io::Error| -> io::Error {
+            io::Error::other(format!("{}: {}", capture.path.display(), e))
+        };
+        let file: File = File::open(&capture.path).map_err(err)?;
+        // Each capture carries its own year over New Year
+        let dates: Dates = Dates::default();
+        let mut last: u64 = 0;
+
+        for (n, line) in input::lines(BufReader::new(file)).enumerate() {
+            let line: String = line.map_err(err)?;
+            let item: Item = match logcat::parse_line(&line) {
+                Some((_, mut lc)) => {
+                    if let Some(ts) = dates.millis(&lc.timestamp) {
+                        last = ts.saturating_add_signed(capture.offset);
+                        if capture.offset != 0 {
+                            lc.timestamp = timestamp::with_millis(&lc.timestamp, last);
+                        }
+                    }
+                    lc.origin = Some(i);
+                    Item::Entry(lc)
+                }
+                None => Item::Raw(line),
+            };
+            keyed.push((last, i, n, item));
+        }
+    }
+
+    keyed.sort_by_key(|(ts, i, n, _): &(u64, usize, usize, Item)| (*ts, *i, *n));
+    Ok(keyed
+        .into_iter()
+        .map(|(_, _, _, item): (u64, usize, usize, Item)| item)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn capture(name: &str, lines: &[&str], offset: i64) -> Capture {
+        let path: PathBuf = std::env::temp_dir().join(format!(
+            "logcat-colorize-merge-{}-{}",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, lines.join("\n")).unwrap();
+        Capture {
+            path,
+            label: name.to_string(),
+            offset,
+        }
+    }
+
+    fn merged(captures: &[Capture]) -> Vec<String> {
+        let items: Vec<Item> = read_all(captures).unwrap();
+        for capture in captures {
+            fs::remove_file(&capture.path).unwrap();
+        }
+        items
+            .into_iter()
+            .map(|item: Item| match item {
+                Item::Entry(lc) => format!("{} {}", lc.timestamp, lc.message),
+                Item::Raw(line) => line,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_across_new_year() {
+        let a: Capture = capture(
+            "a.log",
+            &[
+                "12-31 23:59:59.000  1234  1240 I A: a1",
+                "01-01 00:00:01.000  1234  1240 I A: a2",
+            ],
+            0,
+        );
+        let b: Capture = capture(
+            "b.log",
+            &[
+                "12-31 23:59:58.000  4321  4330 I B: b1",
+                "01-01 00:00:00.000  4321  4330 I B: b2",
+                "--------- beginning of crash",
+                "01-01 00:00:02.000  4321  4330 I B: b3",
+            ],
+            0,
+        );
+        assert_eq!(
+            merged(&[a, b]),
+            [
+                "12-31 23:59:58.000 b1",
+                "12-31 23:59:59.000 a1",
+                "01-01 00:00:00.000 b2",
+                "--------- beginning of crash",
+                "01-01 00:00:01.000 a2",
+                "01-01 00:00:02.000 b3",
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets_shift_over_new_year() {
+        let a: Capture = capture("c.log", &["12-31 23:59:59.500  1  1 I A: a1"], 1000);
+        let b: Capture = capture("d.log", &["01-01 00:00:00.000  2  2 I B: b1"], 0);
+        assert_eq!(
+            merged(&[a, b]),
+            ["01-01 00:00:00.000 b1", "01-01 00:00:00.500 a1"]
+        );
+    }
+
+    #[test]
+    fn parses_offsets() {
+        assert_eq!(parse_offset("b.log=+2.5s"), Ok(("b.log".to_string(), 2500)));
+        assert_eq!(
+            parse_offset("b.log=-300ms"),
+            Ok(("b.log".to_string(), -300))
+        );
+        assert!(parse_offset("b.log").is_err());
+        assert!(parse_offset("b.log=soon").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{logcat::Logcat, timestamp};
+
+// Entries of a burst follow each other this closely, in ms of stream time
+const GAP: u64 = 50;
+// A burst that hasn't closed by then is let go as it came
+const MAX_LINES: usize = 500;
+
+// Brackets still open in JSON text, read a line at a time
+#[derive(Copy, Clone, Default)]
+struct Brackets {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Brackets {
+    // False when `text` closes more than was open, so it can't belong
+    fn feed(&mut self, text: &str) -> bool {
+        for c in text.chars() {
+            match c {
+                _ if self.escaped => self.escaped = false,
+                '\\' if self.in_string => self.escaped = true,
+                '"' => self.in_string = !self.in_string,
+                _ if self.in_string => {}
+                '{' | '[' => self.depth += 1,
+                '}' | ']' if self.depth == 0 => return false,
+                '}' | ']' => self.depth -= 1,
+                _ => {}
+            }
+        }
+        true
+    }
+}
+
+/// Stitches JSON that an app pretty-printed over several entries back into
+/// one (`--join-multiline`): an entry opening an object or array it doesn't
+/// close, then the entries of its tag, pid, tid and level that follow in
+/// quick succession, up to the one balancing the brackets.
+#[derive(Default)]
+pub struct Joiner {
+    burst: Vec<Logcat>,
+    brackets: Brackets,
+}
+
+impl Joiner {
+    /// Takes an entry, returning those ready to go on: itself, a joined
+    /// burst, or a burst that turned out not to be one, as it came.
+    pub fn push(&mut self, lc: Logcat) -> Vec<Logcat> {
+        if !self.burst.is_empty() {
+            let mut brackets: Brackets = self.brackets;
+            if !self.continues(&lc) || !brackets.feed(&lc.message) {
+                let mut ready: Vec<Logcat> = self.drain();
+                ready.extend(self.push(lc));
+                return ready;
+            }
+            self.brackets = brackets;
+            self.burst.push(lc);
+            return match (self.brackets.depth, self.burst.len()) {
+                (0, _) => vec![self.join()],
+                (_, MAX_LINES..) => self.drain(),
+                _ => Vec::new(),
+            };
+        }
+        let message: &str = lc.message.trim();
+        let opens: bool = message.starts_with(['{', '[']) || message.ends_with(['{', '[']);
+        let mut brackets: Brackets = Brackets::default();
+        if opens && brackets.feed(message) && brackets.depth > 0 {
+            self.brackets = brackets;
+            self.burst.push(lc);
+            return Vec::new();
+        }
+        vec![lc]
+    }
+
+    /// Lets the burst being collected go as it came.
+    pub fn drain(&mut self) -> Vec<Logcat> {
+        std::mem::take(&mut self.burst)
+    }
+
+    fn continues(&self, lc: &Logcat) -> bool {
+        let Some(last) = self.burst.last() else {
+            return false;
+        };
+        let soon: bool = match (
+            timestamp::parse_millis(&last.timestamp),
+            timestamp::parse_millis(&lc.timestamp),
+        ) {
+            (Some(a), Some(b)) => b.abs_diff(a) <= GAP,
+            _ => true,
+        };
+        soon && lc.tag == last.tag
+            && lc.process == last.process
+            && lc.thread == last.thread
+            && lc.level == last.level
+            && lc.source == last.source
+            && lc.origin == last.origin
+    }
+
+    // One entry, with the pieces' indentation folded into single spaces
+    fn join(&mut self) -> Logcat {
+        let mut burst: std::vec::IntoIter<Logcat> = self.drain().into_iter();
+        let mut joined: Logcat = burst.next().unwrap_or_default();
+        joined.message.truncate(joined.message.trim_end().len());
+        for lc in burst {
+            let piece: &str = lc.message.trim();
+            if !(piece.is_empty()
+                || piece.starts_with(['}', ']', ','])
+                || joined.message.ends_with(['{', '[']))
+            {
+                joined.message.push(' ');
+            }
+            joined.message.push_str(piece);
+        }
+        joined
+    }
+}
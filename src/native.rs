@@ -0,0 +1,258 @@
+use crate::{
+    adb, classify,
+    control::{self, Command},
+    filter::Filters,
+    input,
+    logcat::{self, Logcat},
+    spotlight::Mode,
+};
+use regex::Regex;
+use std::{
+    io::{self, BufReader, Read, Write},
+    process::{Child, ChildStdout},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread,
+};
+
+// Lines waiting for the extension; beyond this they are dropped and counted
+const QUEUE: usize = 4096;
+// Chrome caps messages to a host at 64 MiB; commands are far smaller
+const MAX_INCOMING: usize = 1 << 20;
+
+enum Msg {
+    Line(String),
+    LogEnd,
+    Command(Result<Command, String>),
+    PortClosed,
+}
+
+/// Serves the device log to a browser extension over Chrome's native
+/// messaging protocol: each message is a native-endian u32 length followed
+/// by that many bytes of UTF-8 JSON, on stdin (extension to host) and stdout
+/// (host to extension).
+///
+/// The host sends `{"type":"entry",...}` per accepted entry, `{"type":"raw",
+/// "line":...}` for unparsed lines, `{"type":"dropped","count":N}` after
+/// lines were dropped because the extension fell behind, and
+/// `{"type":"end"}` when `adb logcat` exits. The extension sends
+/// `{"command":"level W"}` with the `--control` commands and gets
+/// `{"type":"reply","ok":true}` or `{"type":"reply","ok":false,"error":...}`.
+pub fn serve(logcat_args: &[String], mut filters: Filters) -> io::Result<()> {
+    let mut child: Child = adb::spawn_logcat(logcat_args)?;
+    let stdout: ChildStdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| io::Error::other("adb logcat has no stdout"))?;
+
+    let (tx, rx): (SyncSender<Msg>, Receiver<Msg>) = mpsc::sync_channel(QUEUE);
+    let dropped: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+
+    // A slow extension must not stall adb, so lines are dropped, not queued
+    let log_tx: SyncSender<Msg> = tx.clone();
+    let log_dropped: Arc<AtomicUsize> = Arc::clone(&dropped);
+    thread::spawn(move || {
+        for line in input::lines(BufReader::new(stdout)).map_while(Result::ok) {
+            match log_tx.try_send(Msg::Line(line)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    log_dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(TrySendError::Disconnected(_)) => return,
+            }
+        }
+        let _ = log_tx.send(Msg::LogEnd);
+    });
+
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        loop {
+            let msg: Msg = match read_message(&mut stdin) {
+                Ok(Some(json)) => Msg::Command(
+                    string_field(&json, "command")
+                        .ok_or_else(|| "expected {\"command\": \"...\"}".to_string())
+                        .and_then(|c: String| control::parse(&c, Mode::default())),
+                ),
+                Ok(None) | Err(_) => Msg::PortClosed,
+            };
+            let closed: bool = matches!(msg, Msg::PortClosed);
+            if tx.send(msg).is_err() || closed {
+                return;
+            }
+        }
+    });
+
+    let mut out = io::stdout().lock();
+    let mut spot_patterns: Vec<String> = Vec::new();
+    let mut spotlight: Option<Regex> = None;
+    let res: io::Result<()> = (|| -> io::Result<()> {
+        write_message(
+            &mut out,
+            &format!(
+                "{{\"type\":\"hello\",\"version\":{}}}",
+                json_str(env!("CARGO_PKG_VERSION"))
+            ),
+        )?;
+        for msg in rx {
+            let n: usize = dropped.swap(0, Ordering::Relaxed);
+            if n > 0 {
+                write_message(
+                    &mut out,
+                    &format!("{{\"type\":\"dropped\",\"count\":{}}}", n),
+                )?;
+            }
+            match msg {
+                Msg::Line(line) => match logcat::parse_line(&line) {
+                    Some((_, lc)) => {
+                        if let Some(packages) = &mut filters.packages {
+                            packages.observe(&lc);
+                        }
+                        if filters.accepts(&lc) {
+                            write_message(&mut out, &entry_json(&lc, spotlight.as_ref()))?;
+                        }
+                    }
+                    None => write_message(
+                        &mut out,
+                        &format!("{{\"type\":\"raw\",\"line\":{}}}", json_str(&line)),
+                    )?,
+                },
+                Msg::Command(Ok(cmd)) => {
+                    match cmd {
+                        Command::SpotlightAdd(rule) => spot_patterns.push(rule.pattern),
+                        Command::SpotlightClear => spot_patterns.clear(),
+                        Command::Level(level) => filters.min_level = level,
+                        Command::Mute(tag) => filters.muted.push(tag),
+                        Command::Unmute(tag) => filters.muted.retain(|t: &String| *t != tag),
+                        Command::Filter(expr) => filters.expr = expr,
+                        Command::Mark(_) => {
+                            write_message(
+                                &mut out,
+                                "{\"type\":\"reply\",\"ok\":false,\"error\":\"mark is not available over native messaging\"}",
+                            )?;
+                            continue;
+                        }
+                    }
+                    spotlight = match spot_patterns.is_empty() {
+                        true => None,
+                        false => Regex::new(&spot_patterns.join("|")).ok(),
+                    };
+                    write_message(&mut out, "{\"type\":\"reply\",\"ok\":true}")?;
+                }
+                Msg::Command(Err(e)) => write_message(
+                    &mut out,
+                    &format!(
+                        "{{\"type\":\"reply\",\"ok\":false,\"error\":{}}}",
+                        json_str(&e)
+                    ),
+                )?,
+                Msg::LogEnd => return write_message(&mut out, "{\"type\":\"end\"}"),
+                Msg::PortClosed => return Ok(()),
+            }
+        }
+        Ok(())
+    })();
+
+    let _ = child.kill();
+    let _ = child.wait();
+    res
+}
+
+fn entry_json(lc: &Logcat, spotlight: Option<&Regex>) -> String {
+    let event: String = match classify::classify(&lc.message) {
+        Some(kind) => json_str(kind.label()),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"type\":\"entry\",\"timestamp\":{},\"level\":{},\"tag\":{},\"uid\":{},\"pid\":{},\"tid\":{},\"message\":{},\"event\":{},\"spotlight\":{}}}",
+        json_str(&lc.timestamp),
+        json_str(&lc.level),
+        json_str(lc.tag.trim()),
+        json_str(&lc.uid),
+        json_str(&lc.process),
+        json_str(&lc.thread),
+        json_str(&lc.message),
+        event,
+        spotlight.is_some_and(|re: &Regex| re.is_match(&lc.message) || re.is_match(lc.tag.trim()))
+    )
+}
+
+// One framed message, or None once the extension closed the port
+fn read_message(r: &mut impl Read) -> io::Result<Option<String>> {
+    let mut len: [u8; 4] = [0; 4];
+    match r.read_exact(&mut len) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        res => res?,
+    }
+    let len: usize = u32::from_ne_bytes(len) as usize;
+    if len > MAX_INCOMING {
+        return Err(io::Error::other(format!(
+            "message of {} bytes is too large",
+            len
+        )));
+    }
+    let mut buf: Vec<u8> = vec![0; len];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf)
+        .map(Some)
+        .map_err(|_| io::Error::other("message is not UTF-8"))
+}
+
+fn write_message(w: &mut impl Write, json: &str) -> io::Result<()> {
+    w.write_all(&(json.len() as u32).to_ne_bytes())?;
+    w.write_all(json.as_bytes())?;
+    w.flush()
+}
+
+/// `s` as a JSON string literal.
+pub fn json_str(s: &str) -> String {
+    let mut out: String = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// The string value of `key` in a flat JSON object; enough for the commands
+// the extension sends, not a general JSON parser
+fn string_field(json: &str, key: &str) -> Option<String> {
+    let needle: String = json_str(key);
+    let after: &str = &json[json.find(&needle)? + needle.len()..];
+    let mut chars = after
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start()
+        .strip_prefix('"')?
+        .chars();
+    let mut out: String = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(out),
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => out.push(c), // \" \\ \/
+            },
+            c => out.push(c),
+        }
+    }
+}
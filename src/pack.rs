@@ -0,0 +1,29 @@
+use crate::config::Config;
+use std::io;
+
+/// The built-in `--pack` config fragments, kept as ordinary config files
+/// under `packs/` so they can be copied into a `--config` file and edited.
+const PACKS: [(&str, &str); 3] = [
+    ("okhttp", include_str!("../packs/okhttp.toml")),
+    ("firebase", include_str!("../packs/firebase.toml")),
+    ("exoplayer", include_str!("../packs/exoplayer.toml")),
+];
+
+pub fn parse(name: &str) -> Result<String, String> {
+    match PACKS.iter().any(|(n, _)| *n == name) {
+        true => Ok(name.to_string()),
+        false => Err(format!(
+            "unknown pack `{}` (expected one of {})",
+            name,
+            PACKS.map(|(n, _)| n).join(", ")
+        )),
+    }
+}
+
+pub fn load(name: &str) -> io::Result<Config> {
+    let (_, text) = PACKS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .ok_or_else(|| io::Error::other(format!("unknown pack `{}`", name)))?;
+    Config::parse(&format!("pack {}", name), text)
+}
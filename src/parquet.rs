@@ -0,0 +1,500 @@
+use crate::{filter, logcat::Logcat};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+// Rows buffered before a row group is written out
+const ROW_GROUP: usize = 65536;
+
+const MAGIC: &[u8] = b"PAR1";
+
+// Parquet enums (parquet.thrift)
+const BYTE_ARRAY: i32 = 6;
+const INT32: i32 = 1;
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+const UTF8: i32 = 0;
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const UNCOMPRESSED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+
+// Columns in file order; pid and tid are nullable INT32s, the rest strings
+const COLUMNS: [&str; 6] = ["timestamp", "level", "tag", "pid", "tid", "message"];
+
+/// Writes entries to an Apache Parquet file: one uncompressed, PLAIN-encoded
+/// data page per column and row group, with the footer in Thrift's compact
+/// protocol. Enough for pandas, polars and duckdb to read without a schema.
+pub struct Writer {
+    out: BufWriter<File>,
+    pos: u64,
+    strings: [Vec<u8>; 4],      // timestamp, level, tag, message, PLAIN-encoded
+    ids: [Vec<Option<i32>>; 2], // pid, tid
+    rows: usize,
+    groups: Vec<Group>,
+}
+
+struct Chunk {
+    name: &'static str,
+    physical: i32,
+    offset: u64,
+    size: u64,
+}
+
+struct Group {
+    chunks: Vec<Chunk>,
+    rows: usize,
+}
+
+impl Writer {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out: BufWriter<File> = BufWriter::new(
+            File::create(path)
+                .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?,
+        );
+        out.write_all(MAGIC)?;
+        Ok(Self {
+            out,
+            pos: MAGIC.len() as u64,
+            strings: Default::default(),
+            ids: Default::default(),
+            rows: 0,
+            groups: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, lc: &Logcat) -> io::Result<()> {
+        let fields: [&str; 4] = [&lc.timestamp, &lc.level, lc.tag.trim(), &lc.message];
+        for (buf, s) in self.strings.iter_mut().zip(fields) {
+            buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            buf.extend_from_slice(s.as_bytes());
+        }
+        self.ids[0].push(id(&lc.process));
+        self.ids[1].push(id(&lc.thread));
+        self.rows += 1;
+        if self.rows == ROW_GROUP {
+            self.write_group()?;
+        }
+        Ok(())
+    }
+
+    /// Writes what's buffered and the footer.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.rows > 0 {
+            self.write_group()?;
+        }
+        let footer: Vec<u8> = self.footer();
+        self.out.write_all(&footer)?;
+        self.out.write_all(&(footer.len() as u32).to_le_bytes())?;
+        self.out.write_all(MAGIC)?;
+        self.out.flush()
+    }
+
+    fn write_group(&mut self) -> io::Result<()> {
+        let strings: [Vec<u8>; 4] = std::mem::take(&mut self.strings);
+        let ids: [Vec<Option<i32>>; 2] = std::mem::take(&mut self.ids);
+        let [timestamp, level, tag, message] = strings;
+        let [pid, tid] = ids;
+        let pages: [(i32, Vec<u8>); 6] = [
+            (BYTE_ARRAY, timestamp),
+            (BYTE_ARRAY, level),
+            (BYTE_ARRAY, tag),
+            (INT32, optional_ints(&pid)),
+            (INT32, optional_ints(&tid)),
+            (BYTE_ARRAY, message),
+        ];
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        for ((physical, body), name) in pages.into_iter().zip(COLUMNS) {
+            let header: Vec<u8> = page_header(self.rows, body.len());
+            self.out.write_all(&header)?;
+            self.out.write_all(&body)?;
+            let size: u64 = (header.len() + body.len()) as u64;
+            chunks.push(Chunk {
+                name,
+                physical,
+                offset: self.pos,
+                size,
+            });
+            self.pos += size;
+        }
+        self.groups.push(Group {
+            chunks,
+            rows: std::mem::take(&mut self.rows),
+        });
+        Ok(())
+    }
+
+    // FileMetaData
+    fn footer(&self) -> Vec<u8> {
+        let mut t: Thrift = Thrift::default();
+        t.i32(1, 1); // version
+        t.list(2, STRUCT, COLUMNS.len() + 1);
+        t.begin_elem();
+        t.binary(4, b"schema");
+        t.i32(5, COLUMNS.len() as i32);
+        t.end();
+        for name in COLUMNS {
+            t.begin_elem();
+            match name {
+                "pid" | "tid" => {
+                    t.i32(1, INT32);
+                    t.i32(3, OPTIONAL);
+                    t.binary(4, name.as_bytes());
+                }
+                _ => {
+                    t.i32(1, BYTE_ARRAY);
+                    t.i32(3, REQUIRED);
+                    t.binary(4, name.as_bytes());
+                    t.i32(6, UTF8);
+                }
+            }
+            t.end();
+        }
+        let rows: usize = self.groups.iter().map(|g: &Group| g.rows).sum();
+        t.i64(3, rows as i64);
+        t.list(4, STRUCT, self.groups.len());
+        for group in &self.groups {
+            t.begin_elem();
+            t.list(1, STRUCT, group.chunks.len());
+            for chunk in &group.chunks {
+                t.begin_elem();
+                t.i64(2, chunk.offset as i64);
+                t.begin(3); // ColumnMetaData
+                t.i32(1, chunk.physical);
+                t.list(2, I32, 2);
+                t.elem_i32(PLAIN);
+                t.elem_i32(RLE);
+                t.list(3, BINARY, 1);
+                t.elem_binary(chunk.name.as_bytes());
+                t.i32(4, UNCOMPRESSED);
+                t.i64(5, group.rows as i64);
+                t.i64(6, chunk.size as i64);
+                t.i64(7, chunk.size as i64);
+                t.i64(9, chunk.offset as i64);
+                t.end();
+                t.end();
+            }
+            let bytes: u64 = group.chunks.iter().map(|c: &Chunk| c.size).sum();
+            t.i64(2, bytes as i64);
+            t.i64(3, group.rows as i64);
+            t.end();
+        }
+        t.binary(
+            6,
+            format!(
+                "{} version {}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )
+            .as_bytes(),
+        );
+        t.finish()
+    }
+}
+
+fn id(s: &str) -> Option<i32> {
+    filter::parse_id(s).and_then(|v: u64| i32::try_from(v).ok())
+}
+
+// Definition levels (RLE runs of 1-bit values, length-prefixed), then the
+// values that are present
+fn optional_ints(values: &[Option<i32>]) -> Vec<u8> {
+    let mut levels: Vec<u8> = Vec::new();
+    let mut i: usize = 0;
+    while i < values.len() {
+        let defined: bool = values[i].is_some();
+        let run: usize = values[i..]
+            .iter()
+            .take_while(|v: &&Option<i32>| v.is_some() == defined)
+            .count();
+        varint(&mut levels, (run as u64) << 1);
+        levels.push(defined as u8);
+        i += run;
+    }
+    let mut body: Vec<u8> = (levels.len() as u32).to_le_bytes().to_vec();
+    body.extend_from_slice(&levels);
+    for v in values.iter().flatten() {
+        body.extend_from_slice(&v.to_le_bytes());
+    }
+    body
+}
+
+// PageHeader with its DataPageHeader
+fn page_header(rows: usize, size: usize) -> Vec<u8> {
+    let mut t: Thrift = Thrift::default();
+    t.i32(1, DATA_PAGE);
+    t.i32(2, size as i32);
+    t.i32(3, size as i32);
+    t.begin(5);
+    t.i32(1, rows as i32);
+    t.i32(2, PLAIN);
+    t.i32(3, RLE);
+    t.i32(4, RLE);
+    t.end();
+    t.finish()
+}
+
+// Thrift compact protocol type ids
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+/// Just the compact protocol writes the Parquet footer needs.
+struct Thrift {
+    buf: Vec<u8>,
+    last: Vec<i16>, // previous field id, per open struct
+}
+
+impl Default for Thrift {
+    fn default() -> Self {
+        Self {
+            buf: Vec::new(),
+            last: vec![0],
+        }
+    }
+}
+
+impl Thrift {
+    fn field(&mut self, id: i16, ty: u8) {
+        let last: &mut i16 = self.last.last_mut().expect("field outside a struct");
+        match id - *last {
+            delta @ 1..=15 => self.buf.push(((delta as u8) << 4) | ty),
+            _ => {
+                self.buf.push(ty);
+                varint(&mut self.buf, zigzag(id as i64));
+            }
+        }
+        *last = id;
+    }
+
+    fn i32(&mut self, id: i16, v: i32) {
+        self.field(id, I32);
+        self.elem_i32(v);
+    }
+
+    fn i64(&mut self, id: i16, v: i64) {
+        self.field(id, I64);
+        varint(&mut self.buf, zigzag(v));
+    }
+
+    fn binary(&mut self, id: i16, b: &[u8]) {
+        self.field(id, BINARY);
+        self.elem_binary(b);
+    }
+
+    fn list(&mut self, id: i16, elem: u8, len: usize) {
+        self.field(id, LIST);
+        match len {
+            0..=14 => self.buf.push(((len as u8) << 4) | elem),
+            _ => {
+                self.buf.push(0xf0 | elem);
+                varint(&mut self.buf, len as u64);
+            }
+        }
+    }
+
+    fn elem_i32(&mut self, v: i32) {
+        varint(&mut self.buf, zigzag(v as i64));
+    }
+
+    fn elem_binary(&mut self, b: &[u8]) {
+        varint(&mut self.buf, b.len() as u64);
+        self.buf.extend_from_slice(b);
+    }
+
+    // A struct field, or (begin_elem) a struct in a list
+    fn begin(&mut self, id: i16) {
+        self.field(id, STRUCT);
+        self.last.push(0);
+    }
+
+    fn begin_elem(&mut self) {
+        self.last.push(0);
+    }
+
+    fn end(&mut self) {
+        self.buf.push(0); // stop
+        self.last.pop();
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.end();
+        self.buf
+    }
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::{Field, Row, RowAccessor},
+    };
+    use std::{fs, path::PathBuf};
+
+    fn entry(ts: &str, level: &str, tag: &str, pid: &str, message: &str) -> Logcat {
+        Logcat {
+            timestamp: ts.to_string(),
+            level: level.to_string(),
+            tag: tag.to_string(),
+            process: pid.to_string(),
+            thread: pid.to_string(),
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Writes `entries` to a file of its own and returns its path
+    fn write(name: &str, entries: &[Logcat]) -> PathBuf {
+        let path: PathBuf = std::env::temp_dir().join(format!(
+            "logcat-colorize-{}-{}.parquet",
+            std::process::id(),
+            name
+        ));
+        let mut writer: Writer = Writer::create(&path).unwrap();
+        for lc in entries {
+            writer.push(lc).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn tiny() -> Vec<Logcat> {
+        vec![
+            entry("06-01 12:00:00.123", "I", "Tag  ", "1234", "hello"),
+            entry("06-01 12:00:00.124", "E", "Ünï", "", "wörld"),
+        ]
+    }
+
+    // The golden file was written by version 0.2.0: put the version writing
+    // it in `created_by`, the footer's last field, and fix the footer length
+    fn with_version(golden: &[u8]) -> Vec<u8> {
+        let old: &[u8] = b"logcat-colorize version 0.2.0";
+        let at: usize = golden
+            .windows(old.len())
+            .position(|w: &[u8]| w == old)
+            .unwrap();
+        assert_eq!(golden.len(), at + old.len() + 1 + 4 + MAGIC.len());
+        let footer_len: usize = u32::from_le_bytes(
+            golden[golden.len() - 8..golden.len() - 4]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        let footer_start: usize = golden.len() - 8 - footer_len;
+
+        let created_by: String = format!(
+            "{} version {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+        let mut bytes: Vec<u8> = golden[..at - 1].to_vec(); // before its length
+        varint(&mut bytes, created_by.len() as u64);
+        bytes.extend_from_slice(created_by.as_bytes());
+        bytes.push(0); // stop
+        bytes.extend_from_slice(&((bytes.len() - footer_start) as u32).to_le_bytes());
+        bytes.extend_from_slice(MAGIC);
+        bytes
+    }
+
+    // pid and tid of each row, None when null
+    fn ids(row: &Row) -> (Option<i32>, Option<i32>) {
+        let id = |i: usize| -> Option<i32> {
+            match row.get_column_iter().nth(i).map(|(_, f)| f) {
+                Some(Field::Int(v)) => Some(*v),
+                _ => None,
+            }
+        };
+        (id(3), id(4))
+    }
+
+    fn read(path: &Path) -> (i64, Vec<Row>) {
+        let reader: SerializedFileReader<fs::File> =
+            SerializedFileReader::new(fs::File::open(path).unwrap()).unwrap();
+        let rows: i64 = reader.metadata().file_metadata().num_rows();
+        let read: Vec<Row> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        (rows, read)
+    }
+
+    #[test]
+    fn tiny_file_matches_golden_bytes() {
+        let path: PathBuf = write("golden", &tiny());
+        let written: Vec<u8> = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            written,
+            with_version(include_bytes!("../fixtures/tiny.parquet"))
+        );
+    }
+
+    #[test]
+    fn golden_file_reads_back() {
+        let (rows, read) = read(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/tiny.parquet"
+        )));
+        assert_eq!(rows, 2);
+        let columns: Vec<String> = read[0]
+            .get_column_iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        assert_eq!(columns, COLUMNS);
+        assert_eq!(read[0].get_string(0).unwrap(), "06-01 12:00:00.123");
+        assert_eq!(read[0].get_string(2).unwrap(), "Tag");
+        assert_eq!(ids(&read[0]), (Some(1234), Some(1234)));
+        assert_eq!(read[1].get_string(1).unwrap(), "E");
+        assert_eq!(read[1].get_string(2).unwrap(), "Ünï");
+        assert_eq!(ids(&read[1]), (None, None));
+        assert_eq!(read[1].get_string(5).unwrap(), "wörld");
+    }
+
+    #[test]
+    fn round_trips_over_row_groups() {
+        // Past one row group, with runs of null and present ids
+        let entries: Vec<Logcat> = (0..ROW_GROUP + 3)
+            .map(|i: usize| {
+                let pid: String = match i % 5 {
+                    0 | 1 => String::new(),
+                    _ => i.to_string(),
+                };
+                entry("06-01 12:00:00.000", "D", "T", &pid, &format!("m{}", i))
+            })
+            .collect();
+        let path: PathBuf = write("groups", &entries);
+        let (rows, read) = read(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows, entries.len() as i64);
+        assert_eq!(read.len(), entries.len());
+        for (i, row) in read
+            .iter()
+            .enumerate()
+            .step_by(997)
+            .chain(read.iter().enumerate().skip(ROW_GROUP - 1))
+        {
+            let pid: Option<i32> = (i % 5 >= 2).then_some(i as i32);
+            assert_eq!(ids(row), (pid, pid), "row {}", i);
+            assert_eq!(row.get_string(5).unwrap(), &format!("m{}", i));
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{adb, filter::parse_id, logcat::Logcat, poll::Poll};
+use regex::Regex;
+use std::{collections::HashMap, io, sync::LazyLock, time::Duration};
+
+// ActivityManager: "Start proc 1234:com.example/u0a123 for activity ..."
+static RE_START: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bStart proc ([0-9]+):([^/\s]+)").unwrap());
+// ActivityManager: "Process com.example (pid 1234) has died" / "Killing 1234:com.example/u0a123 ..."
+// Zygote: "Process 1234 exited due to signal 9 (Killed)"
+static RE_DEATH: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r"\bProcess \S+ \(pid ([0-9]+)\) has died|\bKilling ([0-9]+):|\bProcess ([0-9]+) exited due to signal").unwrap()
+});
+
+#[derive(Default)]
+struct Proc {
+    name: Option<String>,
+    dead: bool,
+    generation: usize,
+}
+
+/// A pid coming back to life as a (possibly) different process.
+pub struct Reuse {
+    pub pid: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Follows process starts and deaths to notice when a pid gets recycled.
+#[derive(Default)]
+pub struct PidTracker {
+    procs: HashMap<String, Proc>,
+}
+
+impl PidTracker {
+    /// Feeds one entry, returning the reuse it revealed, if any.
+    pub fn observe(&mut self, lc: &Logcat) -> Option<Reuse> {
+        if let Some(pid) = dead_pid(&lc.message) {
+            self.procs.entry(pid.to_string()).or_default().dead = true;
+        }
+
+        let mut reuse: Option<Reuse> = None;
+        if let Some(c) = RE_START.captures(&lc.message) {
+            let name: String = c[2].to_string();
+            let p: &mut Proc = self.procs.entry(c[1].to_string()).or_default();
+            if p.dead || p.name.as_ref().is_some_and(|n: &String| *n != name) {
+                reuse = Some(Reuse {
+                    pid: c[1].to_string(),
+                    old: p.name.clone(),
+                    new: Some(name.clone()),
+                });
+                p.generation += 1;
+            }
+            p.name = Some(name);
+            p.dead = false;
+        }
+
+        // An entry from a pid we saw die means something else owns it now
+        if let Some(p) = self.procs.get_mut(&lc.process)
+            && p.dead
+            && !RE_DEATH.is_match(&lc.message)
+        {
+            reuse = Some(Reuse {
+                pid: lc.process.clone(),
+                old: p.name.take(),
+                new: None,
+            });
+            p.generation += 1;
+            p.dead = false;
+        }
+        reuse
+    }
+
+    /// How many times `pid` has been reused so far.
+    pub fn generation(&self, pid: &str) -> usize {
+        self.procs.get(pid).map_or(0, |p: &Proc| p.generation)
+    }
+
+    /// Records what runs under `pid` when the log didn't say.
+    pub fn name(&mut self, pid: &str, name: String) {
+        self.procs.entry(pid.to_string()).or_default().name = Some(name);
+    }
+}
+
+/// Asks the device, in exec mode, what a reused pid runs now, at most once
+/// every poll interval: the log only names the processes ActivityManager
+/// starts.
+pub struct DeviceProcs {
+    poll: Poll,
+}
+
+impl DeviceProcs {
+    pub fn new(poll_interval: Duration) -> Self {
+        Self {
+            poll: Poll::new(poll_interval),
+        }
+    }
+
+    /// The name `ps` gives `pid`, unless the device was asked too recently.
+    pub fn name(&mut self, pid: &str) -> Option<String> {
+        if !self.poll.due() {
+            return None;
+        }
+        adb::processes()
+            .ok()?
+            .into_iter()
+            .find(|(p, _, _)| p == pid)
+            .map(|(_, _, name)| name)
+    }
+}
+
+// The pid a death message names, whichever form it took
+fn dead_pid(message: &str) -> Option<&str> {
+    RE_DEATH
+        .captures(message)
+        .and_then(|c: regex::Captures<'_>| {
+            c.iter()
+                .skip(1)
+                .flatten()
+                .next()
+                .map(|m: regex::Match<'_>| m.as_str())
+        })
+}
+
+/// A process of a `--package` app starting or dying.
+pub struct PackageEvent {
+    pub pid: u64,
+    pub name: String,
+    pub started: bool,
+}
+
+/// The processes of the `--package` apps: found with `pidof` up front, then
+/// followed through ActivityManager's process starts and deaths, so a
+/// restarted app stays in view. `refresh` asks `pidof` again, for starts
+/// and deaths the log didn't show (rotated out, filtered on the device).
+#[derive(Clone, Default)]
+pub struct PackagePids {
+    packages: Vec<String>,
+    pids: HashMap<u64, String>,
+}
+
+impl PackagePids {
+    pub fn resolve(packages: Vec<String>) -> io::Result<Self> {
+        let mut pids: HashMap<u64, String> = HashMap::new();
+        for package in &packages {
+            for pid in adb::pidof(package)? {
+                pids.insert(pid, package.clone());
+            }
+        }
+        Ok(Self { packages, pids })
+    }
+
+    // The package itself or one of its "com.example:remote" processes
+    fn owns(&self, process: &str) -> bool {
+        self.packages.iter().any(|p: &String| {
+            process
+                .strip_prefix(p.as_str())
+                .is_some_and(|rest: &str| rest.is_empty() || rest.starts_with(':'))
+        })
+    }
+
+    /// Asks `pidof` for the packages' processes again, returning the starts
+    /// and deaths the log hadn't announced. Processes named after a package
+    /// and no longer listed died; others (`com.example:remote`) are left
+    /// to the log, as `pidof` isn't asked about them.
+    pub fn refresh(&mut self) -> io::Result<Vec<PackageEvent>> {
+        let mut events: Vec<PackageEvent> = Vec::new();
+        for package in &self.packages {
+            let pids: Vec<u64> = adb::pidof(package)?;
+            let gone: Vec<u64> = self
+                .pids
+                .iter()
+                .filter(|(pid, name)| *name == package && !pids.contains(pid))
+                .map(|(pid, _)| *pid)
+                .collect();
+            for pid in gone {
+                self.pids.remove(&pid);
+                events.push(PackageEvent {
+                    pid,
+                    name: package.clone(),
+                    started: false,
+                });
+            }
+            for pid in pids {
+                if self.pids.insert(pid, package.clone()).is_none() {
+                    events.push(PackageEvent {
+                        pid,
+                        name: package.clone(),
+                        started: true,
+                    });
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    pub fn contains(&self, pid: u64) -> bool {
+        self.pids.contains_key(&pid)
+    }
+
+    /// Feeds one entry, returning the start or death of a package process
+    /// it announced, if any.
+    pub fn observe(&mut self, lc: &Logcat) -> Option<PackageEvent> {
+        if let Some(c) = RE_START.captures(&lc.message)
+            && self.owns(&c[2])
+            && let Some(pid) = parse_id(&c[1])
+        {
+            self.pids.insert(pid, c[2].to_string());
+            return Some(PackageEvent {
+                pid,
+                name: c[2].to_string(),
+                started: true,
+            });
+        }
+        let pid: u64 = dead_pid(&lc.message).and_then(parse_id)?;
+        let name: String = self.pids.remove(&pid)?;
+        Some(PackageEvent {
+            pid,
+            name,
+            started: false,
+        })
+    }
+}
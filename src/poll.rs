@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+/// How often the features that ask the device (naming reused pids,
+/// `--package`, `--pid-groups`) poll it, unless `--adb-poll-interval` says
+/// otherwise.
+pub const INTERVAL: Duration = Duration::from_secs(10);
+
+/// When a feature may poll the device again. Polls are only made for an
+/// entry coming in, so a stream gone idle leaves the device asleep.
+#[derive(Clone, Debug)]
+pub struct Poll {
+    every: Duration,
+    last: Option<Instant>,
+}
+
+impl Poll {
+    pub fn new(every: Duration) -> Self {
+        Self { every, last: None }
+    }
+
+    /// A schedule whose first poll was just made.
+    pub fn polled(every: Duration) -> Self {
+        Self {
+            every,
+            last: Some(Instant::now()),
+        }
+    }
+
+    /// Whether it's time to poll, counting the poll as made if so.
+    pub fn due(&mut self) -> bool {
+        if self
+            .last
+            .is_some_and(|at: Instant| at.elapsed() < self.every)
+        {
+            return false;
+        }
+        self.last = Some(Instant::now());
+        true
+    }
+}
+
+/// Parses `--adb-poll-interval`: a duration such as "30s" or "2m".
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    match crate::timestamp::parse_duration(s) {
+        Some(ms) if ms >= 1000 => Ok(Duration::from_millis(ms)),
+        Some(_) => Err("polling more than once a second would keep the device busy".to_string()),
+        None => Err(format!("`{}` is not a duration like 30s or 2m", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polls_once_per_interval() {
+        let mut poll: Poll = Poll::new(INTERVAL);
+        assert!(poll.due());
+        assert!(!poll.due());
+        assert!(!Poll::polled(INTERVAL).due());
+        let mut eager: Poll = Poll::polled(Duration::ZERO);
+        assert!(eager.due() && eager.due());
+    }
+
+    #[test]
+    fn intervals() {
+        assert_eq!(parse_interval("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("2m"), Ok(Duration::from_secs(120)));
+        assert!(parse_interval("100ms").is_err());
+        assert!(parse_interval("often").is_err());
+    }
+}
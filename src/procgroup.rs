@@ -0,0 +1,130 @@
+use crate::{adb, logcat::Logcat, poll::Poll};
+use regex::Regex;
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
+
+// App ids start here within each user's uid range
+const FIRST_APP_UID: u64 = 10_000;
+const PER_USER_RANGE: u64 = 100_000;
+
+// "u0_a123" for apps, "u0_i4" for isolated services
+static RE_APP_USER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^u[0-9]+_[ai][0-9]+$").unwrap());
+// ActivityManager: "Start proc 1234:com.example/u0a123 for activity ..."
+static RE_START: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bStart proc ([0-9]+):[^/\s]+/u[0-9]+[ai][0-9]+").unwrap());
+
+// Tags only system_server and the zygotes log under
+const SYSTEM_TAGS: [&str; 10] = [
+    "ActivityManager",
+    "ActivityTaskManager",
+    "PackageManager",
+    "WindowManager",
+    "SystemServer",
+    "SystemServerTiming",
+    "InputDispatcher",
+    "PowerManagerService",
+    "Zygote",
+    "Zygote64",
+];
+// Tags of well-known native daemons
+const NATIVE_TAGS: [&str; 12] = [
+    "init",
+    "vold",
+    "lmkd",
+    "netd",
+    "servicemanager",
+    "hwservicemanager",
+    "SurfaceFlinger",
+    "audioserver",
+    "cameraserver",
+    "keystore2",
+    "installd",
+    "logd",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Group {
+    App,
+    System, // system_server and the zygotes
+    Native, // daemons and other processes of system uids
+}
+
+/// Sorts pids into apps, the system server and native daemons, for
+/// `--pid-groups`: from the `-v uid` field, from ActivityManager's process
+/// starts and from tags only the platform uses, and in exec mode from
+/// `adb shell ps`.
+pub struct ProcGroups {
+    by_pid: HashMap<String, Group>,
+    exec: bool,
+    poll: Poll,
+}
+
+impl ProcGroups {
+    /// In exec mode, asks the device about pids it can't place at most once
+    /// every `poll_interval`.
+    pub fn new(exec: bool, poll_interval: Duration) -> Self {
+        let mut groups: ProcGroups = Self {
+            by_pid: HashMap::new(),
+            exec,
+            poll: Poll::new(poll_interval),
+        };
+        groups.query();
+        groups
+    }
+
+    pub fn observe(&mut self, lc: &Logcat) -> Option<Group> {
+        if let Some(c) = RE_START.captures(&lc.message) {
+            self.by_pid.insert(c[1].to_string(), Group::App);
+        }
+        if !lc.uid.is_empty() {
+            return Some(of_user(&lc.uid, ""));
+        }
+        if lc.process.is_empty() {
+            return None;
+        }
+        if let Some(group) = self.by_pid.get(&lc.process) {
+            return Some(*group);
+        }
+        let tag: &str = lc.tag.trim();
+        let group: Option<Group> = if SYSTEM_TAGS.contains(&tag) {
+            Some(Group::System)
+        } else if NATIVE_TAGS.contains(&tag) {
+            Some(Group::Native)
+        } else {
+            self.query();
+            self.by_pid.get(&lc.process).copied()
+        };
+        if let Some(group) = group {
+            self.by_pid.insert(lc.process.clone(), group);
+        }
+        group
+    }
+
+    // Asks the device, in exec mode and not too often
+    fn query(&mut self) {
+        if !self.exec || !self.poll.due() {
+            return;
+        }
+        // Without the device's answer, the log heuristics still apply
+        if let Ok(procs) = adb::processes() {
+            for (pid, user, name) in procs {
+                self.by_pid.insert(pid, of_user(&user, &name));
+            }
+        }
+    }
+}
+
+// The group of a process run by `user` (a name or a uid) under `name`
+fn of_user(user: &str, name: &str) -> Group {
+    if name == "system_server" || name.starts_with("zygote") || name.ends_with("_zygote") {
+        return Group::System;
+    }
+    match user.parse::<u64>() {
+        Ok(uid) if uid % PER_USER_RANGE >= FIRST_APP_UID => Group::App,
+        Ok(uid) if uid % PER_USER_RANGE == 1000 => Group::System,
+        Ok(_) => Group::Native,
+        Err(_) if RE_APP_USER.is_match(user) => Group::App,
+        Err(_) if user == "system" || user.ends_with("_system") => Group::System,
+        Err(_) => Group::Native,
+    }
+}
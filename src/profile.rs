@@ -0,0 +1,66 @@
+use crate::{
+    logcat::Logcat,
+    render::{Line, Renderer},
+    theme::Theme,
+    timestamp,
+};
+use std::{cmp::Reverse, collections::HashMap, io};
+
+// Tags listed in the report
+const TOP: usize = 10;
+
+/// Attributes the time between consecutive entries to the tag of the
+/// earlier one: a rough picture of where the logged time went.
+#[derive(Default)]
+pub struct Profile {
+    last: Option<(u64, String)>,
+    spent: HashMap<String, u64>,
+}
+
+impl Profile {
+    pub fn record(&mut self, lc: &Logcat) {
+        let Some(ts) = timestamp::parse_millis(&lc.timestamp) else {
+            return;
+        };
+        if let Some((prev, tag)) = &self.last
+            && ts >= *prev
+        {
+            *self.spent.entry(tag.clone()).or_default() += ts - prev;
+        }
+        self.last = Some((ts, lc.tag.trim().to_string()));
+    }
+
+    pub fn print(&self, out: &mut Renderer, theme: &Theme) -> io::Result<()> {
+        let total: u64 = self.spent.values().sum();
+        out.status_blank()?;
+        if total == 0 {
+            return out.status_text("No timed entries to profile");
+        }
+
+        let mut tags: Vec<(&String, &u64)> = self.spent.iter().collect();
+        tags.sort_by_key(|(tag, ms): &(&String, &u64)| (Reverse(**ms), *tag));
+        let width: usize = tags
+            .iter()
+            .take(TOP)
+            .map(|(tag, _): &(&String, &u64)| tag.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        out.status_text(&format!(
+            "Time dominated by ({:.1}s between timed entries):",
+            total as f64 / 1000.0
+        ))?;
+        for (tag, ms) in tags.iter().take(TOP) {
+            let mut line: Line = Line::new();
+            line.plain("  ")
+                .styled(&theme.tag, &format!("{:<w$}", tag, w = width))
+                .plain(&format!(
+                    "  {:>9.3}s  {:>5.1}%",
+                    **ms as f64 / 1000.0,
+                    **ms as f64 * 100.0 / total as f64
+                ));
+            out.status_line(&line)?;
+        }
+        Ok(())
+    }
+}
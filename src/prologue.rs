@@ -0,0 +1,54 @@
+use crate::logcat::Logcat;
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Banner lines framing a prologue, e.g. debuggerd's "*** *** *** ..." or a row of "====="
+static RE_BANNER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?:(?:\*{3}\s*){3,}|[*=]{10,})\s*$").unwrap());
+// "Key: value" header lines, e.g. "Build fingerprint: '...'" or "ABI: 'arm64'"
+static RE_HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*([A-Za-z][A-Za-z0-9 _-]{0,31}):\s").unwrap());
+// debuggerd's "pid: 4242, tid: 4260, name: RenderThread  >>> com.example <<<",
+// which says what crashed and so stays in view
+static RE_IDENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*pid: [0-9]+, tid: [0-9]+, name: ").unwrap());
+
+// Upper bound on buffered entries, so a runaway banner never stalls the output
+const MAX_ENTRIES: usize = 64;
+
+pub fn is_banner(message: &str) -> bool {
+    RE_BANNER.is_match(message)
+}
+
+/// Whether `lc` belongs to the prologue opened by `entries[0]`. A crash
+/// dump's pid/tid/name line ends it, to be shown as it is.
+pub fn continues(entries: &[Logcat], lc: &Logcat) -> bool {
+    let first: &Logcat = &entries[0];
+    entries.len() < MAX_ENTRIES
+        && lc.tag == first.tag
+        && lc.process == first.process
+        && (is_banner(&lc.message)
+            || RE_HEADER.is_match(&lc.message) && !RE_IDENTITY.is_match(&lc.message))
+}
+
+/// Builds the single entry standing in for a collapsed prologue.
+pub fn summarize(entries: &[Logcat]) -> Logcat {
+    let keys: Vec<&str> = entries
+        .iter()
+        .filter_map(|e: &Logcat| RE_HEADER.captures(&e.message))
+        .filter_map(|c: regex::Captures<'_>| c.get(1))
+        .map(|m: regex::Match<'_>| m.as_str().trim())
+        .collect();
+
+    let mut message: String = format!("[{} lines collapsed", entries.len());
+    if !keys.is_empty() {
+        message.push_str(": ");
+        message.push_str(&keys.join(", "));
+    }
+    message.push_str("] (--expand to show)");
+
+    Logcat {
+        message,
+        ..entries[0].clone()
+    }
+}
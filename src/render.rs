@@ -0,0 +1,862 @@
+use crate::{
+    aftercommand::Finished,
+    ansi::{self, Seq, Sgr},
+    autohighlight::{self, Kind},
+    classify::EventKind,
+    codepage,
+    logcat::{Logcat, Options, Source},
+    merge::Capture,
+    spotlight::Spot,
+    statediff::Change,
+    theme::Theme,
+    width,
+};
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    ops::Range,
+};
+
+/// A line being assembled from styled segments. Every styled segment is
+/// closed with a reset, so a finished line never leaks its colors.
+///
+/// Escape sequences would make up most of the output written naively, so
+/// the reset closing the last segment is taken back when another follows,
+/// and only the codes that differ between the two styles are written.
+/// Blanks whose look doesn't depend on the style stay inside it.
+#[derive(Default)]
+pub struct Line {
+    buf: String,
+    style: Sgr,            // in effect before the closing reset
+    backdrop: Option<Sgr>, // --spotlight-line's colors, under every segment
+}
+
+// The closing reset
+const CLOSE: &str = "\x1b[0m";
+
+impl Line {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lays the segments from now on over `style`'s colors.
+    pub fn backdrop(&mut self, style: &Seq) -> &mut Self {
+        self.backdrop = Some(style.sgr().clone());
+        self
+    }
+
+    pub fn styled(&mut self, style: &Seq, text: &str) -> &mut Self {
+        self.push_styled(style.sgr(), text)
+    }
+
+    /// `text` in `style`, set inside text in `base` (see `Sgr::within`).
+    pub fn nested(&mut self, style: &Seq, base: &Seq, text: &str) -> &mut Self {
+        let sgr: Sgr = style.sgr().within(base.sgr());
+        self.push_styled(&sgr, text)
+    }
+
+    fn push_styled(&mut self, style: &Sgr, text: &str) -> &mut Self {
+        match &self.backdrop {
+            Some(backdrop) => {
+                let sgr: Sgr = style.under(backdrop);
+                self.push(&sgr, text)
+            }
+            None => self.push(style, text),
+        }
+    }
+
+    fn push(&mut self, style: &Sgr, text: &str) -> &mut Self {
+        if text.is_empty() {
+            return self;
+        }
+        self.reopen();
+        let codes: String = self.style.transition(style);
+        if !codes.is_empty() {
+            self.buf.push_str(&format!("\x1b[{}m", codes));
+        }
+        self.buf.push_str(text);
+        self.style.clone_from(style);
+        self.close();
+        self
+    }
+
+    /// `text` in `style`, with the spotlight's matches in their rules' styles.
+    pub fn spotted(&mut self, style: &Seq, text: &str, spot: Option<&Spot>) -> &mut Self {
+        let mut at: usize = 0;
+        for (m, spot_style) in spot.into_iter().flat_map(|s: &Spot| s.find_iter(text)) {
+            self.styled(style, &text[at..m.start()])
+                .styled(spot_style, m.as_str());
+            at = m.end();
+        }
+        self.styled(style, &text[at..])
+    }
+
+    pub fn plain(&mut self, text: &str) -> &mut Self {
+        if text.is_empty() {
+            return self;
+        }
+        if let Some(backdrop) = &self.backdrop {
+            let sgr: Sgr = Sgr::default().under(backdrop);
+            return self.push(&sgr, text);
+        }
+        if text.bytes().all(|b: u8| b == b' ') && self.style.blank_safe() {
+            self.reopen();
+            self.buf.push_str(text);
+            self.close();
+            return self;
+        }
+        self.buf.push_str(text);
+        self.style = Sgr::default();
+        self
+    }
+
+    // Takes back the closing reset, if the last segment left one
+    fn reopen(&mut self) {
+        if !self.style.is_default() {
+            self.buf.truncate(self.buf.len() - CLOSE.len());
+        }
+    }
+
+    fn close(&mut self) {
+        if !self.style.is_default() {
+            self.buf.push_str(CLOSE);
+        }
+    }
+
+    /// A level-style badge, padded with one space on each side.
+    pub fn badge(&mut self, style: &Seq, label: &str) -> &mut Self {
+        self.styled(style, &format!(" {} ", label)).plain(" ")
+    }
+}
+
+/// The single owner of everything written to the output streams.
+///
+/// Log lines (entries, raw lines, events) go to the log stream. Status output
+/// (banners, notices, summaries, verdicts, terminal alerts) goes through the
+/// `status_*` methods, which share the log stream unless a separate status
+/// stream was routed, so that pipes can receive log data only.
+pub struct Renderer {
+    out: Box<dyn Write>,
+    status: Option<Box<dyn Write>>,
+    reset: String,
+    reserved: bool, // top rows held for --pin
+    ascii: bool,    // for consoles on a legacy code page
+}
+
+impl Renderer {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Self {
+            out,
+            status: None,
+            reset: ansi::reset().as_str().to_string(),
+            reserved: false,
+            ascii: false,
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(Box::new(io::stdout().lock()))
+    }
+
+    /// Writes everything in ASCII from now on, for consoles that would show
+    /// other characters as mojibake.
+    pub fn set_ascii(&mut self) {
+        self.ascii = true;
+    }
+
+    /// Sends status output to `status` instead of the log stream.
+    pub fn route_status(&mut self, status: Box<dyn Write>) {
+        self.status = Some(status);
+    }
+
+    /// Writes an assembled line; `Line` already closed each of its segments.
+    pub fn line(&mut self, line: &Line) -> io::Result<()> {
+        self.write_line(false, &line.buf, false)
+    }
+
+    /// Writes `text` as one line, closing any escape sequence it left open.
+    pub fn text(&mut self, text: &str) -> io::Result<()> {
+        self.write_line(false, text, leaves_open(text))
+    }
+
+    pub fn status_line(&mut self, line: &Line) -> io::Result<()> {
+        self.write_line(true, &line.buf, false)
+    }
+
+    pub fn status_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_line(true, text, leaves_open(text))
+    }
+
+    pub fn status_blank(&mut self) -> io::Result<()> {
+        self.stream(true).write_all(b"\n")
+    }
+
+    fn write_line(&mut self, status: bool, text: &str, close: bool) -> io::Result<()> {
+        let mut buf: String = String::with_capacity(text.len() + self.reset.len() + 1);
+        match self.ascii {
+            true => buf.push_str(&codepage::to_ascii(text)),
+            false => buf.push_str(text),
+        }
+        if close {
+            buf.push_str(&self.reset);
+        }
+        buf.push('\n');
+        self.stream(status).write_all(buf.as_bytes())
+    }
+
+    fn stream(&mut self, status: bool) -> &mut dyn Write {
+        match &mut self.status {
+            Some(w) if status => w,
+            _ => &mut self.out,
+        }
+    }
+
+    /// Writes terminal control sequences that don't produce visible text.
+    /// They count as status output.
+    pub fn control(&mut self, seq: &str) -> io::Result<()> {
+        self.stream(true).write_all(seq.as_bytes())
+    }
+
+    /// Holds the top `rows` terminal rows, plus a separator, out of the
+    /// scroll region so `pin` can keep lines there.
+    pub fn reserve_rows(&mut self, rows: usize, separator: &Line) -> io::Result<()> {
+        let mut buf: String = String::new();
+        for row in 1..=rows {
+            buf.push_str(&format!("\x1b[{};1H\x1b[2K", row));
+        }
+        buf.push_str(&format!(
+            "\x1b[{};1H\x1b[2K{}\x1b[{};r\x1b[999;1H",
+            rows + 1,
+            separator.buf,
+            rows + 2
+        ));
+        self.reserved = true;
+        self.out.write_all(buf.as_bytes())
+    }
+
+    /// Gives the reserved rows back to the scroll region.
+    pub fn release_rows(&mut self) -> io::Result<()> {
+        if !std::mem::take(&mut self.reserved) {
+            return Ok(());
+        }
+        self.out.write_all(b"\x1b[r\x1b[999;1H")
+    }
+
+    /// Replaces reserved row `row` (1-based) with `line`, cut at the
+    /// terminal's edge, leaving the cursor where it was.
+    pub fn pin(&mut self, row: usize, line: &Line) -> io::Result<()> {
+        let text: Cow<str> = match self.ascii {
+            true => codepage::to_ascii(&line.buf),
+            false => Cow::Borrowed(&line.buf),
+        };
+        let buf: String = format!("\x1b7\x1b[{};1H\x1b[2K\x1b[?7l{}\x1b[?7h\x1b8", row, text);
+        self.out.write_all(buf.as_bytes())
+    }
+
+    pub fn blank(&mut self) -> io::Result<()> {
+        self.out.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(w) = &mut self.status {
+            w.flush()?;
+        }
+        self.out.flush()
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        // Runs on early returns and panics too; lines are written whole, so
+        // only buffered bytes can be outstanding.
+        let _ = self.release_rows();
+        let _ = self.flush();
+    }
+}
+
+// Whether the last SGR sequence in `text` sets attributes rather than clearing
+// them: it's closed once it resets all (0, or nothing) and only resets single
+// attributes after that, so `\x1b[0;31m` leaves red in effect
+fn leaves_open(text: &str) -> bool {
+    let Some(start) = text.rfind("\x1b[") else {
+        return false;
+    };
+    let params: &str = &text[start + 2..];
+    // Truncated sequence
+    let Some(end) = params.find('m') else {
+        return true;
+    };
+    let mut closed: bool = false;
+    let mut codes = params[..end].split(';');
+    while let Some(code) = codes.next() {
+        closed = match code {
+            "" | "0" => true,
+            "22" | "23" | "24" | "25" | "27" | "28" | "29" | "39" | "49" | "59" => closed,
+            // Extended colors, whose arguments aren't codes of their own
+            "38" | "48" | "58" => {
+                match codes.next() {
+                    Some("5") => codes.next(),
+                    Some("2") => codes.nth(2),
+                    _ => None,
+                };
+                false
+            }
+            _ => false,
+        };
+    }
+    !closed
+}
+
+// Per-entry decorations on top of the level colors
+#[derive(Default)]
+pub struct Decor<'a> {
+    pub event: Option<EventKind>,
+    pub msg_override: Option<&'a Seq>,
+    pub pid_seq: Option<&'a Seq>,
+    pub out_of_order: bool,
+    pub spotlight: Option<&'a Spot>,
+    pub prev_timestamp: Option<&'a str>, // of the entry printed before, for --sparse-time
+    pub change: Option<&'a Change>,      // against the tag's previous --diff-tag dump
+    pub humanized: &'a [(usize, String)], // --humanize annotations, after these offsets
+    pub tag_seq: Option<&'a Seq>,        // the tag's own --color-tags color
+    pub context: bool,                   // shown around a --grep match, dimmed
+}
+
+pub fn level_seqs<'t>(level: &str, theme: &'t Theme) -> (&'t Seq, &'t Seq) {
+    match level {
+        "V" => (&theme.id_verbose, &theme.msg_verbose),
+        "D" => (&theme.id_debug, &theme.msg_debug),
+        "I" => (&theme.id_info, &theme.msg_info),
+        "W" => (&theme.id_warning, &theme.msg_warning),
+        "E" => (&theme.id_error, &theme.msg_error),
+        "F" => (&theme.id_fatal, &theme.msg_fatal),
+        "A" => (&theme.id_assert, &theme.msg_assert),
+        _ => (&theme.reset, &theme.reset),
+    }
+}
+
+/// Lane badge and, for some events, a message style overriding the level's.
+pub fn event_seqs(kind: EventKind, theme: &Theme) -> (&Seq, Option<&Seq>) {
+    match kind {
+        EventKind::Security => (&theme.id_security, Some(&theme.msg_security)),
+        EventKind::Crash => (&theme.id_fatal, Some(&theme.msg_fatal)),
+        EventKind::Anr => (&theme.id_error, Some(&theme.msg_error)),
+        EventKind::Blocking => (&theme.id_perf, Some(&theme.msg_perf)),
+        EventKind::Custom(_) => match kind.custom().and_then(|c| c.style.as_ref()) {
+            Some(style) => (style, None),
+            None => (&theme.id_event, None),
+        },
+        _ => (&theme.id_event, None),
+    }
+}
+
+/// Lays out one parsed entry.
+pub fn entry(l: &Logcat, theme: &Theme, opts: &Options, decor: &Decor) -> Line {
+    let spot: Option<&Spot> = decor.spotlight;
+
+    // Level colors
+    let (id_seq, msg_seq) = level_seqs(&l.level, theme);
+    let (tag_seq, msg_seq) = match l.source {
+        Source::Device => (decor.tag_seq.unwrap_or(&theme.tag), msg_seq),
+        Source::Host => (&theme.host_tag, &theme.msg_host),
+    };
+    let msg_seq: &Seq = decor
+        .msg_override
+        .or_else(|| decor.event.and_then(|k: EventKind| event_seqs(k, theme).1))
+        .unwrap_or(msg_seq);
+
+    let mut line: Line = Line::new();
+    if decor.context {
+        line.backdrop(&theme.context);
+    } else if opts.spotlight_line
+        && let Some(style) = spot.and_then(|s: &Spot| {
+            [
+                l.message.as_str(),
+                l.tag.trim(),
+                &l.timestamp,
+                &l.uid,
+                &l.process,
+            ]
+            .iter()
+            .find_map(|text: &&str| s.first(text))
+        })
+    {
+        line.backdrop(style);
+    }
+
+    // Worker threads indented behind a gutter mark in the thread's color
+    if opts.thread_indent && !l.thread.is_empty() && l.thread != l.process {
+        let alt: &[Seq] = &theme.tid_pid_alt;
+        let tid: usize = l.thread.parse().unwrap_or(0);
+        let seq: &Seq = alt.get(tid % alt.len().max(1)).unwrap_or(&theme.tid_pid);
+        line.plain("  ").styled(seq, "│").plain(" ");
+    }
+
+    // Timestamp, in sparse mode with the date only when it changes and the
+    // milliseconds dimmed
+    match split_timestamp(&l.timestamp).filter(|_| opts.sparse_time) {
+        Some((date, time, frac)) => {
+            let same_day: bool = decor
+                .prev_timestamp
+                .and_then(split_timestamp)
+                .is_some_and(|(prev, _, _)| prev == date);
+            if same_day {
+                line.plain(&" ".repeat(date.len() + 1));
+            } else {
+                line.spotted(&theme.timestamp, date, spot).plain(" ");
+            }
+            line.spotted(&theme.timestamp, time, spot)
+                .spotted(&theme.timestamp_fraction, frac, spot)
+                .plain(" ");
+        }
+        None if !l.timestamp.is_empty() => {
+            line.spotted(&theme.timestamp, &l.timestamp, spot)
+                .plain(" ");
+        }
+        None => {}
+    }
+
+    // Which --merge capture the entry came from
+    if let Some(i) = l.origin
+        && let Some(capture) = opts.captures.get(i)
+    {
+        let width: usize = opts
+            .captures
+            .iter()
+            .map(|c: &Capture| c.label.len())
+            .max()
+            .unwrap_or(0);
+        let alt: &[Seq] = &theme.tid_pid_alt;
+        let seq: &Seq = alt.get(i % alt.len().max(1)).unwrap_or(&theme.tag);
+        line.styled(seq, &format!("{:<w$}", capture.label, w = width))
+            .plain(" ");
+    }
+
+    // Level
+    if !l.level.is_empty() {
+        line.badge(id_seq, &l.level);
+    }
+
+    // Event lane
+    if decor.out_of_order {
+        line.badge(&theme.out_of_order, "OOO");
+    }
+    if let Some(kind) = decor.event {
+        line.badge(event_seqs(kind, theme).0, kind.label());
+    }
+
+    // uid (`-v uid`)
+    if !l.uid.is_empty() {
+        line.spotted(&theme.uid, &l.uid, spot).plain(" ");
+    }
+
+    // Secondary user or work profile, unless a `uN_` uid name already says so
+    if let Some(user) = l.user.filter(|u: &u32| *u != 0)
+        && !l.uid.starts_with('u')
+    {
+        line.styled(&theme.user, &format!("u{}", user)).plain(" ");
+    }
+
+    // [pid/tid]
+    if !l.process.is_empty() {
+        let bracket: String = if l.thread.is_empty() {
+            format!("[{}]", l.process)
+        } else {
+            format!("[{}/{}]", l.process, l.thread)
+        };
+        let pid_seq: &Seq = decor.pid_seq.unwrap_or(&theme.tid_pid);
+        line.spotted(pid_seq, &bracket, spot).plain(" ");
+    }
+
+    // Tag, aligned to a fixed number of columns if asked
+    if !l.tag.is_empty() {
+        let tag: String = match opts.tag_width {
+            Some(cols) => width::fit(l.tag.trim_end(), cols),
+            None => l.tag.clone(),
+        };
+        line.spotted(tag_seq, &tag, spot).plain(" ");
+    }
+
+    // Message; of a state dump, only what changed stands out
+    if let Some(change) = decor.change {
+        let mut at: usize = 0;
+        for span in &change.spans {
+            line.styled(&theme.diff_unchanged, &l.message[at..span.start])
+                .styled(&theme.diff_changed, &l.message[span.clone()]);
+            at = span.end;
+        }
+        line.styled(&theme.diff_unchanged, &l.message[at..])
+            .plain(" ");
+        if !change.gone.is_empty() {
+            line.styled(
+                &theme.diff_changed,
+                &format!("[gone: {}]", change.gone.join(", ")),
+            )
+            .plain(" ");
+        }
+    } else if !l.message.is_empty() {
+        let auto: bool = opts.auto_highlight;
+        let mut at: usize = 0;
+        for (end, text) in decor.humanized {
+            message(&mut line, msg_seq, &l.message[at..*end], spot, theme, auto);
+            line.styled(&theme.humanized, &format!(" ({})", text));
+            at = *end;
+        }
+        message(&mut line, msg_seq, &l.message[at..], spot, theme, auto);
+        line.plain(" ");
+    }
+
+    line
+}
+
+// Message text: with --auto-highlight, the URLs, addresses, paths and
+// durations in their slots' colors over the level's, and the spotlight's
+// matches over both in their rules' styles
+fn message(
+    line: &mut Line,
+    style: &Seq,
+    text: &str,
+    spot: Option<&Spot>,
+    theme: &Theme,
+    auto: bool,
+) {
+    if !auto {
+        line.spotted(style, text, spot);
+        return;
+    }
+    let found: Vec<(Range<usize>, Kind)> = autohighlight::find(text);
+    let spots: Vec<(Range<usize>, &Seq)> = spot
+        .into_iter()
+        .flat_map(|s: &Spot| s.find_iter(text))
+        .map(|(m, seq)| (m.range(), seq))
+        .collect();
+    let mut cuts: Vec<usize> = found
+        .iter()
+        .map(|(r, _)| r)
+        .chain(spots.iter().map(|(r, _)| r))
+        .flat_map(|r: &Range<usize>| [r.start, r.end])
+        .chain([0, text.len()])
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+    for piece in cuts.windows(2) {
+        let (at, to) = (piece[0], piece[1]);
+        if let Some((_, seq)) = spots.iter().find(|(r, _)| r.contains(&at)) {
+            line.styled(seq, &text[at..to]);
+        } else if let Some((_, kind)) = found.iter().find(|(r, _)| r.contains(&at)) {
+            let seq: &Seq = match kind {
+                Kind::Url => &theme.auto_url,
+                Kind::Address => &theme.auto_address,
+                Kind::Hex => &theme.auto_hex,
+                Kind::Path => &theme.auto_path,
+                Kind::Duration => &theme.auto_duration,
+            };
+            line.nested(seq, style, &text[at..to]);
+        } else {
+            line.styled(style, &text[at..to]);
+        }
+    }
+}
+
+// "MM-DD HH:MM:SS.mmm" -> ("MM-DD", "HH:MM:SS", ".mmm")
+fn split_timestamp(ts: &str) -> Option<(&str, &str, &str)> {
+    let (date, clock) = ts.split_once(' ')?;
+    let dot: usize = clock.find('.')?;
+    Some((date, &clock[..dot], &clock[dot..]))
+}
+
+/// A line of non-logcat output as it came, with only the spotlight applied.
+pub fn passthrough(text: &str, theme: &Theme, spot: Option<&Spot>, whole_line: bool) -> Line {
+    let mut line: Line = Line::new();
+    if whole_line && let Some(style) = spot.and_then(|s: &Spot| s.first(text)) {
+        line.backdrop(style);
+    }
+    line.spotted(&theme.reset, text, spot);
+    line
+}
+
+/// A single-style annotation line, e.g. a banner or a pid reuse notice.
+pub fn note(style: &Seq, text: &str) -> Line {
+    let mut line: Line = Line::new();
+    line.styled(style, text);
+    line
+}
+
+/// A `--------- beginning of main` buffer separator as a rule across `cols`
+/// columns, with the buffer name as a badge.
+pub fn divider(theme: &Theme, action: &str, buffer: &str, cols: usize) -> Line {
+    let lead: String = format!("──── {} ", action);
+    let used: usize = width::str_width(&lead) + width::str_width(buffer) + 3;
+    let mut line: Line = Line::new();
+    line.styled(&theme.divider, &lead)
+        .badge(&theme.divider_buffer, buffer)
+        .styled(&theme.divider, &"─".repeat(cols.saturating_sub(used)));
+    line
+}
+
+/// The `--after-command` marker: a rule like the buffer dividers, with how
+/// the command ended as a badge.
+pub fn command_marker(theme: &Theme, finished: &Finished, cols: usize) -> Line {
+    let lead: String = format!("──── {} ", finished.command);
+    let secs: f64 = finished.elapsed.as_secs_f64();
+    let (style, label) = match &finished.status {
+        Ok(()) if finished.cleared.as_ref().is_some_and(|c| c.is_ok()) => (
+            &theme.command_ok,
+            format!("done in {:.1}s, buffers cleared", secs),
+        ),
+        Ok(()) => (&theme.command_ok, format!("done in {:.1}s", secs)),
+        Err(status) => (
+            &theme.command_failed,
+            format!("{} after {:.1}s", status, secs),
+        ),
+    };
+    let used: usize = width::str_width(&lead) + width::str_width(&label) + 3;
+    let mut line: Line = Line::new();
+    line.styled(&theme.divider, &lead)
+        .badge(style, &label)
+        .styled(&theme.divider, &"─".repeat(cols.saturating_sub(used)));
+    line
+}
+
+/// Concise one-line form of a classified entry, for `--events-only`.
+pub fn event(l: &Logcat, kind: EventKind, theme: &Theme) -> Line {
+    let (lane_seq, msg_seq) = event_seqs(kind, theme);
+    let msg_seq: &Seq = msg_seq.unwrap_or(level_seqs(&l.level, theme).1);
+
+    let mut line: Line = Line::new();
+    if !l.timestamp.is_empty() {
+        line.styled(&theme.timestamp, &l.timestamp).plain(" ");
+    }
+    line.badge(lane_seq, &format!("{:<5}", kind.label()))
+        .styled(&theme.tag, l.tag.trim())
+        .plain(" ")
+        .styled(msg_seq, &l.message);
+    for (name, value) in kind
+        .custom()
+        .map(|c| c.extract(&l.message))
+        .unwrap_or_default()
+    {
+        line.plain(" ")
+            .styled(&theme.tag, &format!("{}={}", name, value));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ansi::Depth, testutil::Shared, theme::make_theme};
+
+    fn styles() -> Theme {
+        ansi::set_thread_depth(Depth::TrueColor);
+        make_theme()
+    }
+
+    // Every line that opens a style closes it again: its last SGR sequence
+    // (`\x1b[0m`, or the renderer's `\x1b[0;49;39m`) leaves the defaults
+    // in effect, and nothing styled follows it
+    fn assert_closed(output: &str) {
+        for line in output.lines() {
+            let Some(start) = line.rfind("\x1b[") else {
+                continue;
+            };
+            let params: &str = &line[start + 2..];
+            let end: usize = params
+                .find('m')
+                .unwrap_or_else(|| panic!("truncated: {:?}", line));
+            let codes: Vec<&str> = params[..end].split(';').collect();
+            let resets: bool = codes
+                .iter()
+                .rposition(|code: &&str| matches!(*code, "" | "0"))
+                .is_some_and(|last: usize| {
+                    codes[last + 1..]
+                        .iter()
+                        .all(|code: &&str| matches!(*code, "39" | "49"))
+                });
+            assert!(resets, "left open: {:?}", line);
+        }
+    }
+
+    // Bytes of SGR sequences in `text`
+    fn escape_bytes(text: &str) -> usize {
+        text.split('\x1b')
+            .skip(1)
+            .map(|s: &str| s.find('m').map_or(0, |end: usize| end + 2))
+            .sum()
+    }
+
+    // Entries of every level and a few decorations, as a saved capture
+    // would show them
+    const CAPTURE: [&str; 8] = [
+        "06-01 12:00:00.100  1234  1240 V SyncService: Looking up service",
+        "06-01 12:00:00.200  1234  1240 D OkHttp: --> GET https://api.example.com/v1",
+        "06-01 12:00:00.300  1234  1240 I ActivityManager: Displayed com.example/.Main",
+        "06-01 12:00:00.400  1234  1240 W Looper: Slow dispatch took 230ms",
+        "06-01 12:00:00.500  1234  1240 E Loader: Failed to connect to 10.0.2.2:5555",
+        "06-01 12:00:00.600  1234  1240 F DEBUG: Fatal signal 11 (SIGSEGV)",
+        "06-01 12:00:00.700  1234  1240 A libc: Assertion failed",
+        "06-01 12:00:00.800  1234  1240 I ActivityManager: Start proc 4321:com.example",
+    ];
+
+    // The escape bytes rendering CAPTURE took when redundant sequences were
+    // first skipped; more means a regression
+    const CAPTURE_ESCAPE_BUDGET: usize = 319;
+
+    #[test]
+    fn escape_bytes_stay_within_budget() {
+        let theme: Theme = styles();
+        let opts: Options = Options::default();
+        let mut total: usize = 0;
+        let mut naive: usize = 0;
+        for (i, text) in CAPTURE.iter().enumerate() {
+            let lc: Logcat = crate::testutil::entry(text);
+            let decor: Decor = Decor {
+                event: (i == 5).then_some(EventKind::Crash),
+                ..Decor::default()
+            };
+            let line: Line = entry(&lc, &theme, &opts, &decor);
+            assert_closed(&line.buf);
+            total += escape_bytes(&line.buf);
+            // Every field opening its full style and closing it again, as
+            // before sequences were trimmed
+            let (id, msg) = level_seqs(&lc.level, &theme);
+            naive += [&theme.timestamp, id, &theme.tid_pid, &theme.tag, msg]
+                .iter()
+                .map(|seq: &&Seq| seq.as_str().len() + CLOSE.len())
+                .sum::<usize>();
+        }
+        assert!(
+            total <= CAPTURE_ESCAPE_BUDGET,
+            "{} escape bytes, budget {}",
+            total,
+            CAPTURE_ESCAPE_BUDGET
+        );
+        assert!(total * 10 < naive * 6, "{} of naively {}", total, naive);
+    }
+
+    #[test]
+    fn thread_indent_sets_worker_threads_off() {
+        let opts: Options = Options {
+            thread_indent: true,
+            ..Options::default()
+        };
+        let render = |theme: &Theme, text: &str| -> String {
+            entry(
+                &crate::testutil::entry(text),
+                theme,
+                &opts,
+                &Decor::default(),
+            )
+            .buf
+        };
+        ansi::set_thread_depth(Depth::Plain);
+        let plain: Theme = make_theme();
+        let main: String = render(&plain, "06-01 12:00:00.100  1234  1234 I Sync: main");
+        assert!(main.starts_with("06-01 12:00:00.100  I "), "{:?}", main);
+        let worker: String = render(&plain, "06-01 12:00:00.100  1234  1240 I Sync: worker");
+        assert!(
+            worker.starts_with("  │ 06-01 12:00:00.100  I "),
+            "{:?}",
+            worker
+        );
+
+        // The gutter mark takes the thread's color, the same for each of its entries
+        let theme: Theme = styles();
+        let gutter = |tid: &str| -> String {
+            let line: String = render(
+                &theme,
+                &format!("06-01 12:00:00.100  1234  {} I Sync: m", tid),
+            );
+            line[..line.find('│').unwrap()].to_string()
+        };
+        assert_eq!(gutter("1240"), gutter("1240"));
+        assert_ne!(gutter("1240"), gutter("1241"));
+    }
+
+    #[test]
+    #[should_panic(expected = "left open")]
+    fn open_lines_are_caught() {
+        assert_closed("\x1b[31mred\x1b[0m, then \x1b[1mbold");
+    }
+
+    // Runs beside the TrueColor tests: the depth is this thread's own
+    #[test]
+    fn depth_is_per_test() {
+        ansi::set_thread_depth(Depth::Basic);
+        let seq: Seq = Seq::parse("38;2;255;135;0").unwrap();
+        assert!(!seq.as_str().contains("38;2"), "{:?}", seq.as_str());
+
+        ansi::set_thread_depth(Depth::Plain);
+        let theme: Theme = make_theme();
+        let mut line: Line = Line::new();
+        line.styled(&theme.msg_error, "failed").plain(" plain");
+        assert_eq!(line.buf, "failed plain");
+    }
+
+    #[test]
+    fn lines_close_their_styles() {
+        let theme: Theme = styles();
+        let mut line: Line = Line::new();
+        line.styled(&theme.timestamp, "06-01 12:00:00.123")
+            .plain(" ")
+            .badge(&theme.id_error, "E")
+            .styled(&theme.tag, "Tag")
+            .plain(" ")
+            .styled(&theme.msg_error, "failed");
+        assert_closed(&line.buf);
+
+        let mut line: Line = Line::new();
+        line.styled(&theme.msg_info, "styled then").plain(" plain");
+        assert_closed(&line.buf);
+
+        let mut line: Line = Line::new();
+        line.backdrop(&theme.context);
+        line.styled(&theme.tag, "Tag").plain(" under a backdrop");
+        assert_closed(&line.buf);
+    }
+
+    #[test]
+    fn renderer_closes_text_left_open() {
+        let out: Shared = Shared::default();
+        let mut r: Renderer = Renderer::new(Box::new(out.clone()));
+        r.text("\x1b[31mred to the end").unwrap();
+        r.text("\x1b[1;32mclosed\x1b[0m").unwrap();
+        r.status_text("\x1b[35mstatus").unwrap();
+        r.text("no style").unwrap();
+        r.text("reset, then \x1b[0;31mred").unwrap();
+        r.text("\x1b[1mbold, then red and reset\x1b[31;0m").unwrap();
+        drop(r);
+        let text: String = out.text();
+        assert_eq!(text.lines().count(), 6);
+        assert_closed(&text);
+    }
+
+    #[test]
+    fn sgr_closed_only_by_a_final_reset() {
+        assert!(leaves_open("\x1b[0;31mred"));
+        assert!(!leaves_open("red\x1b[31;0m"));
+        assert!(!leaves_open("\x1b[1mbold\x1b[m"));
+        assert!(!leaves_open("\x1b[0;49;39m"));
+        // 0 as a palette index is no reset
+        assert!(leaves_open("\x1b[0;38;5;0mblack"));
+        assert!(leaves_open("\x1b[0;48;2;0;0;0mblack"));
+        assert!(leaves_open("\x1b[31"));
+        assert!(!leaves_open("plain"));
+    }
+
+    #[test]
+    fn drop_flushes_closed_lines_and_releases_rows() {
+        let theme: Theme = styles();
+        let out: Shared = Shared::default();
+        let mut r: Renderer = Renderer::new(Box::new(io::BufWriter::new(out.clone())));
+        r.reserve_rows(1, &note(&theme.divider, "pinned")).unwrap();
+        r.line(&note(&theme.msg_warning, "buffered")).unwrap();
+        r.text("\x1b[36munclosed").unwrap();
+        assert!(out.text().is_empty());
+        drop(r);
+        let text: String = out.text();
+        assert!(text.contains("buffered"));
+        assert!(text.ends_with("\x1b[r\x1b[999;1H"));
+        assert_closed(text.trim_end_matches("\x1b[r\x1b[999;1H"));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::logcat::Logcat;
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+/// Holds entries back for `window` milliseconds of stream time and releases
+/// them sorted by timestamp, undoing small reorderings from merged buffers.
+pub struct Reorder {
+    window: u64,
+    newest: u64,
+    seq: usize,
+    pending: Vec<(u64, usize, Logcat)>,
+}
+
+impl Reorder {
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            newest: 0,
+            seq: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Queues an entry, returning the ones that fell out of the window.
+    pub fn push(&mut self, ts: u64, lc: Logcat) -> Vec<Logcat> {
+        self.newest = self.newest.max(ts);
+        self.seq += 1;
+        let key: (u64, usize) = (ts, self.seq);
+        let at: usize = self
+            .pending
+            .partition_point(|(t, s, _): &(u64, usize, Logcat)| (*t, *s) < key);
+        self.pending.insert(at, (ts, self.seq, lc));
+
+        let ready: usize = self
+            .pending
+            .partition_point(|(t, _, _): &(u64, usize, Logcat)| t + self.window <= self.newest);
+        self.pending
+            .drain(..ready)
+            .map(|(_, _, lc): (u64, usize, Logcat)| lc)
+            .collect()
+    }
+
+    /// Releases everything still queued.
+    pub fn drain(&mut self) -> Vec<Logcat> {
+        self.pending
+            .drain(..)
+            .map(|(_, _, lc): (u64, usize, Logcat)| lc)
+            .collect()
+    }
+}
+
+/// Remembers the last timestamp seen for each pid/tid pair.
+#[derive(Default)]
+pub struct OrderCheck {
+    last: HashMap<(String, String), u64>,
+}
+
+impl OrderCheck {
+    /// Returns true when `lc` is older than the previous entry of its
+    /// thread, flagged or not: one late entry doesn't flag those after it.
+    pub fn is_out_of_order(&mut self, ts: u64, lc: &Logcat) -> bool {
+        let key: (String, String) = (lc.process.clone(), lc.thread.clone());
+        self.last.insert(key, ts).is_some_and(|last: u64| ts < last)
+    }
+}
+
+/// Reads `items` on a thread of its own and yields them, with a `None` each
+/// time nothing came for `quiet`, so entries held back for a later one can
+/// be let out when a live stream goes quiet.
+pub fn ticking<T: Send + 'static>(
+    items: impl Iterator<Item = T> + Send + 'static,
+    quiet: Duration,
+) -> impl Iterator<Item = Option<T>> {
+    let (tx, rx): (Sender<T>, Receiver<T>) = mpsc::channel();
+    thread::spawn(move || {
+        for item in items {
+            if tx.send(item).is_err() {
+                return;
+            }
+        }
+    });
+    std::iter::from_fn(move || match rx.recv_timeout(quiet) {
+        Ok(item) => Some(Some(item)),
+        Err(RecvTimeoutError::Timeout) => Some(None),
+        Err(RecvTimeoutError::Disconnected) => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::Dates;
+
+    fn entry(ts: &str, tid: &str) -> Logcat {
+        Logcat {
+            timestamp: ts.to_string(),
+            process: "1234".to_string(),
+            thread: tid.to_string(),
+            message: format!("{} {}", tid, ts),
+            ..Default::default()
+        }
+    }
+
+    // The stream over New Year, with 01-01 00:00:00.100 logged late
+    const STREAM: [(&str, &str); 5] = [
+        ("12-31 23:59:59.900", "1"),
+        ("01-01 00:00:00.200", "1"),
+        ("01-01 00:00:00.100", "2"),
+        ("12-31 23:59:59.950", "1"),
+        ("01-01 00:00:01.000", "2"),
+    ];
+
+    #[test]
+    fn reorders_over_new_year() {
+        let dates: Dates = Dates::default();
+        let mut reorder: Reorder = Reorder::new(500);
+        let mut out: Vec<String> = Vec::new();
+        for (ts, tid) in STREAM {
+            let ms: u64 = dates.millis(ts).unwrap();
+            out.extend(
+                reorder
+                    .push(ms, entry(ts, tid))
+                    .into_iter()
+                    .map(|lc| lc.timestamp),
+            );
+        }
+        out.extend(reorder.drain().into_iter().map(|lc| lc.timestamp));
+        assert_eq!(
+            out,
+            [
+                "12-31 23:59:59.900",
+                "12-31 23:59:59.950",
+                "01-01 00:00:00.100",
+                "01-01 00:00:00.200",
+                "01-01 00:00:01.000",
+            ]
+        );
+    }
+
+    #[test]
+    fn drains_what_a_quiet_stream_holds() {
+        let mut reorder: Reorder = Reorder::new(500);
+        // Nothing comes out while the stream stays within the window
+        for (ms, tid) in [(1_200, "1"), (1_000, "2"), (1_100, "1")] {
+            assert!(reorder.push(ms, entry(&ms.to_string(), tid)).is_empty());
+        }
+        // When it goes quiet, all of it does, in order
+        let drained: Vec<String> = reorder.drain().into_iter().map(|lc| lc.timestamp).collect();
+        assert_eq!(drained, ["1000", "1100", "1200"]);
+        assert!(reorder.drain().is_empty());
+        // And the window starts over from the next entry
+        assert!(reorder.push(1_300, entry("1300", "1")).is_empty());
+        let released: Vec<String> = reorder
+            .push(1_800, entry("1800", "1"))
+            .into_iter()
+            .map(|lc| lc.timestamp)
+            .collect();
+        assert_eq!(released, ["1300"]);
+    }
+
+    #[test]
+    fn ticks_while_the_stream_is_quiet() {
+        let quiet = std::iter::from_fn(|| {
+            thread::sleep(Duration::from_millis(120));
+            None
+        });
+        let items: Vec<Option<&str>> = ticking(
+            std::iter::once("one").chain(quiet),
+            Duration::from_millis(20),
+        )
+        .collect();
+        assert_eq!(items[0], Some("one"));
+        assert!(items.len() > 2 && items[1..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn checks_order_per_thread_over_new_year() {
+        let dates: Dates = Dates::default();
+        let mut order: OrderCheck = OrderCheck::default();
+        let flagged: Vec<&str> = STREAM
+            .iter()
+            .filter(|(ts, tid)| order.is_out_of_order(dates.millis(ts).unwrap(), &entry(ts, tid)))
+            .map(|(ts, _)| *ts)
+            .collect();
+        // Only the December entry after thread 1 reached January
+        assert_eq!(flagged, ["12-31 23:59:59.950"]);
+    }
+
+    #[test]
+    fn checks_order_against_the_previous_entry() {
+        let dates: Dates = Dates::default();
+        let mut order: OrderCheck = OrderCheck::default();
+        let flagged: Vec<&str> = [
+            "06-01 12:00:00.300",
+            "06-01 12:00:00.100",
+            "06-01 12:00:00.200",
+        ]
+        .into_iter()
+        .filter(|ts| order.is_out_of_order(dates.millis(ts).unwrap(), &entry(ts, "1")))
+        .collect();
+        // .200 is newer than the .100 before it, though older than .300
+        assert_eq!(flagged, ["06-01 12:00:00.100"]);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{adb, timestamp};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+// Rotated files kept unless `keep=` says otherwise
+const KEEP: usize = 5;
+
+/// When the `--tee` capture of a long session is rotated
+/// (`--rotate size=100M,keep=5,gzip` or `time=1h`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Policy {
+    size: Option<u64>,
+    every: Option<Duration>,
+    keep: usize,
+    gzip: bool,
+}
+
+pub fn parse_policy(s: &str) -> Result<Policy, String> {
+    let mut policy: Policy = Policy {
+        size: None,
+        every: None,
+        keep: KEEP,
+        gzip: false,
+    };
+    for part in s.split(',').map(str::trim) {
+        match part.split_once('=') {
+            Some(("size", v)) => {
+                policy.size = Some(
+                    adb::parse_size(v)
+                        .filter(|n: &u64| *n > 0)
+                        .ok_or_else(|| format!("invalid size `{}` (expected e.g. 100M)", v))?,
+                );
+            }
+            Some(("time", v)) => {
+                policy.every = Some(
+                    timestamp::parse_duration(v)
+                        .filter(|ms: &u64| *ms > 0)
+                        .map(Duration::from_millis)
+                        .ok_or_else(|| format!("invalid time `{}` (expected e.g. 1h)", v))?,
+                );
+            }
+            Some(("keep", v)) => {
+                policy.keep = v
+                    .parse()
+                    .ok()
+                    .filter(|n: &usize| *n > 0)
+                    .ok_or_else(|| format!("invalid keep `{}` (expected a count from 1)", v))?;
+            }
+            None if part == "gzip" => policy.gzip = true,
+            _ => {
+                return Err(format!(
+                    "unknown `{}` (expected size=, time=, keep= or gzip)",
+                    part
+                ));
+            }
+        }
+    }
+    match policy.size.is_some() || policy.every.is_some() {
+        true => Ok(policy),
+        false => Err("a rotation needs size= or time=".to_string()),
+    }
+}
+
+/// One output file followed against a policy. Rotating moves it aside as
+/// `NAME.1` (`NAME.1.gz` with gzip), the one before to `NAME.2` and so on,
+/// dropping the one past `keep`; the writer then starts the file afresh.
+pub struct Rotation {
+    policy: Policy,
+    path: PathBuf,
+    since: Instant,
+    gzip: Option<Child>,
+}
+
+impl Rotation {
+    pub fn new(path: &Path, policy: Policy) -> Self {
+        Self {
+            policy,
+            path: path.to_path_buf(),
+            since: Instant::now(),
+            gzip: None,
+        }
+    }
+
+    /// Whether the file, `size` bytes so far, is due to be rotated.
+    pub fn due(&self, size: u64) -> bool {
+        size > 0
+            && (self.policy.size.is_some_and(|max: u64| size >= max)
+                || self
+                    .policy
+                    .every
+                    .is_some_and(|every: Duration| self.since.elapsed() >= every))
+    }
+
+    pub fn rotate(&mut self) -> io::Result<()> {
+        // The previous one has to be compressed before it moves on
+        self.wait();
+        let ext: &str = if self.policy.gzip { ".gz" } else { "" };
+        let err = |e: io::Error| io::Error::other(format!("{}: {}", self.path.display(), e));
+        match fs::remove_file(self.rotated(self.policy.keep, ext)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(err(e)),
+            _ => {}
+        }
+        for n in (1..self.policy.keep).rev() {
+            let from: PathBuf = self.rotated(n, ext);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1, ext)).map_err(err)?;
+            }
+        }
+        let first: PathBuf = self.rotated(1, "");
+        fs::rename(&self.path, &first).map_err(err)?;
+        self.since = Instant::now();
+        if self.policy.gzip {
+            let child: Child = Command::new("gzip")
+                .arg("-f")
+                .arg(&first)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|e: io::Error| io::Error::other(format!("gzip: {}", e)))?;
+            self.gzip = Some(child);
+        }
+        Ok(())
+    }
+
+    // `NAME.<n><ext>`
+    fn rotated(&self, n: usize, ext: &str) -> PathBuf {
+        let mut name: std::ffi::OsString = self.path.clone().into_os_string();
+        name.push(format!(".{}{}", n, ext));
+        PathBuf::from(name)
+    }
+
+    fn wait(&mut self) {
+        if let Some(mut child) = self.gzip.take() {
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for Rotation {
+    fn drop(&mut self) {
+        self.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies() {
+        let policy: Policy = parse_policy("size=100M,keep=3,gzip").unwrap();
+        assert_eq!(policy.size, Some(100 << 20));
+        assert_eq!((policy.keep, policy.gzip), (3, true));
+        let policy: Policy = parse_policy("time=1h").unwrap();
+        assert_eq!(policy.every, Some(Duration::from_secs(3600)));
+        assert_eq!(policy.keep, KEEP);
+        assert!(parse_policy("keep=3").is_err());
+        assert!(parse_policy("size=lots").is_err());
+        assert!(parse_policy("size=1M,weekly").is_err());
+    }
+
+    #[test]
+    fn keeps_the_newest() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("rotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join("capture.txt");
+        let mut rotation: Rotation = Rotation::new(&path, parse_policy("size=3,keep=2").unwrap());
+        for text in ["one", "two", "three"] {
+            fs::write(&path, text).unwrap();
+            assert!(rotation.due(text.len() as u64));
+            rotation.rotate().unwrap();
+        }
+        assert!(!path.exists());
+        assert!(!rotation.due(0));
+        let read = |n: usize| fs::read_to_string(dir.join(format!("capture.txt.{}", n))).ok();
+        assert_eq!(read(1).as_deref(), Some("three"));
+        assert_eq!(read(2).as_deref(), Some("two"));
+        assert_eq!(read(3), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
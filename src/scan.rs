@@ -0,0 +1,388 @@
+use crate::logcat::{FormatKind, Logcat};
+
+/// The formats `parse` reads by hand; the others only have their regex.
+pub const KINDS: [FormatKind; 6] = [
+    FormatKind::ThreadTime,
+    FormatKind::Epoch,
+    FormatKind::Monotonic,
+    FormatKind::Time,
+    FormatKind::Brief,
+    FormatKind::Tag,
+];
+
+/// Parses `line` as one of `KINDS` with a byte scanner, taking the same
+/// lines as the format's regex and giving the same fields; None when it
+/// doesn't match or the format isn't one of `KINDS`.
+pub fn parse(kind: FormatKind, line: &str) -> Option<Logcat> {
+    // The regexes' `.` stops at newlines
+    if line.contains('\n') {
+        return None;
+    }
+    match kind {
+        FormatKind::ThreadTime => {
+            let (timestamp, rest) = date_time(line)?;
+            threadtime(timestamp, rest)
+        }
+        FormatKind::Epoch | FormatKind::Monotonic => {
+            let line: &str = line.trim_start();
+            let seconds: usize = digits(line);
+            let epoch: bool = kind == FormatKind::Epoch;
+            if (epoch && seconds < 9) || (!epoch && !(1..=8).contains(&seconds)) {
+                return None;
+            }
+            let fraction: usize = digits(line[seconds..].strip_prefix('.')?);
+            if fraction < 3 {
+                return None;
+            }
+            let (timestamp, rest) = line.split_at(seconds + 1 + fraction);
+            threadtime(timestamp, rest)
+        }
+        FormatKind::Time => {
+            let (timestamp, rest) = date_time(line)?;
+            let rest: &str = rest.strip_prefix(':').unwrap_or(rest);
+            let lc: Logcat = brief(rest.strip_prefix(' ')?, true)?;
+            Some(Logcat {
+                timestamp: timestamp.to_string(),
+                ..lc
+            })
+        }
+        FormatKind::Brief => brief(line, false),
+        FormatKind::Tag => {
+            let (level, rest) = level(line)?;
+            let (tag, message) = tag_message(rest.strip_prefix('/')?)?;
+            Some(Logcat {
+                level: level.to_string(),
+                tag: tag.to_string(),
+                message: message.to_string(),
+                ..Default::default()
+            })
+        }
+        _ => None,
+    }
+}
+
+// `[YYYY-]MM-DD hh:mm:ss.mmm[uuu[nnn]]` at the start of `line`, and what
+// follows it
+fn date_time(line: &str) -> Option<(&str, &str)> {
+    let b: &[u8] = line.as_bytes();
+    let year: usize = match digits(line) == 4 && b.get(4) == Some(&b'-') {
+        true => 5,
+        false => 0,
+    };
+    let layout: &[u8] = b"00-00 00:00:00.000";
+    let date: &[u8] = b.get(year..year + layout.len())?;
+    let matches: bool = layout.iter().zip(date).all(|(l, c)| match l {
+        b'0' => c.is_ascii_digit(),
+        _ => l == c,
+    });
+    if !matches {
+        return None;
+    }
+    let end: usize = year + layout.len() - 3;
+    let fraction: usize = digits(&line[end..]);
+    if !matches!(fraction, 3 | 6 | 9) {
+        return None;
+    }
+    Some(line.split_at(end + fraction))
+}
+
+// The threadtime layout after its timestamp: `[uid] pid tid L tag: msg`
+fn threadtime(timestamp: &str, rest: &str) -> Option<Logcat> {
+    let rest: &str = spaces(rest)?;
+    let (first, rest) = word(rest)?;
+    let rest: &str = spaces(rest)?;
+    let (second, rest) = word(rest)?;
+    // Without a uid, the tid is followed by the level; with one, the tid
+    // is a third word
+    let (uid, pid, tid, rest) = match level_field(rest) {
+        Some(rest) => ("", first, second, rest),
+        None => {
+            let (tid, rest) = word(spaces(rest)?)?;
+            (first, second, tid, level_field(rest)?)
+        }
+    };
+    if !is_number(pid) || !is_number(tid) {
+        return None;
+    }
+    let (level, rest) = level(&rest[1..])?;
+    let (tag, message) = tag_message(rest.strip_prefix(' ')?)?;
+    Some(Logcat {
+        timestamp: timestamp.to_string(),
+        uid: uid.to_string(),
+        process: pid.to_string(),
+        thread: tid.to_string(),
+        level: level.to_string(),
+        tag: tag.to_string(),
+        message: message.to_string(),
+        ..Default::default()
+    })
+}
+
+// `L/tag([uid:]pid): msg`, the brief layout also ending time lines, where
+// spaces may come before the colon (`spaced`)
+fn brief(line: &str, spaced: bool) -> Option<Logcat> {
+    let (level, rest) = level(line)?;
+    let rest: &str = rest.strip_prefix('/')?;
+    // The tag is the shortest prefix after which the pid field follows
+    rest.match_indices('(').find_map(|(i, _)| {
+        let (uid, pid, message) = pid_field(&rest[i + 1..], spaced)?;
+        Some(Logcat {
+            level: level.to_string(),
+            tag: rest[..i].to_string(),
+            uid: uid.trim().to_string(),
+            process: pid.trim().to_string(),
+            message: message.to_string(),
+            ..Default::default()
+        })
+    })
+}
+
+// `[ uid:]pid): msg` after a tag's opening parenthesis, the uid being
+// tried first
+fn pid_field(s: &str, spaced: bool) -> Option<(&str, &str, &str)> {
+    let with_uid = || -> Option<(&str, &str, &str)> {
+        let (uid, rest) = word(s.trim_start())?;
+        let (pid, message) = pid_tail(rest.strip_prefix(':')?, spaced)?;
+        Some((uid, pid, message))
+    };
+    with_uid().or_else(|| {
+        let (pid, message) = pid_tail(s, spaced)?;
+        Some(("", pid, message))
+    })
+}
+
+// `pid): msg`, pids being digits and spaces
+fn pid_tail(s: &str, spaced: bool) -> Option<(&str, &str)> {
+    let end: usize = s
+        .bytes()
+        .take_while(|b: &u8| *b == b' ' || b.is_ascii_digit())
+        .count();
+    if end == 0 {
+        return None;
+    }
+    let mut rest: &str = s[end..].strip_prefix(')')?;
+    if spaced {
+        rest = rest.trim_start();
+    }
+    Some((&s[..end], message(rest.strip_prefix(':')?)?))
+}
+
+/// Tags that put a parenthesized qualifier and another `: ` before the
+/// message, as in `ReactNativeJS: (dev): msg`. After any other tag, a
+/// message may well start with `(1/3): `. The format regexes spell these
+/// out too.
+pub const QUALIFIED_TAGS: [&str; 1] = ["ReactNativeJS"];
+
+// `tag: msg`: the tag ends at the first colon followed by a space or the
+// end of the line, taking along the qualifier of a QUALIFIED_TAGS tag
+fn tag_message(s: &str) -> Option<(&str, &str)> {
+    let (i, msg) = s
+        .match_indices(':')
+        .find_map(|(i, _)| Some((i, message(&s[i + 1..])?)))?;
+    match qualifier(msg).filter(|_| QUALIFIED_TAGS.contains(&&s[..i])) {
+        Some(len) => Some((&s[..i + 2 + len], message(&msg[len + 1..])?)),
+        None => Some((&s[..i], msg)),
+    }
+}
+
+// The length of a `(...)` group without nested parentheses at the start of
+// `msg`, when a colon ending a tag follows it
+fn qualifier(msg: &str) -> Option<usize> {
+    let inner: &str = msg.strip_prefix('(')?;
+    let close: usize = inner
+        .find(['(', ')'])
+        .filter(|c: &usize| inner.as_bytes()[*c] == b')')?;
+    let len: usize = close + 2;
+    message(msg[len..].strip_prefix(':')?)?;
+    Some(len)
+}
+
+// What follows a colon ending a tag: nothing, or a space and the message
+fn message(s: &str) -> Option<&str> {
+    match s.is_empty() {
+        true => Some(""),
+        false => s.strip_prefix(' '),
+    }
+}
+
+// The level letter at the start of `s`, and what follows it
+fn level(s: &str) -> Option<(&str, &str)> {
+    match s.as_bytes().first()? {
+        b'V' | b'D' | b'I' | b'W' | b'E' | b'F' | b'A' => Some(s.split_at(1)),
+        _ => None,
+    }
+}
+
+// ` L ` with a single space on each side, returning `s` from that first space
+fn level_field(s: &str) -> Option<&str> {
+    let b: &[u8] = s.as_bytes();
+    (b.first() == Some(&b' ') && level(&s[1..]).is_some() && b.get(2) == Some(&b' ')).then_some(s)
+}
+
+// One or more whitespace characters, as the regexes' `\s+`
+fn spaces(s: &str) -> Option<&str> {
+    let rest: &str = s.trim_start();
+    (rest.len() < s.len()).then_some(rest)
+}
+
+// A run of `[A-Za-z0-9_]`
+fn word(s: &str) -> Option<(&str, &str)> {
+    let end: usize = s
+        .bytes()
+        .take_while(|b: &u8| b.is_ascii_alphanumeric() || *b == b'_')
+        .count();
+    (end > 0).then(|| s.split_at(end))
+}
+
+fn digits(s: &str) -> usize {
+    s.bytes().take_while(u8::is_ascii_digit).count()
+}
+
+fn is_number(s: &str) -> bool {
+    digits(s) == s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logcat::parse_regex;
+
+    // (format, line, tag, message)
+    const FIXTURES: [(FormatKind, &str, &str, &str); 17] = [
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 I ReactNativeJS: (dev): Running app",
+            "ReactNativeJS: (dev)",
+            "Running app",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 I ReactNativeJS: (dev) not a qualifier",
+            "ReactNativeJS",
+            "(dev) not a qualifier",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 I Downloader: (1/3): fetching",
+            "Downloader",
+            "(1/3): fetching",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 I ReactNativeJSX: (dev): msg",
+            "ReactNativeJSX",
+            "(dev): msg",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 D libEGL  : loaded /vendor/lib64/egl/libEGL.so",
+            "libEGL  ",
+            "loaded /vendor/lib64/egl/libEGL.so",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 W Weird:Tag: value: 3",
+            "Weird:Tag",
+            "value: 3",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 I Tag (with space): hello",
+            "Tag (with space)",
+            "hello",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 E EmptyTag:",
+            "EmptyTag",
+            "",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123  1234  1240 E EmptyTag: ",
+            "EmptyTag",
+            "",
+        ),
+        (
+            FormatKind::ThreadTime,
+            "2024-06-01 12:00:00.123456 u0_a12  1234  1240 V ReactNativeJS: (dev):",
+            "ReactNativeJS: (dev)",
+            "",
+        ),
+        (
+            FormatKind::Epoch,
+            "1717243200.123  1234  1240 I ReactNativeJS: (dev): epoch",
+            "ReactNativeJS: (dev)",
+            "epoch",
+        ),
+        (
+            FormatKind::Monotonic,
+            "   42.123  1234  1240 I libEGL  : monotonic",
+            "libEGL  ",
+            "monotonic",
+        ),
+        (
+            FormatKind::Time,
+            "06-01 12:00:00.123 I/Tag(x)( 1234): time",
+            "Tag(x)",
+            "time",
+        ),
+        (
+            FormatKind::Brief,
+            "W/ReactNativeJS: (dev)( 1234): brief",
+            "ReactNativeJS: (dev)",
+            "brief",
+        ),
+        (
+            FormatKind::Tag,
+            "I/ReactNativeJS: (dev): tag",
+            "ReactNativeJS: (dev)",
+            "tag",
+        ),
+        (FormatKind::Tag, "I/Weird:Tag:", "Weird:Tag", ""),
+        (FormatKind::Tag, "I/Tag: (a): (b): c", "Tag", "(a): (b): c"),
+    ];
+
+    #[test]
+    fn scanner_agrees_with_regexes() {
+        for (kind, line, tag, message) in FIXTURES {
+            let scanned: Logcat = parse(kind, line).unwrap_or_else(|| panic!("{:?}", line));
+            let matched: Option<Logcat> = parse_regex(kind, line);
+            assert_eq!(Some(&scanned), matched.as_ref(), "{:?}", line);
+            assert_eq!(scanned.tag, tag, "{:?}", line);
+            assert_eq!(scanned.message, message, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn fields_around_odd_tags() {
+        let lc: Logcat = parse(
+            FormatKind::ThreadTime,
+            "06-01 12:00:00.123 u0_a12  1234  1240 I ReactNativeJS: (dev): msg",
+        )
+        .unwrap();
+        assert_eq!(lc.timestamp, "06-01 12:00:00.123");
+        assert_eq!(lc.uid, "u0_a12");
+        assert_eq!((lc.process.as_str(), lc.thread.as_str()), ("1234", "1240"));
+        assert_eq!(lc.level, "I");
+    }
+
+    #[test]
+    fn rejects_what_the_regexes_reject() {
+        for (kind, line) in [
+            (
+                FormatKind::ThreadTime,
+                "06-01 12:00:00.123  1234  1240 I NoColon",
+            ),
+            (
+                FormatKind::ThreadTime,
+                "06-01 12:00:00.123  abc  1240 I Tag: msg",
+            ),
+            (FormatKind::Brief, "I/Tag(abc): msg"),
+            (FormatKind::Tag, "X/Tag: msg"),
+        ] {
+            assert_eq!(parse(kind, line), None, "{:?}", line);
+            assert_eq!(parse_regex(kind, line), None, "{:?}", line);
+        }
+    }
+}
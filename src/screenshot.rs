@@ -0,0 +1,131 @@
+use crate::{adb, logcat::Logcat};
+use regex::Regex;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// At most one capture per this interval, however many entries match
+const MIN_INTERVAL: Duration = Duration::from_secs(3);
+// Width of a --screenshot-preview thumbnail, in terminal cells
+const THUMB_COLUMNS: usize = 24;
+// kitty takes its base64 payload in chunks of at most this size
+const KITTY_CHUNK: usize = 4096;
+
+/// Captures the device screen when an entry's tag or message matches.
+pub struct Screenshots {
+    re: Regex,
+    last: Option<Instant>,
+}
+
+impl Screenshots {
+    pub fn new(re: Regex) -> Self {
+        Self { re, last: None }
+    }
+
+    /// Takes a screenshot for `lc` if it matches and the last one is old
+    /// enough, returning where it was saved.
+    pub fn check(&mut self, lc: &Logcat) -> Option<io::Result<PathBuf>> {
+        if !self.re.is_match(&lc.message) && !self.re.is_match(lc.tag.trim()) {
+            return None;
+        }
+        let now: Instant = Instant::now();
+        if self
+            .last
+            .is_some_and(|t: Instant| now.duration_since(t) < MIN_INTERVAL)
+        {
+            return None;
+        }
+        self.last = Some(now);
+
+        let path: PathBuf = PathBuf::from(file_name(&lc.timestamp));
+        Some(adb::screencap(&path).map(|_| path))
+    }
+}
+
+/// Inline image protocols a terminal may speak.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageProtocol {
+    Kitty,
+    Iterm2,
+}
+
+/// The image protocol of the terminal we run in, going by its environment;
+/// None when it isn't known to show images.
+pub fn image_protocol() -> Option<ImageProtocol> {
+    let var = |name: &str| -> String { std::env::var(name).unwrap_or_default() };
+    let term: String = var("TERM");
+    if term == "xterm-kitty" || term == "xterm-ghostty" || !var("KITTY_WINDOW_ID").is_empty() {
+        return Some(ImageProtocol::Kitty);
+    }
+    match var("TERM_PROGRAM").as_str() {
+        "iTerm.app" | "WezTerm" => Some(ImageProtocol::Iterm2),
+        _ => None,
+    }
+}
+
+/// The escape sequences showing the PNG at `path` as a thumbnail, ending
+/// with the cursor on the line below it.
+pub fn thumbnail(protocol: ImageProtocol, path: &Path) -> io::Result<String> {
+    let data: String = base64(&fs::read(path)?);
+    Ok(match protocol {
+        // Transmit and display (a=T) a PNG (f=100), scaled to c columns;
+        // every chunk but the last has m=1
+        ImageProtocol::Kitty => {
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+            let mut seq: String = String::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more: u8 = (i + 1 < chunks.len()) as u8;
+                let chunk: &str = std::str::from_utf8(chunk).unwrap_or_default();
+                match i {
+                    0 => seq.push_str(&format!(
+                        "\x1b_Ga=T,f=100,q=2,c={},m={};{}\x1b\\",
+                        THUMB_COLUMNS, more, chunk
+                    )),
+                    _ => seq.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk)),
+                }
+            }
+            seq.push('\n');
+            seq
+        }
+        ImageProtocol::Iterm2 => format!(
+            "\x1b]1337;File=inline=1;width={};preserveAspectRatio=1:{}\x07\n",
+            THUMB_COLUMNS, data
+        ),
+    })
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out: String = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n: u32 = group
+            .iter()
+            .enumerate()
+            .fold(0, |n: u32, (i, b): (usize, &u8)| {
+                n | (*b as u32) << (16 - 8 * i)
+            });
+        for i in 0..4 {
+            match i <= group.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+// "10-15 12:00:00.123" -> "screenshot-10-15_12-00-00.123.png", or the host
+// clock for entries without a timestamp
+fn file_name(timestamp: &str) -> String {
+    let stamp: String = if timestamp.is_empty() {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d: Duration| d.as_millis())
+            .to_string()
+    } else {
+        timestamp.replace(' ', "_").replace(':', "-")
+    };
+    format!("screenshot-{}.png", stamp)
+}
@@ -0,0 +1,414 @@
+use crate::{
+    ansi::{self, COLOR_NAMES, Color, Seq, attr, color},
+    config::{Config, Section},
+    filter::TagPattern,
+};
+use regex::{Captures, Match, Regex};
+use std::{collections::HashMap, fs, io, path::Path};
+
+// Backgrounds of the patterns that name no style, in turn; the first keeps
+// a lone spotlight red
+const PALETTE: [&str; 6] = ["red", "yellow", "green", "cyan", "purple", "blue"];
+
+/// A spotlight pattern, with the style of its matches when it names one.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub pattern: String,
+    style: Option<String>, // SGR codes
+}
+
+/// How the patterns of `-s` and of the control socket read:
+/// `--spotlight-ignore-case` and `--spotlight-fixed`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Mode {
+    pub ignore_case: bool,
+    pub fixed: bool, // literal text, not a regex
+}
+
+/// Parses `-s REGEX[=STYLE]`, where STYLE is the matches' background color
+/// (`yellow`, `color208`, `#ff8700`) or SGR codes (`1;43;30`). A `=`
+/// followed by neither is part of the regex.
+pub fn parse_rule(s: &str, mode: Mode) -> Result<Rule, String> {
+    let mut rule: Rule = match s
+        .rsplit_once('=')
+        .and_then(|(pattern, style)| Some((pattern, style_codes(style)?)))
+    {
+        Some((pattern, style)) if !pattern.is_empty() => Rule {
+            pattern: pattern.to_string(),
+            style: Some(style),
+        },
+        _ => Rule {
+            pattern: s.to_string(),
+            style: None,
+        },
+    };
+    if mode.fixed {
+        rule.pattern = regex::escape(&rule.pattern);
+    }
+    if mode.ignore_case {
+        rule.pattern = format!("(?i){}", rule.pattern);
+    }
+    Regex::new(&rule.pattern).map_err(|e: regex::Error| format!("invalid regex: {}", e))?;
+    Ok(rule)
+}
+
+/// The patterns of a `--spotlight-file`: one `REGEX[=STYLE]` per line, as
+/// `-s` takes it, with blank lines and lines starting with `#` skipped.
+pub fn load_file(path: &Path, mode: Mode) -> io::Result<Vec<Rule>> {
+    let text: String = fs::read_to_string(path)
+        .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))?;
+    let mut rules: Vec<Rule> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        rules.push(parse_rule(line, mode).map_err(|e: String| {
+            io::Error::other(format!("{}:{}: {}", path.display(), i + 1, e))
+        })?);
+    }
+    Ok(rules)
+}
+
+// A color as the background, under black or white text, whichever reads
+// better on it
+fn style_codes(style: &str) -> Option<String> {
+    if style.contains(';') {
+        return ansi::valid_codes(style).then(|| style.to_string());
+    }
+    let bg: Color = Color::parse(style).or_else(|| {
+        COLOR_NAMES[..16]
+            .iter()
+            .position(|n: &&str| n.eq_ignore_ascii_case(style))
+            .map(|i: usize| Color::Basic(i as u8))
+    })?;
+    let (r, g, b) = bg.rgb();
+    let luminance: f64 = (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) / 255.0;
+    let fg: &str = match luminance > 0.5 {
+        true => color::F_BLACK,
+        false => color::F_WHITE,
+    };
+    Some(format!("{};{};{}", attr::RESET, bg.codes(true), fg))
+}
+
+/// Spotlight rules compiled into one regex, each match styled as its rule
+/// says or else by the rule's place in the palette.
+pub struct Spot {
+    rules: Vec<Rule>,
+    re: Regex,
+    groups: Vec<usize>, // each rule's capture group in `re`
+    styles: Vec<Seq>,
+}
+
+impl Spot {
+    /// None without rules.
+    pub fn new(rules: Vec<Rule>) -> Option<Self> {
+        if rules.is_empty() {
+            return None;
+        }
+        let mut groups: Vec<usize> = Vec::new();
+        let mut next: usize = 1;
+        for rule in &rules {
+            groups.push(next);
+            next += Regex::new(&rule.pattern).ok()?.captures_len();
+        }
+        let patterns: Vec<String> = rules
+            .iter()
+            .map(|r: &Rule| format!("({})", r.pattern))
+            .collect();
+        let styles: Vec<Seq> = rules
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let palette = || style_codes(PALETTE[i % PALETTE.len()]).unwrap();
+                Seq::parse(&r.style.clone().unwrap_or_else(palette)).unwrap()
+            })
+            .collect();
+        Some(Self {
+            re: Regex::new(&patterns.join("|")).ok()?,
+            rules,
+            groups,
+            styles,
+        })
+    }
+
+    /// The style of the first match in `text`.
+    pub fn first(&self, text: &str) -> Option<&Seq> {
+        let c: Captures = self.re.captures(text)?;
+        Some(&self.styles[self.rule(&c)])
+    }
+
+    /// The matches in `text`, each with its rule's style.
+    pub fn find_iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (Match<'a>, &'a Seq)> {
+        self.re
+            .captures_iter(text)
+            .map(|c: Captures<'a>| (c.get(0).unwrap(), &self.styles[self.rule(&c)]))
+    }
+
+    // The rule whose group took part in a match
+    fn rule(&self, c: &Captures) -> usize {
+        match self.styles.len() {
+            1 => 0,
+            _ => self
+                .groups
+                .iter()
+                .position(|g: &usize| c.get(*g).is_some())
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// A spotlight pattern that only applies to some tags, from a
+/// `[[spotlight]]` table of the config or a pack:
+///
+/// ```toml
+/// [[spotlight]]
+/// tag = "OkHttp*"
+/// regex = '<-- [0-9]{3}'
+/// style = "yellow"    # optional, as after `-s REGEX=`
+/// ```
+#[derive(Clone)]
+pub struct Scoped {
+    tag: TagPattern,
+    rule: Rule,
+}
+
+pub fn load(configs: &[Config], fold_case: bool) -> io::Result<Vec<Scoped>> {
+    let mut scoped: Vec<Scoped> = Vec::new();
+    for config in configs {
+        for section in config.tables("spotlight") {
+            scoped.push(load_scoped(config, section, fold_case)?);
+        }
+    }
+    Ok(scoped)
+}
+
+fn load_scoped(config: &Config, section: &Section, fold_case: bool) -> io::Result<Scoped> {
+    section.check_keys(config, &["tag", "regex", "style"])?;
+    let at = |key: &str| -> usize { section.get(key).map_or(section.line, |e| e.line) };
+
+    let tag: TagPattern = TagPattern::parse(section.required_str(config, "tag")?, fold_case)
+        .map_err(|e: String| config.error(at("tag"), &e))?;
+    let pattern: &str = section.required_str(config, "regex")?;
+    Regex::new(pattern)
+        .map_err(|e: regex::Error| config.error(at("regex"), &format!("invalid regex: {}", e)))?;
+    let style: Option<String> = match section.str(config, "style")? {
+        Some(s) => Some(style_codes(s).ok_or_else(|| {
+            config.error(
+                at("style"),
+                &format!("unknown style `{}` (expected a color or SGR codes)", s),
+            )
+        })?),
+        None => None,
+    };
+    Ok(Scoped {
+        tag,
+        rule: Rule {
+            pattern: pattern.to_string(),
+            style,
+        },
+    })
+}
+
+/// The spotlight of each tag: the global one joined with the scoped
+/// patterns its tag matches, compiled once per tag.
+#[derive(Default)]
+pub struct Spotlights {
+    scoped: Vec<Scoped>,
+    by_tag: HashMap<String, Option<Spot>>,
+}
+
+impl Spotlights {
+    pub fn new(scoped: Vec<Scoped>) -> Self {
+        Self {
+            scoped,
+            by_tag: HashMap::new(),
+        }
+    }
+
+    /// Forgets the compiled spotlights, after the global one changed.
+    pub fn clear(&mut self) {
+        self.by_tag.clear();
+    }
+
+    pub fn get<'a>(&'a mut self, global: Option<&'a Spot>, tag: &str) -> Option<&'a Spot> {
+        let tag: &str = tag.trim();
+        if !self.scoped.iter().any(|s: &Scoped| s.tag.matches(tag)) {
+            return global;
+        }
+        let scoped: &[Scoped] = &self.scoped;
+        self.by_tag
+            .entry(tag.to_string())
+            .or_insert_with(|| -> Option<Spot> {
+                let rules: Vec<Rule> = global
+                    .map_or(&[][..], |g: &Spot| &g.rules)
+                    .iter()
+                    .chain(
+                        scoped
+                            .iter()
+                            .filter(|s: &&Scoped| s.tag.matches(tag))
+                            .map(|s: &Scoped| &s.rule),
+                    )
+                    .cloned()
+                    .collect();
+                Spot::new(rules)
+            })
+            .as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> Rule {
+        parse_rule(s, Mode::default()).unwrap()
+    }
+
+    // Each match in `text` with the index of the rule that styled it
+    fn matches<'a>(spot: &'a Spot, text: &'a str) -> Vec<(&'a str, usize)> {
+        spot.find_iter(text)
+            .map(|(m, style)| {
+                let i: usize = spot.styles.iter().position(|s: &Seq| s == style).unwrap();
+                (m.as_str(), i)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_rules() {
+        let r: Rule = rule("timeout=yellow");
+        assert_eq!(r.pattern, "timeout");
+        assert_eq!(r.style.as_deref(), Some("0;43;30"));
+        assert_eq!(rule("timeout=1;41;97").style.as_deref(), Some("1;41;97"));
+        assert_eq!(rule("retry=#ff8700").pattern, "retry");
+        // A `=` followed by no style is part of the regex
+        let r: Rule = rule("key=value");
+        assert_eq!((r.pattern.as_str(), r.style), ("key=value", None));
+        assert_eq!(rule("=red").pattern, "=red");
+        assert!(parse_rule("(unclosed", Mode::default()).is_err());
+        assert!(parse_rule("(unclosed=red", Mode::default()).is_err());
+    }
+
+    #[test]
+    fn modes() {
+        let fixed: Mode = Mode {
+            fixed: true,
+            ..Default::default()
+        };
+        let r: Rule = parse_rule("a.b(c)=red", fixed).unwrap();
+        assert_eq!(r.pattern, r"a\.b\(c\)");
+        assert!(r.style.is_some());
+        assert!(parse_rule("(unclosed", fixed).is_ok());
+        let both: Mode = Mode {
+            ignore_case: true,
+            fixed: true,
+        };
+        let spot: Spot = Spot::new(vec![parse_rule("wifi.", both).unwrap()]).unwrap();
+        assert_eq!(
+            matches(&spot, "WIFI. wifi! Wifi."),
+            [("WIFI.", 0), ("Wifi.", 0)]
+        );
+    }
+
+    #[test]
+    fn styles_each_rule_in_turn() {
+        let spot: Spot =
+            Spot::new(vec![rule("Wifi"), rule("timeout=yellow"), rule("dns")]).unwrap();
+        assert_eq!(
+            matches(&spot, "dns timeout on Wifi"),
+            [("dns", 2), ("timeout", 1), ("Wifi", 0)]
+        );
+        assert_eq!(
+            spot.styles[0],
+            Seq::parse(&style_codes(PALETTE[0]).unwrap()).unwrap()
+        );
+        assert_eq!(
+            spot.styles[2],
+            Seq::parse(&style_codes(PALETTE[2]).unwrap()).unwrap()
+        );
+        assert_eq!(spot.first("timeout on Wifi"), Some(&spot.styles[1]));
+        assert!(Spot::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn overlapping_matches_go_to_the_earlier_rule() {
+        let text: &str = "WifiManager: scan done";
+        let spot: Spot = Spot::new(vec![rule("Wifi"), rule("WifiManager")]).unwrap();
+        assert_eq!(matches(&spot, text), [("Wifi", 0)]);
+        let spot: Spot = Spot::new(vec![rule("WifiManager"), rule("Wifi")]).unwrap();
+        assert_eq!(matches(&spot, text), [("WifiManager", 0)]);
+        // Leftmost still wins over rule order
+        let spot: Spot = Spot::new(vec![rule("Manager"), rule("Wifi")]).unwrap();
+        assert_eq!(matches(&spot, text), [("Wifi", 1), ("Manager", 0)]);
+    }
+
+    #[test]
+    fn counts_the_groups_of_earlier_rules() {
+        let spot: Spot = Spot::new(vec![
+            rule("(a)(b)?=red"),
+            rule("(?P<n>c)=purple"),
+            rule("d"),
+        ])
+        .unwrap();
+        assert_eq!(spot.groups, [1, 4, 6]);
+        assert_eq!(matches(&spot, "d c ab"), [("d", 2), ("c", 1), ("ab", 0)]);
+    }
+
+    #[test]
+    fn loads_a_spotlight_file() {
+        let dir: std::path::PathBuf =
+            std::env::temp_dir().join(format!("spotlight-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: std::path::PathBuf = dir.join("patterns");
+        fs::write(&path, "# network\n  timeout=yellow  \n\nWifi\n").unwrap();
+        let rules: Vec<Rule> = load_file(&path, Mode::default()).unwrap();
+        let patterns: Vec<&str> = rules.iter().map(|r: &Rule| r.pattern.as_str()).collect();
+        assert_eq!(patterns, ["timeout", "Wifi"]);
+        fs::write(&path, "ok\n\n(bad\n").unwrap();
+        let e: String = load_file(&path, Mode::default()).unwrap_err().to_string();
+        assert!(
+            e.starts_with(&format!("{}:3: invalid regex", path.display())),
+            "{}",
+            e
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scoped_rules_follow_the_global_ones() {
+        let config: Config = Config::parse(
+            "config.toml",
+            "[[spotlight]]\ntag = \"OkHttp*\"\nregex = '<-- [0-9]{3}'\nstyle = \"green\"\n",
+        )
+        .unwrap();
+        let mut spotlights: Spotlights = Spotlights::new(load(&[config], false).unwrap());
+        let global: Spot = Spot::new(vec![rule("<-- 500")]).unwrap();
+        let okhttp: &Spot = spotlights.get(Some(&global), "OkHttp3 ").unwrap();
+        assert_eq!(
+            matches(okhttp, "<-- 500 <-- 200"),
+            [("<-- 500", 0), ("<-- 200", 1)]
+        );
+        // Other tags get the global spotlight as it is
+        let other: &Spot = spotlights.get(Some(&global), "Retrofit").unwrap();
+        assert!(std::ptr::eq(other, &global));
+        assert!(spotlights.get(None, "Retrofit").is_none());
+        assert!(spotlights.get(None, "OkHttp").is_some());
+    }
+
+    #[test]
+    fn rejects_bad_scoped_rules() {
+        let error = |text: &str| {
+            let config: Config = Config::parse("config.toml", text).unwrap();
+            load(&[config], false).err().unwrap().to_string()
+        };
+        assert!(
+            error("[[spotlight]]\ntag = \"x\"\nregex = '('\n")
+                .starts_with("config.toml:3: invalid regex")
+        );
+        assert!(
+            error("[[spotlight]]\ntag = \"x\"\nregex = 'y'\nstyle = \"loud\"\n")
+                .starts_with("config.toml:4: unknown style `loud`")
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{filter::TagPattern, logcat::Logcat};
+use regex::Regex;
+use std::{collections::HashMap, ops::Range, sync::LazyLock};
+
+// `key=value` and `key: value` pairs, with JSON-style quoted keys and
+// values; values end at a separator or a bracket, so nested objects are
+// read as their own pairs
+static RE_PAIR: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(r#""?([A-Za-z_][\w.\-]*)"?\s*[=:]\s*("[^"]*"|[^,;\s(){}\[\]]+)"#).unwrap()
+});
+
+/// What changed in a state dump since the previous one of its tag.
+pub struct Change {
+    pub spans: Vec<Range<usize>>, // of the message: values that changed, and new keys
+    pub gone: Vec<String>,        // keys the previous dump had
+}
+
+/// Diffs the state dumps some tags log over and over (`--diff-tag`): each
+/// message is read as key/value pairs and compared with the previous
+/// message of the same tag.
+pub struct StateDiff {
+    tags: Vec<TagPattern>,
+    last: HashMap<String, Vec<(String, String)>>,
+}
+
+impl StateDiff {
+    pub fn new(tags: Vec<TagPattern>) -> Self {
+        Self {
+            tags,
+            last: HashMap::new(),
+        }
+    }
+
+    /// The changes in `lc` against the tag's previous dump; None for other
+    /// tags, messages without pairs and a tag's first dump.
+    pub fn observe(&mut self, lc: &Logcat) -> Option<Change> {
+        let tag: &str = lc.tag.trim();
+        if !self.tags.iter().any(|t: &TagPattern| t.matches(tag)) {
+            return None;
+        }
+        let pairs: Vec<(String, String, Range<usize>)> = RE_PAIR
+            .captures_iter(&lc.message)
+            .map(|c: regex::Captures| {
+                let value: regex::Match = c.get(2).unwrap();
+                (c[1].to_string(), value.as_str().to_string(), value.range())
+            })
+            .collect();
+        if pairs.is_empty() {
+            return None;
+        }
+
+        let state: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(k, v, _): &(String, String, Range<usize>)| (k.clone(), v.clone()))
+            .collect();
+        let prev: Vec<(String, String)> = self.last.insert(tag.to_string(), state)?;
+        let before = |key: &str| -> Option<&str> {
+            prev.iter()
+                .find(|(k, _): &&(String, String)| k == key)
+                .map(|(_, v): &(String, String)| v.as_str())
+        };
+        Some(Change {
+            spans: pairs
+                .iter()
+                .filter(|(k, v, _): &&(String, String, Range<usize>)| before(k) != Some(v))
+                .map(|(_, _, range): &(String, String, Range<usize>)| range.clone())
+                .collect(),
+            gone: prev
+                .iter()
+                .filter(|(k, _): &&(String, String)| {
+                    !pairs
+                        .iter()
+                        .any(|(key, _, _): &(String, String, Range<usize>)| key == k)
+                })
+                .map(|(k, _): &(String, String)| k.clone())
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    classify::EventKind,
+    filter::{self, Hidden},
+    render::{self, Line, Renderer},
+    theme::Theme,
+};
+use std::{collections::HashMap, io};
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub total: usize,
+    pub unparsed: usize,
+    pub levels: [usize; 6], // V D I W E F
+    pub events: HashMap<EventKind, usize>,
+    pub out_of_order: usize,
+    pub dropped: [usize; Hidden::ALL.len()], // by Hidden
+    pub blocking: HashMap<String, usize>,    // main-thread blocking events, by tag
+}
+
+const LEVELS: [&str; 6] = ["V", "D", "I", "W", "E", "F"];
+// Tags named after the main-thread blocking count
+const SHOWN_CULPRITS: usize = 5;
+
+impl Stats {
+    pub fn record(&mut self, level: &str, tag: &str, event: Option<EventKind>) {
+        self.total += 1;
+        // Asserts count as fatal, as filters rank them
+        if let Some(i) = filter::level_rank(level) {
+            self.levels[i] += 1;
+        }
+        if let Some(kind) = event {
+            *self.events.entry(kind).or_default() += 1;
+        }
+        if event == Some(EventKind::Blocking) {
+            *self.blocking.entry(tag.trim().to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn event_count(&self, kind: EventKind) -> usize {
+        self.events.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn record_unparsed(&mut self) {
+        self.unparsed += 1;
+    }
+
+    pub fn record_dropped(&mut self, why: Hidden) {
+        self.dropped[why as usize] += 1;
+    }
+
+    pub fn print(&self, out: &mut Renderer, theme: &Theme) -> io::Result<()> {
+        let ids = [
+            &theme.id_verbose,
+            &theme.id_debug,
+            &theme.id_info,
+            &theme.id_warning,
+            &theme.id_error,
+            &theme.id_fatal,
+        ];
+
+        out.status_blank()?;
+        out.status_text(&format!(
+            "{} entries, {} unparsed lines",
+            self.total, self.unparsed
+        ))?;
+
+        let mut line: Line = Line::new();
+        for (i, level) in LEVELS.iter().enumerate() {
+            line.badge(ids[i], level)
+                .plain(&format!("{:<8}", self.levels[i]));
+        }
+        out.status_line(&line)?;
+
+        let mut line: Line = Line::new();
+        for kind in EventKind::all() {
+            line.badge(render::event_seqs(kind, theme).0, kind.label())
+                .plain(&format!("{:<8}", self.event_count(kind)));
+        }
+        out.status_line(&line)?;
+
+        if !self.blocking.is_empty() {
+            let mut culprits: Vec<(&String, &usize)> = self.blocking.iter().collect();
+            culprits.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            let named: Vec<String> = culprits
+                .iter()
+                .take(SHOWN_CULPRITS)
+                .map(|(tag, n)| format!("{} {}", tag, n))
+                .collect();
+            let more: String = match culprits.len().saturating_sub(SHOWN_CULPRITS) {
+                0 => String::new(),
+                n => format!(", {} more tags", n),
+            };
+            let mut line: Line = Line::new();
+            line.badge(&theme.id_perf, EventKind::Blocking.label())
+                .plain(&format!(
+                    "main thread blocked {} times: {}{}",
+                    self.event_count(EventKind::Blocking),
+                    named.join(", "),
+                    more
+                ));
+            out.status_line(&line)?;
+        }
+        if self.out_of_order > 0 {
+            let mut line: Line = Line::new();
+            line.badge(&theme.out_of_order, "OOO")
+                .plain(&self.out_of_order.to_string());
+            out.status_line(&line)?;
+        }
+        if let Some(text) = describe_dropped(&self.dropped) {
+            out.status_text(&format!("hidden: {}", text))?;
+        }
+        Ok(())
+    }
+}
+
+/// "120 by level, 4 unparsed", or None when nothing was hidden.
+pub fn describe_dropped(dropped: &[usize; Hidden::ALL.len()]) -> Option<String> {
+    let parts: Vec<String> = Hidden::ALL
+        .iter()
+        .zip(dropped)
+        .filter(|(_, n)| **n > 0)
+        .map(|(why, n)| format!("{} {}", n, why.label()))
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // --recap and --fail-on read levels[5] as every fatal entry, asserts too
+    #[test]
+    fn asserts_count_as_fatal() {
+        let mut stats: Stats = Stats::default();
+        for level in ["V", "D", "I", "W", "E", "F", "A", "?"] {
+            stats.record(level, "Tag", None);
+        }
+        assert_eq!(stats.levels, [1, 1, 1, 1, 1, 2]);
+        assert_eq!(stats.total, 8);
+    }
+}
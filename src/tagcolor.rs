@@ -0,0 +1,60 @@
+use crate::{ansi::Seq, render, theme::Theme};
+use std::collections::HashMap;
+
+/// A color for each tag, the same for a tag on every run (`--color-tags`):
+/// the tag's hash picks it from the theme's tag palette, less the colors
+/// the levels' messages and the pid column before it come in, as the
+/// terminal shows them.
+pub struct TagColors {
+    palette: Vec<Seq>,
+    by_tag: HashMap<String, usize>,
+}
+
+impl TagColors {
+    pub fn new(theme: &Theme) -> Self {
+        let taken: Vec<&str> = ["V", "D", "I", "W", "E", "F", "A"]
+            .iter()
+            .map(|level: &&str| render::level_seqs(level, theme).1)
+            .chain([&theme.tid_pid])
+            .map(|seq: &Seq| seq.sgr().fg_code())
+            .collect();
+        // Fitted to a basic terminal, colors may come twice, which would
+        // make those likelier
+        let mut palette: Vec<Seq> = Vec::new();
+        for seq in &theme.tag_palette {
+            let fg: &str = seq.sgr().fg_code();
+            if !taken.contains(&fg) && !palette.iter().any(|p: &Seq| p.sgr() == seq.sgr()) {
+                palette.push(seq.clone());
+            }
+        }
+        Self {
+            palette,
+            by_tag: HashMap::new(),
+        }
+    }
+
+    /// The tag's color; None when the theme took the whole palette.
+    pub fn get(&mut self, tag: &str) -> Option<&Seq> {
+        if self.palette.is_empty() {
+            return None;
+        }
+        let tag: &str = tag.trim();
+        let len: usize = self.palette.len();
+        let i: usize = match self.by_tag.get(tag) {
+            Some(i) => *i,
+            None => *self
+                .by_tag
+                .entry(tag.to_string())
+                .or_insert((fnv1a(tag) % len as u64) as usize),
+        };
+        self.palette.get(i)
+    }
+}
+
+// std's hasher is seeded per process, and a tag should keep its color
+// across runs
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, b: u8| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
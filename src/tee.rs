@@ -0,0 +1,72 @@
+use crate::rotate::{Policy, Rotation};
+use std::{
+    fs::File,
+    io::{self, LineWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The raw capture of `--tee FILE` (or `--record FILE`): every line read
+/// from the input, as it was read, so the session can be replayed through
+/// the colorizer later. With `--rotate`, the file is moved aside between
+/// two lines once it is due.
+pub struct Tee {
+    path: PathBuf,
+    file: LineWriter<File>,
+    size: u64,
+    rotation: Option<Rotation>,
+}
+
+impl Tee {
+    pub fn create(path: &Path, rotate: Option<Policy>) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: create(path)?,
+            size: 0,
+            rotation: rotate.map(|policy: Policy| Rotation::new(path, policy)),
+        })
+    }
+
+    pub fn line(&mut self, line: &str) -> io::Result<()> {
+        if let Some(rotation) = &mut self.rotation
+            && rotation.due(self.size)
+        {
+            self.file.flush()?;
+            rotation.rotate()?;
+            self.file = create(&self.path)?;
+            self.size = 0;
+        }
+        writeln!(self.file, "{}", line)
+            .map_err(|e: io::Error| io::Error::other(format!("{}: {}", self.path.display(), e)))?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+fn create(path: &Path) -> io::Result<LineWriter<File>> {
+    File::create(path)
+        .map(LineWriter::new)
+        .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotate::parse_policy;
+    use std::fs;
+
+    #[test]
+    fn rotates_between_lines() {
+        let dir: PathBuf = std::env::temp_dir().join(format!("tee-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join("capture.txt");
+        let mut tee: Tee = Tee::create(&path, Some(parse_policy("size=10").unwrap())).unwrap();
+        for line in ["first line", "second", "third"] {
+            tee.line(line).unwrap();
+        }
+        drop(tee);
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("capture.txt.1"), "first line\n");
+        assert_eq!(read("capture.txt"), "second\nthird\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
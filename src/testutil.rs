@@ -0,0 +1,34 @@
+// Helpers shared by the unit tests
+use crate::logcat::{Logcat, parse_line};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// A writer whose bytes stay readable while a renderer or emitter holds
+/// it, and after it is gone.
+#[derive(Clone, Default)]
+pub struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Shared {
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+/// The entry `line` parses to, in whichever format it is.
+pub fn entry(line: &str) -> Logcat {
+    parse_line(line).unwrap().1
+}
@@ -1,4 +1,99 @@
-use crate::ansi::{self, Seq};
+use crate::{
+    ansi::{self, Seq, Sgr},
+    config::{Config, Section, Value},
+    render::{self, Renderer},
+    themeedit,
+};
+use std::io;
+
+/// The built-in `--theme` presets, kept as theme files under `themes/` so
+/// they can be copied to a `--theme-file` and edited.
+const PRESETS: [(&str, &str, &str); 7] = [
+    ("default", "the built-in basic colors", ""),
+    (
+        "light",
+        "the basic colors for light backgrounds",
+        include_str!("../themes/light.toml"),
+    ),
+    (
+        "solarized",
+        "Solarized dark, in 24-bit colors",
+        include_str!("../themes/solarized.toml"),
+    ),
+    (
+        "dracula",
+        "Dracula, in 24-bit colors",
+        include_str!("../themes/dracula.toml"),
+    ),
+    (
+        "nord",
+        "Nord, in 24-bit colors",
+        include_str!("../themes/nord.toml"),
+    ),
+    (
+        "high-contrast",
+        "bright basic colors and bold text",
+        include_str!("../themes/high-contrast.toml"),
+    ),
+    (
+        "mono",
+        "attributes only, in the terminal's own colors",
+        include_str!("../themes/mono.toml"),
+    ),
+];
+
+pub fn parse_preset(name: &str) -> Result<String, String> {
+    match PRESETS.iter().any(|(n, _, _)| *n == name) {
+        true => Ok(name.to_string()),
+        false => Err(format!(
+            "unknown theme `{}` (expected one of {})",
+            name,
+            PRESETS.map(|(n, _, _)| n).join(", ")
+        )),
+    }
+}
+
+/// The theme of a built-in preset: its file over the default styles.
+pub fn preset(name: &str) -> io::Result<Theme> {
+    let (_, _, text) = PRESETS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .ok_or_else(|| io::Error::other(format!("unknown theme `{}`", name)))?;
+    let mut theme: Theme = make_theme();
+    theme.apply_file(&Config::parse(&format!("theme {}", name), text)?)?;
+    // Theme files can't set the pid alternation and the tag palette, whose
+    // bright and pastel colors wash out on a light background
+    if name == "light" {
+        theme.tid_pid_alt = ["0;49;36", "0;49;33", "0;49;32", "0;49;34"]
+            .iter()
+            .map(|codes: &&str| Seq::parse(codes).unwrap())
+            .collect();
+        theme.tag_palette = [
+            "38;5;25", "38;5;130", "38;5;91", "38;5;30", "38;5;161", "38;5;94", "38;5;61",
+            "38;5;127", "38;5;23", "38;5;166", "38;5;55", "38;5;64", "36", "34", "35", "33",
+        ]
+        .iter()
+        .map(|fg: &&str| Seq::parse(&format!("0;49;{}", fg)).unwrap())
+        .collect();
+    }
+    Ok(theme)
+}
+
+/// `--list-themes`: each preset with its sample lines.
+pub fn list_themes(out: &mut Renderer) -> io::Result<()> {
+    for (name, about, _) in PRESETS {
+        let theme: Theme = preset(name)?;
+        out.line(&render::note(
+            &theme.banner,
+            &format!("{} - {}", name, about),
+        ))?;
+        for line in themeedit::samples(&theme) {
+            out.line(&line)?;
+        }
+        out.blank()?;
+    }
+    Ok(())
+}
 
 #[derive(Clone)]
 pub struct Theme {
@@ -8,6 +103,7 @@ pub struct Theme {
     pub id_warning: Seq,
     pub id_error: Seq,
     pub id_fatal: Seq,
+    pub id_assert: Seq,
 
     pub msg_verbose: Seq,
     pub msg_debug: Seq,
@@ -15,10 +111,54 @@ pub struct Theme {
     pub msg_warning: Seq,
     pub msg_error: Seq,
     pub msg_fatal: Seq,
+    pub msg_assert: Seq,
 
     pub timestamp: Seq,
+    pub timestamp_fraction: Seq,
     pub tid_pid: Seq,
+    pub pid_app: Seq,
+    pub pid_system: Seq,
+    pub pid_native: Seq,
+    pub uid: Seq,
+    pub user: Seq,
+    pub tid_pid_alt: Vec<Seq>,
     pub tag: Seq,
+    pub tag_palette: Vec<Seq>, // for --color-tags
+
+    pub id_security: Seq,
+    pub msg_security: Seq,
+    pub id_perf: Seq,
+    pub msg_perf: Seq,
+    pub id_event: Seq,
+
+    pub collapsed: Seq,
+    pub out_of_order: Seq,
+    pub banner: Seq,
+    pub pid_reuse: Seq,
+    pub package: Seq,
+    pub screenshot: Seq,
+    pub webhook: Seq,
+    pub assertion: Seq,
+    pub hint: Seq,
+    pub host_tag: Seq,
+    pub msg_host: Seq,
+    pub divider: Seq,
+    pub divider_buffer: Seq,
+    pub diagnostic: Seq,
+    pub dropped: Seq,
+    pub diff_changed: Seq,
+    pub diff_unchanged: Seq,
+    pub finding: Seq,
+    pub command_ok: Seq,
+    pub command_failed: Seq,
+    pub humanized: Seq,
+    pub context: Seq,
+    pub auto_url: Seq,
+    pub auto_address: Seq,
+    pub auto_hex: Seq,
+    pub auto_path: Seq,
+    pub auto_duration: Seq,
+
     pub reset: Seq,
 }
 
@@ -40,6 +180,7 @@ pub fn make_theme() -> Theme {
         id_warning: seq!(BOLD, B_YELLOW, F_BLACK),
         id_error: seq!(BOLD, B_RED, F_BLACK),
         id_fatal: seq!(BOLD, B_BLACK, F_DEFAULT),
+        id_assert: seq!(BOLD, B_RED, F_WHITE),
 
         msg_verbose: seq!(RESET, B_DEFAULT, F_CYAN),
         msg_debug: seq!(RESET, B_DEFAULT, F_BLUE),
@@ -47,10 +188,310 @@ pub fn make_theme() -> Theme {
         msg_warning: seq!(RESET, B_DEFAULT, F_YELLOW),
         msg_error: seq!(RESET, B_DEFAULT, F_RED),
         msg_fatal: seq!(BOLD, B_DEFAULT, FB_RED),
+        msg_assert: seq!(BOLD, B_DEFAULT, FB_RED),
 
         timestamp: seq!(RESET, B_DEFAULT, F_PURPLE),
+        timestamp_fraction: seq!(FAINT, B_DEFAULT, F_PURPLE),
         tid_pid: seq!(RESET, B_DEFAULT, F_PURPLE),
+        pid_app: seq!(RESET, B_DEFAULT, FB_GREEN),
+        pid_system: seq!(RESET, B_DEFAULT, FB_YELLOW),
+        pid_native: seq!(FAINT, B_DEFAULT, F_GREY),
+        uid: seq!(RESET, B_DEFAULT, FB_BLUE),
+        user: seq!(BOLD, B_DEFAULT, FB_BLUE),
+        tid_pid_alt: vec![
+            seq!(RESET, B_DEFAULT, FB_CYAN),
+            seq!(RESET, B_DEFAULT, FB_YELLOW),
+            seq!(RESET, B_DEFAULT, FB_GREEN),
+            seq!(RESET, B_DEFAULT, FB_PURPLE),
+        ],
         tag: seq!(RESET, B_DEFAULT, F_DEFAULT),
+        // Hues spread around the 256-color cube, with the basic colors
+        // after them for terminals that fit these to the same few
+        tag_palette: [
+            "38;5;75", "38;5;215", "38;5;141", "38;5;43", "38;5;211", "38;5;179", "38;5;111",
+            "38;5;176", "38;5;73", "38;5;209", "38;5;147", "38;5;150", "38;5;39", "38;5;217",
+            "38;5;105", "38;5;187", "95", "96", "35", "94", "97",
+        ]
+        .iter()
+        .map(|fg: &&str| Seq::parse(&format!("0;49;{}", fg)).unwrap())
+        .collect(),
+
+        id_security: seq!(BOLD, B_PURPLE, F_WHITE),
+        msg_security: seq!(BOLD, B_DEFAULT, FB_PURPLE),
+        id_perf: seq!(BOLD, BB_YELLOW, F_BLACK),
+        msg_perf: seq!(UNDERLINE, B_DEFAULT, FB_YELLOW),
+        id_event: seq!(BOLD, B_GREY, F_BLACK),
+
+        collapsed: seq!(FAINT, B_DEFAULT, F_GREY),
+        out_of_order: seq!(BOLD, B_YELLOW, F_BLACK),
+        banner: seq!(BOLD, B_DEFAULT, F_CYAN),
+        pid_reuse: seq!(FAINT, B_DEFAULT, F_PURPLE),
+        package: seq!(FAINT, B_DEFAULT, F_GREEN),
+        screenshot: seq!(FAINT, B_DEFAULT, F_CYAN),
+        webhook: seq!(FAINT, B_DEFAULT, F_CYAN),
+        assertion: seq!(BOLD, B_DEFAULT, FB_RED),
+        hint: seq!(FAINT, B_DEFAULT, F_YELLOW),
+        host_tag: seq!(REVERSE, B_DEFAULT, F_GREY),
+        msg_host: seq!(RESET, B_DEFAULT, F_GREY),
+        divider: seq!(FAINT, B_DEFAULT, F_CYAN),
+        divider_buffer: seq!(BOLD, B_CYAN, F_BLACK),
+        diagnostic: seq!(FAINT, B_DEFAULT, F_GREY),
+        dropped: seq!(FAINT, B_DEFAULT, F_GREY),
+        diff_changed: seq!(BOLD, B_DEFAULT, FB_YELLOW),
+        diff_unchanged: seq!(FAINT, B_DEFAULT, F_GREY),
+        finding: seq!(BOLD, B_DEFAULT, F_YELLOW),
+        command_ok: seq!(BOLD, B_GREEN, F_BLACK),
+        command_failed: seq!(BOLD, B_RED, F_WHITE),
+        humanized: seq!(FAINT, B_DEFAULT, F_GREY),
+        context: seq!(FAINT, B_DEFAULT, F_GREY),
+        // --auto-highlight, over the message's colors: a default color
+        // keeps the message's
+        auto_url: seq!(UNDERLINE, B_DEFAULT, FB_CYAN),
+        auto_address: seq!(RESET, B_DEFAULT, FB_PURPLE),
+        auto_hex: seq!(RESET, B_DEFAULT, FB_BLUE),
+        auto_path: seq!(RESET, B_DEFAULT, FB_GREEN),
+        auto_duration: seq!(BOLD, B_DEFAULT, F_DEFAULT),
+
         reset: ansi::reset(),
     }
 }
+
+// The styles a [theme] table can set, by field name; the pid/tid
+// alternation, the tag palette and the reset aren't among them
+macro_rules! slots {
+    ($($name:ident),* $(,)?) => {
+        impl Theme {
+            pub fn slots(&self) -> Vec<(&'static str, &Seq)> {
+                vec![$((stringify!($name), &self.$name)),*]
+            }
+
+            pub fn slots_mut(&mut self) -> Vec<(&'static str, &mut Seq)> {
+                vec![$((stringify!($name), &mut self.$name)),*]
+            }
+        }
+    };
+}
+
+slots!(
+    id_verbose,
+    id_debug,
+    id_info,
+    id_warning,
+    id_error,
+    id_fatal,
+    id_assert,
+    msg_verbose,
+    msg_debug,
+    msg_info,
+    msg_warning,
+    msg_error,
+    msg_fatal,
+    msg_assert,
+    timestamp,
+    timestamp_fraction,
+    tid_pid,
+    pid_app,
+    pid_system,
+    pid_native,
+    uid,
+    user,
+    tag,
+    id_security,
+    msg_security,
+    id_perf,
+    msg_perf,
+    id_event,
+    collapsed,
+    out_of_order,
+    banner,
+    pid_reuse,
+    package,
+    screenshot,
+    webhook,
+    assertion,
+    hint,
+    host_tag,
+    msg_host,
+    divider,
+    divider_buffer,
+    diagnostic,
+    dropped,
+    diff_changed,
+    diff_unchanged,
+    finding,
+    command_ok,
+    command_failed,
+    humanized,
+    context,
+    auto_url,
+    auto_address,
+    auto_hex,
+    auto_path,
+    auto_duration,
+);
+
+impl Theme {
+    /// Overrides styles from the config's `[theme]` table, which maps slot
+    /// names to SGR codes:
+    ///
+    /// ```toml
+    /// [theme]
+    /// msg_error = "1;49;91"
+    /// tag = "0;49;97"
+    /// ```
+    pub fn apply(&mut self, config: &Config) -> io::Result<()> {
+        match config.tables("theme").next() {
+            Some(section) => self.apply_table(config, section),
+            None => Ok(()),
+        }
+    }
+
+    /// Overrides styles from one table of slot names, such as a
+    /// `[theme.NAME]` table picked by a filter profile.
+    pub fn apply_table(&mut self, config: &Config, section: &Section) -> io::Result<()> {
+        let names: Vec<&str> = self.slots().into_iter().map(|(name, _)| name).collect();
+        section.check_keys(config, &names)?;
+        for (name, seq) in self.slots_mut() {
+            let Some(codes) = section.str(config, name)? else {
+                continue;
+            };
+            *seq = Seq::parse(codes).ok_or_else(|| {
+                let line: usize = section.get(name).map_or(section.line, |e| e.line);
+                config.error(
+                    line,
+                    &format!(
+                        "{}: expected SGR codes such as \"1;49;91\" or \"0;49;38;5;208\"",
+                        name
+                    ),
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Overrides styles from a theme file: slot names at the top mapped to
+    /// SGR codes as in a `[theme]` table, and tables named after slots
+    /// giving any of `attr`, `bg` and `fg` by name or code, the rest kept:
+    ///
+    /// ```toml
+    /// msg_error = "1;49;91"
+    ///
+    /// [msg_info]
+    /// fg = "bright_green"
+    /// attr = "bold"
+    /// ```
+    pub fn apply_file(&mut self, config: &Config) -> io::Result<()> {
+        self.apply_table(config, config.root())?;
+        let names: Vec<&str> = self.slots().into_iter().map(|(name, _)| name).collect();
+        for section in &config.sections[1..] {
+            let Some((_, seq)) = self
+                .slots_mut()
+                .into_iter()
+                .find(|(name, _)| *name == section.name)
+            else {
+                return Err(config.error(
+                    section.line,
+                    &format!(
+                        "unknown style [{}] (expected one of {})",
+                        section.name,
+                        names.join(", ")
+                    ),
+                ));
+            };
+            section.check_keys(config, &["attr", "bg", "fg"])?;
+            let colors: Vec<&str> = [&ansi::COLOR_NAMES[..], &["colorN", "#rrggbb"]].concat();
+            let sgr: &Sgr = seq.sgr();
+            let attr: String = code(config, section, "attr", attr_code, &ansi::ATTR_NAMES)?
+                .unwrap_or_else(|| sgr.attr_codes());
+            let bg: String = code(
+                config,
+                section,
+                "bg",
+                |n| ansi::color_code(n, true),
+                &colors,
+            )?
+            .unwrap_or_else(|| sgr.bg_code().to_string());
+            let fg: String = code(
+                config,
+                section,
+                "fg",
+                |n| ansi::color_code(n, false),
+                &colors,
+            )?
+            .unwrap_or_else(|| sgr.fg_code().to_string());
+            *seq = Seq::new(&attr, &bg, &fg);
+        }
+        Ok(())
+    }
+
+    /// The styles in the theme file format (`--dump-theme`): a table of
+    /// attr, bg and fg names per slot, or SGR codes at the top for the
+    /// slots with more than one attribute, which a table can't give.
+    pub fn to_file(&self) -> String {
+        let mut codes: String = String::new();
+        let mut tables: String = String::new();
+        for (name, seq) in self.slots() {
+            let sgr: Sgr = seq.given();
+            let attr: Option<&str> = ansi::ATTRS
+                .iter()
+                .position(|a: &&str| *a == sgr.attr_codes())
+                .map(|i: usize| ansi::ATTR_NAMES[i]);
+            let bg: Option<String> = ansi::color_name(sgr.bg_code(), true);
+            let fg: Option<String> = ansi::color_name(sgr.fg_code(), false);
+            match (attr, bg, fg) {
+                (Some(attr), Some(bg), Some(fg)) => tables.push_str(&format!(
+                    "\n[{}]\nattr = \"{}\"\nbg = \"{}\"\nfg = \"{}\"\n",
+                    name, attr, bg, fg
+                )),
+                _ => codes.push_str(&format!("{} = \"{}\"\n", name, seq.codes())),
+            }
+        }
+        format!(
+            "# The theme in effect, from --dump-theme: save it as\n\
+             # ~/.config/logcat-colorize/theme.toml and adjust it\n\
+             {}{}{}",
+            if codes.is_empty() { "" } else { "\n" },
+            codes,
+            tables
+        )
+    }
+}
+
+fn attr_code(name: &str) -> Option<String> {
+    ansi::attr_code(name).map(str::to_string)
+}
+
+// An `attr`, `bg` or `fg` value of a theme file table, a name or a code
+fn code(
+    config: &Config,
+    section: &Section,
+    key: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+    names: &[&str],
+) -> io::Result<Option<String>> {
+    let Some(e) = section.get(key) else {
+        return Ok(None);
+    };
+    let given: String = match &e.value {
+        Value::Str(s) => s.clone(),
+        Value::Int(n) => n.to_string(),
+        _ => {
+            return Err(config.error(
+                e.line,
+                &format!("[{}] {}: expected a name or a code", section.name, key),
+            ));
+        }
+    };
+    lookup(&given).map(Some).ok_or_else(|| {
+        config.error(
+            e.line,
+            &format!(
+                "[{}] {}: unknown value `{}` (expected one of {}, or its code)",
+                section.name,
+                key,
+                given,
+                names.join(", ")
+            ),
+        )
+    })
+}
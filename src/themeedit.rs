@@ -0,0 +1,294 @@
+use crate::{
+    ansi::{ATTRS, BGS, FGS, Seq},
+    keymap::{Action, Key, Keymap},
+    render::{self, Line, Renderer},
+    theme::{Theme, make_theme},
+    width,
+};
+use std::{
+    fs,
+    io::{self, IsTerminal, Read},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+// One sample entry per level, as the log stream would show it
+const SAMPLES: [(&str, &str); 7] = [
+    ("V", "Looking up service com.example/.SyncService"),
+    ("D", "onCreate() savedInstanceState=null"),
+    ("I", "Displayed com.example/.MainActivity: +412ms"),
+    ("W", "Slow operation: 230ms so far, now at startProcess"),
+    ("E", "Failed to connect to api.example.com:443"),
+    ("F", "FATAL EXCEPTION: main"),
+    ("A", "Assertion failed: buffer != nullptr"),
+];
+
+/// `theme-edit`: shows sample lines in the current theme and lets each
+/// slot's attribute and colors be cycled from the keyboard, with the keys of
+/// `keys`, then writes the slots that differ from the defaults to the
+/// `[theme]` table of `path`.
+pub fn run(out: &mut Renderer, mut theme: Theme, path: &Path, keys: &Keymap) -> io::Result<()> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(io::Error::other("theme-edit needs a terminal"));
+    }
+    let _tty: Cbreak = Cbreak::enter()?;
+    // Alternate screen, cursor hidden
+    out.control("\x1b[?1049h\x1b[?25l")?;
+    let res: io::Result<()> = edit(out, &mut theme, path, keys);
+    out.control("\x1b[?25h\x1b[?1049l")?;
+    out.flush()?;
+    res
+}
+
+// Keys come in unbuffered and unechoed, Ctrl-C and Ctrl-S included, for as
+// long as this lives
+struct Cbreak(String);
+
+impl Cbreak {
+    fn enter() -> io::Result<Self> {
+        let saved: String = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "min", "1"])?;
+        Ok(Self(saved.trim().to_string()))
+    }
+}
+
+impl Drop for Cbreak {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.0]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let out: std::process::Output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e: io::Error| io::Error::other(format!("stty: {}", e)))?;
+    if !out.status.success() {
+        return Err(io::Error::other(format!(
+            "stty: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+// None at the end of input
+fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let mut byte = || -> io::Result<Option<u8>> {
+        let mut b: [u8; 1] = [0];
+        Ok((input.read(&mut b)? == 1).then_some(b[0]))
+    };
+    let Some(b) = byte()? else {
+        return Ok(None);
+    };
+    if b != 0x1b {
+        return Ok(Some(Key::Char(b)));
+    }
+    // CSI or SS3 arrows: ESC [ A or ESC O A; anything else after ESC is
+    // the key pressed with Alt
+    let c: u8 = match byte()? {
+        Some(b'[' | b'O') => byte()?.unwrap_or_default(),
+        Some(c) => return Ok(Some(Key::Meta(c))),
+        None => return Ok(Some(Key::Char(0x1b))),
+    };
+    Ok(Some(match c {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        _ => Key::Char(0x1b),
+    }))
+}
+
+// A slot as indexes into ATTRS, BGS and FGS; codes outside them are
+// dropped once the slot is edited
+#[derive(Clone, Copy)]
+struct Style {
+    attr: usize,
+    bg: usize,
+    fg: usize,
+}
+
+impl Style {
+    fn of(seq: &Seq) -> Self {
+        let find = |list: &[&str], default: usize| -> usize {
+            seq.codes()
+                .split(';')
+                .find_map(|c: &str| list.iter().position(|l: &&str| *l == c))
+                .unwrap_or(default)
+        };
+        Self {
+            attr: find(&ATTRS, 0),
+            bg: find(&BGS, BGS.len() - 1),
+            fg: find(&FGS, FGS.len() - 1),
+        }
+    }
+
+    fn seq(&self) -> Seq {
+        Seq::new(ATTRS[self.attr], BGS[self.bg], FGS[self.fg])
+    }
+}
+
+// Steps `i` through a list of `len`, wrapping around
+fn cycle(i: usize, len: usize, forward: bool) -> usize {
+    match forward {
+        true => (i + 1) % len,
+        false => (i + len - 1) % len,
+    }
+}
+
+fn edit(out: &mut Renderer, theme: &mut Theme, path: &Path, keys: &Keymap) -> io::Result<()> {
+    let defaults: Theme = make_theme();
+    let count: usize = theme.slots().len();
+    let mut selected: usize = 0;
+    let mut dirty: bool = false;
+    let mut status: String = format!("editing [theme] in {}", path.display());
+    let mut stdin: io::StdinLock<'static> = io::stdin().lock();
+    let help: String = keys.help();
+
+    loop {
+        draw(out, theme, selected, &help, &status)?;
+        let Some(key) = read_key(&mut stdin)? else {
+            return Ok(());
+        };
+        let Some(action) = keys.action(key) else {
+            continue;
+        };
+        let mut slots: Vec<(&'static str, &mut Seq)> = theme.slots_mut();
+        let (name, seq) = &mut slots[selected];
+        let mut style: Style = Style::of(seq);
+        status.clear();
+        match action {
+            Action::SlotUp => selected = cycle(selected, count, false),
+            Action::SlotDown => selected = cycle(selected, count, true),
+            Action::FgNext => style.fg = cycle(style.fg, FGS.len(), true),
+            Action::FgPrev => style.fg = cycle(style.fg, FGS.len(), false),
+            Action::BgNext => style.bg = cycle(style.bg, BGS.len(), true),
+            Action::BgPrev => style.bg = cycle(style.bg, BGS.len(), false),
+            Action::AttrNext => style.attr = cycle(style.attr, ATTRS.len(), true),
+            Action::AttrPrev => style.attr = cycle(style.attr, ATTRS.len(), false),
+            Action::Reset => {
+                **seq = defaults.slots()[selected].1.clone();
+                dirty = true;
+                continue;
+            }
+            Action::Save => {
+                drop(slots);
+                save(theme, &defaults, path)?;
+                dirty = false;
+                status = format!("saved to {}", path.display());
+                continue;
+            }
+            Action::Quit => {
+                if !dirty {
+                    return Ok(());
+                }
+                dirty = false;
+                status = format!(
+                    "unsaved changes: {} to save, {} again to quit",
+                    keys.name(Action::Save),
+                    keys.name(Action::Quit)
+                );
+                continue;
+            }
+        }
+        if !matches!(action, Action::SlotUp | Action::SlotDown) {
+            **seq = style.seq();
+            dirty = true;
+            status = format!("{} = \"{}\"", name, seq.codes());
+        }
+    }
+}
+
+/// One sample entry per level, in `theme`.
+pub fn samples(theme: &Theme) -> Vec<Line> {
+    SAMPLES
+        .iter()
+        .map(|(level, text): &(&str, &str)| {
+            let (id, msg) = render::level_seqs(level, theme);
+            let mut line: Line = Line::new();
+            line.styled(&theme.timestamp, "06-01 12:00:00")
+                .styled(&theme.timestamp_fraction, ".123")
+                .plain(" ")
+                .badge(id, level)
+                .styled(&theme.tid_pid, "[1234/1240]")
+                .plain(" ")
+                .styled(&theme.tag, "ActivityManager")
+                .plain(" ")
+                .styled(msg, text);
+            line
+        })
+        .collect()
+}
+
+fn draw(
+    out: &mut Renderer,
+    theme: &Theme,
+    selected: usize,
+    help: &str,
+    status: &str,
+) -> io::Result<()> {
+    out.control("\x1b[H\x1b[2J")?;
+    out.line(&render::note(&theme.banner, help))?;
+    out.blank()?;
+    for line in samples(theme) {
+        out.line(&line)?;
+    }
+    out.blank()?;
+
+    // The slot list scrolls to keep the selection in view
+    let slots: Vec<(&'static str, &Seq)> = theme.slots();
+    let rows: usize = width::terminal_rows()
+        .saturating_sub(SAMPLES.len() + 5)
+        .max(1);
+    let first: usize = selected.saturating_sub(rows - 1);
+    for (i, (name, seq)) in slots.iter().enumerate().skip(first).take(rows) {
+        let marker: &str = if i == selected { "›" } else { " " };
+        let mut line: Line = Line::new();
+        line.plain(&format!("{} {:<20} ", marker, name))
+            .styled(seq, " Sample ")
+            .plain(&format!(" {}", seq.codes()));
+        out.line(&line)?;
+    }
+    out.blank()?;
+    out.line(&render::note(&theme.hint, status))?;
+    out.flush()
+}
+
+// Rewrites `path` with its [theme] table replaced by the slots that differ
+// from the defaults, keeping everything else as it was
+fn save(theme: &Theme, defaults: &Theme, path: &Path) -> io::Result<()> {
+    let text: String = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut kept: Vec<&str> = Vec::new();
+    let mut inside: bool = false;
+    for line in text.lines() {
+        let header: &str = line.split('#').next().unwrap_or_default().trim();
+        if header.starts_with('[') {
+            inside = header == "[theme]";
+        }
+        if !inside {
+            kept.push(line);
+        }
+    }
+    while kept.last().is_some_and(|l: &&str| l.trim().is_empty()) {
+        kept.pop();
+    }
+
+    let mut out: String = kept.join("\n");
+    if !out.is_empty() {
+        out.push_str("\n\n");
+    }
+    out.push_str("[theme]\n");
+    for ((name, seq), (_, default)) in theme.slots().into_iter().zip(defaults.slots()) {
+        if seq != default {
+            out.push_str(&format!("{} = \"{}\"\n", name, seq.codes()));
+        }
+    }
+    fs::write(path, out)
+        .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))
+}
@@ -0,0 +1,520 @@
+use regex::Regex;
+use std::{
+    cell::Cell,
+    io,
+    sync::LazyLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static RE_EPOCH: LazyLock<Regex> =
+    LazyLock::new(|| -> Regex { Regex::new(r"^([0-9]{9,})\.([0-9]+)$").unwrap() });
+// Epoch or monotonic seconds
+static RE_SECONDS: LazyLock<Regex> =
+    LazyLock::new(|| -> Regex { Regex::new(r"^([0-9]+)\.([0-9]+)$").unwrap() });
+static RE_WALLCLOCK: LazyLock<Regex> = LazyLock::new(|| -> Regex {
+    Regex::new(
+        r"^(?:([0-9]{4})-)?([0-9]{2})-([0-9]{2}) ([0-9]{2}):([0-9]{2}):([0-9]{2})\.([0-9]+)$",
+    )
+    .unwrap()
+});
+
+const DAY: u64 = 86_400_000;
+
+// The host's local time when first asked for, which yearless timestamps are
+// dated against
+static ANCHOR: LazyLock<u64> = LazyLock::new(|| -> u64 {
+    local_millis().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+    })
+});
+
+/// Converts a logcat timestamp to milliseconds since 1970, see `Dates`.
+/// Without a `-v year` prefix, the year is the latest that doesn't put the
+/// time more than a day past the host's clock.
+pub fn parse_millis(ts: &str) -> Option<u64> {
+    Dates::default().millis(ts)
+}
+
+/// Dates the timestamps of one stream, carrying the year from entry to
+/// entry: "MM-DD HH:MM:SS.mmm" (optionally with a `YYYY-` prefix from
+/// `-v year`) becomes milliseconds since 1970 in the same wall clock.
+///
+/// The first yearless time takes the latest year that doesn't put it more
+/// than a day past the anchor; each later one the year that puts it nearest
+/// the time before, so going back by more than half a year is New Year.
+/// Epoch (`-v epoch`) and monotonic (`-v monotonic`) timestamps give
+/// milliseconds since 1970 or since boot, which order a capture just as well.
+#[derive(Clone, Debug)]
+pub struct Dates {
+    anchor: u64,
+    last: Cell<Option<u64>>,
+}
+
+impl Default for Dates {
+    /// Dates anchored on the host's clock.
+    fn default() -> Self {
+        Self::new(*ANCHOR)
+    }
+}
+
+impl Dates {
+    pub fn new(anchor: u64) -> Self {
+        Self {
+            anchor,
+            last: Cell::new(None),
+        }
+    }
+
+    pub fn millis(&self, ts: &str) -> Option<u64> {
+        if let Some(c) = RE_SECONDS.captures(ts) {
+            return Some(c[1].parse::<u64>().ok()? * 1000 + fraction_millis(&c[2]));
+        }
+        let c: regex::Captures<'_> = RE_WALLCLOCK.captures(ts)?;
+        let num = |i: usize| -> u64 { c[i].parse().unwrap_or(0) };
+        let (month, day) = (num(2), num(3));
+        if num(4) > 23 || num(5) > 59 || num(6) > 59 {
+            return None;
+        }
+        let time: u64 = ((num(4) * 60 + num(5)) * 60 + num(6)) * 1000 + fraction_millis(&c[7]);
+
+        let ms: u64 = match c.get(1) {
+            Some(_) => date_millis(num(1), month, day)? + time,
+            None => {
+                let near: u64 = self.last.get().unwrap_or(self.anchor);
+                let year: u64 = civil_from_days(near / DAY).0;
+                // Back far enough for a leap day
+                let dated = (year.saturating_sub(4)..=year + 1)
+                    .filter_map(|y: u64| Some(date_millis(y, month, day)? + time));
+                match self.last.get() {
+                    Some(last) => dated.min_by_key(|ms: &u64| ms.abs_diff(last))?,
+                    None => dated.filter(|ms: &u64| *ms <= self.anchor + DAY).max()?,
+                }
+            }
+        };
+        self.last.set(Some(ms));
+        Some(ms)
+    }
+}
+
+/// Whether `ts` counts from boot (`-v monotonic`), which no wall clock time
+/// compares with.
+pub fn is_monotonic(ts: &str) -> bool {
+    RE_SECONDS.is_match(ts) && !RE_EPOCH.is_match(ts)
+}
+
+// Milliseconds since 1970 at the start of a day; None for a day the month
+// doesn't have, such as 02-29 outside leap years
+fn date_millis(year: u64, month: u64, day: u64) -> Option<u64> {
+    let days: u64 = days_from_civil(year, month, day)?;
+    (civil_from_days(days) == (year, month, day)).then_some(days * DAY)
+}
+
+// Keep millisecond resolution whatever the fraction's precision
+fn fraction_millis(frac: &str) -> u64 {
+    format!("{:0<3}", &frac[..frac.len().min(3)])
+        .parse()
+        .unwrap_or(0)
+}
+
+/// Converts an epoch timestamp ("1697371200.123") to the logcat layout in UTC
+/// ("10-15 12:00:00.123"), keeping the precision of the fraction.
+pub fn epoch_to_wallclock(ts: &str) -> Option<String> {
+    let c: regex::Captures<'_> = RE_EPOCH.captures(ts)?;
+    let secs: u64 = c[1].parse().ok()?;
+    let (_, month, day) = civil_from_days(secs / 86_400);
+    Some(format!(
+        "{:02}-{:02} {:02}:{:02}:{:02}.{:0<3}",
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        &c[2]
+    ))
+}
+
+/// The host's local time as milliseconds since 1970 in its wall clock, from
+/// the system clock and the zone's offset from UTC; None where the offset
+/// can't be had.
+pub fn local_millis() -> Option<u64> {
+    let now: std::time::Duration = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let secs: i64 = now.as_secs() as i64;
+    let local: i64 = secs + utc_offset(secs)?;
+    Some(local as u64 * 1000 + u64::from(now.subsec_millis()))
+}
+
+// Seconds the local zone is ahead of UTC at `secs` since 1970
+#[cfg(unix)]
+fn utc_offset(secs: i64) -> Option<i64> {
+    // time_t is 32 bits on arm-unknown-linux-gnueabihf, 64 elsewhere
+    #[allow(clippy::useless_conversion)]
+    let time = secs.try_into().ok()?;
+    // SAFETY: a zeroed tm is valid; localtime_r writes it in full or fails
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let res: *mut libc::tm = unsafe { libc::localtime_r(&time, &mut tm) };
+    (!res.is_null()).then_some(tm.tm_gmtoff as i64)
+}
+
+#[cfg(windows)]
+fn utc_offset(_secs: i64) -> Option<i64> {
+    use windows_sys::Win32::System::{
+        SystemServices::{TIME_ZONE_ID_DAYLIGHT, TIME_ZONE_ID_STANDARD},
+        Time::{GetTimeZoneInformation, TIME_ZONE_ID_INVALID, TIME_ZONE_INFORMATION},
+    };
+    // SAFETY: a zeroed TIME_ZONE_INFORMATION is valid, and is filled in
+    let mut tzi: TIME_ZONE_INFORMATION = unsafe { std::mem::zeroed() };
+    // Minutes behind UTC
+    let bias: i32 = match unsafe { GetTimeZoneInformation(&mut tzi) } {
+        TIME_ZONE_ID_INVALID => return None,
+        TIME_ZONE_ID_STANDARD => tzi.Bias + tzi.StandardBias,
+        TIME_ZONE_ID_DAYLIGHT => tzi.Bias + tzi.DaylightBias,
+        _ => tzi.Bias,
+    };
+    Some(-i64::from(bias) * 60)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn utc_offset(_secs: i64) -> Option<i64> {
+    None
+}
+
+/// Seconds since 1970 as a UTC date and time to the minute
+/// ("2024-06-19 18:40").
+pub fn epoch_date(secs: u64) -> String {
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60
+    )
+}
+
+// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's algorithm
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z: u64 = days + 719_468;
+    let era: u64 = z / 146_097;
+    let doe: u64 = z - era * 146_097;
+    let yoe: u64 = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy: u64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp: u64 = (5 * doy + 2) / 153;
+    let day: u64 = doy - (153 * mp + 2) / 5 + 1;
+    let month: u64 = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year: u64 = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+// The inverse of `civil_from_days`, for years from 1970; None for a month
+// past 12
+fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    let year: u64 = year - u64::from(month <= 2);
+    let era: u64 = year / 400;
+    let yoe: u64 = year - era * 400;
+    let mp: u64 = (month.checked_sub(1).filter(|m: &u64| *m < 12)? + 10) % 12;
+    let doy: u64 = (153 * mp + 2) / 5 + day.checked_sub(1)?;
+    let doe: u64 = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    (era * 146_097 + doe).checked_sub(719_468)
+}
+
+/// Formats milliseconds since 1970 back into the logcat layout, the inverse
+/// of `parse_millis`, with the year in front as `-v year` has it
+/// ("2024-10-15 14:00:00.123").
+pub fn format_millis(ms: u64) -> String {
+    let (year, month, day) = civil_from_days(ms / DAY);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        ms / 3_600_000 % 24,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Renders `ms` (as returned by `parse_millis`) in the layout of `ts`,
+/// keeping its `-v year` prefix, or the lack of one, and any
+/// `-v usec`/`-v nsec` digits past the millisecond.
+pub fn with_millis(ts: &str, ms: u64) -> String {
+    let fraction: &str = ts.rsplit_once('.').map_or("", |(_, f)| f);
+    let extra: &str = fraction.get(3..).unwrap_or("");
+    if RE_SECONDS.is_match(ts) {
+        return format!("{}.{:03}{}", ms / 1000, ms % 1000, extra);
+    }
+    let dated: String = format_millis(ms);
+    let year: bool = RE_WALLCLOCK
+        .captures(ts)
+        .is_some_and(|c: regex::Captures| c.get(1).is_some());
+    match year {
+        true => format!("{}{}", dated, extra),
+        false => format!(
+            "{}{}",
+            &dated[dated.find('-').map_or(0, |i| i + 1)..],
+            extra
+        ),
+    }
+}
+
+/// Parses a duration such as "500ms", "5s", "2.5s", "2m" or "1h" into
+/// milliseconds.
+pub fn parse_duration(s: &str) -> Option<u64> {
+    let (num, scale) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 1.0)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1000.0)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60_000.0)
+    } else if let Some(n) = s.strip_suffix('h') {
+        (n, 3_600_000.0)
+    } else {
+        return None;
+    };
+    num.parse::<f64>()
+        .ok()
+        .filter(|n: &f64| *n >= 0.0)
+        .map(|n: f64| (n * scale).round() as u64)
+}
+
+/// A `--since` or `--until` time.
+#[derive(Clone, Debug)]
+pub enum Bound {
+    At(String), // a logcat time, with or without its year
+    Ago(u64),   // a duration back from now
+}
+
+impl Bound {
+    /// The bound as milliseconds since 1970, given the current time in the
+    /// same clock: a time without a year is the latest such before `now`.
+    pub fn resolve(&self, now: u64) -> Result<u64, String> {
+        match self {
+            Bound::At(ts) => Dates::new(now)
+                .millis(ts)
+                .ok_or_else(|| format!("`{}` is not a time", ts)),
+            Bound::Ago(ms) => Ok(now.saturating_sub(*ms)),
+        }
+    }
+}
+
+/// Resolves `--since` and `--until` as milliseconds since 1970. `now` is
+/// only asked for when a bound is a duration back from it; otherwise times
+/// without a year are dated against the host's clock, as the entries are.
+pub fn resolve_bounds(
+    since: Option<&Bound>,
+    until: Option<&Bound>,
+    now: impl FnOnce() -> io::Result<u64>,
+) -> io::Result<(Option<u64>, Option<u64>)> {
+    let now: u64 = match [since, until]
+        .into_iter()
+        .flatten()
+        .any(|b: &Bound| matches!(b, Bound::Ago(_)))
+    {
+        true => now()?,
+        false => *ANCHOR,
+    };
+    let resolve = |b: &Bound| -> io::Result<u64> { b.resolve(now).map_err(io::Error::other) };
+    let since: Option<u64> = since.map(resolve).transpose()?;
+    let until: Option<u64> = until.map(resolve).transpose()?;
+    if let (Some(s), Some(u)) = (since, until)
+        && s > u
+    {
+        return Err(io::Error::other(format!(
+            "--since {} is after --until {}",
+            format_millis(s),
+            format_millis(u)
+        )));
+    }
+    Ok((since, until))
+}
+
+/// Parses "MM-DD HH:MM:SS", with an optional fraction and `YYYY-` prefix,
+/// or a duration back from now such as "5m" or "1.5h".
+pub fn parse_bound(s: &str) -> Result<Bound, String> {
+    if let Some(ms) = parse_duration(s) {
+        return Ok(Bound::Ago(ms));
+    }
+    let ts: String = match s.contains('.') {
+        true => s.to_string(),
+        false => format!("{}.000", s),
+    };
+    match RE_WALLCLOCK.is_match(&ts) && Dates::default().millis(&ts).is_some() {
+        true => Ok(Bound::At(ts)),
+        false => Err(format!(
+            "`{}` is neither a time like \"06-01 12:00:00\" nor a duration like 5m",
+            s
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2027-01-01 00:02:00 in milliseconds since 1970
+    const NEW_YEAR: u64 = 1_798_761_720_000;
+
+    #[test]
+    fn local_millis_is_near_the_utc_clock() {
+        let utc: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        // No zone is more than 14 hours off
+        assert!(local_millis().unwrap().abs_diff(utc) <= 14 * 3_600_000);
+    }
+
+    #[test]
+    fn dates_round_trip() {
+        for ts in [
+            "2024-02-29 23:59:59.999",
+            "1970-01-01 00:00:00.000",
+            "2100-03-01 12:30:00.500",
+        ] {
+            let ms: u64 = Dates::default().millis(ts).unwrap();
+            assert_eq!(format_millis(ms), ts);
+        }
+        assert_eq!(Dates::default().millis("2023-02-29 00:00:00.000"), None);
+        assert_eq!(Dates::default().millis("2024-13-01 00:00:00.000"), None);
+        assert_eq!(Dates::default().millis("2024-04-31 00:00:00.000"), None);
+        assert_eq!(Dates::default().millis("06-01 25:00:00.000"), None);
+    }
+
+    #[test]
+    fn yearless_times_take_the_year_before_the_anchor() {
+        let dates: Dates = Dates::new(NEW_YEAR);
+        let ms: u64 = dates.millis("12-31 23:58:00.000").unwrap();
+        assert_eq!(format_millis(ms), "2026-12-31 23:58:00.000");
+        // Half a year back from the last entry rather than from the anchor
+        let ms: u64 = dates.millis("01-01 00:01:00.000").unwrap();
+        assert_eq!(format_millis(ms), "2027-01-01 00:01:00.000");
+        // A leap day lands in the last leap year
+        let ms: u64 = Dates::new(NEW_YEAR).millis("02-29 12:00:00.000").unwrap();
+        assert_eq!(format_millis(ms), "2024-02-29 12:00:00.000");
+    }
+
+    #[test]
+    fn dates_carry_the_year_over_new_year() {
+        let dates: Dates = Dates::new(NEW_YEAR + 100 * DAY);
+        let dated: Vec<String> = [
+            "12-31 23:59:59.000",
+            "01-01 00:00:01.000",
+            "12-31 23:59:59.500",
+            "01-01 00:00:02.000",
+        ]
+        .iter()
+        .map(|ts: &&str| format_millis(dates.millis(ts).unwrap()))
+        .collect();
+        assert_eq!(
+            dated,
+            [
+                "2026-12-31 23:59:59.000",
+                "2027-01-01 00:00:01.000",
+                "2026-12-31 23:59:59.500",
+                "2027-01-01 00:00:02.000"
+            ]
+        );
+    }
+
+    #[test]
+    fn since_just_after_new_year() {
+        // --since 5m at 00:02 on January 1 keeps the last three minutes of December
+        let since: u64 = parse_bound("5m").unwrap().resolve(NEW_YEAR).unwrap();
+        let dates: Dates = Dates::new(NEW_YEAR);
+        assert!(dates.millis("12-31 23:56:59.999").unwrap() < since);
+        assert!(dates.millis("12-31 23:57:00.000").unwrap() >= since);
+        assert!(dates.millis("01-01 00:01:00.000").unwrap() >= since);
+
+        let at: u64 = parse_bound("12-31 23:00:00")
+            .unwrap()
+            .resolve(NEW_YEAR)
+            .unwrap();
+        assert_eq!(format_millis(at), "2026-12-31 23:00:00.000");
+        let at: u64 = parse_bound("2025-06-01 12:00:00")
+            .unwrap()
+            .resolve(NEW_YEAR)
+            .unwrap();
+        assert_eq!(format_millis(at), "2025-06-01 12:00:00.000");
+        assert!(parse_bound("13-01 00:00:00").is_err());
+    }
+
+    #[test]
+    fn absolute_bounds_need_no_clock() {
+        let since: Bound = parse_bound("06-01 12:00:00.300").unwrap();
+        let until: Bound = parse_bound("06-01 12:00:00.700").unwrap();
+        let (since, until) = resolve_bounds(Some(&since), Some(&until), || {
+            panic!("the clock is only for durations")
+        })
+        .unwrap();
+        // Dated as the entries they're compared with
+        let dates: Dates = Dates::default();
+        assert_eq!(since, dates.millis("06-01 12:00:00.300"));
+        assert_eq!(until, dates.millis("06-01 12:00:00.700"));
+        assert!(since.unwrap() < until.unwrap());
+
+        let ago: Bound = parse_bound("5m").unwrap();
+        let (since, until) = resolve_bounds(Some(&ago), None, || Ok(NEW_YEAR)).unwrap();
+        assert_eq!((since, until), (Some(NEW_YEAR - 300_000), None));
+        let err = resolve_bounds(Some(&ago), None, || Err(io::Error::other("no clock")));
+        assert!(err.is_err());
+        let bad: Bound = Bound::At("13-01 00:00:00.000".to_string());
+        assert!(resolve_bounds(Some(&bad), None, || Ok(NEW_YEAR)).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_bounds() {
+        for bad in [
+            "yesterday",
+            "5",
+            "5d",
+            "-5m",
+            "06-01",
+            "06-01 12:00",
+            "6-1 12:00:00",
+            "02-30 12:00:00",
+            "06-01 24:00:00",
+            "06-01 12:60:00",
+            "2023-02-29 00:00:00",
+            "06-01 12:00:00.x",
+            "06-01T12:00:00",
+        ] {
+            assert!(parse_bound(bad).is_err(), "{}", bad);
+        }
+        // February 29 is a time, dated in the last leap year
+        assert!(parse_bound("02-29 12:00:00").is_ok());
+
+        // A window that closes before it opens
+        let since: Bound = parse_bound("2027-01-01 00:01:00").unwrap();
+        let until: Bound = parse_bound("2026-12-31 23:59:00").unwrap();
+        let err = resolve_bounds(Some(&since), Some(&until), || unreachable!()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--since 2027-01-01 00:01:00.000 is after --until 2026-12-31 23:59:00.000"
+        );
+    }
+
+    #[test]
+    fn with_millis_keeps_the_layout() {
+        let ms: u64 = Dates::new(NEW_YEAR).millis("12-31 23:59:59.900").unwrap() + 200;
+        assert_eq!(with_millis("12-31 23:59:59.900", ms), "01-01 00:00:00.100");
+        assert_eq!(
+            with_millis("2026-12-31 23:59:59.900123", ms),
+            "2027-01-01 00:00:00.100123"
+        );
+        assert_eq!(
+            with_millis("1798761720.000", 1_798_761_720_500),
+            "1798761720.500"
+        );
+    }
+
+    #[test]
+    fn epoch_to_wallclock_in_utc() {
+        assert_eq!(
+            epoch_to_wallclock("1697371200.123").as_deref(),
+            Some("10-15 12:00:00.123")
+        );
+    }
+}
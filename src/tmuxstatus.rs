@@ -0,0 +1,145 @@
+use crate::{classify::EventKind, stats::Stats};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+// The tmux user option holding the status, for `#{@logcat}` in status-right
+const OPTION: &str = "@logcat";
+// How often the status is refreshed, which also ages the quiet time
+const INTERVAL: Duration = Duration::from_secs(1);
+// Input gaps up to this long still count as a live stream
+const QUIET_AFTER: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+enum Sink {
+    Tmux,
+    File(PathBuf),
+}
+
+struct Health {
+    errors: usize, // fatal ones included
+    warnings: usize,
+    crashes: usize,
+    anrs: usize,
+    last_input: Instant,
+    ended: bool,
+}
+
+/// `--tmux-status [FILE]`: keeps a one-line summary of the stream's health,
+/// error counts and whether lines still come, in the `@logcat` tmux user
+/// option or in FILE, so a status bar can show it while the pane is hidden.
+pub struct TmuxStatus {
+    sink: Sink,
+    health: Arc<Mutex<Health>>,
+}
+
+impl TmuxStatus {
+    pub fn start(file: Option<&Path>) -> io::Result<Self> {
+        let sink: Sink = match file {
+            Some(path) => Sink::File(path.to_path_buf()),
+            None if env::var_os("TMUX").is_none() => {
+                return Err(io::Error::other(
+                    "--tmux-status: not inside tmux; give a FILE to write the status to",
+                ));
+            }
+            None => Sink::Tmux,
+        };
+        let health: Arc<Mutex<Health>> = Arc::new(Mutex::new(Health {
+            errors: 0,
+            warnings: 0,
+            crashes: 0,
+            anrs: 0,
+            last_input: Instant::now(),
+            ended: false,
+        }));
+        // The first write says whether the sink works at all
+        let mut shown: String = text(&health.lock().unwrap());
+        publish(&sink, &shown)?;
+
+        let (thread_sink, thread_health) = (sink.clone(), Arc::clone(&health));
+        thread::spawn(move || {
+            loop {
+                thread::sleep(INTERVAL);
+                // Held while publishing, so this can't overwrite the end
+                let health: MutexGuard<Health> = thread_health.lock().unwrap();
+                if health.ended {
+                    return;
+                }
+                let now: String = text(&health);
+                if now != shown && publish(&thread_sink, &now).is_ok() {
+                    shown = now;
+                }
+            }
+        });
+        Ok(Self { sink, health })
+    }
+
+    /// Takes the counts after an entry, or notes a line of no format.
+    pub fn update(&self, stats: &Stats) {
+        let mut health: MutexGuard<Health> = self.health.lock().unwrap();
+        health.errors = stats.levels[4] + stats.levels[5];
+        health.warnings = stats.levels[3];
+        health.crashes = stats.event_count(EventKind::Crash);
+        health.anrs = stats.event_count(EventKind::Anr);
+        health.last_input = Instant::now();
+    }
+
+    /// Leaves the final counts, marked as ended.
+    pub fn close(&self) -> io::Result<()> {
+        let mut health: MutexGuard<Health> = self.health.lock().unwrap();
+        health.ended = true;
+        publish(&self.sink, &text(&health))
+    }
+}
+
+// "3 err 12 warn 1 crash · live"
+fn text(health: &Health) -> String {
+    let mut parts: Vec<String> = vec![
+        format!("{} err", health.errors),
+        format!("{} warn", health.warnings),
+    ];
+    if health.crashes > 0 {
+        parts.push(format!("{} crash", health.crashes));
+    }
+    if health.anrs > 0 {
+        parts.push(format!("{} anr", health.anrs));
+    }
+    let quiet: u64 = health.last_input.elapsed().as_secs();
+    let state: String = match () {
+        _ if health.ended => "ended".to_string(),
+        _ if health.last_input.elapsed() < QUIET_AFTER => "live".to_string(),
+        _ if quiet < 60 => format!("quiet {}s", quiet),
+        _ if quiet < 3600 => format!("quiet {}m", quiet / 60),
+        _ => format!("quiet {}h", quiet / 3600),
+    };
+    format!("{} · {}", parts.join(" "), state)
+}
+
+fn publish(sink: &Sink, text: &str) -> io::Result<()> {
+    match sink {
+        Sink::Tmux => {
+            let status: ExitStatus = Command::new("tmux")
+                .args(["set-option", "-gq", OPTION, text])
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map_err(|e: io::Error| io::Error::other(format!("tmux: {}", e)))?;
+            match status.success() {
+                true => Ok(()),
+                false => Err(io::Error::other("tmux: set-option failed")),
+            }
+        }
+        // Renamed into place, so a status bar never reads half a line
+        Sink::File(path) => {
+            let tmp: PathBuf = path.with_extension("tmp");
+            fs::write(&tmp, format!("{}\n", text))
+                .and_then(|_| fs::rename(&tmp, path))
+                .map_err(|e: io::Error| io::Error::other(format!("{}: {}", path.display(), e)))
+        }
+    }
+}
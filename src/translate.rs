@@ -0,0 +1,48 @@
+use regex::Regex;
+use std::{fs, io, path::Path};
+
+/// Regex rewrites turning localized platform messages back into their AOSP
+/// wording, so classifiers and filters written against English keep working.
+///
+/// One rule per line, `PATTERN => REPLACEMENT`, where the replacement may use
+/// `$1`-style capture references. Blank lines and `#` comments are skipped:
+///
+/// ```text
+/// # MIUI, zh-CN
+/// ^进程 (\S+) \(pid (\d+)\) 已终止 => Process $1 (pid $2) has died
+/// ```
+pub struct Translations {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Translations {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text: String = fs::read_to_string(path)?;
+        let mut rules: Vec<(Regex, String)> = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| -> io::Error {
+                io::Error::other(format!("{}:{}: {}", path.display(), n + 1, msg))
+            };
+            let (pattern, replacement) = line
+                .split_once(" => ")
+                .ok_or_else(|| err("expected `PATTERN => REPLACEMENT`".to_string()))?;
+            let re: Regex =
+                Regex::new(pattern.trim()).map_err(|e: regex::Error| err(e.to_string()))?;
+            rules.push((re, replacement.trim().to_string()));
+        }
+        Ok(Self { rules })
+    }
+
+    /// Rewrites `message` with the first matching rule, if any.
+    pub fn apply(&self, message: &str) -> Option<String> {
+        self.rules
+            .iter()
+            .find(|(re, _): &&(Regex, String)| re.is_match(message))
+            .map(|(re, rep): &(Regex, String)| re.replace_all(message, rep.as_str()).into_owned())
+    }
+}
@@ -0,0 +1,59 @@
+use crate::logcat::Logcat;
+use regex::Regex;
+use std::{collections::HashMap, sync::LazyLock};
+
+// Android uids are the user id times this, plus the app id
+const PER_USER_RANGE: u64 = 100_000;
+
+// `-v uid` names: "u10_a123", "u10_system"
+static RE_UID_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^u([0-9]+)_").unwrap());
+// ActivityManager: "Start proc 1234:com.example/u10a123 for activity ..."
+static RE_START: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bStart proc ([0-9]+):[^/\s]+/u([0-9]+)[as][0-9]+").unwrap());
+// Platform messages about an app of some user: "Killing 1234:com.example/u10a123",
+// ActivityTaskManager's "START u10 {act=...}"
+static RE_MESSAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"/u([0-9]+)[as][0-9]+\b|\bSTART u([0-9]+) \{").unwrap());
+
+/// The Android user of a `-v uid` field, numeric or named. Names of system
+/// ids without a `uN_` prefix ("system", "root") are user 0's.
+pub fn from_uid(uid: &str) -> Option<u32> {
+    if uid.is_empty() {
+        return None;
+    }
+    if let Ok(n) = uid.parse::<u64>() {
+        return u32::try_from(n / PER_USER_RANGE).ok();
+    }
+    match RE_UID_NAME.captures(uid) {
+        Some(c) => c[1].parse().ok(),
+        None => Some(0),
+    }
+}
+
+/// Tells which Android user (a secondary user or a work profile) entries
+/// belong to: from the `-v uid` field when there is one, then from a
+/// platform message naming an app's user, then from the user the entry's
+/// process was started for.
+#[derive(Default)]
+pub struct UserTracker {
+    by_pid: HashMap<String, u32>,
+}
+
+impl UserTracker {
+    pub fn observe(&mut self, lc: &Logcat) -> Option<u32> {
+        if let Some(c) = RE_START.captures(&lc.message)
+            && let Ok(user) = c[2].parse::<u32>()
+        {
+            self.by_pid.insert(c[1].to_string(), user);
+        }
+        from_uid(&lc.uid)
+            .or_else(|| {
+                RE_MESSAGE
+                    .captures(&lc.message)
+                    .and_then(|c: regex::Captures| {
+                        c.get(1).or_else(|| c.get(2))?.as_str().parse().ok()
+                    })
+            })
+            .or_else(|| self.by_pid.get(&lc.process).copied())
+    }
+}
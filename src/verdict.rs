@@ -0,0 +1,127 @@
+use crate::{
+    classify::EventKind,
+    expr::{self, Cmp, Logic, ParseResult, Parser, Token},
+    filter,
+    stats::Stats,
+};
+
+/// End-of-stream health thresholds for `--fail-on`, such as
+/// `E>=5 || F>=1 || CRASH>0`. Counters are level letters (or names),
+/// event labels (`CRASH`, `ANR`, `JANK`, ..., and those of `--config`
+/// classifiers), `total` and `unparsed`.
+#[derive(Clone, Debug)]
+pub struct FailOn {
+    src: String,
+    expr: Logic<Threshold>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Threshold {
+    counter: Counter,
+    cmp: Cmp,
+    n: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Counter {
+    Level(usize),
+    Event(EventKind),
+    Total,
+    Unparsed,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Fail,
+}
+
+/// Parses `--fail-on`, once `--config` classifiers are registered.
+pub fn parse(src: &str) -> Result<FailOn, String> {
+    Ok(FailOn {
+        src: src.to_string(),
+        expr: expr::parse_with(src, threshold)?,
+    })
+}
+
+fn threshold(p: &mut Parser<Threshold>) -> ParseResult<Threshold> {
+    let (pos, name) = p.next();
+    let counter: Counter = match &name {
+        Token::Word(w) => counter(w).ok_or_else(|| {
+            (
+                pos,
+                format!(
+                    "unknown counter `{}` (expected a level, an event such as CRASH or ANR, total or unparsed)",
+                    w
+                ),
+            )
+        })?,
+        t => return Err((pos, format!("expected a counter, found {}", expr::show(t)))),
+    };
+    let (op_pos, op) = p.comparison("counter")?;
+    let cmp: Cmp =
+        Cmp::from_op(op).ok_or_else(|| (op_pos, format!("`{}` compares text, not counts", op)))?;
+    let (value_pos, value) = p.value()?;
+    let n: usize = value
+        .parse()
+        .map_err(|_| (value_pos, format!("`{}` is not a count", value)))?;
+    Ok(Threshold { counter, cmp, n })
+}
+
+fn counter(name: &str) -> Option<Counter> {
+    if let Ok(level) = filter::parse_level(name) {
+        return filter::level_rank(&level).map(Counter::Level);
+    }
+    if let Some(kind) = EventKind::all()
+        .into_iter()
+        .find(|k: &EventKind| k.label().eq_ignore_ascii_case(name))
+    {
+        return Some(Counter::Event(kind));
+    }
+    match name.to_ascii_lowercase().as_str() {
+        "total" => Some(Counter::Total),
+        "unparsed" => Some(Counter::Unparsed),
+        _ => None,
+    }
+}
+
+impl FailOn {
+    pub fn verdict(&self, stats: &Stats) -> Verdict {
+        let count = |c: &Counter| -> usize {
+            match c {
+                Counter::Level(rank) => stats.levels[*rank],
+                Counter::Event(kind) => stats.event_count(*kind),
+                Counter::Total => stats.total,
+                Counter::Unparsed => stats.unparsed,
+            }
+        };
+        match self
+            .expr
+            .eval(&|t: &Threshold| t.cmp.holds(count(&t.counter).cmp(&t.n)))
+        {
+            true => Verdict::Fail,
+            false => Verdict::Pass,
+        }
+    }
+
+    /// One `key=value` line for scripts, e.g.
+    /// `verdict=FAIL fail_on="E>=5 || F>=1" V=0 D=3 I=40 W=2 E=7 F=0 total=52 unparsed=0`.
+    pub fn summary(&self, verdict: Verdict, stats: &Stats) -> String {
+        let levels: Vec<String> = ["V", "D", "I", "W", "E", "F"]
+            .iter()
+            .zip(stats.levels)
+            .map(|(l, n): (&&str, usize)| format!("{}={}", l, n))
+            .collect();
+        format!(
+            "verdict={} fail_on={:?} {} total={} unparsed={}",
+            match verdict {
+                Verdict::Pass => "PASS",
+                Verdict::Fail => "FAIL",
+            },
+            self.src,
+            levels.join(" "),
+            stats.total,
+            stats.unparsed
+        )
+    }
+}
@@ -0,0 +1,266 @@
+// Terminal column widths, following the East Asian Width and emoji
+// presentation rules closely enough for tag and message alignment.
+
+use std::sync::OnceLock;
+
+// Ranges rendered two columns wide
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),   // Hangul Jamo initials
+    (0x231A, 0x231B),   // watch, hourglass
+    (0x2329, 0x232A),   // angle brackets
+    (0x23E9, 0x23EC),   // media controls
+    (0x23F0, 0x23F0),   // alarm clock
+    (0x23F3, 0x23F3),   // hourglass
+    (0x25FD, 0x25FE),   // small squares
+    (0x2614, 0x2615),   // umbrella, hot beverage
+    (0x2648, 0x2653),   // zodiac
+    (0x267F, 0x267F),   // wheelchair
+    (0x2693, 0x2693),   // anchor
+    (0x26A1, 0x26A1),   // high voltage
+    (0x26AA, 0x26AB),   // circles
+    (0x26BD, 0x26BE),   // balls
+    (0x26C4, 0x26C5),   // snowman, sun
+    (0x26CE, 0x26CE),   // ophiuchus
+    (0x26D4, 0x26D4),   // no entry
+    (0x26EA, 0x26EA),   // church
+    (0x26F2, 0x26F3),   // fountain, golf
+    (0x26F5, 0x26F5),   // sailboat
+    (0x26FA, 0x26FA),   // tent
+    (0x26FD, 0x26FD),   // fuel pump
+    (0x2705, 0x2705),   // check mark
+    (0x270A, 0x270B),   // fists
+    (0x2728, 0x2728),   // sparkles
+    (0x274C, 0x274C),   // cross mark
+    (0x274E, 0x274E),   // cross mark button
+    (0x2753, 0x2755),   // question marks
+    (0x2757, 0x2757),   // exclamation mark
+    (0x2795, 0x2797),   // math signs
+    (0x27B0, 0x27B0),   // curly loop
+    (0x27BF, 0x27BF),   // double curly loop
+    (0x2B1B, 0x2B1C),   // large squares
+    (0x2B50, 0x2B50),   // star
+    (0x2B55, 0x2B55),   // circle
+    (0x2E80, 0x303E),   // CJK radicals, punctuation
+    (0x3041, 0x33FF),   // kana, CJK compatibility
+    (0x3400, 0x4DBF),   // CJK extension A
+    (0x4E00, 0x9FFF),   // CJK unified ideographs
+    (0xA000, 0xA4CF),   // Yi
+    (0xA960, 0xA97F),   // Hangul Jamo extended A
+    (0xAC00, 0xD7A3),   // Hangul syllables
+    (0xF900, 0xFAFF),   // CJK compatibility ideographs
+    (0xFE10, 0xFE19),   // vertical forms
+    (0xFE30, 0xFE6F),   // CJK compatibility forms, small forms
+    (0xFF00, 0xFF60),   // fullwidth forms
+    (0xFFE0, 0xFFE6),   // fullwidth signs
+    (0x16FE0, 0x18CFF), // Tangut
+    (0x1B000, 0x1B2FF), // kana supplement
+    (0x1F004, 0x1F004), // mahjong tile
+    (0x1F0CF, 0x1F0CF), // playing card
+    (0x1F18E, 0x1F18E), // AB button
+    (0x1F191, 0x1F19A), // squared words
+    (0x1F200, 0x1F251), // enclosed ideographs
+    (0x1F300, 0x1F64F), // pictographs, emoticons
+    (0x1F680, 0x1F6FF), // transport and map
+    (0x1F7E0, 0x1F7EB), // colored circles and squares
+    (0x1F90C, 0x1F9FF), // supplemental symbols and pictographs
+    (0x1FA70, 0x1FAFF), // symbols and pictographs extended A
+    (0x20000, 0x2FFFD), // CJK extensions B..F
+    (0x30000, 0x3FFFD), // CJK extension G
+];
+
+// Ranges taking no column of their own
+const ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036F),   // combining diacritics
+    (0x0483, 0x0489),   // Cyrillic combining marks
+    (0x0591, 0x05BD),   // Hebrew points
+    (0x0610, 0x061A),   // Arabic marks
+    (0x064B, 0x065F),   // Arabic vowels
+    (0x1AB0, 0x1AFF),   // combining diacritics extended
+    (0x1DC0, 0x1DFF),   // combining diacritics supplement
+    (0x200B, 0x200F),   // zero-width space, joiners, direction marks
+    (0x202A, 0x202E),   // bidi embedding
+    (0x2060, 0x2064),   // word joiner, invisible operators
+    (0x20D0, 0x20FF),   // combining marks for symbols
+    (0xFE00, 0xFE0F),   // variation selectors
+    (0xFE20, 0xFE2F),   // combining half marks
+    (0xFEFF, 0xFEFF),   // byte order mark
+    (0x1F3FB, 0x1F3FF), // skin tone modifiers
+    (0xE0000, 0xE0FFF), // tags, variation selectors supplement
+];
+
+fn in_table(table: &[(u32, u32)], c: u32) -> bool {
+    table
+        .binary_search_by(|&(lo, hi): &(u32, u32)| {
+            if hi < c {
+                std::cmp::Ordering::Less
+            } else if lo > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+pub fn char_width(c: char) -> usize {
+    let cp: u32 = c as u32;
+    if cp < 0x20 || (0x7F..0xA0).contains(&cp) || in_table(ZERO, cp) {
+        0
+    } else if in_table(WIDE, cp) {
+        2
+    } else {
+        1
+    }
+}
+
+// The chars of `s` with their widths, where an emoji joined on with a
+// zero-width joiner draws within the glyph before it (👩‍💻 is one)
+fn widths(s: &str) -> impl Iterator<Item = (char, usize)> + '_ {
+    let mut joined: bool = false;
+    s.chars().map(move |c: char| {
+        let w: usize = match char_width(c) {
+            2 if joined => 0,
+            w => w,
+        };
+        joined = c == '\u{200D}';
+        (c, w)
+    })
+}
+
+/// Columns `s` occupies on a terminal.
+pub fn str_width(s: &str) -> usize {
+    widths(s).map(|(_, w)| w).sum()
+}
+
+/// Pads or truncates `s` to exactly `cols` columns, marking cuts with `…`.
+pub fn fit(s: &str, cols: usize) -> String {
+    let width: usize = str_width(s);
+    if width <= cols {
+        return format!("{}{}", s, " ".repeat(cols - width));
+    }
+    if cols == 0 {
+        return String::new();
+    }
+
+    let mut out: String = String::new();
+    let mut used: usize = 0;
+    for (c, w) in widths(s) {
+        if used + w > cols - 1 {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    // A wide char may leave one column free before the ellipsis
+    out.push_str(&" ".repeat(cols - 1 - used));
+    out.push('…');
+    out
+}
+
+static COLUMNS: OnceLock<usize> = OnceLock::new();
+
+/// Fixes the width `terminal_columns` gives, whatever the terminal.
+pub fn set_terminal_columns(cols: usize) {
+    let _ = COLUMNS.set(cols);
+}
+
+/// Columns of the terminal on stdout: `$COLUMNS` when set, else the tty's
+/// size, else 80 (e.g. when piped), unless `set_terminal_columns` fixed them.
+pub fn terminal_columns() -> usize {
+    if let Some(cols) = COLUMNS.get() {
+        return *cols;
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c: String| c.parse().ok())
+        .filter(|c: &usize| *c > 0)
+        .or_else(|| tty_size().map(|(cols, _)| cols))
+        .unwrap_or(80)
+}
+
+/// Rows of the terminal on stdout: `$LINES` when set, else the tty's size,
+/// else 24.
+pub fn terminal_rows() -> usize {
+    std::env::var("LINES")
+        .ok()
+        .and_then(|r: String| r.parse().ok())
+        .filter(|r: &usize| *r > 0)
+        .or_else(|| tty_size().map(|(_, rows)| rows))
+        .unwrap_or(24)
+}
+
+#[cfg(unix)]
+fn tty_size() -> Option<(usize, usize)> {
+    // SAFETY: a zeroed winsize is valid, and TIOCGWINSZ only writes it
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    // Fails when stdout isn't a terminal
+    let res: i32 = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) };
+    (res == 0 && ws.ws_col > 0 && ws.ws_row > 0)
+        .then_some((usize::from(ws.ws_col), usize::from(ws.ws_row)))
+}
+
+#[cfg(not(unix))]
+fn tty_size() -> Option<(usize, usize)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn str_width_of_mixed_text() {
+        assert_eq!(str_width("ActivityManager"), 15);
+        assert_eq!(str_width("日本語"), 6);
+        assert_eq!(str_width("tag日本"), 7);
+        assert_eq!(str_width("한국어"), 6);
+        assert_eq!(str_width("🔥ok"), 4);
+        // Combining marks draw over the char before them
+        assert_eq!(str_width("e\u{301}te\u{301}"), 3);
+        assert_eq!(str_width("a\u{20DD}"), 1);
+        // ZWJ sequences, skin tones and presentation selectors: one glyph
+        assert_eq!(str_width("👩\u{200D}💻"), 2);
+        assert_eq!(str_width("👨\u{200D}👩\u{200D}👧\u{200D}👦"), 2);
+        assert_eq!(str_width("👍\u{1F3FD}"), 2);
+        assert_eq!(str_width("❤\u{FE0F}"), 1);
+        assert_eq!(str_width("a\u{200D}b"), 2);
+        assert_eq!(str_width("\x1b\t"), 0);
+    }
+
+    #[test]
+    fn fit_pads_to_columns() {
+        assert_eq!(fit("Tag", 6), "Tag   ");
+        assert_eq!(fit("日本", 6), "日本  ");
+        assert_eq!(fit("e\u{301}", 3), "e\u{301}  ");
+        assert_eq!(fit("👩\u{200D}💻", 3), "👩\u{200D}💻 ");
+    }
+
+    #[test]
+    fn fit_cuts_with_an_ellipsis() {
+        assert_eq!(fit("ActivityManager", 8), "Activit…");
+        assert_eq!(fit("日本語タグ", 7), "日本語…");
+        assert_eq!(fit("abc", 0), "");
+        assert_eq!(fit("abc", 1), "…");
+        for (s, cols) in [("日本語タグ", 7), ("ab日本語", 6), ("🔥🔥🔥", 4)] {
+            assert_eq!(str_width(&fit(s, cols)), cols, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn fit_leaves_a_straddling_wide_char_out() {
+        // 日 would take columns 5 and 6, past the ellipsis at 6
+        assert_eq!(fit("abcd日本", 6), "abcd …");
+        assert_eq!(fit("日本語", 4), "日 …");
+        assert_eq!(fit("a🔥🔥", 3), "a …");
+    }
+
+    #[test]
+    fn fit_keeps_clusters_whole() {
+        assert_eq!(
+            fit("e\u{301}e\u{301}e\u{301}", 3),
+            "e\u{301}e\u{301}e\u{301}"
+        );
+        assert_eq!(fit("e\u{301}e\u{301}e\u{301}x", 3), "e\u{301}e\u{301}…");
+        assert_eq!(fit("👩\u{200D}💻👩\u{200D}💻", 3), "👩\u{200D}💻…");
+    }
+}